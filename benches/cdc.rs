@@ -0,0 +1,88 @@
+//! Throughput of the Data-Code chunking.
+//!
+//! Before measuring, the chunk boundaries of [`cdc_chunks`] are checked
+//! against a plain byte-by-byte gear hash, so a faster chunker can never
+//! change the Data-Codes. Run with `cargo bench --bench cdc`.
+
+use std::hint::black_box;
+
+use _core::cdc::{cdc_chunks, DATA_AVG_CHUNK_SIZE};
+use _core::constants::CDC_GEAR;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const INPUT_SIZE: usize = 16 * 1024 * 1024;
+
+/// Deterministic pseudo-random input (xorshift)
+fn input() -> Vec<u8> {
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    (0..INPUT_SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+/// Chunk lengths as computed by the reference implementation, tail included
+fn reference_lengths(data: &[u8], avg_size: usize) -> Vec<usize> {
+    let (min_size, max_size) = (avg_size / 4, avg_size * 8);
+    let center_size = avg_size - (min_size + min_size.div_ceil(2));
+    let bits = avg_size.trailing_zeros();
+    let (mask_s, mask_l) = ((1u32 << (bits + 1)) - 1, (1u32 << (bits - 1)) - 1);
+    let mut lengths = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let mut pattern: u32 = 0;
+        let mut cut = rest.len().min(max_size);
+        for i in min_size.min(rest.len())..cut {
+            pattern = (pattern >> 1).wrapping_add(CDC_GEAR[rest[i] as usize]);
+            let mask = if i < center_size { mask_s } else { mask_l };
+            if pattern & mask == 0 {
+                cut = i + 1;
+                break;
+            }
+        }
+        lengths.push(cut);
+        rest = &rest[cut..];
+    }
+    lengths
+}
+
+fn chunk_lengths(data: &[u8], avg_size: usize) -> Vec<usize> {
+    let (chunks, tail) = cdc_chunks(data, false, avg_size).unwrap();
+    let mut lengths: Vec<usize> = chunks.iter().map(|chunk| chunk.len()).collect();
+    lengths.push(tail.len());
+    lengths
+}
+
+fn bench_cdc(c: &mut Criterion) {
+    let data = input();
+    for avg_size in [256, DATA_AVG_CHUNK_SIZE, 8192] {
+        assert_eq!(
+            chunk_lengths(&data, avg_size),
+            reference_lengths(&data, avg_size),
+            "chunk boundaries differ from the reference for an average of {avg_size}"
+        );
+    }
+
+    let mut group = c.benchmark_group("cdc");
+    group.throughput(Throughput::Bytes(INPUT_SIZE as u64));
+    group.sample_size(20);
+    group.bench_function("cdc_chunks", |b| {
+        b.iter(|| {
+            cdc_chunks(black_box(&data), false, DATA_AVG_CHUNK_SIZE)
+                .unwrap()
+                .0
+                .len()
+        })
+    });
+    group.bench_function("reference", |b| {
+        b.iter(|| reference_lengths(black_box(&data), DATA_AVG_CHUNK_SIZE).len())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_cdc);
+criterion_main!(benches);
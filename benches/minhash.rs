@@ -0,0 +1,34 @@
+//! Throughput of the Data-Code MinHash over chunk features.
+//!
+//! Run with `cargo bench --bench minhash`.
+
+use std::hint::black_box;
+
+use _core::minhash::minhash_256;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+/// Features of a highly fragmented 1 GiB input at 1 KiB per chunk
+const FEATURES: usize = 1024 * 1024;
+
+fn bench_minhash(c: &mut Criterion) {
+    let mut state: u32 = 0x2545_F491;
+    let features: Vec<u32> = (0..FEATURES)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("minhash");
+    group.throughput(Throughput::Elements(FEATURES as u64));
+    group.sample_size(20);
+    group.bench_function("minhash_256", |b| {
+        b.iter(|| minhash_256(black_box(&features)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_minhash);
+criterion_main!(benches);
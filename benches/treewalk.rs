@@ -0,0 +1,52 @@
+//! Ignore-aware directory walk over a large tree.
+//!
+//! Builds a tree of 100k empty files in 1000 directories with nested
+//! `.isccignore` files and walks it with `treewalk_ignore`.
+//! Run with `cargo bench --bench treewalk --features treewalk`.
+
+use std::fs;
+use std::hint::black_box;
+use std::path::Path;
+
+use _core::treewalk::treewalk_ignore;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const DIRS: usize = 1000;
+const FILES_PER_DIR: usize = 100;
+
+fn build_tree(root: &Path) {
+    fs::write(
+        root.join(".isccignore"),
+        "*.tmp\n*.bak\nbuild/\n/cache/\n**/generated/*.rs\n!keep.tmp\n",
+    )
+    .unwrap();
+    for d in 0..DIRS {
+        let dir = root
+            .join(format!("group{}", d / 100))
+            .join(format!("dir{d}"));
+        fs::create_dir_all(&dir).unwrap();
+        if d % 10 == 0 {
+            fs::write(dir.join(".isccignore"), "*.log\n!important.log\n").unwrap();
+        }
+        for f in 0..FILES_PER_DIR {
+            let extension = ["txt", "tmp", "log", "rs", "bak"][f % 5];
+            fs::write(dir.join(format!("file{f}.{extension}")), b"").unwrap();
+        }
+    }
+}
+
+fn bench_treewalk(c: &mut Criterion) {
+    let temp = tempfile::tempdir().unwrap();
+    build_tree(temp.path());
+
+    let mut group = c.benchmark_group("treewalk");
+    group.throughput(Throughput::Elements((DIRS * FILES_PER_DIR) as u64));
+    group.sample_size(10);
+    group.bench_function("treewalk_ignore_100k", |b| {
+        b.iter(|| treewalk_ignore(black_box(temp.path()), ".isccignore", None, None).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_treewalk);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+#ifndef ISCC_SUM_H
+#define ISCC_SUM_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Opaque incremental ISCC-SUM hasher
+ */
+typedef struct IsccSumProcessor IsccSumProcessor;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create a processor for standard (64-bit units) or wide (128-bit units)
+ * codes of `bits` length per unit (0 for the default of the mode), adding
+ * the units to the result if `add_units` is set.
+ *
+ * Returns NULL if the options are invalid. Release the processor with
+ * [`iscc_sum_free`].
+ */
+IsccSumProcessor *iscc_sum_new(bool wide, bool add_units, uint32_t bits);
+
+/**
+ * Feed the next `len` bytes at `data` to the processor.
+ *
+ * Returns false if `processor` is NULL, or `data` is NULL with a non-zero
+ * `len`.
+ *
+ * # Safety
+ *
+ * `processor` must be NULL or a live pointer from [`iscc_sum_new`], and
+ * `data` must be valid for reads of `len` bytes.
+ */
+bool iscc_sum_update(IsccSumProcessor *processor, const uint8_t *data, size_t len);
+
+/**
+ * Result for the content fed so far as a NUL-terminated JSON object with
+ * the keys `iscc`, `datahash`, `filesize` and, if requested, `units`.
+ *
+ * Returns NULL if `processor` is NULL. Release the string with
+ * [`iscc_sum_string_free`].
+ *
+ * # Safety
+ *
+ * `processor` must be NULL or a live pointer from [`iscc_sum_new`].
+ */
+char *iscc_sum_result_json(const IsccSumProcessor *processor);
+
+/**
+ * Release a string returned by this library; NULL is ignored.
+ *
+ * # Safety
+ *
+ * `string` must be NULL or a string from [`iscc_sum_result_json`] that was
+ * not released yet.
+ */
+void iscc_sum_string_free(char *string);
+
+/**
+ * Release a processor; NULL is ignored.
+ *
+ * # Safety
+ *
+ * `processor` must be NULL or a pointer from [`iscc_sum_new`] that was not
+ * released yet.
+ */
+void iscc_sum_free(IsccSumProcessor *processor);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ISCC_SUM_H */
@@ -0,0 +1,103 @@
+//! Hashing of tokio `AsyncRead` sources (`async` feature).
+//!
+//! Input is read asynchronously into a buffer while the previous buffer is
+//! hashed on tokio's blocking thread pool, so the CPU-heavy chunking and
+//! hashing never runs on (or stalls) the async worker threads. Must be called
+//! from within a tokio runtime.
+
+use std::io;
+use std::mem;
+use std::path::Path;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::task;
+
+use crate::error::{Error, Result};
+use crate::options::IsccSumOptions;
+use crate::sum::{IsccSumProcessor, IsccSumResult};
+
+/// Bytes handed to the blocking pool at once
+const ASYNC_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Fill the buffer as far as possible; returns fewer bytes only at the end of input
+async fn read_full<R: AsyncRead + Unpin>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+/// Generate ISCC-SUM for all bytes of an async reader
+pub async fn hash_async_reader<R: AsyncRead + Unpin>(
+    mut reader: R,
+    options: &IsccSumOptions,
+) -> Result<IsccSumResult> {
+    let processor = IsccSumProcessor::with_options(options.clone());
+    let mut buffer = vec![0u8; ASYNC_BUFFER_SIZE];
+    // The processor and a spare buffer travel through the hashing tasks
+    let mut hashing = task::spawn_blocking(move || (processor, vec![0u8; ASYNC_BUFFER_SIZE]));
+    loop {
+        options.check_cancelled()?;
+        // Read the next buffer while the previous one is hashed
+        let n = read_full(&mut reader, &mut buffer).await?;
+        let (mut processor, spare) = hashing.await.map_err(io::Error::other)?;
+        if n == 0 {
+            return Ok(processor.result());
+        }
+        let data = mem::replace(&mut buffer, spare);
+        hashing = task::spawn_blocking(move || {
+            processor.update(&data[..n]);
+            (processor, data)
+        });
+    }
+}
+
+/// Generate ISCC-SUM for the contents of a file, read asynchronously
+pub async fn hash_file_async<P: AsRef<Path>>(
+    path: P,
+    options: &IsccSumOptions,
+) -> Result<IsccSumResult> {
+    let path = path.as_ref();
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| Error::io(path, e))?;
+    hash_async_reader(file, options).await.map_err(|e| match e {
+        Error::Io { path: None, source } => Error::io(path, source),
+        e => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sum::hash_bytes;
+
+    #[test]
+    fn test_async_matches_sync() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let data: Vec<u8> = (0..2_500_000u32).map(|i| (i * 29 % 251) as u8).collect();
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        std::fs::write(&path, &data).unwrap();
+        let options = IsccSumOptions::builder().add_units(true).build().unwrap();
+        let expected = hash_bytes(&data, &options);
+
+        runtime.block_on(async {
+            assert_eq!(
+                hash_async_reader(&data[..], &options).await.unwrap(),
+                expected
+            );
+            assert_eq!(hash_file_async(&path, &options).await.unwrap(), expected);
+            let empty = hash_async_reader(&b""[..], &options).await.unwrap();
+            assert_eq!(empty, hash_bytes(b"", &options));
+            let missing = hash_file_async(dir.path().join("missing"), &options).await;
+            assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+        });
+    }
+}
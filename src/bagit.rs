@@ -0,0 +1,112 @@
+//! BagIt (RFC 8493) tag files with ISCC as a manifest algorithm.
+//!
+//! A bag is a directory with a `bagit.txt` declaration, the payload below
+//! `data/` and one `manifest-<algorithm>.txt` per checksum algorithm listing
+//! `<checksum> <path>` lines. `tagmanifest-<algorithm>.txt` lists the tag files
+//! (everything outside `data/` except the tag manifests themselves). ISCC
+//! manifests use `iscc` as the algorithm name and `ISCC:<code>` checksums.
+
+/// Name of the bag declaration file
+pub const DECLARATION_NAME: &str = "bagit.txt";
+
+/// Contents of a new bag declaration
+pub const DECLARATION: &str = "BagIt-Version: 1.0\nTag-File-Character-Encoding: UTF-8\n";
+
+/// Directory holding the payload
+pub const PAYLOAD_DIR: &str = "data";
+
+/// Payload manifest written by isum
+pub const PAYLOAD_MANIFEST: &str = "manifest-iscc.txt";
+
+/// Tag manifest written by isum
+pub const TAG_MANIFEST: &str = "tagmanifest-iscc.txt";
+
+/// Kind of a manifest file in the bag root
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ManifestKind {
+    Payload,
+    Tag,
+}
+
+/// Split a manifest file name into its kind and algorithm (`manifest-sha256.txt`)
+pub fn manifest_algorithm(file_name: &str) -> Option<(ManifestKind, &str)> {
+    let stem = file_name.strip_suffix(".txt")?;
+    let (kind, algorithm) = if let Some(algorithm) = stem.strip_prefix("tagmanifest-") {
+        (ManifestKind::Tag, algorithm)
+    } else {
+        (ManifestKind::Payload, stem.strip_prefix("manifest-")?)
+    };
+    (!algorithm.is_empty()).then_some((kind, algorithm))
+}
+
+/// Format a manifest line, percent-encoding `%`, CR and LF in the path
+pub fn format_manifest_line(checksum: &str, path: &str) -> String {
+    let path = path
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    format!("{checksum}  {path}")
+}
+
+/// Parse a manifest line into checksum and decoded path.
+///
+/// Returns `None` for malformed lines and for paths escaping the bag.
+pub fn parse_manifest_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let line = line.strip_prefix('\u{feff}').unwrap_or(line);
+    let (checksum, path) = line.split_once([' ', '\t'])?;
+    let path = path.trim_start_matches([' ', '\t']);
+    if checksum.is_empty() || path.is_empty() {
+        return None;
+    }
+    let path = path
+        .replace("%0A", "\n")
+        .replace("%0a", "\n")
+        .replace("%0D", "\r")
+        .replace("%0d", "\r")
+        .replace("%25", "%");
+    let escapes = path.starts_with('/')
+        || path
+            .split(['/', '\\'])
+            .any(|component| component == ".." || component.contains(':'));
+    (!escapes).then(|| (checksum.to_string(), path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_algorithm() {
+        assert_eq!(
+            manifest_algorithm("manifest-iscc.txt"),
+            Some((ManifestKind::Payload, "iscc"))
+        );
+        assert_eq!(
+            manifest_algorithm("tagmanifest-sha256.txt"),
+            Some((ManifestKind::Tag, "sha256"))
+        );
+        assert_eq!(manifest_algorithm("bag-info.txt"), None);
+        assert_eq!(manifest_algorithm("manifest-.txt"), None);
+    }
+
+    #[test]
+    fn test_manifest_line_roundtrip() {
+        let line = format_manifest_line("ISCC:K4AO", "data/100% new\nfile.txt");
+        assert_eq!(line, "ISCC:K4AO  data/100%25 new%0Afile.txt");
+        assert_eq!(
+            parse_manifest_line(&line),
+            Some((
+                "ISCC:K4AO".to_string(),
+                "data/100% new\nfile.txt".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_manifest_line("abc123 data/with space.txt\r\n"),
+            Some(("abc123".to_string(), "data/with space.txt".to_string()))
+        );
+        assert_eq!(parse_manifest_line("abc123"), None);
+        assert_eq!(parse_manifest_line("abc123 data/../../etc/passwd"), None);
+        assert_eq!(parse_manifest_line("abc123 /etc/passwd"), None);
+    }
+}
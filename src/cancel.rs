@@ -0,0 +1,101 @@
+//! Cooperative cancellation of long-running operations.
+//!
+//! A [`CancellationToken`] is handed to hashing (via
+//! [`crate::options::IsccSumOptionsBuilder::cancellation`]) or tree walking
+//! (see [`crate::treewalk::treewalk_ignore_cancellable`]). The operation checks
+//! it between buffers or directories and stops with [`Error::Cancelled`] or
+//! [`Error::DeadlineExceeded`], so embedding applications can abort cleanly
+//! from another thread or bound the run time.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+
+/// Shared flag (with an optional deadline) to stop operations early.
+///
+/// Clones share the flag, so cancelling one cancels all of them.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token that also expires at `deadline`
+    pub fn with_deadline(deadline: Instant) -> Self {
+        CancellationToken {
+            deadline: Some(deadline),
+            ..Self::default()
+        }
+    }
+
+    /// Token that also expires `timeout` from now
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self::with_deadline(Instant::now() + timeout)
+    }
+
+    /// Request cancellation; operations stop at their next check
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// True once cancelled or past the deadline
+    pub fn is_cancelled(&self) -> bool {
+        self.check().is_err()
+    }
+
+    /// Fail with [`Error::Cancelled`] or [`Error::DeadlineExceeded`] if the
+    /// operation should stop
+    pub fn check(&self) -> Result<()> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(Error::Cancelled);
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(Error::DeadlineExceeded);
+        }
+        Ok(())
+    }
+}
+
+/// Tokens are equal if they share the flag and the deadline
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled) && self.deadline == other.deadline
+    }
+}
+
+impl Eq for CancellationToken {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+        assert_eq!(token, clone);
+        assert_ne!(token, CancellationToken::new());
+        clone.cancel();
+        assert!(matches!(token.check(), Err(Error::Cancelled)));
+
+        let expired = CancellationToken::with_deadline(Instant::now());
+        assert!(matches!(expired.check(), Err(Error::DeadlineExceeded)));
+        let later = CancellationToken::with_timeout(Duration::from_secs(3600));
+        assert!(!later.is_cancelled());
+    }
+}
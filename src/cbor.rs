@@ -0,0 +1,145 @@
+//! Deterministic CBOR (RFC 8949) encoding of checksum manifests.
+//!
+//! Encoding follows the core deterministic encoding requirements: definite
+//! lengths, shortest integer and length arguments, and map keys sorted by the
+//! bytewise order of their encodings. Floats use the shortest of single and
+//! double precision that preserves the value. The same results therefore
+//! always produce the same bytes, so a manifest can itself be hashed and
+//! referenced.
+
+use serde_json::{json, Value};
+
+/// Schema identifier embedded in every CBOR manifest
+pub const MANIFEST_SCHEMA: &str = "iscc-sum-manifest";
+
+/// Version of the manifest record layout
+pub const MANIFEST_VERSION: u64 = 1;
+
+/// Encode a JSON value as deterministic CBOR
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_into(value, &mut out);
+    out
+}
+
+/// Encode a manifest document holding the given file records, sorted by path
+pub fn encode_manifest(mut files: Vec<Value>) -> Vec<u8> {
+    files.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+    encode(&json!({
+        "schema": MANIFEST_SCHEMA,
+        "version": MANIFEST_VERSION,
+        "files": files,
+    }))
+}
+
+/// Write a major type with its argument in the shortest form
+fn write_head(major: u8, argument: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match argument {
+        0..=23 => out.push(major | argument as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, argument as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(argument as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&argument.to_be_bytes());
+        }
+    }
+}
+
+fn encode_into(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Number(number) => {
+            if let Some(n) = number.as_u64() {
+                write_head(0, n, out);
+            } else if let Some(n) = number.as_i64() {
+                // Negative integers encode -1 - n
+                write_head(1, !(n as u64), out);
+            } else {
+                let float = number.as_f64().unwrap_or(f64::NAN);
+                if float as f32 as f64 == float {
+                    out.push(0xfa);
+                    out.extend_from_slice(&(float as f32).to_be_bytes());
+                } else {
+                    out.push(0xfb);
+                    out.extend_from_slice(&float.to_be_bytes());
+                }
+            }
+        }
+        Value::String(text) => {
+            write_head(3, text.len() as u64, out);
+            out.extend_from_slice(text.as_bytes());
+        }
+        Value::Array(items) => {
+            write_head(4, items.len() as u64, out);
+            for item in items {
+                encode_into(item, out);
+            }
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(Vec<u8>, &Value)> = map
+                .iter()
+                .map(|(key, value)| (encode(&Value::String(key.clone())), value))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            write_head(5, entries.len() as u64, out);
+            for (key, value) in entries {
+                out.extend_from_slice(&key);
+                encode_into(value, out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_rfc8949_examples() {
+        // Examples from RFC 8949 Appendix A
+        assert_eq!(encode(&json!(0)), [0x00]);
+        assert_eq!(encode(&json!(23)), [0x17]);
+        assert_eq!(encode(&json!(24)), [0x18, 0x18]);
+        assert_eq!(encode(&json!(1000)), [0x19, 0x03, 0xe8]);
+        assert_eq!(
+            encode(&json!(1_000_000_000_000u64)),
+            [0x1b, 0x00, 0x00, 0x00, 0xe8, 0xd4, 0xa5, 0x10, 0x00]
+        );
+        assert_eq!(encode(&json!(-1000)), [0x39, 0x03, 0xe7]);
+        assert_eq!(encode(&json!(100000.0)), [0xfa, 0x47, 0xc3, 0x50, 0x00]);
+        assert_eq!(encode(&json!("IETF")), [0x64, 0x49, 0x45, 0x54, 0x46]);
+        assert_eq!(encode(&json!([1, [2, 3]])), [0x82, 0x01, 0x82, 0x02, 0x03]);
+        assert_eq!(encode(&json!(null)), [0xf6]);
+        assert_eq!(encode(&json!(true)), [0xf5]);
+    }
+
+    #[test]
+    fn test_map_keys_sorted_by_encoding() {
+        // Shorter keys sort first regardless of insertion order
+        let value = json!({"bb": 1, "a": 2, "c": 3});
+        assert_eq!(
+            encode(&value),
+            [0xa3, 0x61, b'a', 0x02, 0x61, b'c', 0x03, 0x62, b'b', b'b', 0x01]
+        );
+    }
+
+    #[test]
+    fn test_manifest_is_deterministic() {
+        let a = json!({"path": "a.txt", "iscc": "ISCC:A", "filesize": 1});
+        let b = json!({"filesize": 2, "iscc": "ISCC:B", "path": "b.txt"});
+        let first = encode_manifest(vec![a.clone(), b.clone()]);
+        assert_eq!(first, encode_manifest(vec![b, a]));
+        // Map of three entries: files, schema, version
+        assert_eq!(first[0], 0xa3);
+    }
+}
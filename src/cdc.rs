@@ -4,53 +4,203 @@
 //! based on content patterns. The chunking is deterministic and content-aware, making it
 //! ideal for deduplication and similarity detection.
 
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+use xxhash_rust::xxh32::xxh32;
+
 use crate::constants::CDC_GEAR;
+use crate::error::{Error, Result};
 
 /// Default average chunk size for Data-Code
 pub const DATA_AVG_CHUNK_SIZE: usize = 1024;
 
-/// Calculate CDC parameters based on the desired average chunk size.
+/// Chunk sizes of the content-defined chunking.
 ///
-/// Returns: (min_size, max_size, center_size, mask_s, mask_l)
-#[inline]
-fn cdc_params(avg_size: usize) -> (usize, usize, usize, u32, u32) {
-    let min_size = avg_size / 4;
-    let max_size = avg_size * 8;
-    let offset = min_size + min_size.div_ceil(2);
-    let center_size = avg_size - offset;
-    let bits = (avg_size as f64).log2().round() as u32;
-    let mask = |b: u32| (1 << b) - 1;
-    let mask_s = mask(bits + 1);
-    let mask_l = mask(bits - 1);
-    (min_size, max_size, center_size, mask_s, mask_l)
+/// The default (1024 bytes on average, 256 minimum, 8192 maximum) is the one of
+/// the ISCC Data-Code. Other parameters yield Data-Codes that are not
+/// ISCC-standard and only comparable to codes built with the same parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CdcParams {
+    avg_size: usize,
+    min_size: usize,
+    max_size: usize,
 }
 
+impl Default for CdcParams {
+    fn default() -> Self {
+        Self::derived(DATA_AVG_CHUNK_SIZE)
+    }
+}
+
+impl CdcParams {
+    /// Parameters for an average chunk size with the minimum (a quarter) and
+    /// maximum (eight times) derived from it
+    pub fn new(avg_size: usize) -> Result<Self> {
+        Self::with_sizes(avg_size, avg_size / 4, avg_size * 8)
+    }
+
+    /// Parameters with explicit sizes. The average must be a power of two from
+    /// 64 to 65536, one and a half minimum sizes must not exceed it and the
+    /// maximum must be at least the average.
+    pub fn with_sizes(avg_size: usize, min_size: usize, max_size: usize) -> Result<Self> {
+        let invalid = |message: String| Err(Error::InvalidOptions(message));
+        if !avg_size.is_power_of_two() || !(64..=65536).contains(&avg_size) {
+            return invalid(format!(
+                "average chunk size must be a power of two from 64 to 65536, got {avg_size}"
+            ));
+        }
+        if min_size + min_size.div_ceil(2) > avg_size {
+            return invalid(format!(
+                "minimum chunk size {min_size} is too large for the average of {avg_size}"
+            ));
+        }
+        if max_size < avg_size || max_size > u32::MAX as usize {
+            return invalid(format!(
+                "maximum chunk size {max_size} is out of range for the average of {avg_size}"
+            ));
+        }
+        Ok(CdcParams {
+            avg_size,
+            min_size,
+            max_size,
+        })
+    }
+
+    /// Derived parameters for an average size that is known to be valid
+    pub(crate) fn derived(avg_size: usize) -> Self {
+        CdcParams {
+            avg_size,
+            min_size: avg_size / 4,
+            max_size: avg_size * 8,
+        }
+    }
+
+    pub fn avg_size(&self) -> usize {
+        self.avg_size
+    }
+
+    pub fn min_size(&self) -> usize {
+        self.min_size
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// True for the parameters of the ISCC Data-Code
+    pub fn is_standard(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns: (min_size, max_size, center_size, mask_s, mask_l)
+    #[inline]
+    fn cut_params(&self) -> (usize, usize, usize, u32, u32) {
+        let offset = self.min_size + self.min_size.div_ceil(2);
+        let center_size = self.avg_size - offset;
+        let bits = round_log2(self.avg_size);
+        let mask = |b: u32| (1 << b) - 1;
+        let mask_s = mask(bits + 1);
+        let mask_l = mask(bits - 1);
+        (self.min_size, self.max_size, center_size, mask_s, mask_l)
+    }
+}
+
+/// `log2(value)` rounded to the nearest integer, without floating point
+fn round_log2(value: usize) -> u32 {
+    let floor = value.ilog2();
+    // Round up from 2^(floor + 1/2), where the square reaches 2^(2 * floor + 1)
+    let square = value as u128 * value as u128;
+    floor + u32::from(square >= 1 << (2 * floor + 1))
+}
+
+/// Bytes hashed per block by [`scan`]
+const LANES: usize = 8;
+
+// `scan` relies on gear values below 2^31
+const _: () = {
+    let mut i = 0;
+    while i < CDC_GEAR.len() {
+        assert!(CDC_GEAR[i] < 1 << 31);
+        i += 1;
+    }
+};
+
 /// Find the offset for the next chunk boundary in the buffer.
 ///
-/// This function uses a rolling hash with the GEAR table to find content-defined
-/// chunk boundaries. It's optimized for performance with minimal branching.
+/// Hashing starts at the minimum chunk size (FastCDC cut-point skipping) and
+/// tests the stricter `mask_s` before the center size and the looser `mask_l`
+/// after it (FastCDC normalized chunking).
 #[inline(always)]
 fn cdc_offset(buffer: &[u8], mi: usize, ma: usize, cs: usize, mask_s: u32, mask_l: u32) -> usize {
     let size = buffer.len();
     let mut pattern: u32 = 0;
-    let mut i = std::cmp::min(mi, size);
-    let barrier = std::cmp::min(cs, size);
-    while i < barrier {
-        pattern = (pattern >> 1).wrapping_add(CDC_GEAR[buffer[i] as usize]);
-        if pattern & mask_s == 0 {
-            return i + 1;
+    let mut i = core::cmp::min(mi, size);
+    for (barrier, mask) in [(cs, mask_s), (ma, mask_l)] {
+        let barrier = core::cmp::min(barrier, size);
+        if let Some(cut) = scan(buffer, &mut i, barrier, mask, &mut pattern) {
+            return cut;
         }
-        i += 1;
     }
-    let barrier = std::cmp::min(ma, size);
-    while i < barrier {
-        pattern = (pattern >> 1).wrapping_add(CDC_GEAR[buffer[i] as usize]);
-        if pattern & mask_l == 0 {
-            return i + 1;
+    i
+}
+
+/// Roll the gear hash `pattern` over the bytes from `i` to `barrier` and
+/// return the end of the chunk at the first byte where `pattern & mask == 0`.
+///
+/// As gear values are below 2^31, `(pattern >> 1) + gear` never wraps, so the
+/// pattern after the bytes `b_0..=b_j` is `(pattern + w_j) >> (j + 1)` with
+/// `w_j = sum(gear(b_k) << (k + 1))`. Its masked bits are bits `j + 1` and up
+/// of the sum, all below bit 32, so each byte of a block of [`LANES`] is tested
+/// with a wrapping `u32` add and `& (mask << (j + 1))`. These lanes do not
+/// depend on each other and compile to SSE2/NEON code, instead of one shift
+/// and add after another. The bytes left over use the plain rolling hash; both
+/// yield identical boundaries.
+#[inline(always)]
+fn scan(
+    buffer: &[u8],
+    i: &mut usize,
+    barrier: usize,
+    mask: u32,
+    pattern: &mut u32,
+) -> Option<usize> {
+    let masks: [u32; LANES] = core::array::from_fn(|j| mask << (j + 1));
+    while *i + LANES <= barrier {
+        let block = &buffer[*i..*i + LANES];
+        let mut sums = [0u32; LANES];
+        let mut sum = 0u64;
+        for (j, &byte) in block.iter().enumerate() {
+            sum += (CDC_GEAR[byte as usize] as u64) << (j + 1);
+            sums[j] = sum as u32;
+        }
+        let mut hits = 0u32;
+        for j in 0..LANES {
+            hits |= ((pattern.wrapping_add(sums[j]) & masks[j] == 0) as u32) << j;
+        }
+        if hits != 0 {
+            return Some(*i + hits.trailing_zeros() as usize + 1);
+        }
+        *pattern = ((*pattern as u64 + sum) >> LANES) as u32;
+        *i += LANES;
+    }
+    while *i < barrier {
+        *pattern = (*pattern >> 1).wrapping_add(CDC_GEAR[buffer[*i] as usize]);
+        *i += 1;
+        if *pattern & mask == 0 {
+            return Some(*i);
         }
-        i += 1;
     }
-    i
+    None
+}
+
+/// Length of the next Data-Code chunk at the start of `data`, if its boundary
+/// does not depend on bytes beyond the end of `data`.
+pub(crate) fn final_data_cut(data: &[u8], params: &CdcParams) -> Option<usize> {
+    let (mi, ma, cs, mask_s, mask_l) = params.cut_params();
+    let cut = cdc_offset(data, mi, ma, cs, mask_s, mask_l);
+    (cut < data.len()).then_some(cut)
 }
 
 /// Split data into content-defined chunks.
@@ -62,12 +212,27 @@ fn cdc_offset(buffer: &[u8], mi: usize, ma: usize, cs: usize, mask_s: u32, mask_
 /// # Arguments
 /// * `data` - The input data to be chunked
 /// * `utf32` - If true, ensures chunk boundaries align to 4-byte boundaries for UTF-32
-/// * `avg_chunk_size` - The target average size for chunks
+/// * `avg_chunk_size` - The target average size for chunks, a power of two
+///   from 64 to 65536 (see [`CdcParams::new`])
 ///
 /// # Returns
-/// A tuple of (complete_chunks, tail_chunk)
-pub fn cdc_chunks(data: &[u8], utf32: bool, avg_chunk_size: usize) -> (Vec<&[u8]>, &[u8]) {
-    let (mi, ma, cs, mask_s, mask_l) = cdc_params(avg_chunk_size);
+/// A tuple of (complete_chunks, tail_chunk), or [`Error::InvalidOptions`] for
+/// an unsupported average size
+pub fn cdc_chunks(data: &[u8], utf32: bool, avg_chunk_size: usize) -> Result<(Vec<&[u8]>, &[u8])> {
+    Ok(cdc_chunks_with(
+        data,
+        utf32,
+        &CdcParams::new(avg_chunk_size)?,
+    ))
+}
+
+/// Split data into content-defined chunks like [`cdc_chunks`], with explicit chunk sizes
+pub fn cdc_chunks_with<'a>(
+    data: &'a [u8],
+    utf32: bool,
+    params: &CdcParams,
+) -> (Vec<&'a [u8]>, &'a [u8]) {
+    let (mi, ma, cs, mask_s, mask_l) = params.cut_params();
     let mut chunks = Vec::new();
     let mut buffer = data;
     while !buffer.is_empty() {
@@ -90,13 +255,241 @@ pub fn cdc_chunks(data: &[u8], utf32: bool, avg_chunk_size: usize) -> (Vec<&[u8]
     (chunks, tail)
 }
 
+/// Iterator over the content-defined chunks of a byte slice.
+///
+/// Yields `(offset, length, bytes)` for every chunk including the last one, so
+/// the chunks cover the whole input; an empty input yields no chunks.
+#[derive(Debug, Clone)]
+pub struct ChunkerIter<'a> {
+    data: &'a [u8],
+    offset: u64,
+    params: CdcParams,
+}
+
+impl<'a> ChunkerIter<'a> {
+    /// Chunks as cut by the ISCC Data-Code
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::with_params(data, CdcParams::default())
+    }
+
+    pub fn with_params(data: &'a [u8], params: CdcParams) -> Self {
+        ChunkerIter {
+            data,
+            offset: 0,
+            params,
+        }
+    }
+}
+
+impl<'a> Iterator for ChunkerIter<'a> {
+    type Item = (u64, usize, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let (mi, ma, cs, mask_s, mask_l) = self.params.cut_params();
+        let cut = cdc_offset(self.data, mi, ma, cs, mask_s, mask_l);
+        let (chunk, rest) = self.data.split_at(cut);
+        let offset = self.offset;
+        self.data = rest;
+        self.offset += cut as u64;
+        Some((offset, cut, chunk))
+    }
+}
+
+/// Lazy chunker over a reader, yielding the same chunks as [`ChunkerIter`].
+///
+/// Chunks borrow from an internal buffer, so they are taken one at a time with
+/// [`Chunker::next_chunk`] instead of through `Iterator`. Memory use is
+/// bounded by the larger of 1 MiB and twice the maximum chunk size.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Chunker<R> {
+    reader: R,
+    params: CdcParams,
+    buffer: Vec<u8>,
+    /// Start of the unchunked bytes in `buffer`
+    start: usize,
+    /// End of the bytes read into `buffer`
+    end: usize,
+    eof: bool,
+    offset: u64,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Chunker<R> {
+    /// Chunks as cut by the ISCC Data-Code
+    pub fn new(reader: R) -> Self {
+        Self::with_params(reader, CdcParams::default())
+    }
+
+    pub fn with_params(reader: R, params: CdcParams) -> Self {
+        let size = (1024 * 1024).max(2 * params.max_size());
+        Chunker {
+            reader,
+            params,
+            buffer: vec![0; size],
+            start: 0,
+            end: 0,
+            eof: false,
+            offset: 0,
+        }
+    }
+
+    /// Read up to the next chunk boundary and return `(offset, length, bytes)`,
+    /// or `None` at the end of the stream
+    pub fn next_chunk(&mut self) -> io::Result<Option<(u64, usize, &[u8])>> {
+        // A boundary is certain once a maximum chunk size is at hand
+        while !self.eof && self.end - self.start < self.params.max_size() {
+            if self.end == self.buffer.len() {
+                self.buffer.copy_within(self.start..self.end, 0);
+                self.end -= self.start;
+                self.start = 0;
+            }
+            match self.reader.read(&mut self.buffer[self.end..]) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.end += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        if self.start == self.end {
+            return Ok(None);
+        }
+        let (mi, ma, cs, mask_s, mask_l) = self.params.cut_params();
+        let cut = cdc_offset(
+            &self.buffer[self.start..self.end],
+            mi,
+            ma,
+            cs,
+            mask_s,
+            mask_l,
+        );
+        let chunk = &self.buffer[self.start..self.start + cut];
+        let offset = self.offset;
+        self.start += cut;
+        self.offset += cut as u64;
+        Ok(Some((offset, cut, chunk)))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// A content-defined chunk of a stream as seen by the Data-Code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataChunk {
+    /// Byte offset of the chunk in the stream
+    pub offset: u64,
+    /// Chunk length in bytes
+    pub length: usize,
+    /// xxHash32 of the chunk, the feature fed into the Data-Code MinHash
+    pub feature: u32,
+}
+
+/// Streaming chunker producing the same chunk boundaries and features as the Data-Code
+#[derive(Debug, Default)]
+pub struct DataChunker {
+    tail: Vec<u8>,
+    offset: u64,
+}
+
+impl DataChunker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Push more data and return the chunks completed by it
+    pub fn push(&mut self, data: &[u8]) -> Vec<DataChunk> {
+        let mut completed = Vec::new();
+        self.push_with(data, |chunk, _| completed.push(chunk));
+        completed
+    }
+
+    /// Push more data and pass each completed chunk with its bytes to `f`
+    pub fn push_with<F: FnMut(DataChunk, &[u8])>(&mut self, data: &[u8], mut f: F) {
+        self.tail.extend_from_slice(data);
+        let (chunks, tail) = cdc_chunks_with(&self.tail, false, &CdcParams::default());
+        for chunk in chunks {
+            f(
+                DataChunk {
+                    offset: self.offset,
+                    length: chunk.len(),
+                    feature: xxh32(chunk, 0),
+                },
+                chunk,
+            );
+            self.offset += chunk.len() as u64;
+        }
+        self.tail = tail.to_vec();
+    }
+
+    /// Return the final (possibly empty) tail chunk
+    pub fn finish(self) -> DataChunk {
+        self.finish_with(|_, _| {})
+    }
+
+    /// Pass the final (possibly empty) tail chunk with its bytes to `f` and return it
+    pub fn finish_with<F: FnOnce(DataChunk, &[u8])>(self, f: F) -> DataChunk {
+        let chunk = DataChunk {
+            offset: self.offset,
+            length: self.tail.len(),
+            feature: xxh32(&self.tail, 0),
+        };
+        f(chunk, &self.tail);
+        chunk
+    }
+}
+
+/// Read a stream to the end and pass each Data-Code chunk with its bytes to `f`.
+///
+/// The final tail chunk is included even if empty, so the chunk features are
+/// exactly the Data-Code MinHash input.
+#[cfg(feature = "std")]
+pub fn for_each_data_chunk<R, F>(reader: &mut R, mut f: F) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(DataChunk, &[u8]),
+{
+    let mut chunker = DataChunker::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => chunker.push_with(&buffer[..n], &mut f),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    chunker.finish_with(f);
+    Ok(())
+}
+
+/// Read a stream to the end and return all of its Data-Code chunks (see [`for_each_data_chunk`])
+#[cfg(feature = "std")]
+pub fn read_data_chunks<R: Read>(reader: &mut R) -> io::Result<Vec<DataChunk>> {
+    let mut chunks = Vec::new();
+    for_each_data_chunk(reader, |chunk, _| chunks.push(chunk))?;
+    Ok(chunks)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_round_log2() {
+        for value in (1..100_000).chain([1 << 40, (1 << 40) + (1 << 39)]) {
+            assert_eq!(round_log2(value), (value as f64).log2().round() as u32);
+        }
+    }
 
     #[test]
     fn test_cdc_params() {
-        let (min, max, center, mask_s, mask_l) = cdc_params(1024);
+        let (min, max, center, mask_s, mask_l) = CdcParams::derived(1024).cut_params();
         assert_eq!(min, 256);
         assert_eq!(max, 8192);
         assert_eq!(center, 640);
@@ -104,10 +497,110 @@ mod tests {
         assert_eq!(mask_l, 511);
     }
 
+    #[test]
+    fn test_cdc_params_validation() {
+        assert!(CdcParams::default().is_standard());
+        assert_eq!(CdcParams::new(1024).unwrap(), CdcParams::default());
+        let params = CdcParams::with_sizes(1024, 128, 2048).unwrap();
+        assert!(!params.is_standard());
+        assert_eq!(params.cut_params(), (128, 2048, 832, 2047, 511));
+        assert!(CdcParams::new(1000).is_err());
+        assert!(CdcParams::with_sizes(1024, 700, 8192).is_err());
+        assert!(CdcParams::with_sizes(1024, 256, 512).is_err());
+        // The raw chunking functions validate as well; masks of huge averages overflow
+        assert!(cdc_chunks(b"data", false, 1000).is_err());
+        assert!(cdc_chunks(b"data", false, 1 << 23).is_err());
+
+        // Chunks respect the explicit limits
+        let data: Vec<u8> = (0..100_000u32).map(|i| ((i * 7919) >> 3) as u8).collect();
+        let (chunks, _) = cdc_chunks_with(&data, false, &params);
+        assert!(chunks.iter().all(|c| (129..=2048).contains(&c.len())));
+    }
+
+    #[test]
+    fn test_block_scan_matches_rolling_hash() {
+        let rolling = |buffer: &[u8], params: &CdcParams| {
+            let (mi, ma, cs, mask_s, mask_l) = params.cut_params();
+            let mut pattern: u32 = 0;
+            let end = buffer.len().min(ma);
+            for i in mi.min(end)..end {
+                pattern = (pattern >> 1).wrapping_add(CDC_GEAR[buffer[i] as usize]);
+                if pattern & if i < cs { mask_s } else { mask_l } == 0 {
+                    return i + 1;
+                }
+            }
+            end
+        };
+        let mut state: u32 = 1;
+        let data: Vec<u8> = (0..200_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        for params in [
+            CdcParams::default(),
+            CdcParams::new(64).unwrap(),
+            CdcParams::new(65536).unwrap(),
+            CdcParams::with_sizes(1024, 0, 1024).unwrap(),
+            CdcParams::with_sizes(256, 3, 5000).unwrap(),
+        ] {
+            let (mi, ma, cs, mask_s, mask_l) = params.cut_params();
+            let mut pos = 0;
+            while pos < data.len() {
+                let buffer = &data[pos..];
+                let cut = cdc_offset(buffer, mi, ma, cs, mask_s, mask_l);
+                assert_eq!(cut, rolling(buffer, &params), "{params:?} at {pos}");
+                // Also start at unaligned offsets within the chunk
+                pos += cut.clamp(1, 997);
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chunker() {
+        /// Reader returning at most 1000 bytes per read
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = buf.len().min(1000).min(self.0.len());
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+
+        let data: Vec<u8> = (0..3_000_000u32)
+            .map(|i| ((i * 31) ^ (i >> 5)) as u8)
+            .collect();
+        let (chunks, tail) = cdc_chunks(&data, false, DATA_AVG_CHUNK_SIZE).unwrap();
+        let mut expected: Vec<&[u8]> = chunks;
+        expected.push(tail);
+        let from_slice: Vec<_> = ChunkerIter::new(&data).collect();
+        assert_eq!(from_slice.len(), expected.len());
+        let mut offset = 0;
+        for ((chunk_offset, length, bytes), chunk) in from_slice.iter().zip(&expected) {
+            assert_eq!(
+                (*chunk_offset, *length, *bytes),
+                (offset, chunk.len(), *chunk)
+            );
+            offset += *length as u64;
+        }
+
+        let mut chunker = Chunker::new(Trickle(&data));
+        for expected in &from_slice {
+            assert_eq!(chunker.next_chunk().unwrap(), Some(*expected));
+        }
+        assert_eq!(chunker.next_chunk().unwrap(), None);
+        assert_eq!(ChunkerIter::new(b"").count(), 0);
+        assert_eq!(Chunker::new(&b""[..]).next_chunk().unwrap(), None);
+    }
+
     #[test]
     fn test_cdc_chunks_empty() {
         let data = b"";
-        let (chunks, tail) = cdc_chunks(data, false, 1024);
+        let (chunks, tail) = cdc_chunks(data, false, 1024).unwrap();
         assert_eq!(chunks.len(), 0);
         assert_eq!(tail.len(), 0);
     }
@@ -115,7 +608,7 @@ mod tests {
     #[test]
     fn test_cdc_chunks_small() {
         let data = b"Hello, World!";
-        let (chunks, tail) = cdc_chunks(data, false, 1024);
+        let (chunks, tail) = cdc_chunks(data, false, 1024).unwrap();
         assert_eq!(chunks.len(), 0);
         assert_eq!(tail, data);
     }
@@ -123,8 +616,8 @@ mod tests {
     #[test]
     fn test_cdc_chunks_deterministic() {
         let data = vec![0u8; 10000];
-        let (chunks1, tail1) = cdc_chunks(&data, false, 1024);
-        let (chunks2, tail2) = cdc_chunks(&data, false, 1024);
+        let (chunks1, tail1) = cdc_chunks(&data, false, 1024).unwrap();
+        let (chunks2, tail2) = cdc_chunks(&data, false, 1024).unwrap();
 
         assert_eq!(chunks1.len(), chunks2.len());
         assert_eq!(tail1, tail2);
@@ -143,7 +636,7 @@ mod tests {
             *item = (i % 256) as u8;
         }
 
-        let (chunks, _) = cdc_chunks(&data, true, 64);
+        let (chunks, _) = cdc_chunks(&data, true, 64).unwrap();
 
         // Verify all chunks are 4-byte aligned
         for chunk in &chunks {
@@ -155,4 +648,31 @@ mod tests {
             );
         }
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_data_chunks_matches_data_hasher() {
+        use crate::data::DataHasher;
+        use crate::minhash::minhash_256;
+
+        let data: Vec<u8> = (0..50_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        let chunks = read_data_chunks(&mut data.as_slice()).unwrap();
+
+        // Chunks are contiguous and cover the whole input
+        let mut offset = 0;
+        for chunk in &chunks {
+            assert_eq!(chunk.offset, offset);
+            offset += chunk.length as u64;
+        }
+        assert_eq!(offset, data.len() as u64);
+
+        let mut hasher = DataHasher::new();
+        for part in data.chunks(777) {
+            hasher.push(part);
+        }
+        let features: Vec<u32> = chunks.iter().map(|c| c.feature).collect();
+        assert_eq!(minhash_256(&features), hasher.digest());
+    }
 }
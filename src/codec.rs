@@ -0,0 +1,747 @@
+//! Typed ISCC codes with header decoding.
+//!
+//! An ISCC is a header followed by a body, base32-encoded (RFC 4648, no
+//! padding) and usually written with an `ISCC:` prefix. The header holds the
+//! main type, subtype, version and length fields, each encoded as a
+//! variable-length nibble sequence and padded to a whole byte. For units the
+//! length field gives the body size (`bits / 32 - 1`); for composite codes it
+//! is a bitmap of the optional units, and the subtype decides the unit size.
+//!
+//! Composite codes are split into their units with [`IsccCode::units`] and
+//! built from units with [`compose`], following the codec rules of
+//! `iscc-core`: the body holds the optional Meta, Semantic and Content units
+//! followed by the Data and Instance units, 64 bits each (128 bits each for
+//! the wide ISCC-SUM).
+//!
+//! Wide ISCC-SUMs may also hold 192- or 256-bit units. As `iscc-core` defines
+//! only 128-bit wide codes, this is an extension: the length field of a wide
+//! composite holds the unit size beyond 128 bits in steps of 64 bits (0, 1 or
+//! 2), so standard wide codes are unchanged.
+//!
+//! [`compare`] measures the Hamming distance between the units two codes
+//! have in common.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "python")]
+use pyo3::exceptions::PyValueError;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::PyDict;
+
+/// Prefix of the canonical string form
+pub const PREFIX: &str = "ISCC:";
+
+/// Composite subtype of an ISCC-SUM (64-bit Data-Code and Instance-Code)
+pub const SUBTYPE_SUM: u32 = 0b0101;
+
+/// Composite subtype without Semantic or Content units
+pub const SUBTYPE_NONE: u32 = 0b0110;
+
+/// Composite subtype of a wide ISCC-SUM (128-bit or longer Data-Code and Instance-Code)
+pub const SUBTYPE_WIDE: u32 = 0b0111;
+
+/// Unit lengths of ISCC-SUM composites in bits
+pub const SUM_BITS: [u32; 4] = [64, 128, 192, 256];
+
+/// Main type of an ISCC, the first header field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MainType {
+    Meta,
+    Semantic,
+    Content,
+    Data,
+    Instance,
+    /// Composite of several units
+    Iscc,
+    Id,
+    Flake,
+}
+
+impl MainType {
+    const ALL: [MainType; 8] = [
+        MainType::Meta,
+        MainType::Semantic,
+        MainType::Content,
+        MainType::Data,
+        MainType::Instance,
+        MainType::Iscc,
+        MainType::Id,
+        MainType::Flake,
+    ];
+
+    /// Main type for a header field value
+    pub fn from_value(value: u32) -> Option<Self> {
+        Self::ALL.get(value as usize).copied()
+    }
+
+    /// Header field value of the main type
+    pub fn value(self) -> u32 {
+        self as u32
+    }
+
+    /// Lowercase name of the main type (`data`, `instance`, ...)
+    pub fn name(self) -> &'static str {
+        match self {
+            MainType::Meta => "meta",
+            MainType::Semantic => "semantic",
+            MainType::Content => "content",
+            MainType::Data => "data",
+            MainType::Instance => "instance",
+            MainType::Iscc => "iscc",
+            MainType::Id => "id",
+            MainType::Flake => "flake",
+        }
+    }
+}
+
+/// Reasons a string or byte sequence is not a valid ISCC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    /// Not valid base32
+    InvalidBase32,
+    /// The input ends inside the header
+    TruncatedHeader,
+    /// The main type is not defined by the ISCC specification
+    UnknownMainType(u32),
+    /// A header field value is too large to encode
+    FieldOverflow(u32),
+    /// The body does not have the size the header declares
+    BodyLength { expected: usize, actual: usize },
+    /// The units cannot be combined into a composite code
+    InvalidUnits(&'static str),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::InvalidBase32 => write!(f, "invalid base32 encoding"),
+            CodecError::TruncatedHeader => write!(f, "truncated ISCC header"),
+            CodecError::UnknownMainType(value) => write!(f, "unknown main type {value}"),
+            CodecError::FieldOverflow(value) => write!(f, "header field value {value} too large"),
+            CodecError::BodyLength { expected, actual } => write!(
+                f,
+                "body has {} bits, header declares {} bits",
+                actual * 8,
+                expected * 8
+            ),
+            CodecError::InvalidUnits(reason) => write!(f, "invalid units: {reason}"),
+        }
+    }
+}
+
+impl core::error::Error for CodecError {}
+
+/// A decoded ISCC: header fields and body
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IsccCode {
+    maintype: MainType,
+    subtype: u32,
+    version: u32,
+    length: u32,
+    body: Vec<u8>,
+}
+
+impl IsccCode {
+    /// Create a code from header fields and body, checking the body size
+    pub fn new(
+        maintype: MainType,
+        subtype: u32,
+        version: u32,
+        length: u32,
+        body: Vec<u8>,
+    ) -> Result<Self, CodecError> {
+        let code = IsccCode {
+            maintype,
+            subtype,
+            version,
+            length,
+            body,
+        };
+        // Reject field values the header cannot hold
+        code.header()?;
+        code.check_body()?;
+        Ok(code)
+    }
+
+    /// Decode a code from its binary form (header and body)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        let mut nibbles = Nibbles { bytes, position: 0 };
+        let maintype = nibbles.read_field()?;
+        let subtype = nibbles.read_field()?;
+        let version = nibbles.read_field()?;
+        let length = nibbles.read_field()?;
+        // The header is padded to a whole byte
+        let header_len = nibbles.position.div_ceil(2);
+        let maintype =
+            MainType::from_value(maintype).ok_or(CodecError::UnknownMainType(maintype))?;
+        let code = IsccCode {
+            maintype,
+            subtype,
+            version,
+            length,
+            body: bytes[header_len..].to_vec(),
+        };
+        code.check_body()?;
+        Ok(code)
+    }
+
+    /// Binary form of the code (header and body)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // Field values were validated on construction
+        let mut bytes = self.header().unwrap_or_default();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+
+    pub fn maintype(&self) -> MainType {
+        self.maintype
+    }
+
+    pub fn subtype(&self) -> u32 {
+        self.subtype
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Raw length field: body size for units, optional unit bitmap for composites
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Size of the body in bits
+    pub fn bits(&self) -> usize {
+        self.body.len() * 8
+    }
+
+    /// Split a composite code into its units; other codes are their only unit.
+    ///
+    /// Units carry version 0. Semantic and Content units take the subtype of
+    /// the composite, all other units subtype 0.
+    pub fn units(&self) -> Vec<IsccCode> {
+        if self.maintype != MainType::Iscc {
+            return vec![self.clone()];
+        }
+        let mut maintypes = Vec::new();
+        if !matches!(self.subtype, SUBTYPE_SUM | SUBTYPE_WIDE) {
+            for (bit, maintype) in OPTIONAL_UNITS {
+                if self.length & bit != 0 {
+                    maintypes.push(maintype);
+                }
+            }
+        }
+        maintypes.extend([MainType::Data, MainType::Instance]);
+
+        let unit_len = self.composite_unit_len();
+        maintypes
+            .into_iter()
+            .zip(self.body.chunks_exact(unit_len))
+            .map(|(maintype, body)| {
+                let subtype = match maintype {
+                    MainType::Semantic | MainType::Content => self.subtype,
+                    _ => 0,
+                };
+                IsccCode {
+                    maintype,
+                    subtype,
+                    version: 0,
+                    length: (unit_len / 4 - 1) as u32,
+                    body: body.to_vec(),
+                }
+            })
+            .collect()
+    }
+
+    /// Encoded header bytes
+    fn header(&self) -> Result<Vec<u8>, CodecError> {
+        let mut nibbles = Vec::new();
+        for value in [
+            self.maintype.value(),
+            self.subtype,
+            self.version,
+            self.length,
+        ] {
+            write_field(value, &mut nibbles)?;
+        }
+        if nibbles.len() % 2 == 1 {
+            nibbles.push(0);
+        }
+        Ok(nibbles
+            .chunks(2)
+            .map(|pair| (pair[0] << 4) | pair[1])
+            .collect())
+    }
+
+    /// Size of the units of a composite in bytes
+    fn composite_unit_len(&self) -> usize {
+        match self.subtype {
+            SUBTYPE_WIDE => 16 + 8 * self.length as usize,
+            _ => 8,
+        }
+    }
+
+    /// Body size in bytes implied by the header, if the main type defines one
+    fn expected_body_len(&self) -> Option<usize> {
+        match self.maintype {
+            MainType::Meta
+            | MainType::Semantic
+            | MainType::Content
+            | MainType::Data
+            | MainType::Instance
+            | MainType::Flake => Some((self.length as usize + 1) * 4),
+            MainType::Iscc => match self.subtype {
+                SUBTYPE_SUM => Some(16),
+                SUBTYPE_WIDE => Some(2 * self.composite_unit_len()),
+                // Data and Instance units plus the optional units, 64 bits each
+                _ => Some((2 + self.length.count_ones() as usize) * 8),
+            },
+            MainType::Id => None,
+        }
+    }
+
+    fn check_body(&self) -> Result<(), CodecError> {
+        if self.maintype == MainType::Iscc && self.subtype == SUBTYPE_WIDE && self.length > 2 {
+            return Err(CodecError::FieldOverflow(self.length));
+        }
+        match self.expected_body_len() {
+            Some(expected) if expected != self.body.len() => Err(CodecError::BodyLength {
+                expected,
+                actual: self.body.len(),
+            }),
+            None if self.body.is_empty() => Err(CodecError::BodyLength {
+                expected: 8,
+                actual: 0,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for IsccCode {
+    type Err = CodecError;
+
+    /// Parse a code with or without the `ISCC:` prefix
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let code = code.trim();
+        let code = code.strip_prefix(PREFIX).unwrap_or(code);
+        let bytes = base32::decode(base32::Alphabet::Rfc4648 { padding: false }, code)
+            .filter(|_| !code.is_empty())
+            .ok_or(CodecError::InvalidBase32)?;
+        IsccCode::from_bytes(&bytes)
+    }
+}
+
+impl fmt::Display for IsccCode {
+    /// Canonical form with `ISCC:` prefix
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoded = base32::encode(
+            base32::Alphabet::Rfc4648 { padding: false },
+            &self.to_bytes(),
+        );
+        write!(f, "{PREFIX}{encoded}")
+    }
+}
+
+/// Optional composite units by their bit in the length field, in body order
+const OPTIONAL_UNITS: [(u32, MainType); 3] = [
+    (0b100, MainType::Meta),
+    (0b010, MainType::Semantic),
+    (0b001, MainType::Content),
+];
+
+/// Build a composite code from units.
+///
+/// Data-Code and Instance-Code units are required, Meta, Semantic and Content
+/// units are optional; each main type may appear once, in any order. Units are
+/// truncated to `bits`, one of [`SUM_BITS`]. Composites of more than 64 bits
+/// are wide ISCC-SUMs built from exactly a Data-Code and an Instance-Code.
+pub fn compose(units: &[IsccCode], bits: u32) -> Result<IsccCode, CodecError> {
+    if !SUM_BITS.contains(&bits) {
+        return Err(CodecError::InvalidUnits("bits must be 64, 128, 192 or 256"));
+    }
+    let wide = bits > 64;
+    let mut units: Vec<&IsccCode> = units.iter().collect();
+    units.sort_by_key(|unit| unit.maintype);
+    let maintypes: Vec<MainType> = units.iter().map(|unit| unit.maintype).collect();
+    if maintypes.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err(CodecError::InvalidUnits("duplicate unit type"));
+    }
+    if !maintypes.ends_with(&[MainType::Data, MainType::Instance]) {
+        return Err(CodecError::InvalidUnits(
+            "Data-Code and Instance-Code units are required",
+        ));
+    }
+    if units.iter().any(|unit| {
+        matches!(
+            unit.maintype,
+            MainType::Iscc | MainType::Id | MainType::Flake
+        )
+    }) {
+        return Err(CodecError::InvalidUnits("only units can be composed"));
+    }
+
+    let unit_len = bits as usize / 8;
+    if wide && units.len() != 2 {
+        return Err(CodecError::InvalidUnits(
+            "wide codes hold only Data-Code and Instance-Code units",
+        ));
+    }
+    if units.iter().any(|unit| unit.body.len() < unit_len) {
+        return Err(CodecError::InvalidUnits("unit too short"));
+    }
+
+    // Semantic and Content units must agree on the subtype of the composite
+    let mut subtypes = units
+        .iter()
+        .filter(|unit| matches!(unit.maintype, MainType::Semantic | MainType::Content))
+        .map(|unit| unit.subtype);
+    let subtype = match subtypes.next() {
+        Some(subtype) if subtypes.all(|other| other == subtype) => subtype,
+        Some(_) => return Err(CodecError::InvalidUnits("unit subtypes differ")),
+        None if wide => SUBTYPE_WIDE,
+        None if units.len() == 2 => SUBTYPE_SUM,
+        None => SUBTYPE_NONE,
+    };
+    let length = if wide {
+        (bits - 128) / 64
+    } else {
+        OPTIONAL_UNITS
+            .iter()
+            .filter(|(_, maintype)| maintypes.contains(maintype))
+            .map(|(bit, _)| bit)
+            .sum()
+    };
+    let body = units
+        .iter()
+        .flat_map(|unit| &unit.body[..unit_len])
+        .copied()
+        .collect();
+    IsccCode::new(MainType::Iscc, subtype, 0, length, body)
+}
+
+/// Bit distance between the units of one main type in two codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnitDistance {
+    pub maintype: MainType,
+    /// Number of differing bits
+    pub distance: u32,
+    /// Number of compared bits (the shorter of the two units)
+    pub bits: u32,
+}
+
+/// Result of comparing two codes, see [`compare`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    /// Distances of the units present in both codes, in unit order
+    pub units: Vec<UnitDistance>,
+    /// Similarity from 0.0 (unrelated) to 1.0 (same content)
+    pub similarity: f64,
+}
+
+impl Comparison {
+    /// Distance of the units of the given main type, if both codes have one
+    pub fn distance(&self, maintype: MainType) -> Option<u32> {
+        self.units
+            .iter()
+            .find(|unit| unit.maintype == maintype)
+            .map(|unit| unit.distance)
+    }
+}
+
+/// Compare the units two codes have in common by Hamming distance.
+///
+/// Composite codes are split into units and units are matched by main type;
+/// units of different lengths are compared on the shorter prefix. The
+/// similarity is the share of matching bits over all similarity-preserving
+/// units (every unit but the Instance-Code). An Instance-Code is a
+/// cryptographic hash, so if it is the only common unit the similarity is 1.0
+/// for equal and 0.0 for different codes. Codes without common units have a
+/// similarity of 0.0.
+pub fn compare(a: &IsccCode, b: &IsccCode) -> Comparison {
+    let b_units = b.units();
+    let units: Vec<UnitDistance> = a
+        .units()
+        .iter()
+        .filter_map(|unit| {
+            let other = b_units
+                .iter()
+                .find(|other| other.maintype == unit.maintype)?;
+            let len = unit.body.len().min(other.body.len());
+            Some(UnitDistance {
+                maintype: unit.maintype,
+                distance: unit.body[..len]
+                    .iter()
+                    .zip(&other.body[..len])
+                    .map(|(x, y)| (x ^ y).count_ones())
+                    .sum(),
+                bits: len as u32 * 8,
+            })
+        })
+        .collect();
+
+    let (distance, bits) = units
+        .iter()
+        .filter(|unit| unit.maintype != MainType::Instance)
+        .fold((0, 0), |(distance, bits), unit| {
+            (distance + unit.distance, bits + unit.bits)
+        });
+    let similarity = if bits > 0 {
+        1.0 - distance as f64 / bits as f64
+    } else if units.is_empty() {
+        0.0
+    } else if units[0].distance == 0 {
+        1.0
+    } else {
+        0.0
+    };
+    Comparison { units, similarity }
+}
+
+/// Compare two ISCC codes (Python-exposed function).
+///
+/// Returns a dict with the `similarity` and the `distances` and compared
+/// `bits` of the common units keyed by unit name (`data`, `instance`, ...).
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "compare")]
+pub fn py_compare<'py>(py: Python<'py>, a: &str, b: &str) -> PyResult<Bound<'py, PyDict>> {
+    let parse = |code: &str| {
+        code.parse::<IsccCode>()
+            .map_err(|e| PyValueError::new_err(format!("Invalid ISCC {code:?}: {e}")))
+    };
+    let comparison = compare(&parse(a)?, &parse(b)?);
+    let distances = PyDict::new(py);
+    let bits = PyDict::new(py);
+    for unit in &comparison.units {
+        distances.set_item(unit.maintype.name(), unit.distance)?;
+        bits.set_item(unit.maintype.name(), unit.bits)?;
+    }
+    let dict = PyDict::new(py);
+    dict.set_item("similarity", comparison.similarity)?;
+    dict.set_item("distances", distances)?;
+    dict.set_item("bits", bits)?;
+    Ok(dict)
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for IsccCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for IsccCode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = alloc::string::String::deserialize(deserializer)?;
+        code.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Reader over the nibbles of a header
+struct Nibbles<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Nibbles<'_> {
+    fn next(&mut self) -> Result<u32, CodecError> {
+        let byte = *self
+            .bytes
+            .get(self.position / 2)
+            .ok_or(CodecError::TruncatedHeader)?;
+        let nibble = if self.position.is_multiple_of(2) {
+            byte >> 4
+        } else {
+            byte & 0x0f
+        };
+        self.position += 1;
+        Ok(nibble as u32)
+    }
+
+    /// Read a variable-length field (1 to 4 nibbles)
+    fn read_field(&mut self) -> Result<u32, CodecError> {
+        let first = self.next()?;
+        // Leading one bits give the number of extra nibbles; each size class
+        // continues where the previous one ends (0-7, 8-71, 72-583, 584-4679)
+        let (extra, prefix_bits, offset) = match first {
+            0b0000..=0b0111 => return Ok(first),
+            0b1000..=0b1011 => (1, 2, 8),
+            0b1100..=0b1101 => (2, 3, 72),
+            0b1110 => (3, 4, 584),
+            _ => return Err(CodecError::FieldOverflow(first)),
+        };
+        let mut value = first & (0x0f >> prefix_bits);
+        for _ in 0..extra {
+            value = (value << 4) | self.next()?;
+        }
+        Ok(value + offset)
+    }
+}
+
+/// Append a field value as variable-length nibbles
+fn write_field(value: u32, nibbles: &mut Vec<u8>) -> Result<(), CodecError> {
+    let (count, prefix, offset): (u32, u32, u32) = match value {
+        0..=7 => (1, 0, 0),
+        8..=71 => (2, 0b10, 8),
+        72..=583 => (3, 0b110, 72),
+        584..=4679 => (4, 0b1110, 584),
+        _ => return Err(CodecError::FieldOverflow(value)),
+    };
+    let bits = count * 4;
+    let prefix_bits = count.min(4);
+    let encoded = (prefix << (bits - prefix_bits)) | (value - offset);
+    for i in (0..count).rev() {
+        nibbles.push(((encoded >> (i * 4)) & 0x0f) as u8);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    const SUM: &str = "ISCC:K4AOMGOGQJA4Y46PAC4YPPA63GKSIFDBTTI3VPDUVUKBVU6TP2HSA7Y";
+
+    #[test]
+    fn test_parse_and_display() {
+        let code: IsccCode = SUM.parse().unwrap();
+        assert_eq!(code.maintype(), MainType::Iscc);
+        assert_eq!(code.subtype(), SUBTYPE_WIDE);
+        assert_eq!(code.version(), 0);
+        assert_eq!(code.length(), 0);
+        assert_eq!(code.bits(), 256);
+        assert_eq!(code.to_string(), SUM);
+        assert_eq!(SUM.strip_prefix(PREFIX).unwrap().parse(), Ok(code));
+
+        let unit = IsccCode::new(MainType::Data, 0, 0, 1, vec![0xab; 8]).unwrap();
+        assert_eq!(unit.to_bytes()[..2], [0b0011_0000, 0b0000_0001]);
+        assert_eq!(unit.to_string().parse(), Ok(unit));
+    }
+
+    #[test]
+    fn test_varnibble_fields() {
+        for value in [0, 7, 8, 71, 72, 583, 584, 4679] {
+            let code = IsccCode::new(MainType::Id, value, 0, 0, vec![1; 8]).unwrap();
+            let parsed = IsccCode::from_bytes(&code.to_bytes()).unwrap();
+            assert_eq!(parsed.subtype(), value);
+            assert_eq!(parsed.body(), &[1; 8]);
+        }
+        assert_eq!(
+            IsccCode::new(MainType::Id, 4680, 0, 0, vec![1; 8]),
+            Err(CodecError::FieldOverflow(4680))
+        );
+    }
+
+    #[test]
+    fn test_units_and_compose() {
+        let code: IsccCode = SUM.parse().unwrap();
+        let units = code.units();
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[0].maintype(), MainType::Data);
+        assert_eq!(units[1].maintype(), MainType::Instance);
+        assert_eq!(units[0].bits(), 128);
+        assert_eq!(units[0].length(), 3);
+        assert_eq!(compose(&units, 128), Ok(code.clone()));
+
+        // Narrow composite from the same units keeps the first 64 bits of each
+        let narrow = compose(&[units[1].clone(), units[0].clone()], 64).unwrap();
+        assert_eq!(narrow.subtype(), SUBTYPE_SUM);
+        assert_eq!(narrow.body()[..8], code.body()[..8]);
+        assert_eq!(narrow.body()[8..], code.body()[16..24]);
+
+        // Optional units are recorded in the length field
+        let content = IsccCode::new(MainType::Content, 0, 0, 1, vec![7; 8]).unwrap();
+        let meta = IsccCode::new(MainType::Meta, 0, 0, 1, vec![9; 8]).unwrap();
+        let mut all = vec![content, meta];
+        all.extend(narrow.units());
+        let composite = compose(&all, 64).unwrap();
+        assert_eq!(composite.subtype(), 0);
+        assert_eq!(composite.length(), 0b101);
+        assert_eq!(composite.bits(), 256);
+        let split = composite.units();
+        assert_eq!(
+            split.iter().map(|unit| unit.maintype()).collect::<Vec<_>>(),
+            [
+                MainType::Meta,
+                MainType::Content,
+                MainType::Data,
+                MainType::Instance
+            ]
+        );
+        assert_eq!(compose(&split, 64), Ok(composite));
+
+        assert!(compose(&units[..1], 64).is_err());
+        assert!(compose(&all, 128).is_err());
+        assert!(compose(&[units[0].clone(), units[0].clone(), units[1].clone()], 64).is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let wide: IsccCode = SUM.parse().unwrap();
+        let units = wide.units();
+        let narrow = compose(&units, 64).unwrap();
+
+        // Same content at different lengths compares on the common prefix
+        let same = compare(&wide, &narrow);
+        assert_eq!(same.similarity, 1.0);
+        assert_eq!(same.distance(MainType::Data), Some(0));
+        assert_eq!(same.units[0].bits, 64);
+
+        // Flip 8 Data-Code bits and change the Instance-Code
+        let mut data = units[0].body().to_vec();
+        data[0] ^= 0xff;
+        let changed = compose(
+            &[
+                IsccCode::new(MainType::Data, 0, 0, 3, data).unwrap(),
+                IsccCode::new(MainType::Instance, 0, 0, 3, vec![0; 16]).unwrap(),
+            ],
+            128,
+        )
+        .unwrap();
+        let comparison = compare(&wide, &changed);
+        assert_eq!(comparison.distance(MainType::Data), Some(8));
+        assert_eq!(comparison.similarity, 1.0 - 8.0 / 128.0);
+
+        // Only Instance-Codes in common: exact match or nothing
+        assert_eq!(compare(&units[1], &wide).similarity, 1.0);
+        assert_eq!(compare(&units[1], &changed).similarity, 0.0);
+        assert_eq!(compare(&units[0], &units[1]).units, vec![]);
+        assert_eq!(compare(&units[0], &units[1]).similarity, 0.0);
+    }
+
+    #[test]
+    fn test_invalid_codes() {
+        assert_eq!("ISCC:".parse::<IsccCode>(), Err(CodecError::InvalidBase32));
+        assert_eq!(
+            "ISCC:a1!".parse::<IsccCode>(),
+            Err(CodecError::InvalidBase32)
+        );
+        assert_eq!(
+            IsccCode::from_bytes(&[0b0011_0000]),
+            Err(CodecError::TruncatedHeader)
+        );
+        // 64-bit Data-Code header with a 32-bit body
+        assert_eq!(
+            IsccCode::from_bytes(&[0b0011_0000, 0b0000_0001, 1, 2, 3, 4]),
+            Err(CodecError::BodyLength {
+                expected: 8,
+                actual: 4
+            })
+        );
+        assert_eq!(
+            IsccCode::from_bytes(&[0b1111_0000, 0, 0]),
+            Err(CodecError::FieldOverflow(0b1111))
+        );
+    }
+}
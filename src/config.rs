@@ -0,0 +1,268 @@
+//! User configuration shared by the `isum` CLI and library consumers.
+//!
+//! Settings are resolved in the following order, later sources overriding earlier ones:
+//!
+//! 1. Built-in defaults
+//! 2. The config file: `$ISUM_CONFIG` if set, else `$XDG_CONFIG_HOME/isum/config.toml`,
+//!    else `~/.config/isum/config.toml` (`%APPDATA%\isum\config.toml` on Windows)
+//! 3. `ISUM_BITS`, `ISUM_IGNORE_FILE`, `ISUM_GLOBAL_IGNORE`, `ISUM_THREADS` and
+//!    `ISUM_EXCLUDE` (comma-separated) environment variables
+//! 4. Command line options
+//!
+//! Without a configured `global_ignore` file, `ignore` next to the default config
+//! file (`~/.config/isum/ignore`) is used if it exists. Its gitignore-style patterns
+//! apply to every directory walk, e.g. to leave out `Thumbs.db` and `.DS_Store`.
+//!
+//! Example `config.toml`:
+//!
+//! ```toml
+//! bits = 128
+//! ignore_file = ".isccignore"
+//! global_ignore = "/etc/isum/ignore"
+//! threads = 4
+//! exclude = ["*.tmp", ".git/**"]
+//! ```
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Environment variable selecting an explicit config file
+pub const CONFIG_ENV: &str = "ISUM_CONFIG";
+
+/// Checksum widths supported by ISCC-SUM
+const SUPPORTED_BITS: [u32; 2] = [128, 256];
+
+/// Configured defaults; unset values fall back to the built-in defaults
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Checksum width in bits (128 or 256)
+    pub bits: Option<u32>,
+    /// Name of the gitignore-style ignore file honored during traversal
+    pub ignore_file: Option<String>,
+    /// Gitignore-style file with patterns applied to every traversal
+    pub global_ignore: Option<PathBuf>,
+    /// Number of worker threads
+    pub threads: Option<usize>,
+    /// Exclude glob patterns
+    pub exclude: Vec<String>,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Directory of the isum config files
+fn config_dir() -> Option<PathBuf> {
+    let config_dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    config_dir.map(|dir| dir.join("isum"))
+}
+
+impl Config {
+    /// Load the config file (if present) and apply `ISUM_*` environment variables
+    pub fn load() -> io::Result<Self> {
+        let mut config = match Self::default_path() {
+            Some(path) if path.is_file() => Self::from_file(&path)?,
+            _ => Config::default(),
+        };
+        config.apply_env(env::vars())?;
+        if config.global_ignore.is_none() {
+            config.global_ignore = Self::global_ignore_path().filter(|path| path.is_file());
+        }
+        Ok(config)
+    }
+
+    /// Location of the config file, honoring `$ISUM_CONFIG`
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+        config_dir().map(|dir| dir.join("config.toml"))
+    }
+
+    /// Location of the global ignore file used unless one is configured
+    pub fn global_ignore_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("ignore"))
+    }
+
+    /// Read and parse a config file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        Self::from_toml(&contents).map_err(|e| invalid(format!("{}: {e}", path.display())))
+    }
+
+    /// Parse config file contents
+    pub fn from_toml(contents: &str) -> io::Result<Self> {
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+        let mut config = Config::default();
+
+        for (key, value) in &table {
+            let wrong_type = |expected: &str| invalid(format!("'{key}' must be {expected}"));
+            match key.as_str() {
+                "bits" => {
+                    let bits = value.as_integer().ok_or_else(|| wrong_type("an integer"))?;
+                    config.bits = Some(parse_bits(&bits.to_string())?);
+                }
+                "ignore_file" => {
+                    let name = value.as_str().ok_or_else(|| wrong_type("a string"))?;
+                    config.ignore_file = Some(name.to_string());
+                }
+                "global_ignore" => {
+                    let path = value.as_str().ok_or_else(|| wrong_type("a string"))?;
+                    config.global_ignore = Some(PathBuf::from(path));
+                }
+                "threads" => {
+                    let threads = value.as_integer().ok_or_else(|| wrong_type("an integer"))?;
+                    config.threads = Some(parse_threads(&threads.to_string())?);
+                }
+                "exclude" => {
+                    config.exclude = value
+                        .as_array()
+                        .and_then(|items| {
+                            items
+                                .iter()
+                                .map(|item| item.as_str().map(str::to_string))
+                                .collect()
+                        })
+                        .ok_or_else(|| wrong_type("an array of strings"))?;
+                }
+                _ => return Err(invalid(format!("unknown key '{key}'"))),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Override settings from `ISUM_*` variables (typically `std::env::vars()`)
+    pub fn apply_env<I, K, V>(&mut self, vars: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (key, value) in vars {
+            let value = value.as_ref().trim();
+            let result = match key.as_ref() {
+                "ISUM_BITS" => parse_bits(value).map(|bits| self.bits = Some(bits)),
+                "ISUM_IGNORE_FILE" => {
+                    self.ignore_file = Some(value.to_string()).filter(|v| !v.is_empty());
+                    Ok(())
+                }
+                "ISUM_GLOBAL_IGNORE" => {
+                    self.global_ignore = Some(PathBuf::from(value)).filter(|_| !value.is_empty());
+                    Ok(())
+                }
+                "ISUM_THREADS" => parse_threads(value).map(|n| self.threads = Some(n)),
+                "ISUM_EXCLUDE" => {
+                    self.exclude = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|p| !p.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                    Ok(())
+                }
+                _ => Ok(()),
+            };
+            result.map_err(|e| invalid(format!("{}: {e}", key.as_ref())))?;
+        }
+        Ok(())
+    }
+
+    /// True if 128-bit checksums are configured
+    pub fn narrow(&self) -> bool {
+        self.bits == Some(128)
+    }
+}
+
+fn parse_bits(value: &str) -> io::Result<u32> {
+    value
+        .parse()
+        .ok()
+        .filter(|bits| SUPPORTED_BITS.contains(bits))
+        .ok_or_else(|| invalid(format!("invalid bits '{value}' (expected 128 or 256)")))
+}
+
+fn parse_threads(value: &str) -> io::Result<usize> {
+    value
+        .parse()
+        .ok()
+        .filter(|&threads| threads > 0)
+        .ok_or_else(|| {
+            invalid(format!(
+                "invalid threads '{value}' (expected a positive integer)"
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            "bits = 128\nignore_file = \".isccignore\"\nthreads = 4\nexclude = [\"*.tmp\"]\n\
+             global_ignore = \"/etc/isum/ignore\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                bits: Some(128),
+                ignore_file: Some(".isccignore".to_string()),
+                global_ignore: Some(PathBuf::from("/etc/isum/ignore")),
+                threads: Some(4),
+                exclude: vec!["*.tmp".to_string()],
+            }
+        );
+        assert!(config.narrow());
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_from_toml_invalid() {
+        assert!(Config::from_toml("bits = 64").is_err());
+        assert!(Config::from_toml("threads = 0").is_err());
+        assert!(Config::from_toml("exclude = \"*.tmp\"").is_err());
+        assert!(Config::from_toml("colour = true").is_err());
+        assert!(Config::from_toml("bits = ").is_err());
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config = Config::from_toml("bits = 128\nexclude = [\"*.tmp\"]").unwrap();
+        config
+            .apply_env([
+                ("ISUM_BITS", "256"),
+                ("ISUM_EXCLUDE", "*.log, *.bak"),
+                ("ISUM_THREADS", "2"),
+                ("ISUM_GLOBAL_IGNORE", "/etc/isum/ignore"),
+                ("PATH", "/usr/bin"),
+            ])
+            .unwrap();
+        assert_eq!(
+            config.global_ignore,
+            Some(PathBuf::from("/etc/isum/ignore"))
+        );
+        assert_eq!(config.bits, Some(256));
+        assert_eq!(config.exclude, vec!["*.log", "*.bak"]);
+        assert_eq!(config.threads, Some(2));
+        assert!(!config.narrow());
+
+        let err = config.apply_env([("ISUM_BITS", "wide")]).unwrap_err();
+        assert!(err.to_string().starts_with("ISUM_BITS"));
+    }
+}
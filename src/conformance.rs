@@ -0,0 +1,283 @@
+//! ISCC conformance test vectors.
+//!
+//! The crate embeds the conformance vectors it is tested against (inputs and
+//! expected outputs of the reference implementation `iscc-core`), so code
+//! embedding the crate can prove at runtime that it computes the same codes,
+//! for example in its own test suite or a startup check:
+//!
+//! ```text
+//! let report = conformance::selftest();
+//! assert!(report.is_success(), "{:?}", report.failures().collect::<Vec<_>>());
+//! ```
+//!
+//! [`vectors`] iterates over all vectors; [`data_code_vectors`] and its
+//! siblings over those of one function. `isum selftest` runs the same checks.
+
+use std::sync::OnceLock;
+
+use serde_json::{json, Value};
+
+use crate::codec::{IsccCode, MainType};
+use crate::content_text::gen_text_code;
+use crate::data::DataHasher;
+use crate::instance::InstanceHasher;
+use crate::meta::gen_meta_code;
+use crate::options::IsccSumOptions;
+use crate::sum::IsccSumProcessor;
+
+/// Conformance vectors as JSON: `{function: {name: {"inputs": [...], "outputs": {...}}}}`
+pub const TEST_VECTORS: &str = include_str!("../tests/test_vectors.json");
+
+/// A single conformance vector
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vector {
+    /// Reference function the vector tests (`gen_data_code`, `code_iscc_sum`, ...)
+    pub function: String,
+    /// Name of the vector, unique per function
+    pub name: String,
+    /// Positional arguments of the reference function; binary inputs are
+    /// `stream:` followed by hex
+    pub inputs: Value,
+    /// Expected outputs by key; keys not listed are not checked
+    pub outputs: Value,
+}
+
+impl Vector {
+    /// Compute the outputs of the vector and compare them with the expected ones
+    pub fn check(&self) -> Result<(), String> {
+        let actual = compute(&self.function, &self.inputs)?;
+        for (key, value) in self.outputs.as_object().into_iter().flatten() {
+            if actual[key] != *value {
+                return Err(format!("{key}: expected {value}, got {}", actual[key]));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of checking one vector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub function: String,
+    pub name: String,
+    /// Why the vector failed, `None` if it passed
+    pub error: Option<String>,
+}
+
+/// Outcomes of [`selftest`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// One outcome per vector, in the order of [`vectors`]
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Report {
+    /// Number of vectors that passed
+    pub fn passed(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.error.is_none()).count()
+    }
+
+    /// Outcomes of the vectors that failed
+    pub fn failures(&self) -> impl Iterator<Item = &Outcome> {
+        self.outcomes.iter().filter(|o| o.error.is_some())
+    }
+
+    /// True if every vector passed
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+/// Check every embedded conformance vector
+pub fn selftest() -> Report {
+    Report {
+        outcomes: vectors()
+            .map(|vector| Outcome {
+                error: vector.check().err(),
+                function: vector.function,
+                name: vector.name,
+            })
+            .collect(),
+    }
+}
+
+/// All embedded conformance vectors
+pub fn vectors() -> impl Iterator<Item = Vector> {
+    parsed()
+        .as_object()
+        .into_iter()
+        .flatten()
+        .flat_map(|(function, cases)| {
+            cases
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(move |(name, case)| Vector {
+                    function: function.clone(),
+                    name: name.clone(),
+                    inputs: case["inputs"].clone(),
+                    outputs: case["outputs"].clone(),
+                })
+        })
+}
+
+/// Conformance vectors of one reference function
+pub fn function_vectors(function: &str) -> impl Iterator<Item = Vector> + '_ {
+    vectors().filter(move |vector| vector.function == function)
+}
+
+/// Vectors of the Data-Code (`gen_data_code`)
+pub fn data_code_vectors() -> impl Iterator<Item = Vector> {
+    function_vectors("gen_data_code")
+}
+
+/// Vectors of the Instance-Code (`gen_instance_code`)
+pub fn instance_code_vectors() -> impl Iterator<Item = Vector> {
+    function_vectors("gen_instance_code")
+}
+
+/// Vectors of the ISCC-SUM (`code_iscc_sum`)
+pub fn iscc_sum_vectors() -> impl Iterator<Item = Vector> {
+    function_vectors("code_iscc_sum")
+}
+
+/// Vectors of the Meta-Code (`gen_meta_code`)
+pub fn meta_code_vectors() -> impl Iterator<Item = Vector> {
+    function_vectors("gen_meta_code")
+}
+
+/// Vectors of the Content-Code Text (`gen_text_code`)
+pub fn text_code_vectors() -> impl Iterator<Item = Vector> {
+    function_vectors("gen_text_code")
+}
+
+fn parsed() -> &'static Value {
+    static VECTORS: OnceLock<Value> = OnceLock::new();
+    VECTORS.get_or_init(|| serde_json::from_str(TEST_VECTORS).expect("valid embedded vectors"))
+}
+
+/// Outputs of `function` for `inputs`, keyed like the reference outputs
+fn compute(function: &str, inputs: &Value) -> Result<Value, String> {
+    Ok(match function {
+        "gen_data_code" => {
+            let mut hasher = DataHasher::new();
+            hasher.push(&stream(&inputs[0])?);
+            json!({ "iscc": unit_code(MainType::Data, &hasher.digest(), &inputs[1])? })
+        }
+        "gen_instance_code" => {
+            let mut hasher = InstanceHasher::new();
+            hasher.push(&stream(&inputs[0])?);
+            json!({
+                "iscc": unit_code(MainType::Instance, &hasher.digest(), &inputs[1])?,
+                "datahash": hasher.multihash(),
+                "filesize": hasher.filesize(),
+            })
+        }
+        "code_iscc_sum" => {
+            let wide = inputs[1].as_bool().ok_or("invalid wide input")?;
+            let add_units = inputs[2].as_bool().ok_or("invalid add_units input")?;
+            let options = IsccSumOptions::builder()
+                .wide(wide)
+                .add_units(add_units)
+                .build()
+                .map_err(|e| e.to_string())?;
+            let mut processor = IsccSumProcessor::with_options(options);
+            processor.update(&stream(&inputs[0])?);
+            let result = processor.result();
+            json!({
+                "iscc": result.iscc,
+                "datahash": result.datahash,
+                "filesize": result.filesize,
+                "units": result.units,
+            })
+        }
+        "gen_meta_code" => {
+            let name = inputs[0].as_str().ok_or("invalid name input")?;
+            // The reference passes absent descriptions and metadata as empty strings
+            let description = inputs[1].as_str().filter(|d| !d.is_empty());
+            let meta = Some(&inputs[2]).filter(|m| !m.is_null() && m.as_str() != Some(""));
+            let result = gen_meta_code(name, description, meta, bits(&inputs[3])?)
+                .map_err(|e| e.to_string())?;
+            json!({
+                "iscc": result.iscc,
+                "name": result.name,
+                "metahash": result.metahash,
+            })
+        }
+        "gen_text_code" => {
+            let text = inputs[0].as_str().ok_or("invalid text input")?;
+            let result = gen_text_code(text, bits(&inputs[1])?).map_err(|e| e.to_string())?;
+            json!({ "iscc": result.iscc, "characters": result.characters })
+        }
+        _ => return Err(format!("unknown function '{function}'")),
+    })
+}
+
+/// Bytes of a `stream:<hex>` input
+fn stream(input: &Value) -> Result<Vec<u8>, String> {
+    input
+        .as_str()
+        .and_then(|s| s.strip_prefix("stream:"))
+        .and_then(|h| hex::decode(h).ok())
+        .ok_or_else(|| "invalid stream input".to_string())
+}
+
+fn bits(input: &Value) -> Result<u32, String> {
+    input
+        .as_u64()
+        .filter(|b| b % 32 == 0 && (32..=256).contains(b))
+        .map(|b| b as u32)
+        .ok_or_else(|| "invalid bits input".to_string())
+}
+
+/// Code of a unit of `maintype` from a digest truncated to `bits`
+fn unit_code(maintype: MainType, digest: &[u8], bits: &Value) -> Result<String, String> {
+    let bits = self::bits(bits)?;
+    let body = digest[..bits as usize / 8].to_vec();
+    let code = IsccCode::new(maintype, 0, 0, bits / 32 - 1, body).map_err(|e| e.to_string())?;
+    Ok(code.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest() {
+        let report = selftest();
+        assert!(
+            report.is_success(),
+            "{:?}",
+            report.failures().collect::<Vec<_>>()
+        );
+        assert_eq!(report.passed(), vectors().count());
+        assert_eq!(data_code_vectors().count(), 4);
+        assert!(meta_code_vectors().count() > 0 && text_code_vectors().count() > 0);
+    }
+
+    #[test]
+    fn test_vector_mismatch() {
+        let vector = |function: &str, inputs: Value, outputs: Value| Vector {
+            function: function.to_string(),
+            name: "test".to_string(),
+            inputs,
+            outputs,
+        };
+        let inputs = json!(["stream:ff00", 64]);
+        let expected = json!({"iscc": "ISCC:GAAXL2XYM5BQIAZ3"});
+        assert!(vector("gen_data_code", inputs.clone(), expected)
+            .check()
+            .is_ok());
+        let err = vector(
+            "gen_data_code",
+            inputs.clone(),
+            json!({"iscc": "ISCC:GAAAAAAAAAAAAAAA"}),
+        )
+        .check()
+        .unwrap_err();
+        assert!(err.starts_with("iscc: expected"));
+        assert!(vector("gen_unknown", inputs, json!({})).check().is_err());
+        let bare = vector("gen_data_code", json!(["ff00", 64]), json!({}));
+        assert!(bare.check().is_err());
+    }
+}
@@ -0,0 +1,566 @@
+//! ISCC Content-Code Audio.
+//!
+//! The Content-Code Audio is built from a Chromaprint fingerprint, a series
+//! of 32-bit features describing how the pitch classes (chroma) of the audio
+//! change over time. [`gen_audio_code`] turns a fingerprint into the code as
+//! `gen_audio_code_v0` of `iscc-core` does: SimHashes over all features, over
+//! quarters of the features in time order and over thirds of the sorted
+//! features, 32 bits each.
+//!
+//! [`Fingerprinter`] computes fingerprints from PCM samples with the pipeline
+//! of Chromaprint's default algorithm: resampling to 11025 Hz mono, 4096
+//! sample frames with a hop of 1365 samples, 12 chroma bands between 28 Hz
+//! and 3520 Hz, smoothing and normalization, and 16 classifiers with 2-bit
+//! quantizers. It does not use Chromaprint's resampler, so fingerprints
+//! (and codes) differ in detail from `fpcalc`; for codes identical to
+//! `iscc-sdk`, pass the output of `fpcalc -raw` to [`gen_audio_code`].
+//!
+//! With the `audio` feature, [`gen_audio_code_file`] decodes WAV, FLAC, MP3,
+//! AAC, Ogg Vorbis and Matroska audio files.
+
+#[cfg(feature = "audio")]
+use std::fs::File;
+#[cfg(feature = "audio")]
+use std::path::Path;
+
+use crate::codec::{IsccCode, MainType};
+use crate::error::{Error, Result};
+use crate::meta::simhash;
+
+/// Subtype of Content-Code units for audio
+pub const SUBTYPE_AUDIO: u32 = 2;
+
+/// Sample rate the audio is resampled to
+pub const SAMPLE_RATE: u32 = 11025;
+
+const FRAME_SIZE: usize = 4096;
+const FRAME_HOP: usize = FRAME_SIZE / 3;
+const MIN_FREQ: f64 = 28.0;
+const MAX_FREQ: f64 = 3520.0;
+const BANDS: usize = 12;
+const CHROMA_FILTER: [f64; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+
+/// Classifiers of Chromaprint's default algorithm: filter type, first band,
+/// number of bands, number of frames, and quantizer thresholds
+const CLASSIFIERS: [(u8, usize, usize, usize, [f64; 3]); 16] = [
+    (0, 4, 3, 15, [1.98215, 2.35817, 2.63523]),
+    (4, 4, 6, 15, [-1.03809, -0.651211, -0.282167]),
+    (1, 0, 4, 16, [-0.298702, 0.119262, 0.558497]),
+    (3, 8, 2, 12, [-0.105439, 0.0153946, 0.135898]),
+    (3, 4, 4, 8, [-0.142891, 0.0258736, 0.200632]),
+    (4, 0, 3, 5, [-0.826319, -0.590612, -0.368214]),
+    (1, 2, 2, 9, [-0.557409, -0.233035, 0.0534525]),
+    (2, 7, 3, 4, [-0.0646826, 0.00620476, 0.0784847]),
+    (2, 6, 2, 16, [-0.192387, -0.029699, 0.215855]),
+    (2, 1, 3, 2, [-0.0397818, -0.00568076, 0.0292026]),
+    (5, 10, 1, 15, [-0.53823, -0.369934, -0.190235]),
+    (3, 6, 2, 10, [-0.124877, 0.0296483, 0.139239]),
+    (2, 1, 1, 14, [-0.101475, 0.0225617, 0.231971]),
+    (3, 5, 6, 4, [-0.0799915, -0.00729616, 0.063262]),
+    (1, 9, 2, 12, [-0.272556, 0.019424, 0.302559]),
+    (3, 4, 2, 14, [-0.164292, -0.0321188, 0.0846339]),
+];
+
+/// Frames the widest classifier spans
+const CLASSIFIER_FRAMES: usize = 16;
+
+/// Result of [`gen_audio_code`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AudioCodeResult {
+    /// Content-Code Audio with `ISCC:` prefix
+    pub iscc: String,
+    /// Number of fingerprint features the code was built from
+    pub features: usize,
+}
+
+/// Generate a Content-Code Audio of `bits` length (32 to 256, in steps of
+/// 32) from a Chromaprint fingerprint
+pub fn gen_audio_code(fingerprint: &[i32], bits: u32) -> Result<AudioCodeResult> {
+    if !(32..=256).contains(&bits) || !bits.is_multiple_of(32) {
+        return Err(Error::InvalidInput(format!(
+            "bits must be a multiple of 32 from 32 to 256, got {bits}"
+        )));
+    }
+    let digest = soft_hash_audio(fingerprint)[..bits as usize / 8].to_vec();
+    let code = IsccCode::new(MainType::Content, SUBTYPE_AUDIO, 0, bits / 32 - 1, digest)?;
+    Ok(AudioCodeResult {
+        iscc: code.to_string(),
+        features: fingerprint.len(),
+    })
+}
+
+/// 256-bit similarity digest of a Chromaprint fingerprint
+pub fn soft_hash_audio(fingerprint: &[i32]) -> [u8; 32] {
+    let digests: Vec<[u8; 4]> = if fingerprint.is_empty() {
+        vec![[0; 4]]
+    } else {
+        fingerprint
+            .iter()
+            .map(|feature| feature.to_be_bytes())
+            .collect()
+    };
+    let mut sorted = fingerprint.to_vec();
+    sorted.sort_unstable();
+    let sorted: Vec<[u8; 4]> = sorted.iter().map(|feature| feature.to_be_bytes()).collect();
+
+    let parts = std::iter::once(simhash(&digests))
+        .chain(divide(&digests, 4).map(simhash_part))
+        .chain(divide(&sorted, 3).map(simhash_part));
+    let mut out = [0u8; 32];
+    for (chunk, part) in out.chunks_exact_mut(4).zip(parts) {
+        chunk.copy_from_slice(&part);
+    }
+    out
+}
+
+/// SimHash of a part, zero for empty parts
+fn simhash_part(part: &[[u8; 4]]) -> [u8; 4] {
+    if part.is_empty() {
+        [0; 4]
+    } else {
+        simhash(part)
+    }
+}
+
+/// Split into `n` consecutive parts whose sizes differ by at most one, the
+/// longer parts first
+fn divide<T>(items: &[T], n: usize) -> impl Iterator<Item = &[T]> {
+    let (size, rest) = (items.len() / n, items.len() % n);
+    (0..n).map(move |i| {
+        let start = i * size + i.min(rest);
+        &items[start..start + size + usize::from(i < rest)]
+    })
+}
+
+/// Chromaprint-style fingerprinting of PCM audio.
+///
+/// Feed interleaved samples in `[-1, 1]` with [`Fingerprinter::push`] and
+/// call [`Fingerprinter::finish`] for the fingerprint, one feature for every
+/// 1365 samples at 11025 Hz (about 124 ms) once 20 frames are available.
+#[derive(Debug, Clone)]
+pub struct Fingerprinter {
+    channels: usize,
+    /// Output samples per input sample
+    ratio: f64,
+    /// Position within the current output sample, in output samples
+    phase: f64,
+    sum: f64,
+    count: u32,
+    /// Resampled samples not yet fully consumed by frames
+    samples: Vec<f64>,
+    window: Vec<f64>,
+    /// Chroma band of each FFT bin between the minimum and maximum frequency
+    bins: Vec<(usize, usize)>,
+    chroma: Vec<[f64; BANDS]>,
+}
+
+impl Fingerprinter {
+    pub fn new(sample_rate: u32, channels: usize) -> Result<Self> {
+        if sample_rate == 0 || channels == 0 {
+            return Err(Error::InvalidInput(
+                "sample rate and channels must not be zero".to_string(),
+            ));
+        }
+        let window = (0..FRAME_SIZE)
+            .map(|i| {
+                0.54 - 0.46
+                    * (i as f64 * 2.0 * std::f64::consts::PI / (FRAME_SIZE - 1) as f64).cos()
+            })
+            .collect();
+        let index = |freq: f64| (FRAME_SIZE as f64 * freq / SAMPLE_RATE as f64).round() as usize;
+        let bins = (index(MIN_FREQ).max(1)..index(MAX_FREQ).min(FRAME_SIZE / 2))
+            .map(|i| {
+                let freq = i as f64 * SAMPLE_RATE as f64 / FRAME_SIZE as f64;
+                let octave = (freq / (440.0 / 16.0)).log2();
+                (i, (BANDS as f64 * (octave - octave.floor())) as usize)
+            })
+            .collect();
+        Ok(Fingerprinter {
+            channels,
+            ratio: SAMPLE_RATE as f64 / sample_rate as f64,
+            phase: 0.0,
+            sum: 0.0,
+            count: 0,
+            samples: Vec::new(),
+            window,
+            bins,
+            chroma: Vec::new(),
+        })
+    }
+
+    /// Add interleaved samples; a trailing partial frame of channels is ignored
+    pub fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            let mono = frame.iter().map(|&s| s as f64).sum::<f64>() / self.channels as f64;
+            // Average the input samples falling into each output sample
+            self.sum += mono;
+            self.count += 1;
+            self.phase += self.ratio;
+            while self.phase >= 1.0 {
+                self.samples.push(self.sum / self.count as f64);
+                self.phase -= 1.0;
+                if self.phase >= 1.0 {
+                    // Upsampling: repeat the sample
+                    continue;
+                }
+                self.sum = 0.0;
+                self.count = 0;
+            }
+        }
+        let mut start = 0;
+        while self.samples.len() - start >= FRAME_SIZE {
+            let frame = self.chroma_frame(&self.samples[start..start + FRAME_SIZE]);
+            self.chroma.push(frame);
+            start += FRAME_HOP;
+        }
+        self.samples.drain(..start);
+    }
+
+    /// The fingerprint of the samples pushed so far
+    pub fn finish(&self) -> Vec<i32> {
+        let image: Vec<[f64; BANDS]> = self
+            .chroma
+            .windows(CHROMA_FILTER.len())
+            .map(|rows| {
+                let mut row = [0.0; BANDS];
+                for (band, value) in row.iter_mut().enumerate() {
+                    *value = rows
+                        .iter()
+                        .zip(CHROMA_FILTER)
+                        .map(|(raw, weight)| raw[band] * weight)
+                        .sum();
+                }
+                let norm = row.iter().map(|v| v * v).sum::<f64>().sqrt();
+                row.map(|v| if norm < 0.01 { 0.0 } else { v / norm })
+            })
+            .collect();
+        if image.len() < CLASSIFIER_FRAMES {
+            return Vec::new();
+        }
+
+        // Integral image with a zero row and column in front
+        let mut integral = vec![[0.0; BANDS + 1]; image.len() + 1];
+        for (x, row) in image.iter().enumerate() {
+            for y in 0..BANDS {
+                integral[x + 1][y + 1] =
+                    row[y] + integral[x][y + 1] + integral[x + 1][y] - integral[x][y];
+            }
+        }
+        let area = |x1: usize, y1: usize, x2: usize, y2: usize| {
+            integral[x2][y2] - integral[x1][y2] - integral[x2][y1] + integral[x1][y1]
+        };
+
+        (0..=image.len() - CLASSIFIER_FRAMES)
+            .map(|x| {
+                let mut bits = 0u32;
+                for &(kind, y, h, w, [t0, t1, t2]) in &CLASSIFIERS {
+                    let (a, b) = match kind {
+                        0 => (area(x, y, x + w, y + h), 0.0),
+                        1 => {
+                            let h2 = h / 2;
+                            (area(x, y + h2, x + w, y + h), area(x, y, x + w, y + h2))
+                        }
+                        2 => {
+                            let w2 = w / 2;
+                            (area(x + w2, y, x + w, y + h), area(x, y, x + w2, y + h))
+                        }
+                        3 => {
+                            let (w2, h2) = (w / 2, h / 2);
+                            (
+                                area(x, y + h2, x + w2, y + h) + area(x + w2, y, x + w, y + h2),
+                                area(x, y, x + w2, y + h2) + area(x + w2, y + h2, x + w, y + h),
+                            )
+                        }
+                        4 => {
+                            let h3 = h / 3;
+                            (
+                                area(x, y + h3, x + w, y + 2 * h3),
+                                area(x, y, x + w, y + h3) + area(x, y + 2 * h3, x + w, y + h),
+                            )
+                        }
+                        _ => {
+                            let w3 = w / 3;
+                            (
+                                area(x + w3, y, x + 2 * w3, y + h),
+                                area(x, y, x + w3, y + h) + area(x + 2 * w3, y, x + w, y + h),
+                            )
+                        }
+                    };
+                    let value = (1.0 + a).ln() - (1.0 + b).ln();
+                    let level = match value {
+                        v if v < t0 => 0,
+                        v if v < t1 => 1,
+                        v if v < t2 => 3,
+                        _ => 2,
+                    };
+                    bits = (bits << 2) | level;
+                }
+                bits as i32
+            })
+            .collect()
+    }
+
+    /// Chroma energies of one frame
+    fn chroma_frame(&self, samples: &[f64]) -> [f64; BANDS] {
+        let mut re: Vec<f64> = samples
+            .iter()
+            .zip(&self.window)
+            .map(|(s, w)| s * w)
+            .collect();
+        let mut im = vec![0.0; FRAME_SIZE];
+        fft(&mut re, &mut im);
+        let mut chroma = [0.0; BANDS];
+        for &(bin, band) in &self.bins {
+            chroma[band] += re[bin] * re[bin] + im[bin] * im[bin];
+        }
+        chroma
+    }
+}
+
+/// In-place radix-2 FFT
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f64::consts::PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Generate a Content-Code Audio from interleaved PCM samples in `[-1, 1]`
+pub fn gen_audio_code_pcm(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: usize,
+    bits: u32,
+) -> Result<AudioCodeResult> {
+    let mut fingerprinter = Fingerprinter::new(sample_rate, channels)?;
+    fingerprinter.push(samples);
+    gen_audio_code(&fingerprinter.finish(), bits)
+}
+
+/// Decode the first audio track of a file and compute its fingerprint
+#[cfg(feature = "audio")]
+pub fn fingerprint_file(path: &Path) -> Result<Vec<i32>> {
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::errors::Error as DecodeError;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::probe::Hint;
+
+    let invalid = |e: DecodeError| Error::InvalidInput(format!("cannot decode audio: {e}"));
+    let file = File::open(path).map_err(|e| Error::io(path, e))?;
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut format = symphonia::default::get_probe()
+        .format(&hint, stream, &Default::default(), &Default::default())
+        .map_err(invalid)?
+        .format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.sample_rate.is_some())
+        .ok_or_else(|| Error::InvalidInput("no audio track".to_string()))?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &Default::default())
+        .map_err(invalid)?;
+
+    let mut fingerprinter: Option<Fingerprinter> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(invalid(e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // Skip corrupt packets as players do
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(e) => return Err(invalid(e)),
+        };
+        let spec = *decoded.spec();
+        let fingerprinter = match &mut fingerprinter {
+            Some(fingerprinter) => fingerprinter,
+            None => fingerprinter.insert(Fingerprinter::new(spec.rate, spec.channels.count())?),
+        };
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        fingerprinter.push(buffer.samples());
+    }
+    Ok(fingerprinter.map(|f| f.finish()).unwrap_or_default())
+}
+
+/// Decode an audio file and generate its Content-Code Audio
+#[cfg(feature = "audio")]
+pub fn gen_audio_code_file(path: &Path, bits: u32) -> Result<AudioCodeResult> {
+    gen_audio_code(&fingerprint_file(path)?, bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::compare;
+
+    /// Seconds of a chord progression, one chord per second
+    fn music(seconds: usize, sample_rate: u32, channels: usize, transpose: f64) -> Vec<f32> {
+        let chords = [
+            [261.63, 329.63, 392.0],
+            [220.0, 261.63, 329.63],
+            [174.61, 220.0, 261.63],
+        ];
+        let rate = sample_rate as f64;
+        (0..seconds * sample_rate as usize)
+            .flat_map(|i| {
+                let chord = chords[(i / sample_rate as usize) % chords.len()];
+                let t = i as f64 / rate;
+                let sample: f64 = chord
+                    .iter()
+                    .map(|f| (2.0 * std::f64::consts::PI * f * transpose * t).sin() / 4.0)
+                    .sum();
+                std::iter::repeat_n(sample as f32, channels)
+            })
+            .collect()
+    }
+
+    fn code(samples: &[f32], sample_rate: u32, channels: usize) -> IsccCode {
+        gen_audio_code_pcm(samples, sample_rate, channels, 64)
+            .unwrap()
+            .iscc
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_gen_audio_code() {
+        let result = gen_audio_code(&[], 64).unwrap();
+        assert_eq!(result.iscc, "ISCC:EIAQAAAAAAAAAAAA");
+
+        let fingerprint: Vec<i32> = (0..40).map(|i: i32| i.wrapping_mul(-0x3579_BDF1)).collect();
+        let digest = soft_hash_audio(&fingerprint);
+        assert_eq!(
+            digest[..4],
+            simhash(
+                &fingerprint
+                    .iter()
+                    .map(|f| f.to_be_bytes())
+                    .collect::<Vec<_>>()
+            )
+        );
+        let result = gen_audio_code(&fingerprint, 256).unwrap();
+        let code: IsccCode = result.iscc.parse().unwrap();
+        assert_eq!(
+            (code.maintype(), code.subtype(), code.body()),
+            (MainType::Content, 2, &digest[..])
+        );
+        assert_eq!(result.features, 40);
+        assert!(gen_audio_code(&fingerprint, 0).is_err());
+    }
+
+    #[test]
+    fn test_divide() {
+        let parts: Vec<&[u8]> = divide(&[1, 2, 3, 4, 5], 3).collect();
+        assert_eq!(parts, [&[1, 2][..], &[3, 4], &[5]]);
+        let parts: Vec<&[u8]> = divide(&[1], 4).collect();
+        assert_eq!(parts, [&[1][..], &[], &[], &[]]);
+    }
+
+    #[test]
+    fn test_fingerprinter() {
+        let mut fingerprinter = Fingerprinter::new(SAMPLE_RATE, 1).unwrap();
+        let samples = music(6, SAMPLE_RATE, 1, 1.0);
+        // Pushing in pieces gives the same fingerprint
+        for piece in samples.chunks(1000) {
+            fingerprinter.push(piece);
+        }
+        let fingerprint = fingerprinter.finish();
+        let frames = (samples.len() - FRAME_SIZE) / FRAME_HOP + 1;
+        assert_eq!(
+            fingerprint.len(),
+            frames - CHROMA_FILTER.len() + 1 - CLASSIFIER_FRAMES + 1
+        );
+        let whole = gen_audio_code_pcm(&samples, SAMPLE_RATE, 1, 64).unwrap();
+        assert_eq!(gen_audio_code(&fingerprint, 64).unwrap(), whole);
+
+        // Resampled stereo copies are near duplicates, other music is not
+        let original = code(&samples, SAMPLE_RATE, 1);
+        let stereo = code(&music(6, 44100, 2, 1.0), 44100, 2);
+        assert!(compare(&original, &stereo).similarity > 0.9);
+        let other = code(&music(6, SAMPLE_RATE, 1, 1.26), SAMPLE_RATE, 1);
+        assert!(compare(&original, &other).similarity < compare(&original, &stereo).similarity);
+
+        // Too short for a single feature
+        assert!(
+            gen_audio_code_pcm(&samples[..20000], SAMPLE_RATE, 1, 64)
+                .unwrap()
+                .features
+                == 0
+        );
+        assert!(Fingerprinter::new(0, 1).is_err());
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn test_gen_audio_code_file() {
+        let samples = music(6, 22050, 2, 1.0);
+        let pcm: Vec<u8> = samples
+            .iter()
+            .flat_map(|s| ((s * 32767.0) as i16).to_le_bytes())
+            .collect();
+        let mut wav = Vec::new();
+        wav.extend(b"RIFF");
+        wav.extend((36 + pcm.len() as u32).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes()); // PCM
+        wav.extend(2u16.to_le_bytes()); // channels
+        wav.extend(22050u32.to_le_bytes());
+        wav.extend((22050u32 * 4).to_le_bytes());
+        wav.extend(4u16.to_le_bytes());
+        wav.extend(16u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend((pcm.len() as u32).to_le_bytes());
+        wav.extend(&pcm);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chords.wav");
+        std::fs::write(&path, wav).unwrap();
+        let result = gen_audio_code_file(&path, 64).unwrap();
+        let decoded: IsccCode = result.iscc.parse().unwrap();
+        assert!(compare(&decoded, &code(&samples, 22050, 2)).similarity > 0.95);
+
+        std::fs::write(&path, b"not audio").unwrap();
+        assert!(gen_audio_code_file(&path, 64).is_err());
+    }
+}
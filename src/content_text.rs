@@ -0,0 +1,105 @@
+//! ISCC Content-Code Text.
+//!
+//! The Content-Code Text identifies a text by its content rather than its
+//! encoding or layout: the text is collapsed ([`text_collapse`]: lowercase,
+//! without whitespace, punctuation and accents), split into character
+//! 13-grams, hashed with xxh32 and combined with the same 256-bit MinHash as
+//! the Data-Code. This follows `gen_text_code_v0` of `iscc-core`, so texts
+//! that differ only in formatting get the same code and similar texts get
+//! codes at a small Hamming distance.
+//!
+//! The input is plain text as extracted from a document; [`decode_text`]
+//! recognizes files that are UTF-8 text as they are.
+
+use xxhash_rust::xxh32::xxh32;
+
+use crate::codec::{IsccCode, MainType};
+use crate::error::{Error, Result};
+use crate::meta::text_collapse;
+use crate::minhash::minhash_256;
+
+/// Width of the character n-grams
+pub const TEXT_NGRAM_SIZE: usize = 13;
+
+/// Subtype of Content-Code units for text
+pub const SUBTYPE_TEXT: u32 = 0;
+
+/// Result of [`gen_text_code`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextCodeResult {
+    /// Content-Code Text with `ISCC:` prefix
+    pub iscc: String,
+    /// Number of characters of the collapsed text
+    pub characters: usize,
+}
+
+/// Generate a Content-Code Text of `bits` length (32 to 256, in steps of 32)
+pub fn gen_text_code(text: &str, bits: u32) -> Result<TextCodeResult> {
+    if !(32..=256).contains(&bits) || !bits.is_multiple_of(32) {
+        return Err(Error::InvalidInput(format!(
+            "bits must be a multiple of 32 from 32 to 256, got {bits}"
+        )));
+    }
+    let text = text_collapse(text);
+    let digest = soft_hash_text(&text);
+    let body = digest[..bits as usize / 8].to_vec();
+    let code = IsccCode::new(MainType::Content, SUBTYPE_TEXT, 0, bits / 32 - 1, body)?;
+    Ok(TextCodeResult {
+        iscc: code.to_string(),
+        characters: text.chars().count(),
+    })
+}
+
+/// 256-bit similarity digest of collapsed text
+pub fn soft_hash_text(text: &str) -> Vec<u8> {
+    let chars: Vec<char> = text.chars().collect();
+    let count = chars.len().saturating_sub(TEXT_NGRAM_SIZE) + 1;
+    let mut ngram = String::new();
+    let features: Vec<u32> = (0..count)
+        .map(|start| {
+            ngram.clear();
+            ngram.extend(&chars[start..(start + TEXT_NGRAM_SIZE).min(chars.len())]);
+            xxh32(ngram.as_bytes(), 0)
+        })
+        .collect();
+    minhash_256(&features)
+}
+
+/// The content of a plain text file: valid UTF-8 (a byte order mark is
+/// skipped) without NUL bytes, which mark binary files
+pub fn decode_text(data: &[u8]) -> Option<&str> {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    if data.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gen_text_code() {
+        let result = gen_text_code("Hello World", 64).unwrap();
+        assert_eq!(result.iscc, "ISCC:EAASKDNZNYGUUF5A");
+        assert_eq!(result.characters, 10);
+
+        // Formatting does not change the code
+        let reformatted = gen_text_code("  hello,\n\tWORLD! ", 64).unwrap();
+        assert_eq!(reformatted, result);
+
+        let code: IsccCode = gen_text_code("", 128).unwrap().iscc.parse().unwrap();
+        assert_eq!((code.maintype(), code.bits()), (MainType::Content, 128));
+        assert!(gen_text_code("Hello World", 16).is_err());
+    }
+
+    #[test]
+    fn test_decode_text() {
+        assert_eq!(decode_text(b"\xEF\xBB\xBFplain"), Some("plain"));
+        assert_eq!(decode_text("grüße".as_bytes()), Some("grüße"));
+        assert_eq!(decode_text(b"bin\0ary"), None);
+        assert_eq!(decode_text(b"\xFF\xFE"), None);
+    }
+}
@@ -0,0 +1,323 @@
+//! Video similarity hash.
+//!
+//! Videos are sampled at 5 frames per second and every frame is reduced to a
+//! signature in the style of the MPEG-7 video signature: the frame is
+//! averaged down to 32×32 blocks, and 380 elements compare the mean
+//! brightness of two block regions each, quantized to three levels (darker,
+//! about equal, brighter) with a per-frame threshold that puts a third of
+//! the elements in the middle level.
+//!
+//! The hash is a MinHash over words of the frame signatures: each run of 10
+//! elements forms a word, and the set of distinct (position, word) pairs over
+//! all frames is hashed like the Data-Code chunk features. Re-encoded or
+//! rescaled copies keep most words, so their hashes are at a small Hamming
+//! distance; the order of the frames does not matter.
+//!
+//! This is not the WTA-hash of `iscc-core`'s `gen_video_code_v0` (which
+//! needs the MPEG-7 signatures of ffmpeg's `signature` filter), so the hash is
+//! a plain hex digest rather than a Content-Code Video: it is only comparable
+//! with hashes from this module and must not pass for an ISCC unit.
+//!
+//! With the `video` feature, [`gen_video_hash_file`] decodes video files with
+//! the `ffmpeg` program, which must be installed.
+
+use std::collections::BTreeSet;
+#[cfg(feature = "video")]
+use std::io::Read;
+#[cfg(feature = "video")]
+use std::path::Path;
+#[cfg(feature = "video")]
+use std::process::{Command, Stdio};
+
+use xxhash_rust::xxh32::xxh32;
+
+use crate::error::{Error, Result};
+use crate::minhash::minhash_256;
+
+/// Frames per second sampled from videos
+pub const VIDEO_FPS: u32 = 5;
+
+/// Number of elements of a frame signature
+pub const FRAME_SIGNATURE_LEN: usize = 380;
+
+/// Signature of one frame: 380 elements of 0 (darker), 1 (about equal) or 2
+/// (brighter)
+pub type FrameSignature = [u8; FRAME_SIGNATURE_LEN];
+
+/// Blocks per side of the grid frames are averaged to
+const GRID: usize = 32;
+
+/// Elements per word
+const WORD_LEN: usize = 10;
+
+/// Block region: x, y, width, height
+type Region = (usize, usize, usize, usize);
+
+/// Result of [`gen_video_hash`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VideoHashResult {
+    /// Hex-encoded similarity digest (no ISCC header)
+    pub hash: String,
+    /// Number of frame signatures the hash was built from
+    pub frames: usize,
+}
+
+/// Generate a video similarity hash of `bits` length (32 to 256, in steps of
+/// 32) from frame signatures
+pub fn gen_video_hash(signatures: &[FrameSignature], bits: u32) -> Result<VideoHashResult> {
+    if !(32..=256).contains(&bits) || !bits.is_multiple_of(32) {
+        return Err(Error::InvalidInput(format!(
+            "bits must be a multiple of 32 from 32 to 256, got {bits}"
+        )));
+    }
+    Ok(VideoHashResult {
+        hash: hex::encode(&soft_hash_video(signatures)[..bits as usize / 8]),
+        frames: signatures.len(),
+    })
+}
+
+/// 256-bit similarity digest of frame signatures
+pub fn soft_hash_video(signatures: &[FrameSignature]) -> Vec<u8> {
+    let words: BTreeSet<(usize, u32)> = signatures
+        .iter()
+        .flat_map(|signature| {
+            signature
+                .chunks(WORD_LEN)
+                .map(|word| word.iter().fold(0u32, |value, &e| value * 3 + e as u32))
+                .enumerate()
+        })
+        .collect();
+    let features: Vec<u32> = words
+        .into_iter()
+        .map(|(position, word)| {
+            let mut bytes = [0u8; 6];
+            bytes[..2].copy_from_slice(&(position as u16).to_le_bytes());
+            bytes[2..].copy_from_slice(&word.to_le_bytes());
+            xxh32(&bytes, 0)
+        })
+        .collect();
+    minhash_256(&features)
+}
+
+/// Signature of a grayscale frame (`width × height` luma bytes in row order)
+pub fn frame_signature(luma: &[u8], width: usize, height: usize) -> Result<FrameSignature> {
+    if width == 0 || height == 0 || luma.len() != width * height {
+        return Err(Error::InvalidInput(format!(
+            "expected a {width}×{height} frame, got {} bytes",
+            luma.len()
+        )));
+    }
+
+    // Integral image of the 32×32 block means, with a zero row and column
+    let mut integral = [[0.0f64; GRID + 1]; GRID + 1];
+    for by in 0..GRID {
+        let (y0, y1) = block_range(by, height);
+        for bx in 0..GRID {
+            let (x0, x1) = block_range(bx, width);
+            let sum: u64 = (y0..y1)
+                .map(|y| {
+                    luma[y * width + x0..y * width + x1]
+                        .iter()
+                        .map(|&p| p as u64)
+                        .sum::<u64>()
+                })
+                .sum();
+            let mean = sum as f64 / ((y1 - y0) * (x1 - x0)) as f64;
+            integral[by + 1][bx + 1] =
+                mean + integral[by][bx + 1] + integral[by + 1][bx] - integral[by][bx];
+        }
+    }
+    let mean = |&(x, y, w, h): &Region| {
+        (integral[y + h][x + w] - integral[y][x + w] - integral[y + h][x] + integral[y][x])
+            / (w * h) as f64
+    };
+
+    let differences: Vec<f64> = regions().iter().map(|(a, b)| mean(a) - mean(b)).collect();
+    let mut magnitudes: Vec<f64> = differences.iter().map(|d| d.abs()).collect();
+    magnitudes.sort_by(f64::total_cmp);
+    let threshold = magnitudes[FRAME_SIGNATURE_LEN / 3];
+
+    let mut signature = [1u8; FRAME_SIGNATURE_LEN];
+    for (element, difference) in signature.iter_mut().zip(differences) {
+        if difference < -threshold {
+            *element = 0;
+        } else if difference > threshold {
+            *element = 2;
+        }
+    }
+    Ok(signature)
+}
+
+/// Pixel range of block `index` of 32 along an axis of `size` pixels; small
+/// frames repeat pixels
+fn block_range(index: usize, size: usize) -> (usize, usize) {
+    let start = index * size / GRID;
+    let end = ((index + 1) * size / GRID).max(start + 1);
+    (start.min(size - 1), end.min(size))
+}
+
+/// Block regions (x, y, width, height) compared by the signature elements,
+/// generated once from a fixed seed: squares and bars of 1 to 8 blocks
+fn regions() -> &'static [(Region, Region)] {
+    use std::sync::OnceLock;
+    static REGIONS: OnceLock<Vec<(Region, Region)>> = OnceLock::new();
+    REGIONS.get_or_init(|| {
+        let mut state: u64 = 0x5EED_1D0C_0DE5_1A7E;
+        let mut next = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        let region = |next: &mut dyn FnMut(usize) -> usize| {
+            let (w, h) = match next(3) {
+                0 => {
+                    let side = 1 + next(8);
+                    (side, side)
+                }
+                1 => (1 + next(8), 1 + next(2)),
+                _ => (1 + next(2), 1 + next(8)),
+            };
+            (next(GRID - w + 1), next(GRID - h + 1), w, h)
+        };
+        (0..FRAME_SIGNATURE_LEN)
+            .map(|_| (region(&mut next), region(&mut next)))
+            .collect()
+    })
+}
+
+/// Decode a video with `ffmpeg` and compute the signatures of its frames,
+/// sampled at [`VIDEO_FPS`]
+#[cfg(feature = "video")]
+pub fn frame_signatures_file(path: &Path) -> Result<Vec<FrameSignature>> {
+    const SIZE: usize = 2 * GRID;
+
+    let filter = format!("fps={VIDEO_FPS},scale={SIZE}:{SIZE}:flags=area,format=gray");
+    let mut child = Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-i"])
+        .arg(path)
+        .args(["-an", "-vf", &filter, "-f", "rawvideo", "pipe:1"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                Error::Unsupported("video decoding requires the ffmpeg program")
+            }
+            _ => Error::io(path, e),
+        })?;
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    // Drain stderr concurrently so ffmpeg cannot block on a full pipe
+    let errors = std::thread::spawn(move || {
+        let mut errors = String::new();
+        let _ = stderr.read_to_string(&mut errors);
+        errors
+    });
+    let mut signatures = Vec::new();
+    let mut frame = vec![0u8; SIZE * SIZE];
+    let read = loop {
+        match stdout.read_exact(&mut frame) {
+            Ok(()) => signatures.push(frame_signature(&frame, SIZE, SIZE)?),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break Ok(()),
+            Err(e) => break Err(Error::io(path, e)),
+        }
+    };
+    let status = child.wait().map_err(|e| Error::io(path, e))?;
+    let errors = errors.join().unwrap_or_default();
+    read?;
+    if !status.success() {
+        return Err(Error::InvalidInput(format!(
+            "cannot decode video {}: {}",
+            path.display(),
+            errors.trim()
+        )));
+    }
+    Ok(signatures)
+}
+
+/// Decode a video with `ffmpeg` and generate its similarity hash
+#[cfg(feature = "video")]
+pub fn gen_video_hash_file(path: &Path, bits: u32) -> Result<VideoHashResult> {
+    gen_video_hash(&frame_signatures_file(path)?, bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames of a bright square moving over a gradient, with optional noise
+    fn video(frames: usize, size: usize, noise: u8, speed: usize) -> Vec<FrameSignature> {
+        let mut state: u32 = 12345;
+        (0..frames)
+            .map(|t| {
+                let luma: Vec<u8> = (0..size * size)
+                    .map(|i| {
+                        let (x, y) = (i % size * 64 / size, i / size * 64 / size);
+                        let square = (x + 64 - (t * speed) % 64) % 64 < 16 && (20..40).contains(&y);
+                        let value = if square { 230 } else { (x + y) as u8 };
+                        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                        value.saturating_add(((state >> 16) % (noise as u32 + 1)) as u8)
+                    })
+                    .collect();
+                frame_signature(&luma, size, size).unwrap()
+            })
+            .collect()
+    }
+
+    fn hash(signatures: &[FrameSignature]) -> Vec<u8> {
+        hex::decode(gen_video_hash(signatures, 64).unwrap().hash).unwrap()
+    }
+
+    /// Share of equal bits of two digests
+    fn similarity(a: &[u8], b: &[u8]) -> f64 {
+        let distance: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+        1.0 - distance as f64 / (a.len() * 8) as f64
+    }
+
+    #[test]
+    fn test_frame_signature() {
+        let signature = video(1, 64, 0, 1)[0];
+        let levels = |level| signature.iter().filter(|&&e| e == level).count();
+        assert!(levels(1) >= FRAME_SIGNATURE_LEN / 3);
+        assert!(levels(0) > 0 && levels(2) > 0);
+        // A frame of another size gives nearly the same signature
+        let rescaled = video(1, 96, 0, 1)[0];
+        let same = signature
+            .iter()
+            .zip(rescaled)
+            .filter(|(a, b)| **a == *b)
+            .count();
+        assert!(same > FRAME_SIGNATURE_LEN * 9 / 10);
+
+        assert!(frame_signature(&[0; 10], 4, 4).is_err());
+        assert!(frame_signature(&[7; 16], 4, 4).is_ok());
+    }
+
+    #[test]
+    fn test_gen_video_hash() {
+        let original = video(40, 64, 0, 1);
+        let result = gen_video_hash(&original, 64).unwrap();
+        assert_eq!(result.frames, 40);
+        // A bare digest, not an ISCC code
+        assert_eq!(result.hash.len(), 16);
+        assert!(!result.hash.starts_with("ISCC:"));
+        let original = hash(&original);
+
+        let noisy = hash(&video(40, 96, 2, 1));
+        let other = hash(&video(40, 64, 0, 5));
+        let noisy_similarity = similarity(&original, &noisy);
+        assert!(noisy_similarity > 0.8);
+        assert!(similarity(&original, &other) < noisy_similarity);
+
+        // Frame order does not matter
+        let mut reversed = video(40, 64, 0, 1);
+        reversed.reverse();
+        assert_eq!(hash(&reversed), original);
+        assert_eq!(gen_video_hash(&reversed, 256).unwrap().hash.len(), 64);
+        assert!(gen_video_hash(&[], 24).is_err());
+    }
+}
@@ -4,18 +4,49 @@
 //! hashing of data streams using Content-Defined Chunking (CDC) and MinHash
 //! for creating compact, similarity-preserving signatures.
 
+use alloc::string::ToString;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::iter::Peekable;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+#[cfg(feature = "python")]
 use pyo3::types::{PyBytes, PyDict};
+#[cfg(feature = "std")]
+use rayon::prelude::*;
 use xxhash_rust::xxh32::xxh32;
 
-use crate::cdc::{cdc_chunks, DATA_AVG_CHUNK_SIZE};
-use crate::minhash::minhash_256;
+use crate::cdc::{final_data_cut, CdcParams, ChunkerIter};
+use crate::error::{Error, Result};
+use crate::instance::{invalid_state, take};
+use crate::minhash::MinHash;
+
+/// Bytes kept from the start of a range to resynchronize chunking when merging
+pub const RESYNC_WINDOW: usize = 64 * 1024;
+
+/// Bytes of chunks per batch whose features are hashed in parallel
+#[cfg(feature = "std")]
+const FEATURE_BATCH: usize = 1024 * 1024;
 
 /// DataHasher collects xxhash32 digests of CDC chunks.
+///
+/// Hashers are `Send` and `Sync`: they own their state, so they can be moved to
+/// worker threads or kept in a pool, and `&self` methods may run concurrently.
+/// Cloning copies the whole state, so a snapshot can be finalized while the
+/// original keeps hashing.
+#[derive(Clone)]
 pub struct DataHasher {
     chunk_features: Vec<u32>,
     tail: Vec<u8>,
     finalized: bool,
+    /// Bytes in completed chunks
+    chunked: u64,
+    /// First bytes of a range hasher (see [`DataHasher::for_range`])
+    head: Option<Vec<u8>>,
+    /// Ends of the chunks completed within the head, relative to the range start
+    head_cuts: Vec<u32>,
+    /// Chunk sizes of the content-defined chunking
+    pub(crate) cdc: CdcParams,
 }
 
 impl Default for DataHasher {
@@ -26,60 +57,353 @@ impl Default for DataHasher {
 
 impl DataHasher {
     pub fn new() -> Self {
+        Self::with_cdc_params(CdcParams::default())
+    }
+
+    /// Hasher with a non-standard average chunk size and the minimum and
+    /// maximum derived from it (see [`CdcParams::new`])
+    pub fn with_avg_chunk_size(avg_chunk_size: usize) -> Result<Self> {
+        Ok(Self::with_cdc_params(CdcParams::new(avg_chunk_size)?))
+    }
+
+    /// Hasher with explicit chunk sizes; only [`CdcParams::default`] yields
+    /// standard Data-Codes
+    pub fn with_cdc_params(cdc: CdcParams) -> Self {
         let mut hasher = DataHasher {
             chunk_features: Vec::new(),
             tail: Vec::new(),
             finalized: false,
+            chunked: 0,
+            head: None,
+            head_cuts: Vec::new(),
+            cdc,
         };
         // Match Python reference implementation which calls push(b"") in __init__
         hasher.push(b"");
         hasher
     }
 
+    /// Hasher for a part of a stream that is merged into the hasher of the
+    /// preceding part later (see [`DataHasher::merge`])
+    pub fn for_range() -> Self {
+        DataHasher {
+            head: Some(Vec::new()),
+            ..Self::new()
+        }
+    }
+
+    /// Range hasher with the chunk sizes of this hasher, for a part of the
+    /// stream hashed on another thread and merged back later
+    pub fn fork(&self) -> Self {
+        DataHasher {
+            head: Some(Vec::new()),
+            ..Self::with_cdc_params(self.cdc)
+        }
+    }
+
     pub fn push(&mut self, data: &[u8]) {
-        // Prepend any tail carried over from previous push.
-        let combined: Vec<u8> = if !self.tail.is_empty() {
-            [self.tail.as_slice(), data].concat()
-        } else {
-            data.to_vec()
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("chunk", bytes = data.len()).entered();
+        if let Some(head) = &mut self.head {
+            let take = RESYNC_WINDOW.saturating_sub(head.len()).min(data.len());
+            head.extend_from_slice(&data[..take]);
+        }
+        // Finish the chunks starting in the tail carried over from the previous
+        // push, copying at most one maximum chunk of the new data
+        let mut rest = data;
+        while !self.tail.is_empty() {
+            let carried = self.tail.len();
+            let window = rest.len().min(self.cdc.max_size() + 1);
+            self.tail.extend_from_slice(&rest[..window]);
+            let Some(cut) = final_data_cut(&self.tail, &self.cdc) else {
+                // Not enough data to place the boundary, all of it is in the tail
+                return;
+            };
+            let feature = xxh32(&self.tail[..cut], 0);
+            self.add_chunk(feature, cut);
+            if cut >= carried {
+                rest = &rest[cut - carried..];
+                self.tail.clear();
+            } else {
+                self.tail.truncate(carried);
+                self.tail.drain(..cut);
+            }
+        }
+        // Chunk the rest in place, keeping its last chunk as the new tail
+        let mut chunks = ChunkerIter::with_params(rest, self.cdc).peekable();
+        #[cfg(feature = "std")]
+        if rest.len() > FEATURE_BATCH {
+            self.push_batches(chunks);
+            return;
+        }
+        while let Some((_, length, chunk)) = chunks.next() {
+            if chunks.peek().is_none() {
+                self.tail.extend_from_slice(chunk);
+                break;
+            }
+            self.add_chunk(xxh32(chunk, 0), length);
+        }
+    }
+
+    /// Chunk a large input, hashing the features of each batch of chunks on
+    /// the rayon pool while the next batch is scanned
+    #[cfg(feature = "std")]
+    fn push_batches(&mut self, mut chunks: Peekable<ChunkerIter<'_>>) {
+        let mut last: &[u8] = &[];
+        let mut pending: Vec<&[u8]> = Vec::new();
+        loop {
+            let (scanned, features) = rayon::join(
+                || {
+                    let mut batch = Vec::new();
+                    let mut bytes = 0;
+                    while bytes < FEATURE_BATCH {
+                        let Some((_, length, chunk)) = chunks.next() else {
+                            break;
+                        };
+                        if chunks.peek().is_none() {
+                            last = chunk;
+                            break;
+                        }
+                        batch.push(chunk);
+                        bytes += length;
+                    }
+                    batch
+                },
+                || {
+                    pending
+                        .par_iter()
+                        .map(|chunk| xxh32(chunk, 0))
+                        .collect::<Vec<_>>()
+                },
+            );
+            for (feature, chunk) in features.into_iter().zip(&pending) {
+                self.add_chunk(feature, chunk.len());
+            }
+            if scanned.is_empty() {
+                break;
+            }
+            pending = scanned;
+        }
+        self.tail.extend_from_slice(last);
+    }
+
+    fn add_chunk(&mut self, feature: u32, length: usize) {
+        #[cfg(feature = "metrics")]
+        crate::telemetry::record_chunk(length);
+        self.chunk_features.push(feature);
+        self.chunked += length as u64;
+        if self.head.is_some() && self.chunked <= RESYNC_WINDOW as u64 {
+            self.head_cuts.push(self.chunked as u32);
+        }
+    }
+
+    /// Append the range hasher of the directly following part of the stream.
+    ///
+    /// Chunks are recomputed across the boundary until they line up with the
+    /// chunks of `next` again, which content-defined chunking normally does
+    /// within a few chunks. Fails (leaving this hasher unchanged) if that does
+    /// not happen within the first [`RESYNC_WINDOW`] bytes of `next`.
+    pub fn merge(&mut self, next: DataHasher) -> Result<()> {
+        let invalid = |message: &str| Error::InvalidRange(message.to_string());
+        if self.finalized || next.finalized {
+            return Err(invalid("finalized hasher cannot be merged"));
+        }
+        let Some(head) = next.head else {
+            return Err(invalid("only range hashers can be merged"));
         };
-        let (chunks, new_tail) = cdc_chunks(&combined, false, DATA_AVG_CHUNK_SIZE);
-        for chunk in chunks {
-            self.chunk_features.push(xxh32(chunk, 0));
+        if next.cdc != self.cdc {
+            return Err(invalid(
+                "hashers with different chunk sizes cannot be merged",
+            ));
         }
-        self.tail = new_tail.to_vec();
+        if head.len() as u64 == next.chunked + next.tail.len() as u64 {
+            // The whole range is at hand
+            self.push(&head);
+            return Ok(());
+        }
+
+        let self_len = self.chunked + self.tail.len() as u64;
+        let buffer = [self.tail.as_slice(), &head].concat();
+        let mut features = Vec::new();
+        let mut cuts = Vec::new();
+        let mut pos = 0;
+        let skip = loop {
+            let Some(cut) = final_data_cut(&buffer[pos..], &self.cdc) else {
+                return Err(invalid(
+                    "Data-Code chunks did not resynchronize at the range boundary",
+                ));
+            };
+            features.push(xxh32(&buffer[pos..pos + cut], 0));
+            pos += cut;
+            cuts.push(self.chunked + pos as u64);
+            if pos >= self.tail.len() {
+                let boundary = (pos - self.tail.len()) as u32;
+                if boundary == 0 {
+                    break 0;
+                }
+                if let Ok(index) = next.head_cuts.binary_search(&boundary) {
+                    break index + 1;
+                }
+            }
+        };
+
+        if let Some(own_head) = &mut self.head {
+            let take = RESYNC_WINDOW.saturating_sub(own_head.len()).min(head.len());
+            own_head.extend_from_slice(&head[..take]);
+            let next_cuts = next.head_cuts[skip..].iter().map(|&c| self_len + c as u64);
+            self.head_cuts.extend(
+                cuts.into_iter()
+                    .chain(next_cuts)
+                    .take_while(|&c| c <= RESYNC_WINDOW as u64)
+                    .map(|c| c as u32),
+            );
+        }
+        self.chunk_features.extend(features);
+        self.chunk_features
+            .extend_from_slice(&next.chunk_features[skip..]);
+        self.chunked = self_len + next.chunked;
+        self.tail = next.tail;
+        Ok(())
     }
 
-    fn finalize(&mut self) {
+    /// Return the 256-bit (32-byte) digest of the data pushed so far.
+    ///
+    /// Does not change the hasher, so more data can be pushed afterwards.
+    pub fn digest(&self) -> Vec<u8> {
+        self.minhash().digest()
+    }
+
+    /// Chunk features (xxHash32 per chunk) the digest is computed from,
+    /// including the feature of the current tail
+    pub fn features(&self) -> Vec<u32> {
+        let mut features = self.chunk_features.clone();
+        // States written by versions that finalized in place already hold the
+        // tail feature
         if !self.finalized {
             // Always process tail if it exists (even if empty)
             // This matches the Python reference which uses 'if self.tail is not None'
-            self.chunk_features.push(xxh32(&self.tail, 0));
-            self.tail.clear();
-            self.finalized = true;
+            features.push(xxh32(&self.tail, 0));
+        }
+        features
+    }
+
+    /// MinHash of the chunk features, whose [`MinHash::digest`] is the digest
+    pub fn minhash(&self) -> MinHash {
+        let mut minhash = MinHash::default();
+        minhash.add_features(&self.features());
+        minhash
+    }
+
+    /// Append the hasher state: the chunk features so far and the unchunked tail.
+    ///
+    /// Layout (integers little-endian): `u8` flags (1: finalized, 2: range
+    /// hasher), `u64` bytes in completed chunks, `u64` number of features
+    /// followed by a `u32` each, `u32` tail length followed by its bytes. Range
+    /// hashers add a `u32` head length followed by its bytes and a `u32` number
+    /// of chunk ends within the head followed by a `u32` each.
+    pub fn write_state(&self, out: &mut Vec<u8>) {
+        out.push(self.finalized as u8 | (self.head.is_some() as u8) << 1);
+        out.extend_from_slice(&self.chunked.to_le_bytes());
+        out.extend_from_slice(&(self.chunk_features.len() as u64).to_le_bytes());
+        for feature in &self.chunk_features {
+            out.extend_from_slice(&feature.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.tail.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.tail);
+        if let Some(head) = &self.head {
+            out.extend_from_slice(&(head.len() as u32).to_le_bytes());
+            out.extend_from_slice(head);
+            out.extend_from_slice(&(self.head_cuts.len() as u32).to_le_bytes());
+            for cut in &self.head_cuts {
+                out.extend_from_slice(&cut.to_le_bytes());
+            }
         }
     }
 
-    /// Finalize and return the 256-bit (32-byte) digest.
-    pub fn digest(&mut self) -> Vec<u8> {
-        self.finalize();
-        minhash_256(&self.chunk_features)
+    /// Read a state written by [`DataHasher::write_state`]; the average chunk
+    /// size is not part of the state and starts out as the default
+    pub fn read_state(input: &mut &[u8]) -> Result<Self> {
+        let flags = take(input, 1)?[0];
+        if flags > 3 {
+            return Err(invalid_state());
+        }
+        let chunked = read_u64(input)?;
+        let count = read_u64(input)?;
+        let chunk_features = read_u32s(input, count)?;
+        let len = read_u32(input)? as usize;
+        let tail = take(input, len)?.to_vec();
+        let (head, head_cuts) = if flags & 2 != 0 {
+            let len = read_u32(input)? as usize;
+            let head = take(input, len)?.to_vec();
+            let count = read_u32(input)?;
+            let cuts = read_u32s(input, count as u64)?;
+            let valid = head.len() <= RESYNC_WINDOW
+                && cuts.windows(2).all(|w| w[0] < w[1])
+                && cuts.last().is_none_or(|&c| c as usize <= head.len());
+            if !valid {
+                return Err(invalid_state());
+            }
+            (Some(head), cuts)
+        } else {
+            (None, Vec::new())
+        };
+        Ok(DataHasher {
+            chunk_features,
+            tail,
+            finalized: flags & 1 != 0,
+            chunked,
+            head,
+            head_cuts,
+            cdc: CdcParams::default(),
+        })
     }
 }
 
+fn read_u32(input: &mut &[u8]) -> Result<u32> {
+    Ok(u32::from_le_bytes(take(input, 4)?.try_into().unwrap()))
+}
+
+fn read_u64(input: &mut &[u8]) -> Result<u64> {
+    Ok(u64::from_le_bytes(take(input, 8)?.try_into().unwrap()))
+}
+
+fn read_u32s(input: &mut &[u8], count: u64) -> Result<Vec<u32>> {
+    if count > input.len() as u64 / 4 {
+        return Err(invalid_state());
+    }
+    Ok(take(input, count as usize * 4)?
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect())
+}
+
 /// A Python-exposed data processor that implements an incremental Data-Code digest.
+#[cfg(feature = "python")]
 #[pyclass]
 pub struct DataCodeProcessor {
     hasher: DataHasher,
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl DataCodeProcessor {
+    /// Chunk sizes other than the defaults (1024 bytes on average, a quarter
+    /// of that minimum, eight times maximum) yield non-standard Data-Codes.
     #[new]
-    fn new() -> Self {
-        DataCodeProcessor {
-            hasher: DataHasher::new(),
-        }
+    #[pyo3(signature = (avg_chunk_size=1024, min_chunk_size=None, max_chunk_size=None))]
+    fn new(
+        avg_chunk_size: usize,
+        min_chunk_size: Option<usize>,
+        max_chunk_size: Option<usize>,
+    ) -> PyResult<Self> {
+        let cdc = CdcParams::with_sizes(
+            avg_chunk_size,
+            min_chunk_size.unwrap_or(avg_chunk_size / 4),
+            max_chunk_size.unwrap_or(avg_chunk_size * 8),
+        )?;
+        Ok(DataCodeProcessor {
+            hasher: DataHasher::with_cdc_params(cdc),
+        })
     }
 
     /// Incrementally push a chunk of data.
@@ -89,6 +413,13 @@ impl DataCodeProcessor {
         self.hasher.push(data);
     }
 
+    /// Return a copy of the processor to take interim results from
+    fn copy(&self) -> Self {
+        DataCodeProcessor {
+            hasher: self.hasher.clone(),
+        }
+    }
+
     /// Finalize the processing and return a dictionary with the 256-bit digest.
     ///
     /// The returned dict is of the format: {"digest": <256-bit-bytes-digest>}.
@@ -103,6 +434,7 @@ impl DataCodeProcessor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec;
 
     #[test]
     fn test_data_hasher_new() {
@@ -122,13 +454,22 @@ mod tests {
     }
 
     #[test]
-    fn test_data_hasher_finalize() {
+    fn test_data_hasher_digest_is_repeatable() {
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i * 13 % 251) as u8).collect();
         let mut hasher = DataHasher::new();
-        hasher.push(b"Hello, World!");
-        hasher.finalize();
-        // After finalize, tail should be processed
-        assert_eq!(hasher.chunk_features.len(), 1);
-        assert_eq!(hasher.tail.len(), 0);
+        hasher.push(&data[..7000]);
+        let interim = hasher.digest();
+        // Digest leaves the tail unprocessed
+        assert_eq!(hasher.digest(), interim);
+        assert!(!hasher.tail.is_empty());
+        hasher.push(&data[7000..]);
+
+        let mut whole = DataHasher::new();
+        whole.push(&data);
+        assert_eq!(hasher.digest(), whole.digest());
+        let mut head = DataHasher::new();
+        head.push(&data[..7000]);
+        assert_eq!(interim, head.digest());
     }
 
     #[test]
@@ -153,6 +494,45 @@ mod tests {
         assert_eq!(digest1, digest2);
     }
 
+    #[test]
+    fn test_data_hasher_parallel_features() {
+        let data: Vec<u8> = (0..5_000_000u32)
+            .map(|i| (i.wrapping_mul(2_246_822_519) >> 17) as u8)
+            .collect();
+        let mut whole = DataHasher::new();
+        whole.push(&data);
+        let mut pieces = DataHasher::new();
+        for part in data.chunks(65536) {
+            pieces.push(part);
+        }
+        assert_eq!(whole.chunk_features, pieces.chunk_features);
+        assert_eq!(whole.tail, pieces.tail);
+        assert_eq!(whole.chunked, pieces.chunked);
+    }
+
+    #[test]
+    fn test_data_hasher_push_sizes() {
+        let data: Vec<u8> = (0..300_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        let (chunks, tail) = crate::cdc::cdc_chunks(&data, false, 1024).unwrap();
+        let mut expected: Vec<u32> = chunks.iter().map(|chunk| xxh32(chunk, 0)).collect();
+        expected.push(xxh32(tail, 0));
+
+        // From small pieces to pieces longer than the maximum chunk size
+        for piece in [13, 255, 1024, 8191, 8192, 8193, 50_000] {
+            let mut hasher = DataHasher::new();
+            for part in data.chunks(piece) {
+                hasher.push(part);
+                hasher.push(b"");
+            }
+            let mut features = hasher.chunk_features.clone();
+            features.push(xxh32(&hasher.tail, 0));
+            assert_eq!(features, expected, "pieces of {piece} bytes");
+            assert_eq!(hasher.chunked + hasher.tail.len() as u64, data.len() as u64);
+        }
+    }
+
     #[test]
     fn test_data_hasher_large_data() {
         let mut hasher = DataHasher::new();
@@ -169,4 +549,48 @@ mod tests {
         // After digest, should have processed at least one chunk
         assert!(!hasher.chunk_features.is_empty() || features_before > 0);
     }
+
+    #[test]
+    fn test_data_hasher_merge_resynchronizes() {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let data: Vec<u8> = (0..300_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let mut whole = DataHasher::new();
+        whole.push(&data);
+        let expected = whole.digest();
+
+        for bounds in [
+            vec![0, 1000, data.len()],
+            vec![0, 65_536, 131_072, data.len()],
+            vec![0, 100_003, 100_100, 250_000, data.len()],
+        ] {
+            let mut parts = bounds.windows(2).map(|w| {
+                let mut hasher = DataHasher::for_range();
+                for piece in data[w[0]..w[1]].chunks(7919) {
+                    hasher.push(piece);
+                }
+                let mut state = Vec::new();
+                hasher.write_state(&mut state);
+                DataHasher::read_state(&mut &state[..]).unwrap()
+            });
+            let mut merged = parts.next().unwrap();
+            for part in parts {
+                merged.merge(part).unwrap();
+            }
+            assert_eq!(merged.digest(), expected, "{bounds:?}");
+        }
+
+        // Only range hashers carry the head needed for merging
+        let mut first = DataHasher::new();
+        first.push(&data[..1000]);
+        let mut next = DataHasher::new();
+        next.push(&data[1000..]);
+        assert!(first.merge(next).is_err());
+    }
 }
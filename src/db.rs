@@ -0,0 +1,222 @@
+//! SQLite checksum database for long-term fixity checking.
+//!
+//! Each row records a file's path, size, modification time, ISCC-SUM code,
+//! full-length units and datahash together with the time it was added and last
+//! verified, so verification can be spread over many runs (oldest first)
+//! instead of re-reading a whole collection at once.
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::io;
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS files (
+    path TEXT PRIMARY KEY,
+    size INTEGER NOT NULL,
+    mtime_ns INTEGER,
+    iscc TEXT NOT NULL,
+    data_unit TEXT,
+    instance_unit TEXT,
+    datahash TEXT NOT NULL,
+    added INTEGER NOT NULL,
+    last_verified INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS files_last_verified ON files (last_verified);
+";
+
+/// A recorded file; times are seconds since the Unix epoch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbRecord {
+    pub path: String,
+    pub size: u64,
+    pub mtime_ns: Option<i64>,
+    pub iscc: String,
+    pub data_unit: Option<String>,
+    pub instance_unit: Option<String>,
+    pub datahash: String,
+    pub added: i64,
+    pub last_verified: i64,
+}
+
+impl DbRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(DbRecord {
+            path: row.get(0)?,
+            size: row.get::<_, i64>(1)? as u64,
+            mtime_ns: row.get(2)?,
+            iscc: row.get(3)?,
+            data_unit: row.get(4)?,
+            instance_unit: row.get(5)?,
+            datahash: row.get(6)?,
+            added: row.get(7)?,
+            last_verified: row.get(8)?,
+        })
+    }
+}
+
+const COLUMNS: &str =
+    "path, size, mtime_ns, iscc, data_unit, instance_unit, datahash, added, last_verified";
+
+fn db_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(format!("database: {e}"))
+}
+
+/// Handle to a checksum database file
+pub struct ChecksumDb {
+    conn: Connection,
+}
+
+impl ChecksumDb {
+    /// Create a new database (or open an existing one) and ensure the schema exists
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let conn = Connection::open(path).map_err(db_error)?;
+        conn.execute_batch(SCHEMA).map_err(db_error)?;
+        Ok(ChecksumDb { conn })
+    }
+
+    /// Open an existing database
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: No such database (run isum db init)", path.display()),
+            ));
+        }
+        Self::create(path)
+    }
+
+    /// Insert or replace a record
+    pub fn upsert(&self, record: &DbRecord) -> io::Result<()> {
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT OR REPLACE INTO files ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+                ),
+                params![
+                    record.path,
+                    record.size as i64,
+                    record.mtime_ns,
+                    record.iscc,
+                    record.data_unit,
+                    record.instance_unit,
+                    record.datahash,
+                    record.added,
+                    record.last_verified,
+                ],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Look up the record of a path
+    pub fn get(&self, path: &str) -> io::Result<Option<DbRecord>> {
+        self.conn
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM files WHERE path = ?1"),
+                [path],
+                DbRecord::from_row,
+            )
+            .optional()
+            .map_err(db_error)
+    }
+
+    /// Records last verified before `before`, least recently verified first
+    pub fn due(&self, before: i64, limit: Option<usize>) -> io::Result<Vec<DbRecord>> {
+        let limit = limit.map_or(-1, |n| n as i64);
+        let mut statement = self
+            .conn
+            .prepare(&format!(
+                "SELECT {COLUMNS} FROM files WHERE last_verified < ?1
+                 ORDER BY last_verified, path LIMIT ?2"
+            ))
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map(params![before, limit], DbRecord::from_row)
+            .map_err(db_error)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(db_error)
+    }
+
+    /// All recorded paths in order
+    pub fn paths(&self) -> io::Result<Vec<String>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT path FROM files ORDER BY path")
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map([], |row| row.get(0))
+            .map_err(db_error)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(db_error)
+    }
+
+    /// Record a successful verification
+    pub fn mark_verified(&self, path: &str, time: i64) -> io::Result<()> {
+        self.conn
+            .execute(
+                "UPDATE files SET last_verified = ?2 WHERE path = ?1",
+                params![path, time],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Remove the record of a path
+    pub fn remove(&self, path: &str) -> io::Result<()> {
+        self.conn
+            .execute("DELETE FROM files WHERE path = ?1", [path])
+            .map_err(db_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(path: &str, last_verified: i64) -> DbRecord {
+        DbRecord {
+            path: path.to_string(),
+            size: 42,
+            mtime_ns: Some(1_700_000_000_000_000_000),
+            iscc: "ISCC:K4AOMGOGQJA4Y46PAC4YPPA63GKSIFDBTTI3VPDUVUKBVU6TP2HSA7Y".to_string(),
+            data_unit: None,
+            instance_unit: None,
+            datahash: "1e20".to_string(),
+            added: 100,
+            last_verified,
+        }
+    }
+
+    #[test]
+    fn test_db_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("fixity.db");
+        assert!(ChecksumDb::open(&db_path).is_err());
+
+        let db = ChecksumDb::create(&db_path).unwrap();
+        db.upsert(&record("/b", 300)).unwrap();
+        db.upsert(&record("/a", 200)).unwrap();
+        db.upsert(&record("/c", 100)).unwrap();
+        assert_eq!(db.get("/a").unwrap(), Some(record("/a", 200)));
+        assert_eq!(db.get("/missing").unwrap(), None);
+        assert_eq!(db.paths().unwrap(), vec!["/a", "/b", "/c"]);
+
+        // Least recently verified first, limited
+        let due: Vec<String> = db
+            .due(250, None)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.path)
+            .collect();
+        assert_eq!(due, vec!["/c", "/a"]);
+        assert_eq!(db.due(1000, Some(1)).unwrap()[0].path, "/c");
+
+        db.mark_verified("/c", 400).unwrap();
+        db.remove("/b").unwrap();
+        drop(db);
+
+        let db = ChecksumDb::open(&db_path).unwrap();
+        assert_eq!(db.get("/c").unwrap().unwrap().last_verified, 400);
+        assert_eq!(db.paths().unwrap(), vec!["/a", "/c"]);
+    }
+}
@@ -0,0 +1,164 @@
+//! Error type of the library.
+//!
+//! Fallible library functions return [`Result`], so callers can match on the
+//! cause of a failure instead of parsing messages. [`Error`] converts into
+//! `io::Error` (keeping the closest [`io::ErrorKind`]) for code built around
+//! `std::io`, and into Python exceptions for the bindings. Without `std` the
+//! I/O and path variants do not exist, without `treewalk` the pattern variant.
+
+use alloc::string::String;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "python")]
+use pyo3::exceptions::{PyIOError, PyValueError};
+#[cfg(feature = "python")]
+use pyo3::PyErr;
+
+use crate::codec::CodecError;
+
+/// Result type of the library
+pub type Result<T, E = Error> = core::result::Result<T, E>;
+
+/// Errors returned by the library
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// I/O failure, with the path it occurred on if known
+    #[cfg(feature = "std")]
+    #[error("{}", io_message(path.as_deref(), source))]
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// Ignore pattern that is not a valid glob
+    #[cfg(feature = "treewalk")]
+    #[error("invalid ignore pattern: {0}")]
+    InvalidPattern(#[from] globset::Error),
+    /// Path that cannot be processed, such as a file name that is not UTF-8
+    #[cfg(feature = "std")]
+    #[error("invalid path {}: {reason}", path.display())]
+    InvalidPath { path: PathBuf, reason: &'static str },
+    /// Malformed ISCC code
+    #[error("invalid ISCC: {0}")]
+    InvalidIscc(#[from] CodecError),
+    /// Options rejected by [`crate::options::IsccSumOptionsBuilder::build`]
+    #[error("invalid options: {0}")]
+    InvalidOptions(String),
+    /// Serialized hasher state that cannot be restored
+    #[error("invalid state: {0}")]
+    InvalidState(String),
+    /// Range hasher that cannot be created or merged
+    #[error("invalid range: {0}")]
+    InvalidRange(String),
+    /// Input that cannot be processed, such as an empty Meta-Code name
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+    /// Key file without a usable Ed25519 key
+    #[cfg(feature = "std")]
+    #[error("invalid key {}: {reason}", path.display())]
+    InvalidKey { path: PathBuf, reason: &'static str },
+    /// Manifest signature that is malformed or does not match
+    #[error("invalid signature: {0}")]
+    InvalidSignature(&'static str),
+    /// Operation not supported by the configuration at hand
+    #[error("unsupported: {0}")]
+    Unsupported(&'static str),
+    /// Operation stopped by its [`crate::cancel::CancellationToken`]
+    #[error("operation cancelled")]
+    Cancelled,
+    /// Deadline of the operation's [`crate::cancel::CancellationToken`] passed
+    #[error("deadline exceeded")]
+    DeadlineExceeded,
+}
+
+#[cfg(feature = "std")]
+fn io_message(path: Option<&Path>, source: &io::Error) -> String {
+    match path {
+        Some(path) => format!("{}: {source}", path.display()),
+        None => source.to_string(),
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error {
+    /// I/O failure on `path`
+    pub fn io<P: Into<PathBuf>>(path: P, source: io::Error) -> Self {
+        Error::Io {
+            path: Some(path.into()),
+            source,
+        }
+    }
+
+    /// The closest `io::ErrorKind`, as used when converting into `io::Error`
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::Io { source, .. } => source.kind(),
+            #[cfg(feature = "treewalk")]
+            Error::InvalidPattern(_) => io::ErrorKind::InvalidInput,
+            Error::InvalidPath { .. }
+            | Error::InvalidOptions(_)
+            | Error::InvalidInput(_)
+            | Error::InvalidRange(_) => io::ErrorKind::InvalidInput,
+            Error::InvalidIscc(_)
+            | Error::InvalidState(_)
+            | Error::InvalidKey { .. }
+            | Error::InvalidSignature(_) => io::ErrorKind::InvalidData,
+            Error::Unsupported(_) => io::ErrorKind::Unsupported,
+            Error::Cancelled => io::ErrorKind::Other,
+            Error::DeadlineExceeded => io::ErrorKind::TimedOut,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io { path: None, source }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io { path: None, source } => source,
+            err => io::Error::new(err.kind(), err),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<Error> for PyErr {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io { .. } => PyIOError::new_err(err.to_string()),
+            err => PyValueError::new_err(err.to_string()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_conversion() {
+        let err = Error::io("data.bin", io::Error::from(io::ErrorKind::NotFound));
+        assert!(err.to_string().starts_with("data.bin: "));
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::NotFound);
+
+        // Plain io errors pass through unchanged
+        let err: Error = io::Error::other("disk on fire").into();
+        assert!(matches!(err, Error::Io { path: None, .. }));
+        assert_eq!(io::Error::from(err).to_string(), "disk on fire");
+
+        let err: Error = "ISCC:AAAA".parse::<crate::IsccCode>().unwrap_err().into();
+        assert!(matches!(err, Error::InvalidIscc(_)));
+        let err = io::Error::from(err);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.get_ref().unwrap().is::<Error>());
+    }
+}
@@ -0,0 +1,318 @@
+//! Flake-Codes and ISCC-IDs: time-ordered unique identifiers.
+//!
+//! A Flake-Code (`gen_flake_code_v0` of `iscc-core`) is a 48-bit timestamp
+//! in milliseconds since the Unix epoch followed by random bits, 64 to 256
+//! bits in total. Flake-Codes sort by creation time in their binary form, so
+//! registries can mint one per declaration without coordination.
+//!
+//! An ISCC-ID (version 1) is a 52-bit timestamp in microseconds followed by
+//! the 12-bit id of the hub that minted it; the realm is the subtype.
+//!
+//! [`FlakeGenerator`] and [`IsccIdGenerator`] keep their codes strictly
+//! increasing: codes minted within the same clock tick (or after the clock
+//! went back) count on from the previous code instead of repeating or
+//! preceding it.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::codec::{IsccCode, MainType};
+use crate::error::{Error, Result};
+
+/// Size of the Flake-Code timestamp in bytes
+const FLAKE_TIMESTAMP_BYTES: usize = 6;
+
+/// Largest ISCC-ID timestamp (52 bits)
+const ID_TIMESTAMP_MAX: u64 = (1 << 52) - 1;
+
+/// Largest hub id of an ISCC-ID (12 bits)
+pub const HUB_ID_MAX: u16 = (1 << 12) - 1;
+
+/// Highest realm (subtype) of an ISCC-ID
+pub const REALM_MAX: u32 = 1;
+
+/// Version of ISCC-IDs minted by this module
+const ID_VERSION: u32 = 1;
+
+/// Per-length generators behind [`gen_flake_code`]
+static FLAKES: Mutex<BTreeMap<u32, FlakeGenerator>> = Mutex::new(BTreeMap::new());
+
+/// Result of [`gen_flake_code`] and [`FlakeGenerator::generate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlakeCodeResult {
+    /// Flake-Code with `ISCC:` prefix
+    pub iscc: String,
+    /// Timestamp of the code in milliseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// Result of [`gen_iscc_id`] and [`IsccIdGenerator::generate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IsccIdResult {
+    /// ISCC-ID with `ISCC:` prefix
+    pub iscc: String,
+    /// Timestamp of the ID in microseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Id of the hub that minted the ID
+    pub hub_id: u16,
+}
+
+/// Mints strictly increasing Flake-Codes of one length
+#[derive(Debug, Clone)]
+pub struct FlakeGenerator {
+    bits: u32,
+    last: Option<(u64, Vec<u8>)>,
+}
+
+impl FlakeGenerator {
+    /// Generator for Flake-Codes of `bits` length (64 to 256, in steps of 32)
+    pub fn new(bits: u32) -> Result<Self> {
+        if !(64..=256).contains(&bits) || !bits.is_multiple_of(32) {
+            return Err(Error::InvalidInput(format!(
+                "bits must be a multiple of 32 from 64 to 256, got {bits}"
+            )));
+        }
+        Ok(FlakeGenerator { bits, last: None })
+    }
+
+    /// Mint a Flake-Code for the current time
+    pub fn generate(&mut self) -> Result<FlakeCodeResult> {
+        let millis = now().as_millis() as u64;
+        self.generate_at(millis)
+    }
+
+    /// Mint a Flake-Code for `millis` since the Unix epoch.
+    ///
+    /// If `millis` is not past the previous code, the code keeps the previous
+    /// timestamp and counts its random bits up by one.
+    pub fn generate_at(&mut self, millis: u64) -> Result<FlakeCodeResult> {
+        if millis >> 48 != 0 {
+            return Err(Error::InvalidInput(format!(
+                "timestamp {millis} does not fit into 48 bits"
+            )));
+        }
+        let (millis, random) = match self.last.take() {
+            Some((last, mut random)) if millis <= last => {
+                if increment(&mut random) {
+                    (last, random)
+                } else {
+                    // The random bits ran over: move on to the next tick
+                    let next = last + 1;
+                    if next >> 48 != 0 {
+                        return Err(Error::InvalidInput(
+                            "Flake-Code timestamps exhausted".to_string(),
+                        ));
+                    }
+                    (next, self.random()?)
+                }
+            }
+            _ => (millis, self.random()?),
+        };
+        let mut body = millis.to_be_bytes()[8 - FLAKE_TIMESTAMP_BYTES..].to_vec();
+        body.extend_from_slice(&random);
+        let code = IsccCode::new(MainType::Flake, 0, 0, self.bits / 32 - 1, body)?;
+        self.last = Some((millis, random));
+        Ok(FlakeCodeResult {
+            iscc: code.to_string(),
+            timestamp: millis,
+        })
+    }
+
+    /// Fresh random bits following the timestamp
+    fn random(&self) -> Result<Vec<u8>> {
+        let mut random = vec![0u8; self.bits as usize / 8 - FLAKE_TIMESTAMP_BYTES];
+        getrandom::fill(&mut random)
+            .map_err(|e| io::Error::other(format!("random source: {e}")))?;
+        Ok(random)
+    }
+}
+
+/// Mint a Flake-Code of `bits` length (64 to 256, in steps of 32).
+///
+/// Codes of the same length minted by one process are strictly increasing.
+pub fn gen_flake_code(bits: u32) -> Result<FlakeCodeResult> {
+    let mut flakes = FLAKES.lock().unwrap_or_else(|e| e.into_inner());
+    match flakes.get_mut(&bits) {
+        Some(generator) => generator.generate(),
+        None => {
+            let mut generator = FlakeGenerator::new(bits)?;
+            let result = generator.generate()?;
+            flakes.insert(bits, generator);
+            Ok(result)
+        }
+    }
+}
+
+/// Mints strictly increasing ISCC-IDs for one hub and realm
+#[derive(Debug, Clone)]
+pub struct IsccIdGenerator {
+    hub_id: u16,
+    realm: u32,
+    last: Option<u64>,
+}
+
+impl IsccIdGenerator {
+    /// Generator for the hub `hub_id` (0 to 4095) in `realm` (0 or 1)
+    pub fn new(hub_id: u16, realm: u32) -> Result<Self> {
+        check_id_fields(hub_id, realm)?;
+        Ok(IsccIdGenerator {
+            hub_id,
+            realm,
+            last: None,
+        })
+    }
+
+    /// Mint an ISCC-ID for the current time
+    pub fn generate(&mut self) -> Result<IsccIdResult> {
+        let micros = now().as_micros() as u64;
+        self.generate_at(micros)
+    }
+
+    /// Mint an ISCC-ID for `micros` since the Unix epoch, or one microsecond
+    /// past the previous ID if `micros` is not past it
+    pub fn generate_at(&mut self, micros: u64) -> Result<IsccIdResult> {
+        let micros = match self.last {
+            Some(last) if micros <= last => last + 1,
+            _ => micros,
+        };
+        let result = gen_iscc_id(micros, self.hub_id, self.realm)?;
+        self.last = Some(micros);
+        Ok(result)
+    }
+}
+
+/// ISCC-ID for `micros` since the Unix epoch, minted by the hub `hub_id`
+/// (0 to 4095) in `realm` (0 or 1)
+pub fn gen_iscc_id(micros: u64, hub_id: u16, realm: u32) -> Result<IsccIdResult> {
+    check_id_fields(hub_id, realm)?;
+    if micros > ID_TIMESTAMP_MAX {
+        return Err(Error::InvalidInput(format!(
+            "timestamp {micros} does not fit into 52 bits"
+        )));
+    }
+    let body = (micros << 12 | u64::from(hub_id)).to_be_bytes().to_vec();
+    let code = IsccCode::new(MainType::Id, realm, ID_VERSION, 0, body)?;
+    Ok(IsccIdResult {
+        iscc: code.to_string(),
+        timestamp: micros,
+        hub_id,
+    })
+}
+
+/// Timestamp and hub id of an ISCC-ID minted by [`gen_iscc_id`]
+pub fn decode_iscc_id(code: &IsccCode) -> Result<IsccIdResult> {
+    if code.maintype() != MainType::Id || code.version() != ID_VERSION || code.bits() != 64 {
+        return Err(Error::InvalidInput(format!(
+            "not a version {ID_VERSION} ISCC-ID: {code}"
+        )));
+    }
+    let value = u64::from_be_bytes(code.body().try_into().expect("64-bit body"));
+    Ok(IsccIdResult {
+        iscc: code.to_string(),
+        timestamp: value >> 12,
+        hub_id: (value & u64::from(HUB_ID_MAX)) as u16,
+    })
+}
+
+fn check_id_fields(hub_id: u16, realm: u32) -> Result<()> {
+    if hub_id > HUB_ID_MAX {
+        return Err(Error::InvalidInput(format!(
+            "hub id must be at most {HUB_ID_MAX}, got {hub_id}"
+        )));
+    }
+    if realm > REALM_MAX {
+        return Err(Error::InvalidInput(format!(
+            "realm must be at most {REALM_MAX}, got {realm}"
+        )));
+    }
+    Ok(())
+}
+
+/// Time since the Unix epoch (zero if the clock is set before it)
+fn now() -> std::time::Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Count big-endian bytes up by one; false if they ran over to zero
+fn increment(bytes: &mut [u8]) -> bool {
+    for byte in bytes.iter_mut().rev() {
+        let (value, carry) = byte.overflowing_add(1);
+        *byte = value;
+        if !carry {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flake_generator() {
+        let mut generator = FlakeGenerator::new(64).unwrap();
+        let first = generator.generate_at(1_700_000_000_000).unwrap();
+        let code: IsccCode = first.iscc.parse().unwrap();
+        assert_eq!((code.maintype(), code.bits()), (MainType::Flake, 64));
+        assert_eq!(code.body()[..6], 1_700_000_000_000u64.to_be_bytes()[2..]);
+        assert_eq!(first.timestamp, 1_700_000_000_000);
+
+        // Same tick and a clock going back both count on from the last code
+        let second = generator.generate_at(1_700_000_000_000).unwrap();
+        let third = generator.generate_at(1_600_000_000_000).unwrap();
+        let bodies: Vec<Vec<u8>> = [&first, &second, &third]
+            .iter()
+            .map(|r| r.iscc.parse::<IsccCode>().unwrap().body().to_vec())
+            .collect();
+        assert!(bodies[0] < bodies[1] && bodies[1] < bodies[2]);
+        assert_eq!(third.timestamp, 1_700_000_000_000);
+
+        assert!(FlakeGenerator::new(32).is_err());
+        assert!(FlakeGenerator::new(96)
+            .unwrap()
+            .generate_at(1 << 48)
+            .is_err());
+        let wide: IsccCode = gen_flake_code(256).unwrap().iscc.parse().unwrap();
+        assert_eq!(wide.bits(), 256);
+    }
+
+    #[test]
+    fn test_increment() {
+        let mut bytes = [0x00, 0xff];
+        assert!(increment(&mut bytes));
+        assert_eq!(bytes, [0x01, 0x00]);
+        let mut bytes = [0xff, 0xff];
+        assert!(!increment(&mut bytes));
+        assert_eq!(bytes, [0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_iscc_id() {
+        let result = gen_iscc_id(1_700_000_000_000_000, 7, 1).unwrap();
+        let code: IsccCode = result.iscc.parse().unwrap();
+        assert_eq!(
+            (code.maintype(), code.subtype(), code.version(), code.bits()),
+            (MainType::Id, 1, 1, 64)
+        );
+        assert_eq!(decode_iscc_id(&code).unwrap(), result);
+
+        let mut generator = IsccIdGenerator::new(HUB_ID_MAX, 0).unwrap();
+        let first = generator.generate_at(1_000).unwrap();
+        let second = generator.generate_at(1_000).unwrap();
+        assert_eq!((second.timestamp, second.hub_id), (1_001, HUB_ID_MAX));
+        assert!(first.iscc != second.iscc);
+
+        assert!(gen_iscc_id(1 << 52, 0, 0).is_err());
+        assert!(IsccIdGenerator::new(4096, 0).is_err());
+        assert!(IsccIdGenerator::new(0, 2).is_err());
+        let flake: IsccCode = gen_flake_code(64).unwrap().iscc.parse().unwrap();
+        assert!(decode_iscc_id(&flake).is_err());
+    }
+}
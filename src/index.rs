@@ -0,0 +1,310 @@
+//! Similarity index for near-duplicate lookup by Data-Code.
+//!
+//! Files are keyed by the first 64 bits of their Data-Code and stored in a
+//! BK-tree over the Hamming distance, so a query only visits the subtrees that
+//! can hold matches within the requested distance. The tree is persisted as is,
+//! so loading an index does not rebuild it.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// File signature of a persisted index
+const MAGIC: &[u8; 8] = b"ISCCIDX1";
+
+/// A BK-tree node: all entries with the same key and the children by distance
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Node {
+    key: u64,
+    entries: Vec<u32>,
+    children: Vec<(u8, u32)>,
+}
+
+/// A persisted BK-tree of Data-Code keys and file paths
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SimilarityIndex {
+    paths: Vec<String>,
+    nodes: Vec<Node>,
+}
+
+/// Extract the 64-bit Data-Code key from a Data-Code unit or an ISCC-SUM code
+pub fn data_code_key(iscc: &str) -> Option<u64> {
+    let bytes = base32::decode(
+        base32::Alphabet::Rfc4648 { padding: false },
+        iscc.strip_prefix("ISCC:")?,
+    )?;
+    let body = bytes.get(2..)?;
+    let data = match bytes[0] >> 4 {
+        // Data-Code unit
+        0b0011 => body,
+        // ISCC-SUM composite: Data and Instance bodies of equal length
+        0b0101 if body.len() % 2 == 0 => &body[..body.len() / 2],
+        _ => return None,
+    };
+    Some(u64::from_be_bytes(data.get(..8)?.try_into().ok()?))
+}
+
+/// Extract the 64-bit key from a raw Data-Code digest
+pub fn digest_key(digest: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(digest.get(..8)?.try_into().ok()?))
+}
+
+impl SimilarityIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of indexed files
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Add a file under its Data-Code key
+    pub fn insert(&mut self, key: u64, path: String) {
+        let entry = self.paths.len() as u32;
+        self.paths.push(path);
+
+        if self.nodes.is_empty() {
+            self.nodes.push(Node {
+                key,
+                entries: vec![entry],
+                children: Vec::new(),
+            });
+            return;
+        }
+
+        let mut current = 0;
+        loop {
+            let distance = (self.nodes[current].key ^ key).count_ones() as u8;
+            if distance == 0 {
+                self.nodes[current].entries.push(entry);
+                return;
+            }
+            match self.nodes[current]
+                .children
+                .iter()
+                .find(|(d, _)| *d == distance)
+            {
+                Some(&(_, child)) => current = child as usize,
+                None => {
+                    let child = self.nodes.len() as u32;
+                    self.nodes.push(Node {
+                        key,
+                        entries: vec![entry],
+                        children: Vec::new(),
+                    });
+                    self.nodes[current].children.push((distance, child));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Files whose key is within `max_distance` bits, closest first (then by path)
+    pub fn query(&self, key: u64, max_distance: u32) -> Vec<(u32, &str)> {
+        let mut matches = Vec::new();
+        let mut pending = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(index) = pending.pop() {
+            let node = &self.nodes[index];
+            let distance = (node.key ^ key).count_ones();
+            if distance <= max_distance {
+                for &entry in &node.entries {
+                    matches.push((distance, self.paths[entry as usize].as_str()));
+                }
+            }
+            // Triangle inequality: only children within [distance - max, distance + max]
+            let range = distance.saturating_sub(max_distance)..=distance + max_distance;
+            pending.extend(
+                node.children
+                    .iter()
+                    .filter(|(d, _)| range.contains(&(*d as u32)))
+                    .map(|&(_, child)| child as usize),
+            );
+        }
+        matches.sort();
+        matches
+    }
+
+    /// Write the index to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&(self.paths.len() as u64).to_le_bytes())?;
+        for path in &self.paths {
+            out.write_all(&(path.len() as u32).to_le_bytes())?;
+            out.write_all(path.as_bytes())?;
+        }
+        out.write_all(&(self.nodes.len() as u64).to_le_bytes())?;
+        for node in &self.nodes {
+            out.write_all(&node.key.to_le_bytes())?;
+            out.write_all(&(node.entries.len() as u32).to_le_bytes())?;
+            for entry in &node.entries {
+                out.write_all(&entry.to_le_bytes())?;
+            }
+            out.write_all(&[node.children.len() as u8])?;
+            for (distance, child) in &node.children {
+                out.write_all(&[*distance])?;
+                out.write_all(&child.to_le_bytes())?;
+            }
+        }
+        out.flush()
+    }
+
+    /// Read an index written by [`SimilarityIndex::save`]
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not an isum index file");
+        let mut input = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic).map_err(|_| invalid())?;
+        if &magic != MAGIC {
+            return Err(invalid());
+        }
+
+        let path_count = read_u64(&mut input)? as usize;
+        let mut paths = Vec::with_capacity(path_count.min(1 << 20));
+        for _ in 0..path_count {
+            let mut bytes = vec![0u8; read_u32(&mut input)? as usize];
+            input.read_exact(&mut bytes)?;
+            paths.push(String::from_utf8(bytes).map_err(|_| invalid())?);
+        }
+
+        let node_count = read_u64(&mut input)? as usize;
+        let mut nodes = Vec::with_capacity(node_count.min(1 << 20));
+        for _ in 0..node_count {
+            let key = read_u64(&mut input)?;
+            let entries = (0..read_u32(&mut input)?)
+                .map(|_| read_u32(&mut input))
+                .collect::<io::Result<Vec<_>>>()?;
+            let mut count = [0u8; 1];
+            input.read_exact(&mut count)?;
+            let children = (0..count[0])
+                .map(|_| {
+                    let mut distance = [0u8; 1];
+                    input.read_exact(&mut distance)?;
+                    Ok((distance[0], read_u32(&mut input)?))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            nodes.push(Node {
+                key,
+                entries,
+                children,
+            });
+        }
+
+        // Reject references that would make queries panic
+        let valid = nodes.iter().all(|node| {
+            node.entries.iter().all(|&e| (e as usize) < paths.len())
+                && node
+                    .children
+                    .iter()
+                    .all(|&(_, c)| (c as usize) < nodes.len())
+        });
+        if !valid {
+            return Err(invalid());
+        }
+        Ok(SimilarityIndex { paths, nodes })
+    }
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SimilarityIndex {
+        let mut index = SimilarityIndex::new();
+        index.insert(0, "zero".to_string());
+        index.insert(0b1, "one-bit".to_string());
+        index.insert(0b1111, "four-bits".to_string());
+        index.insert(u64::MAX, "all-bits".to_string());
+        index.insert(0, "zero-copy".to_string());
+        index
+    }
+
+    #[test]
+    fn test_query() {
+        let index = sample();
+        assert_eq!(index.len(), 5);
+        assert_eq!(
+            index.query(0, 1),
+            vec![(0, "zero"), (0, "zero-copy"), (1, "one-bit")]
+        );
+        assert_eq!(index.query(0, 4).len(), 4);
+        assert_eq!(index.query(u64::MAX, 0), vec![(0, "all-bits")]);
+        assert!(SimilarityIndex::new().query(0, 64).is_empty());
+    }
+
+    #[test]
+    fn test_query_matches_linear_scan() {
+        let mut index = SimilarityIndex::new();
+        let mut keys = Vec::new();
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        for i in 0..2000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            // Cluster half of the keys around a few centers
+            let key = if i % 2 == 0 {
+                state
+            } else {
+                (state & 0xff) ^ 0xdead_beef
+            };
+            keys.push(key);
+            index.insert(key, i.to_string());
+        }
+        for max_distance in [0, 3, 8, 20] {
+            let expected = keys
+                .iter()
+                .filter(|k| (*k ^ 0xdead_beef).count_ones() <= max_distance)
+                .count();
+            assert_eq!(index.query(0xdead_beef, max_distance).len(), expected);
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("files.idx");
+        let index = sample();
+        index.save(&path).unwrap();
+        assert_eq!(SimilarityIndex::load(&path).unwrap(), index);
+
+        std::fs::write(&path, b"garbage").unwrap();
+        assert!(SimilarityIndex::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_data_code_key() {
+        // ISCC-SUM composite (wide) and the equivalent Data-Code unit share the key
+        let sum = "ISCC:K4AOMGOGQJA4Y46PAC4YPPA63GKSIFDBTTI3VPDUVUKBVU6TP2HSA7Y";
+        let key = data_code_key(sum).unwrap();
+        let mut unit = vec![0b0011_0000, 0b0000_0001];
+        unit.extend_from_slice(&key.to_be_bytes());
+        let unit = format!(
+            "ISCC:{}",
+            base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &unit)
+        );
+        assert_eq!(data_code_key(&unit), Some(key));
+        assert_eq!(data_code_key("ISCC:AAAA"), None);
+        assert_eq!(data_code_key("not-a-code"), None);
+    }
+}
@@ -2,14 +2,131 @@
 //!
 //! This module provides the InstanceCodeProcessor which implements incremental
 //! hashing of data streams using BLAKE3 for creating cryptographic file hashes.
+//!
+//! The BLAKE3 tree is built from fixed-size blocks so the hasher state (the
+//! chaining values of completed subtrees and the bytes of the current block)
+//! can be saved and restored, see [`InstanceHasher::write_state`]. Hashers for
+//! block-aligned byte ranges can be merged into the hasher of the whole stream,
+//! see [`InstanceHasher::merge`].
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::fmt;
+use core::str::FromStr;
 
+use blake3::hazmat::{
+    merge_subtrees_non_root, merge_subtrees_root, ChainingValue, HasherExt, Mode,
+};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+#[cfg(feature = "python")]
 use pyo3::types::{PyBytes, PyDict};
+#[cfg(feature = "std")]
+use rayon::prelude::*;
+
+use crate::error::{Error, Result};
+
+/// Size of the BLAKE3 subtrees hashed at once (64 chunks)
+pub const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Whole blocks in a pushed slice from which they are hashed in parallel
+#[cfg(feature = "std")]
+const PARALLEL_BLOCKS: usize = 16;
+
+/// Secret key for non-standard keyed Instance-Codes (see [`InstanceHasher::with_key`])
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct InstanceKey([u8; blake3::KEY_LEN]);
+
+impl InstanceKey {
+    pub fn new(key: [u8; blake3::KEY_LEN]) -> Self {
+        InstanceKey(key)
+    }
+
+    /// Key derived from secret material for a namespace such as a tenant,
+    /// so one secret yields uncorrelated digests per namespace
+    pub fn derive(namespace: &str, secret: &[u8]) -> Self {
+        let context = format!("iscc-sum instance key {namespace}");
+        InstanceKey(blake3::derive_key(&context, secret))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; blake3::KEY_LEN] {
+        &self.0
+    }
+}
+
+/// Keys are not printed
+impl fmt::Debug for InstanceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("InstanceKey(..)")
+    }
+}
+
+/// Encoding of the BLAKE3 multihash in [`crate::sum::IsccSumResult::datahash`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DatahashEncoding {
+    /// Plain hex without a multibase prefix (`1e20...`)
+    #[default]
+    Hex,
+    /// Multibase base58btc (`z...`)
+    Base58btc,
+    /// Multibase base64url without padding (`u...`)
+    Base64url,
+    /// CIDv1 with the raw codec, multibase base32 (`b...`)
+    Cid,
+}
+
+impl DatahashEncoding {
+    /// Names of all encodings, as accepted by [`DatahashEncoding::from_str`]
+    pub const NAMES: [&'static str; 4] = ["hex", "base58btc", "base64url", "cid"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DatahashEncoding::Hex => "hex",
+            DatahashEncoding::Base58btc => "base58btc",
+            DatahashEncoding::Base64url => "base64url",
+            DatahashEncoding::Cid => "cid",
+        }
+    }
+}
+
+impl FromStr for DatahashEncoding {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "hex" => Ok(DatahashEncoding::Hex),
+            "base58btc" => Ok(DatahashEncoding::Base58btc),
+            "base64url" => Ok(DatahashEncoding::Base64url),
+            "cid" => Ok(DatahashEncoding::Cid),
+            _ => Err(format!("unknown datahash encoding: {name}")),
+        }
+    }
+}
+
+impl fmt::Display for DatahashEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
 
 /// InstanceHasher collects data and computes BLAKE3 hash.
+///
+/// Like [`crate::data::DataHasher`], hashers are `Send` and `Sync`, and clones
+/// are independent snapshots of the state.
+#[derive(Clone)]
 pub struct InstanceHasher {
-    hasher: blake3::Hasher,
+    /// Chaining values of completed subtrees, in stream order
+    stack: Vec<ChainingValue>,
+    /// Index of the first block (non-zero for a range of the stream)
+    first: u64,
+    /// Number of completed blocks
+    blocks: u64,
+    /// Bytes of the current block, hashed once more data follows
+    block: Vec<u8>,
     filesize: u64,
+    /// Key of the keyed BLAKE3 mode
+    key: Option<InstanceKey>,
 }
 
 impl Default for InstanceHasher {
@@ -21,31 +138,267 @@ impl Default for InstanceHasher {
 impl InstanceHasher {
     pub fn new() -> Self {
         let mut hasher = InstanceHasher {
-            hasher: blake3::Hasher::new(),
+            stack: Vec::new(),
+            first: 0,
+            blocks: 0,
+            block: Vec::with_capacity(BLOCK_SIZE),
             filesize: 0,
+            key: None,
         };
         // Match Python reference implementation which calls push(b"") in __init__
         hasher.push(b"");
         hasher
     }
 
-    pub fn push(&mut self, data: &[u8]) {
+    pub fn push(&mut self, mut data: &[u8]) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("instance_hash", bytes = data.len()).entered();
         self.filesize += data.len() as u64;
-        self.hasher.update(data);
+        while !data.is_empty() {
+            // A full block is only hashed once more data follows, as the last
+            // block may be the root
+            if self.block.len() == BLOCK_SIZE {
+                let cv = self.block_cv(&self.block);
+                self.push_cv(cv);
+                self.block.clear();
+            }
+            #[cfg(feature = "std")]
+            if self.block.is_empty() && data.len() > PARALLEL_BLOCKS * BLOCK_SIZE {
+                // Hash the whole blocks of a large input on the rayon pool,
+                // keeping the last (possibly final) block back
+                let count = (data.len() - 1) / BLOCK_SIZE;
+                let (blocks, rest) = data.split_at(count * BLOCK_SIZE);
+                let start = self.first + self.blocks;
+                let key = self.key.as_ref();
+                let cvs: Vec<ChainingValue> = blocks
+                    .par_chunks(BLOCK_SIZE)
+                    .enumerate()
+                    .map(|(i, block)| block_cv_at(start + i as u64, block, key))
+                    .collect();
+                for cv in cvs {
+                    self.push_cv(cv);
+                }
+                data = rest;
+                continue;
+            }
+            if self.block.is_empty() && data.len() > BLOCK_SIZE {
+                // Hash whole blocks straight from the input
+                let cv = self.block_cv(&data[..BLOCK_SIZE]);
+                self.push_cv(cv);
+                data = &data[BLOCK_SIZE..];
+                continue;
+            }
+            let take = (BLOCK_SIZE - self.block.len()).min(data.len());
+            self.block.extend_from_slice(&data[..take]);
+            data = &data[take..];
+        }
+    }
+
+    /// Hasher computing the keyed BLAKE3 hash instead of the plain one.
+    ///
+    /// Keyed digests are not ISCC-standard: they only match digests made with
+    /// the same key, so they cannot be correlated across keys.
+    pub fn with_key(key: InstanceKey) -> Self {
+        InstanceHasher {
+            key: Some(key),
+            ..Self::new()
+        }
+    }
+
+    /// Key of a keyed hasher
+    pub fn key(&self) -> Option<&InstanceKey> {
+        self.key.as_ref()
+    }
+
+    fn mode(&self) -> Mode<'_> {
+        match &self.key {
+            Some(key) => Mode::KeyedHash(key.as_bytes()),
+            None => Mode::Hash,
+        }
+    }
+
+    /// Hasher for the part of a stream starting at `offset`, a multiple of [`BLOCK_SIZE`]
+    pub fn for_range(offset: u64) -> Result<Self> {
+        if !offset.is_multiple_of(BLOCK_SIZE as u64) {
+            return Err(Error::InvalidRange(format!(
+                "offset {offset} is not a multiple of {BLOCK_SIZE}"
+            )));
+        }
+        Ok(InstanceHasher {
+            first: offset / BLOCK_SIZE as u64,
+            ..Self::new()
+        })
+    }
+
+    /// Range hasher with the key of this hasher for the part of the stream
+    /// starting at `offset` (see [`InstanceHasher::for_range`])
+    pub fn fork(&self, offset: u64) -> Result<Self> {
+        Ok(InstanceHasher {
+            key: self.key,
+            ..Self::for_range(offset)?
+        })
+    }
+
+    /// Stream offset of the first byte hashed by this hasher
+    pub fn offset(&self) -> u64 {
+        self.first * BLOCK_SIZE as u64
+    }
+
+    /// Chaining value of a block (or the final partial block) at the current offset
+    fn block_cv(&self, block: &[u8]) -> ChainingValue {
+        block_cv_at(self.first + self.blocks, block, self.key.as_ref())
+    }
+
+    /// Add the chaining value of a completed block, merging complete subtrees
+    fn push_cv(&mut self, cv: ChainingValue) {
+        self.push_subtree(cv, 1);
+    }
+
+    /// Add the chaining value of a complete subtree of `size` blocks (a power of two)
+    fn push_subtree(&mut self, mut cv: ChainingValue, mut size: u64) {
+        let mut start = self.first + self.blocks;
+        self.blocks += size;
+        // A right child merges with its left sibling unless that lies before this range
+        while !start.is_multiple_of(2 * size) && start - size >= self.first {
+            let left = self.stack.pop().expect("left subtree");
+            cv = merge_subtrees_non_root(&left, &cv, self.mode());
+            start -= size;
+            size *= 2;
+        }
+        self.stack.push(cv);
+    }
+
+    /// Append the hasher of the directly following range.
+    ///
+    /// This range must end on a block boundary (only the last range of a stream
+    /// may end anywhere). Merging in stream order yields the same digest as
+    /// hashing the whole stream at once.
+    pub fn merge(&mut self, next: InstanceHasher) -> Result<()> {
+        self.check_merge(&next)?;
+        if next.filesize == 0 {
+            return Ok(());
+        }
+        if !self.block.is_empty() {
+            let cv = self.block_cv(&self.block);
+            self.push_cv(cv);
+        }
+        for (cv, size) in next
+            .stack
+            .iter()
+            .zip(subtree_sizes(next.first, next.blocks))
+        {
+            self.push_subtree(*cv, size);
+        }
+        self.block = next.block;
+        self.filesize += next.filesize;
+        Ok(())
+    }
+
+    /// Check that `next` can be merged without changing this hasher
+    pub(crate) fn check_merge(&self, next: &InstanceHasher) -> Result<()> {
+        if self.key != next.key {
+            return Err(Error::InvalidRange(
+                "hashers with different keys cannot be merged".to_string(),
+            ));
+        }
+        let end = self.offset() + self.filesize;
+        if end != next.offset() {
+            return Err(Error::InvalidRange(format!(
+                "range at offset {} does not follow the range ending at {end}",
+                next.offset()
+            )));
+        }
+        Ok(())
     }
 
     /// Return blake3 hash digest.
+    ///
+    /// Only meaningful for a hasher starting at offset 0.
     pub fn digest(&self) -> Vec<u8> {
-        self.hasher.finalize().as_bytes().to_vec()
+        if self.stack.is_empty() {
+            let hash = match &self.key {
+                Some(key) => blake3::keyed_hash(key.as_bytes(), &self.block),
+                None => blake3::hash(&self.block),
+            };
+            return hash.as_bytes().to_vec();
+        }
+        let mut cv = self.block_cv(&self.block);
+        for left in self.stack[1..].iter().rev() {
+            cv = merge_subtrees_non_root(left, &cv, self.mode());
+        }
+        merge_subtrees_root(&self.stack[0], &cv, self.mode())
+            .as_bytes()
+            .to_vec()
+    }
+
+    /// Append the hasher state: the completed subtree chaining values and the
+    /// current block (the byte count follows from the block count).
+    ///
+    /// Layout (integers little-endian): `u64` index of the first block, `u64`
+    /// completed blocks, `u8` number of chaining values followed by 32 bytes
+    /// each, `u32` length of the current block followed by its bytes.
+    pub fn write_state(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.first.to_le_bytes());
+        out.extend_from_slice(&self.blocks.to_le_bytes());
+        out.push(self.stack.len() as u8);
+        for cv in &self.stack {
+            out.extend_from_slice(cv);
+        }
+        out.extend_from_slice(&(self.block.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.block);
+    }
+
+    /// Read a state written by [`InstanceHasher::write_state`]
+    pub fn read_state(input: &mut &[u8]) -> Result<Self> {
+        let first = u64::from_le_bytes(take(input, 8)?.try_into().unwrap());
+        let blocks = u64::from_le_bytes(take(input, 8)?.try_into().unwrap());
+        if first
+            .checked_add(blocks)
+            .is_none_or(|end| end > u64::MAX / BLOCK_SIZE as u64)
+        {
+            return Err(invalid_state());
+        }
+        let count = take(input, 1)?[0] as usize;
+        let stack = (0..count)
+            .map(|_| Ok(take(input, 32)?.try_into().unwrap()))
+            .collect::<Result<Vec<ChainingValue>>>()?;
+        let len = u32::from_le_bytes(take(input, 4)?.try_into().unwrap()) as usize;
+        if len > BLOCK_SIZE
+            || count != subtree_sizes(first, blocks).count()
+            || (blocks > 0 && len == 0)
+        {
+            return Err(invalid_state());
+        }
+        let block = take(input, len)?.to_vec();
+        let filesize = blocks
+            .checked_mul(BLOCK_SIZE as u64)
+            .and_then(|n| n.checked_add(len as u64))
+            .ok_or_else(invalid_state)?;
+        Ok(InstanceHasher {
+            stack,
+            first,
+            blocks,
+            block,
+            filesize,
+            key: None,
+        })
     }
 
     /// Return blake3 digest as multihash.
     pub fn multihash(&self) -> String {
-        let mh_prefix = vec![0x1e, 0x20]; // BLAKE3 multihash prefix
-        let digest = self.digest();
-        let mut result = mh_prefix;
-        result.extend_from_slice(&digest);
-        hex::encode(result)
+        hex::encode(self.multihash_bytes())
+    }
+
+    /// BLAKE3 multihash (prefix `0x1e20` followed by the digest)
+    pub fn multihash_bytes(&self) -> Vec<u8> {
+        let mut multihash = vec![0x1e, 0x20]; // BLAKE3 multihash prefix
+        multihash.extend_from_slice(&self.digest());
+        multihash
+    }
+
+    /// BLAKE3 multihash in the given encoding (see [`encode_multihash`])
+    pub fn encoded_multihash(&self, encoding: DatahashEncoding) -> String {
+        encode_multihash(&self.multihash_bytes(), encoding)
     }
 
     /// Get the total filesize processed
@@ -54,12 +407,77 @@ impl InstanceHasher {
     }
 }
 
+/// Encode a multihash as hex, multibase string or CIDv1 (version 1, raw codec
+/// `0x55`, multibase base32 lowercase)
+pub fn encode_multihash(multihash: &[u8], encoding: DatahashEncoding) -> String {
+    use base64::Engine;
+    match encoding {
+        DatahashEncoding::Hex => hex::encode(multihash),
+        DatahashEncoding::Base58btc => format!("z{}", bs58::encode(multihash).into_string()),
+        DatahashEncoding::Base64url => {
+            let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(multihash);
+            format!("u{encoded}")
+        }
+        DatahashEncoding::Cid => {
+            let cid = [&[0x01, 0x55], multihash].concat();
+            let encoded = base32::encode(base32::Alphabet::Rfc4648Lower { padding: false }, &cid);
+            format!("b{encoded}")
+        }
+    }
+}
+
+/// Chaining value of the block with the given index in the stream
+fn block_cv_at(index: u64, block: &[u8], key: Option<&InstanceKey>) -> ChainingValue {
+    let mut hasher = match key {
+        Some(key) => blake3::Hasher::new_keyed(key.as_bytes()),
+        None => blake3::Hasher::new(),
+    };
+    hasher
+        .set_input_offset(index * BLOCK_SIZE as u64)
+        .update(block);
+    hasher.finalize_non_root()
+}
+
+/// Sizes (in blocks) of the complete subtrees covering `count` blocks from block `first`
+fn subtree_sizes(first: u64, count: u64) -> impl Iterator<Item = u64> {
+    let end = first + count;
+    let mut start = first;
+    core::iter::from_fn(move || {
+        if start >= end {
+            return None;
+        }
+        // The largest aligned power of two that fits
+        let mut size = 1u64 << start.trailing_zeros().min(63);
+        while start + size > end {
+            size /= 2;
+        }
+        start += size;
+        Some(size)
+    })
+}
+
+pub(crate) fn invalid_state() -> Error {
+    Error::InvalidState("invalid hasher state".to_string())
+}
+
+/// Split off the next `len` bytes of a serialized state
+pub(crate) fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(invalid_state());
+    }
+    let (head, rest) = input.split_at(len);
+    *input = rest;
+    Ok(head)
+}
+
 /// A Python-exposed instance processor that implements an incremental Instance-Code digest.
+#[cfg(feature = "python")]
 #[pyclass]
 pub struct InstanceCodeProcessor {
     hasher: InstanceHasher,
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl InstanceCodeProcessor {
     #[new]
@@ -76,6 +494,13 @@ impl InstanceCodeProcessor {
         self.hasher.push(data);
     }
 
+    /// Return a copy of the processor to take interim results from
+    fn copy(&self) -> Self {
+        InstanceCodeProcessor {
+            hasher: self.hasher.clone(),
+        }
+    }
+
     /// Finalize the processing and return a dictionary with the results.
     ///
     /// The returned dict contains:
@@ -99,6 +524,71 @@ impl InstanceCodeProcessor {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_instance_hasher_parallel_blocks() {
+        let data: Vec<u8> = (0..5_000_000u32).map(|i| (i % 251) as u8).collect();
+        let expected = blake3::hash(&data);
+        // Unaligned start and a block-aligned end of the parallel part
+        for split in [0, 1000, BLOCK_SIZE] {
+            let mut hasher = InstanceHasher::new();
+            hasher.push(&data[..split]);
+            hasher.push(&data[split..]);
+            assert_eq!(hasher.digest(), expected.as_bytes());
+        }
+        let mut range = InstanceHasher::for_range(BLOCK_SIZE as u64 * 3).unwrap();
+        range.push(&data[BLOCK_SIZE * 3..]);
+        let mut head = InstanceHasher::new();
+        head.push(&data[..BLOCK_SIZE * 3]);
+        head.merge(range).unwrap();
+        assert_eq!(head.digest(), expected.as_bytes());
+    }
+
+    #[test]
+    fn test_keyed_instance_hasher() {
+        let key = InstanceKey::derive("tenant-a", b"secret");
+        assert_ne!(key, InstanceKey::derive("tenant-b", b"secret"));
+        assert_eq!(format!("{key:?}"), "InstanceKey(..)");
+        let data: Vec<u8> = (0..3_000_000u32).map(|i| (i % 253) as u8).collect();
+        for len in [0, 1000, BLOCK_SIZE * 3 + 7, data.len()] {
+            let mut hasher = InstanceHasher::with_key(key);
+            hasher.push(&data[..len]);
+            let expected = blake3::keyed_hash(key.as_bytes(), &data[..len]);
+            assert_eq!(hasher.digest(), expected.as_bytes());
+        }
+        let mut keyed = InstanceHasher::with_key(key);
+        keyed.push(&data[..BLOCK_SIZE]);
+        let mut range = InstanceHasher::for_range(BLOCK_SIZE as u64).unwrap();
+        range.push(&data[BLOCK_SIZE..]);
+        assert!(keyed.merge(range).is_err());
+    }
+
+    #[test]
+    fn test_encoded_multihash() {
+        let hasher = InstanceHasher::new();
+        let cases = [
+            (
+                DatahashEncoding::Hex,
+                "1e20af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            ),
+            (
+                DatahashEncoding::Base58btc,
+                "zgW9T5gSXhgQB5LaqmnJrLVVpHawhM7youzHnuY6HyGBDy3",
+            ),
+            (
+                DatahashEncoding::Base64url,
+                "uHiCvE0m59fmhpqBATeo23MlJm8slya3BErfMmpPK5B8yYg",
+            ),
+            (
+                DatahashEncoding::Cid,
+                "bafkr4ifpcne3t5pzugtkaqcn5i3nzskjtpfslsnnyejlpte2spfoihzsmi",
+            ),
+        ];
+        for (encoding, expected) in cases {
+            assert_eq!(hasher.encoded_multihash(encoding), expected);
+        }
+        assert_eq!(hasher.multihash(), cases[0].1);
+    }
+
     #[test]
     fn test_instance_hasher_new() {
         let hasher = InstanceHasher::new();
@@ -155,6 +645,91 @@ mod tests {
         assert_eq!(digest.len(), 32);
     }
 
+    #[test]
+    fn test_instance_hasher_matches_blake3() {
+        let data: Vec<u8> = (0..5 * BLOCK_SIZE + 1234)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        for len in [
+            0,
+            1,
+            1024,
+            1025,
+            BLOCK_SIZE,
+            BLOCK_SIZE + 1,
+            2 * BLOCK_SIZE,
+            data.len(),
+        ] {
+            for step in [1000, BLOCK_SIZE, 3 * BLOCK_SIZE + 7] {
+                let mut hasher = InstanceHasher::new();
+                for part in data[..len].chunks(step) {
+                    hasher.push(part);
+                }
+                assert_eq!(hasher.digest(), blake3::hash(&data[..len]).as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_instance_hasher_state_roundtrip() {
+        let data: Vec<u8> = (0..3 * BLOCK_SIZE + 99).map(|i| (i % 7) as u8).collect();
+        for split in [0, 500, BLOCK_SIZE, 2 * BLOCK_SIZE + 1] {
+            let mut hasher = InstanceHasher::new();
+            hasher.push(&data[..split]);
+            let mut state = Vec::new();
+            hasher.write_state(&mut state);
+            let mut restored = InstanceHasher::read_state(&mut &state[..]).unwrap();
+            assert_eq!(restored.filesize(), split as u64);
+            restored.push(&data[split..]);
+            assert_eq!(restored.digest(), blake3::hash(&data).as_bytes());
+        }
+        assert!(InstanceHasher::read_state(&mut &[0u8; 3][..]).is_err());
+    }
+
+    #[test]
+    fn test_instance_hasher_merge_ranges() {
+        let data: Vec<u8> = (0..11 * BLOCK_SIZE + 777)
+            .map(|i| (i % 241) as u8)
+            .collect();
+        let expected = blake3::hash(&data);
+        for cuts in [
+            vec![0, data.len()],
+            vec![0, BLOCK_SIZE, data.len()],
+            vec![
+                0,
+                3 * BLOCK_SIZE,
+                4 * BLOCK_SIZE,
+                9 * BLOCK_SIZE,
+                data.len(),
+            ],
+            vec![0, 5 * BLOCK_SIZE, 11 * BLOCK_SIZE, data.len()],
+            vec![0, 2 * BLOCK_SIZE, 2 * BLOCK_SIZE, data.len()],
+        ] {
+            let mut parts = cuts.windows(2).map(|w| {
+                let mut hasher = InstanceHasher::for_range(w[0] as u64).unwrap();
+                hasher.push(&data[w[0]..w[1]]);
+                // Ranges travel as serialized state
+                let mut state = Vec::new();
+                hasher.write_state(&mut state);
+                InstanceHasher::read_state(&mut &state[..]).unwrap()
+            });
+            let mut hasher = parts.next().unwrap();
+            for part in parts {
+                hasher.merge(part).unwrap();
+            }
+            assert_eq!(hasher.digest(), expected.as_bytes(), "{cuts:?}");
+            assert_eq!(hasher.filesize(), data.len() as u64);
+        }
+
+        // Unaligned or non-adjacent ranges are rejected
+        assert!(InstanceHasher::for_range(100).is_err());
+        let mut head = InstanceHasher::new();
+        head.push(&data[..100]);
+        let mut next = InstanceHasher::for_range(BLOCK_SIZE as u64).unwrap();
+        next.push(&data[BLOCK_SIZE..]);
+        assert!(head.merge(next).is_err());
+    }
+
     #[test]
     fn test_instance_hasher_empty_data() {
         let hasher = InstanceHasher::new();
@@ -1,15 +1,25 @@
 from iscc_sum._core import (
     DataCodeProcessor,
     InstanceCodeProcessor,
+    IsccSegment,
     IsccSumProcessor,
     IsccSumResult,
+    compare,
+    gen_image_code,
+    gen_meta_code,
+    gen_mixed_code,
 )
 from iscc_sum.code_iscc_sum import code_iscc_sum
 
 __all__ = [
     "DataCodeProcessor",
     "InstanceCodeProcessor",
+    "IsccSegment",
     "IsccSumProcessor",
     "IsccSumResult",
     "code_iscc_sum",
+    "compare",
+    "gen_image_code",
+    "gen_meta_code",
+    "gen_mixed_code",
 ]
@@ -1,4 +1,4 @@
-from typing import TypedDict
+from typing import Any, TypedDict
 
 class DataCodeResult(TypedDict):
     digest: bytes
@@ -8,33 +8,109 @@ class InstanceCodeResult(TypedDict):
     multihash: str
     filesize: int
 
+class ComparisonResult(TypedDict):
+    similarity: float
+    distances: dict[str, int]
+    bits: dict[str, int]
+
+class _MetaCodeResultRequired(TypedDict):
+    iscc: str
+    name: str
+    metahash: str
+
+class MetaCodeResult(_MetaCodeResultRequired, total=False):
+    description: str
+    meta: str
+
+class ImageCodeResult(TypedDict):
+    iscc: str
+    width: int
+    height: int
+
+class MixedCodeResult(TypedDict):
+    iscc: str
+    parts: list[str]
+
 class IsccSumResult:
     iscc: str
     datahash: str
     filesize: int
     units: list[str] | None
+    hashes: dict[str, str]
+    minhash: list[int] | None
+    features: list[int] | None
+    segments: list[IsccSegment] | None
+    nonstandard: bool
 
     def __new__(
         cls, iscc: str, datahash: str, filesize: int, units: list[str] | None = None
     ) -> IsccSumResult: ...
-    def __getitem__(self, key: str) -> str | int | list[str] | None: ...
+    def __getitem__(
+        self, key: str
+    ) -> str | int | bool | list[str] | list[int] | dict[str, str] | list[IsccSegment] | None: ...
     def __contains__(self, key: str) -> bool: ...
     def __len__(self) -> int: ...
     def __repr__(self) -> str: ...
 
+class IsccSegment:
+    offset: int
+    size: int
+    iscc: str
+    datahash: str
+
+    def __repr__(self) -> str: ...
+
 class DataCodeProcessor:
-    def __new__(cls) -> DataCodeProcessor: ...
+    def __new__(
+        cls,
+        avg_chunk_size: int = 1024,
+        min_chunk_size: int | None = None,
+        max_chunk_size: int | None = None,
+    ) -> DataCodeProcessor: ...
     def update(self, data: bytes) -> None: ...
+    def copy(self) -> DataCodeProcessor: ...
     def result(self) -> DataCodeResult: ...
 
 class InstanceCodeProcessor:
     def __new__(cls) -> InstanceCodeProcessor: ...
     def update(self, data: bytes) -> None: ...
+    def copy(self) -> InstanceCodeProcessor: ...
     def result(self) -> InstanceCodeResult: ...
 
 class IsccSumProcessor:
-    def __new__(cls) -> IsccSumProcessor: ...
+    def __new__(
+        cls, hashes: list[str] | None = None, segment_size: int | None = None
+    ) -> IsccSumProcessor: ...
     def update(self, data: bytes) -> None: ...
-    def result(self, wide: bool = False, add_units: bool = False) -> IsccSumResult: ...
+    def result(
+        self,
+        wide: bool = False,
+        add_units: bool = False,
+        bits: int | None = None,
+        add_minhash: bool = False,
+        add_features: bool = False,
+    ) -> IsccSumResult: ...
+    def copy(self) -> IsccSumProcessor: ...
+    def serialize_state(self) -> bytes: ...
+    @staticmethod
+    def restore_state(state: bytes) -> IsccSumProcessor: ...
+    @staticmethod
+    def for_range(offset: int) -> IsccSumProcessor: ...
+    def merge(self, next: IsccSumProcessor) -> None: ...
 
-def code_iscc_sum(path: str, wide: bool = False, add_units: bool = False) -> IsccSumResult: ...
+def code_iscc_sum(
+    path: str,
+    wide: bool = False,
+    add_units: bool = True,
+    bits: int | None = None,
+    segment_size: int | None = None,
+) -> IsccSumResult: ...
+def compare(a: str, b: str) -> ComparisonResult: ...
+def gen_meta_code(
+    name: str,
+    description: str | None = None,
+    meta: str | dict[str, Any] | None = None,
+    bits: int = 64,
+) -> MetaCodeResult: ...
+def gen_image_code(data: bytes, bits: int = 64) -> ImageCodeResult: ...
+def gen_mixed_code(codes: list[str], bits: int = 64) -> MixedCodeResult: ...
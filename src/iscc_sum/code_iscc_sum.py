@@ -14,8 +14,8 @@ from iscc_sum import IsccSumProcessor, IsccSumResult
 IO_READ_SIZE = 2097152  # 2MB chunks for efficient file reading
 
 
-def code_iscc_sum(uri, wide=False, add_units=True):
-    # type: (str, bool, bool) -> IsccSumResult
+def code_iscc_sum(uri, wide=False, add_units=True, bits=None, segment_size=None):
+    # type: (str, bool, bool, int | None, int | None) -> IsccSumResult
     """
     Generate an ISCC-CODE SUM for a file using Python I/O and Rust processing.
 
@@ -26,13 +26,17 @@ def code_iscc_sum(uri, wide=False, add_units=True):
     :param uri: Path or fsspec URL to the file to process
     :param wide: If True, generates 128-bit codes (256-bit total); if False, 64-bit codes (128-bit total)
     :param add_units: If True, includes individual Data-Code and Instance-Code units in the result
+    :param bits: Length of the Data-Code and Instance-Code in the composite (64, 128, 192 or 256);
+        overrides `wide` if given
+    :param segment_size: Also compute the ISCC-SUM of every segment of this many bytes
     :return: Dictionary compatible object with following properties:
         - iscc: The composite ISCC-CODE SUM identifier
         - datahash: Blake3 multihash of the file content
         - filesize: Size of the file in bytes
         - units: List of individual ISCC units [Data-Code, Instance-Code] (if add_units=True)
+        - segments: List of IsccSegment with offset, size, iscc and datahash (if segment_size is given)
     """
-    processor = IsccSumProcessor()
+    processor = IsccSumProcessor(segment_size=segment_size)
     path = UPath(uri)
 
     with path.open("rb") as stream:
@@ -41,4 +45,4 @@ def code_iscc_sum(uri, wide=False, add_units=True):
             processor.update(data)
             data = stream.read(IO_READ_SIZE)
 
-    return processor.result(wide=wide, add_units=add_units)
+    return processor.result(wide=wide, add_units=add_units, bits=bits)
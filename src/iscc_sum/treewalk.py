@@ -9,8 +9,14 @@ This module provides a layered approach to directory traversal:
 
 Each layer builds on the previous, ensuring consistent cross-platform ordering
 while enabling progressive filtering capabilities for content hashing workflows.
+
+Symlinks are skipped by default. With ``symlinks="follow-files"`` symlinks to files
+are listed, with ``symlinks="follow-all"`` symlinked directories are walked as well
+and a symlink to one of its own ancestor directories raises an ``OSError``. On Windows
+junctions and volume mount points are treated as symlinks.
 """
 
+import errno
 import os
 from os import DirEntry
 from pathlib import Path
@@ -19,26 +25,80 @@ from unicodedata import normalize
 
 import pathspec
 
-
-def listdir(path):
-    # type: (str|Path) -> list[DirEntry]
+SYMLINK_POLICIES = ("skip", "follow-files", "follow-all")
+
+# Reparse tags with this bit (symlinks, junctions, volume mount points) link to another path
+_NAME_SURROGATE = 0x20000000
+
+
+def _follow(entry, symlinks):
+    # type: (DirEntry, str) -> bool
+    """Whether a directory entry is listed under the symlink policy."""
+    if not _is_link(entry):
+        return True
+    if symlinks == "follow-files":
+        return entry.is_file()
+    if symlinks == "follow-all":
+        return entry.is_file() or entry.is_dir()
+    return False
+
+
+def _is_link(entry):
+    # type: (DirEntry) -> bool
+    """Whether an entry is a symlink or, on Windows, another name-surrogate reparse point."""
+    if entry.is_symlink():
+        return True
+    if os.name != "nt":
+        return False
+    # The lstat result of a Windows DirEntry is cached from the directory listing
+    return bool(getattr(entry.stat(follow_symlinks=False), "st_reparse_tag", 0) & _NAME_SURROGATE)
+
+
+def _enter(path, symlinks, ancestors):
+    # type: (Path, str, tuple) -> tuple
+    """Add a directory to its ancestors, raising OSError on a symlink cycle."""
+    if symlinks != "follow-all":
+        return ancestors
+    stat = os.stat(path)
+    key = (stat.st_dev, stat.st_ino)
+    if key in ancestors:
+        raise OSError(errno.ELOOP, "symlink cycle: directory is its own ancestor", str(path))
+    return ancestors + (key,)
+
+
+def listdir(path, symlinks="skip"):
+    # type: (str|Path, str) -> list[DirEntry]
     """
     List directory entries with deterministic cross-platform sorting.
 
     Returns directory entries sorted by NFC-normalized UTF-8 encoded names,
     ensuring consistent ordering across different filesystems and locales.
-    Symlinks are excluded for security and consistency.
+    Symlinks are excluded for security and consistency unless `symlinks` follows them.
 
     :param path: Directory path to list
+    :param symlinks: Symlink policy: "skip", "follow-files" or "follow-all"
     :return: Sorted list of DirEntry objects (excluding symlinks)
     """
+    if symlinks not in SYMLINK_POLICIES:
+        raise ValueError(f"unknown symlink policy: {symlinks}")
     with os.scandir(path) as it:
-        filtered = [e for e in it if not e.is_symlink()]
-    return sorted(filtered, key=lambda e: (normalize("NFC", e.name).encode("utf-8"), e.name.encode("utf-8")))
+        filtered = [e for e in it if _follow(e, symlinks)]
+    return sorted(filtered, key=_sort_key)
+
 
+def _sort_key(entry):
+    # type: (DirEntry) -> tuple[bytes, bytes]
+    """NFC-normalized UTF-8 name with the original name as tie-breaker; raw bytes for non-UTF-8 names."""
+    try:
+        return normalize("NFC", entry.name).encode("utf-8"), entry.name.encode("utf-8")
+    except UnicodeEncodeError:
+        # Undecodable bytes are kept as surrogate escapes by os.scandir
+        raw = os.fsencode(entry.name)
+        return raw, raw
 
-def treewalk(path):
-    # type: (str|Path) -> Iterator[Path]
+
+def treewalk(path, symlinks="skip"):
+    # type: (str|Path, str) -> Iterator[Path]
     """
     Walk a directory tree and yield file paths in deterministic order.
 
@@ -51,10 +111,16 @@ def treewalk(path):
     Symlinks are ignored for security and consistency.
 
     :param path: Directory path to walk
+    :param symlinks: Symlink policy: "skip", "follow-files" or "follow-all"
     :return: Iterator yielding Path objects for each file found
     """
-    path = Path(path).resolve(strict=True)
-    entries = listdir(path)
+    yield from _treewalk(Path(path).resolve(strict=True), symlinks, ())
+
+
+def _treewalk(path, symlinks, ancestors):
+    # type: (Path, str, tuple) -> Iterator[Path]
+    ancestors = _enter(path, symlinks, ancestors)
+    entries = listdir(path, symlinks)
     dirs = [d for d in entries if d.is_dir()]
     files = [f for f in entries if f.is_file()]
 
@@ -70,11 +136,11 @@ def treewalk(path):
 
     # Then recurse into directories
     for dir_entry in dirs:
-        yield from treewalk(Path(dir_entry.path))
+        yield from _treewalk(Path(dir_entry.path), symlinks, ancestors)
 
 
-def treewalk_ignore(path, ignore_file_name, root_path=None, ignore_spec=None):
-    # type: (str|Path, str, Path|None, pathspec.PathSpec|None) -> Iterator[Path]
+def treewalk_ignore(path, ignore_file_name, root_path=None, ignore_spec=None, symlinks="skip"):
+    # type: (str|Path, str, Path|None, pathspec.PathSpec|None, str) -> Iterator[Path]
     """
     Walk a directory tree while respecting ignore file patterns.
 
@@ -85,6 +151,7 @@ def treewalk_ignore(path, ignore_file_name, root_path=None, ignore_spec=None):
     :param ignore_file_name: Name of the ignore-file to look for (e.g., '.gitignore')
     :param root_path: Root directory for relative path calculations (defaults to the path argument)
     :param ignore_spec: Existing PathSpec with ignored patterns to extend
+    :param symlinks: Symlink policy: "skip", "follow-files" or "follow-all"
     :return: Iterator yielding Path objects for non-ignored files
     """
     path = Path(path).resolve(strict=True)
@@ -92,6 +159,12 @@ def treewalk_ignore(path, ignore_file_name, root_path=None, ignore_spec=None):
         root_path = path
     else:
         root_path = Path(root_path).resolve(strict=True)
+    yield from _treewalk_ignore(path, ignore_file_name, root_path, ignore_spec, symlinks, ())
+
+
+def _treewalk_ignore(path, ignore_file_name, root_path, ignore_spec, symlinks, ancestors):
+    # type: (Path, str, Path, pathspec.PathSpec|None, str, tuple) -> Iterator[Path]
+    ancestors = _enter(path, symlinks, ancestors)
 
     # Load local ignore rules if present
     local_ignore = path / ignore_file_name
@@ -100,7 +173,7 @@ def treewalk_ignore(path, ignore_file_name, root_path=None, ignore_spec=None):
             new_spec = pathspec.PathSpec.from_lines(pathspec.patterns.GitWildMatchPattern, f)
             ignore_spec = new_spec if ignore_spec is None else ignore_spec + new_spec
 
-    entries = listdir(path)
+    entries = listdir(path, symlinks)
     dirs = [d for d in entries if d.is_dir()]
     files = [f for f in entries if f.is_file()]
 
@@ -140,11 +213,11 @@ def treewalk_ignore(path, ignore_file_name, root_path=None, ignore_spec=None):
     for dir_entry in dirs:
         dir_path = Path(dir_entry.path)
         if not should_ignore(dir_path, is_dir=True):
-            yield from treewalk_ignore(dir_path, ignore_file_name, root_path, ignore_spec)
+            yield from _treewalk_ignore(dir_path, ignore_file_name, root_path, ignore_spec, symlinks, ancestors)
 
 
-def treewalk_iscc(path):
-    # type: (str|Path) -> Iterator[Path]
+def treewalk_iscc(path, symlinks="skip"):
+    # type: (str|Path, str) -> Iterator[Path]
     """
     Walk directory tree with ISCC-specific ignore rules.
 
@@ -155,12 +228,13 @@ def treewalk_iscc(path):
     Uses the same deterministic ordering as treewalk_ignore.
 
     :param path: Directory path to walk
+    :param symlinks: Symlink policy: "skip", "follow-files" or "follow-all"
     :return: Iterator yielding Path objects for non-ignored, non-ISCC metadata files
     """
     path = Path(path).resolve(strict=True)
 
     # Use treewalk_ignore with .isccignore files
-    for file_path in treewalk_ignore(path, ".isccignore"):
+    for file_path in treewalk_ignore(path, ".isccignore", symlinks=symlinks):
         # Skip files ending with .iscc.json
         if not file_path.name.endswith(".iscc.json"):
             yield file_path
@@ -1,24 +1,119 @@
 // Core library implementation for iscc-sum
+//
+// The Python bindings are only compiled with the `python` feature, the directory
+// walker with `treewalk`, the metrics with `metrics`, the Meta-Code with `meta` and
+// the manifest, sidecar, index and archive modules of the `isum` tool with `cli`.
+// The default `core` feature compiles the hashing library only; without `std` only
+// the `no_std` + `alloc` core is compiled.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
+#[cfg(feature = "async")]
+pub mod async_io;
+#[cfg(feature = "cli")]
+pub mod bagit;
+#[cfg(feature = "std")]
+pub mod cancel;
+#[cfg(feature = "cli")]
+pub mod cbor;
 pub mod cdc;
+pub mod codec;
+#[cfg(feature = "cli")]
+pub mod config;
+#[cfg(feature = "meta")]
+pub mod conformance;
 pub mod constants;
+#[cfg(feature = "meta")]
+pub mod content_audio;
+#[cfg(feature = "std")]
+pub mod content_image;
+#[cfg(feature = "meta")]
+pub mod content_mixed;
+#[cfg(feature = "meta")]
+pub mod content_text;
+#[cfg(feature = "std")]
+pub mod content_video;
 pub mod data;
+#[cfg(feature = "cli")]
+pub mod db;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "flake")]
+pub mod flake;
+#[cfg(feature = "cli")]
+pub mod index;
 pub mod instance;
+#[cfg(feature = "cli")]
+pub mod manifest;
+#[cfg(feature = "meta")]
+pub mod meta;
+#[cfg(feature = "cli")]
+pub mod metadata;
 pub mod minhash;
+#[cfg(feature = "std")]
+pub mod options;
+#[cfg(feature = "cli")]
+pub mod outboard;
+#[cfg(feature = "std")]
+pub mod progress;
+#[cfg(feature = "digest")]
+pub mod rustcrypto;
+#[cfg(feature = "cli")]
+pub mod sidecar;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "std")]
 pub mod sum;
+#[cfg(feature = "metrics")]
+pub mod telemetry;
+#[cfg(feature = "treewalk")]
 pub mod treewalk;
+#[cfg(feature = "cli")]
+pub mod warc;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "async")]
+pub use async_io::{hash_async_reader, hash_file_async};
+#[cfg(feature = "std")]
+pub use cancel::CancellationToken;
+pub use codec::{compare, Comparison, IsccCode};
+pub use error::{Error, Result};
+#[cfg(feature = "meta")]
+pub use meta::{gen_meta_code, MetaCodeResult};
+#[cfg(feature = "std")]
+pub use options::{DatahashEncoding, HashAlgorithm, IsccSumOptions};
+#[cfg(feature = "std")]
+pub use progress::Progress;
+#[cfg(feature = "std")]
+pub use stream::{IsccSumReader, IsccSumWriter};
+#[cfg(feature = "treewalk")]
+pub use sum::hash_tree;
+#[cfg(feature = "std")]
+pub use sum::{hash_bytes, hash_file, hash_reader, IsccSegment, IsccSumResult};
 
 /// A Python module implemented in Rust. The name of this function must match
 /// the `lib.name` setting in the `Cargo.toml`, else Python will not be able to
 /// import the module.
+#[cfg(feature = "python")]
 #[pymodule]
 fn _core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<data::DataCodeProcessor>()?;
     m.add_class::<instance::InstanceCodeProcessor>()?;
     m.add_class::<sum::IsccSumProcessor>()?;
     m.add_class::<sum::IsccSumResult>()?;
-    m.add_function(wrap_pyfunction!(sum::code_iscc_sum, m)?)?;
+    m.add_class::<sum::IsccSegment>()?;
+    m.add_function(wrap_pyfunction!(sum::py_code_iscc_sum, m)?)?;
+    m.add_function(wrap_pyfunction!(codec::py_compare, m)?)?;
+    m.add_function(wrap_pyfunction!(meta::py_gen_meta_code, m)?)?;
+    m.add_function(wrap_pyfunction!(content_mixed::py_gen_mixed_code, m)?)?;
+    #[cfg(feature = "image")]
+    m.add_function(wrap_pyfunction!(content_image::py_gen_image_code, m)?)?;
     Ok(())
 }
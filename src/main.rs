@@ -1,15 +1,62 @@
 // Main entry point for the iscc-sum CLI tool
 
-use clap::Parser;
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use ed25519_dalek::VerifyingKey;
 use globset::{Glob, GlobSet, GlobSetBuilder};
-use std::fs::File;
-use std::io::{self, BufReader, Read};
-use std::path::PathBuf;
+use md5::Md5;
+#[cfg(unix)]
+use metrics::{counter, describe_counter, Unit};
+#[cfg(unix)]
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+#[cfg(feature = "network")]
+use std::sync::OnceLock;
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing_subscriber::fmt::format::FmtSpan;
 use walkdir::WalkDir;
 
 // Import from the library crate
+use _core::bagit::{self, ManifestKind};
+use _core::cancel::CancellationToken;
+use _core::cbor;
+use _core::cdc::{self, DataChunk};
+use _core::codec::{self as iscc_codec, IsccCode, MainType};
+use _core::config::Config;
+use _core::conformance;
+use _core::content_mixed;
+use _core::content_text;
+#[cfg(feature = "video")]
+use _core::content_video;
+use _core::db::{ChecksumDb, DbRecord};
+use _core::index::{self, SimilarityIndex};
+use _core::manifest::{self, MANIFEST_NAME};
+use _core::metadata::{self as file_metadata, FileMetadata, METADATA_MANIFEST_NAME};
+use _core::minhash::minhash_256;
+use _core::options::{HashAlgorithm, IsccSumOptions};
+use _core::outboard::{self, OutboardEncoder, VerifyError};
+use _core::sidecar::{self, Sidecar};
 use _core::sum::{IsccSumProcessor, IsccSumResult};
+#[cfg(unix)]
+use _core::telemetry;
+use _core::treewalk::{treewalk_channel, IgnoreSpec, SymlinkPolicy, WalkEvent, WalkOptions};
+use _core::warc;
 
 /// Generate ISCC Data-Code and Instance-Code checksums
 #[derive(Parser)]
@@ -27,20 +74,98 @@ Examples:
   isum file.txt                  # Generate checksum for a single file
   isum file1.txt file2.txt       # Generate checksums for multiple files
   echo \"hello\" | isum           # Generate checksum from stdin
+  cat a.txt | isum b.txt - c.txt # Mix stdin (-) with files
+  isum --allow-special <(gzip -dc data.gz)  # Hash a process substitution pipe
   isum directory/                # Process all files in directory recursively
   isum --narrow file.txt         # Generate 128-bit checksum (default: 256-bit)
   isum --exclude \"*.log\" dir/    # Exclude log files
-  isum --max-depth 1 dir/        # Process only immediate subdirectories"
+  isum --max-depth 1 dir/        # Process only immediate subdirectories
+  isum --xattr dir/              # Store checksums in extended attributes
+  isum --check-xattr dir/        # Verify files against stored attributes
+  isum --sidecar movie.mp4       # Write checksum sidecar movie.mp4.iscc
+  isum --check-sidecar dir/      # Verify files against their sidecars
+  isum --manifests dir/          # Write an ISCC-SUMS file into each directory
+  isum --check-manifests dir/    # Verify all ISCC-SUMS files below dir/
+  isum --cbor sums.cbor dir/ && isum sums.cbor  # Deterministic CBOR manifest and its ISCC
+  isum --hashdeep --hash-also md5 --hash-also sha256 dir/  # hashdeep-style audit file
+  isum --warc crawl.warc.gz      # One ISCC per archived response, keyed by target URI
+  isum --outboard movie.mp4      # Also write the BLAKE3 tree to movie.mp4.obao
+  isum --state disk.state disk.img  # Resume an interrupted hash of a huge file
+  curl -s URL | isum verify-stream - --outboard movie.mp4.obao --expect ISCC:K4A...  # Fail fast
+  isum --format json --lookup https://registry.example/api/lookup file.jpg  # Prior declarations
+  isum keygen isum.key isum.pub && isum --manifests --sign isum.key dir/  # Signed manifests
+  isum --check-manifests --verify-signature isum.pub dir/  # Verify integrity and origin
+  isum --hash-also sha256 --format json dir/  # Add SHA-256 digests to JSON output
+  isum --min-size 1M dir/        # Only hash files of at least 1 MiB
+  isum --newer-than ISCC-SUMS dir/  # Only hash files modified after ISCC-SUMS
+  isum --list-ignored --ignore-file .isccignore dir/  # Show skipped files and why
+  isum --dry-run dir/            # List the files that would be hashed, in order
+  isum --root /data /data/set/   # Output paths like set/file.txt on every platform
+  isum --time=5 --format json /nas  # Per-file timings and the 5 slowest files
+  isum --limit-rate 50M --check-manifests /nas  # Verify at most 50 MiB/s
+  isum --nice-io --check-manifests /nas  # Verify with low CPU and idle I/O priority
+  isum @files.txt                # Read arguments from files.txt, one per line
+  isum selftest                  # Verify this binary against the ISCC conformance vectors
+  isum completions bash          # Print a bash completion script
+  isum manpage > isum.1          # Write the man page
+  isum dedupe --similar dir/     # Report duplicate and near-duplicate files
+  isum dedupe --script hardlink dir/ > dedupe.sh  # Script replacing duplicates with links
+  isum delta old.bin new.bin     # Shared chunks, differing ranges and similarity
+  isum chunks --format json file # Offset, length and hash of each Data-Code chunk
+  isum compare backup/ photos/   # Added, removed, modified and moved files
+  isum manifest-diff old.json new.json  # Changes between two saved runs
+  isum db init fixity.db && isum db add fixity.db /archive  # Record a collection
+  isum db verify --older-than 30 --limit 1000 fixity.db  # Incremental fixity check
+  isum index build files.idx /archive  # Build a similarity index
+  isum index query files.idx ISCC:K4A... --max-distance 8  # Find near-duplicates
+  isum daemon serve /tmp/isum.sock --index files.idx &  # Warm cache and index
+  isum daemon hash /tmp/isum.sock file.bin  # Hash through the daemon
+  isum bag create dir/ && isum bag validate dir/  # BagIt bag with manifest-iscc.txt
+
+On Windows, wildcard arguments such as *.bin are expanded by isum itself, paths longer
+than 260 characters are supported and --ads controls NTFS alternate data streams.
+
+Defaults for --narrow (bits = 128), --ignore-file, --threads and --exclude are read
+from ~/.config/isum/config.toml (or $ISUM_CONFIG) and overridden by the ISUM_BITS,
+ISUM_IGNORE_FILE, ISUM_THREADS and ISUM_EXCLUDE environment variables. Command line
+options take precedence over both; configured excludes are combined with --exclude.
+
+Arguments of the form @FILE are replaced by the lines of FILE (one argument per
+line, empty lines ignored). Use ./@name to pass a path that starts with @.
+
+Exit status:
+  0  success
+  1  checksum verification failed (mismatch, missing or malformed entries)
+  2  usage error (invalid arguments or option combinations)
+  3  I/O error (some inputs could not be found or read)
+  130  interrupted by SIGINT/SIGTERM (results of completed files are still written)
+
+On SIGINT or SIGTERM the file being hashed is finished, the results and manifests of
+all completed files are flushed and isum exits with status 130. A second signal
+terminates immediately."
 )]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
-    /// Files to process (reads from stdin if not provided)
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Files to process (reads from stdin if not provided or if FILE is -)
     #[arg(value_name = "FILE")]
     files: Vec<PathBuf>,
 
+    /// Hash named pipes and devices given as arguments (e.g. /dev/stdin, <(cmd))
+    /// instead of rejecting them
+    #[arg(long)]
+    allow_special: bool,
+
     /// Generate narrower 128-bit ISCC checksums (default: 256-bit)
     #[arg(short, long)]
     narrow: bool,
 
+    /// Generate 256-bit checksums even if 128-bit checksums are configured
+    #[arg(long, conflicts_with = "narrow")]
+    wide: bool,
+
     /// Process directories recursively (default when directory argument is provided)
     #[arg(short, long, conflicts_with = "no_recursive")]
     recursive: bool,
@@ -58,18 +183,944 @@ struct Cli {
     /// 0=current dir only, 1=include immediate subdirs, etc.
     #[arg(long, value_name = "N")]
     max_depth: Option<usize>,
+
+    /// Skip files matched by gitignore-style NAME files (e.g. .isccignore) in traversed directories;
+    /// repeat to read several, where the NAME given first takes precedence
+    #[arg(long, value_name = "NAME")]
+    ignore_file: Vec<String>,
+
+    /// Skip files matched by the gitignore-style patterns in FILE in every traversed
+    /// directory tree (default: ~/.config/isum/ignore if it exists, see --no-config)
+    #[arg(long, value_name = "FILE")]
+    global_ignore: Option<PathBuf>,
+
+    /// Match --ignore-file and --global-ignore patterns regardless of letter case (as
+    /// the default Windows and macOS filesystems treat names)
+    #[arg(long)]
+    ignore_case: bool,
+
+    /// Treat git repositories like git: skip `.git`, also apply `.git/info/exclude` and
+    /// do not apply the ignore files of a repository inside nested repositories. In a
+    /// subdirectory of a repository the ignore files above it apply as well
+    #[arg(long)]
+    git: bool,
+
+    /// List the files skipped by --exclude and --ignore-file patterns and the ignored
+    /// directories that are not descended into (with the pattern and its source) instead
+    /// of hashing
+    #[arg(long, conflicts_with_all = ["verify", "xattr", "sidecar", "manifests"])]
+    list_ignored: bool,
+
+    /// Output paths relative to DIR with `/` separators on all platforms
+    /// (files outside DIR keep their path as given)
+    #[arg(long, value_name = "DIR")]
+    root: Option<PathBuf>,
+
+    /// List the files that would be hashed, in processing order, without reading them
+    #[arg(long, conflicts_with_all = ["verify", "list_ignored"])]
+    dry_run: bool,
+
+    /// Number of worker threads (default: number of CPUs)
+    #[arg(long, value_name = "N")]
+    threads: Option<usize>,
+
+    /// Ignore the config file and ISUM_* environment variables
+    #[arg(long)]
+    no_config: bool,
+
+    /// Store each checksum in the `user.iscc.sum` extended attribute of its file
+    #[arg(long, conflicts_with = "verify")]
+    xattr: bool,
+
+    /// Verify files against the checksum stored in their `user.iscc.sum` extended attribute
+    #[arg(long, group = "verify")]
+    check_xattr: bool,
+
+    /// Write a JSON sidecar next to each file (e.g. movie.mp4.iscc) with code, units,
+    /// filesize and datahash
+    #[arg(long, conflicts_with = "verify")]
+    sidecar: bool,
+
+    /// Verify files against their JSON sidecar files
+    #[arg(long, group = "verify")]
+    check_sidecar: bool,
+
+    /// Write the BLAKE3 tree of each file to an outboard file next to it (e.g.
+    /// movie.mp4.obao) for checking streamed or partial copies with isum verify-stream
+    #[arg(long, conflicts_with_all = ["verify", "warc", "dry_run", "list_ignored"])]
+    outboard: bool,
+
+    /// Write an ISCC-SUMS manifest into each traversed directory listing its immediate files
+    #[arg(long, conflicts_with = "verify")]
+    manifests: bool,
+
+    /// Recursively find ISCC-SUMS manifests and verify the files they list
+    #[arg(long, group = "verify")]
+    check_manifests: bool,
+
+    /// Sign each written manifest with the Ed25519 secret key in KEYFILE (see isum keygen),
+    /// storing the signature next to it (ISCC-SUMS.sig)
+    #[arg(long, value_name = "KEYFILE", requires = "manifests")]
+    sign: Option<PathBuf>,
+
+    /// Require each checked manifest to carry a valid signature by the Ed25519 public key
+    /// in PUBKEY
+    #[arg(long, value_name = "PUBKEY", requires = "check_manifests")]
+    verify_signature: Option<PathBuf>,
+
+    /// Record mode bits, ownership, mtime and symlink targets in JSON output, sidecars and
+    /// ISCC-META files next to ISCC-SUMS manifests, separate from the content-derived ISCC
+    #[arg(long, conflicts_with_all = ["verify", "dry_run", "list_ignored"])]
+    with_metadata: bool,
+
+    /// Also compare the recorded metadata with --check-sidecar or --check-manifests
+    #[arg(long, requires = "verify", conflicts_with = "check_xattr")]
+    verify_metadata: bool,
+
+    /// Query an ISCC registry at URL (`GET URL?iscc=<code>`, answering a JSON array of
+    /// declarations or an object with a `matches` array) and add the matches to JSON output
+    #[arg(
+        long,
+        value_name = "URL",
+        conflicts_with_all = ["verify", "dry_run", "list_ignored", "hashdeep"]
+    )]
+    lookup: Option<String>,
+
+    /// Hash the payload of each response record in .warc and .warc.gz files (one result
+    /// per target URI) instead of the archive file itself
+    #[arg(
+        long,
+        conflicts_with_all = ["verify", "xattr", "sidecar", "manifests", "with_metadata", "dry_run"]
+    )]
+    warc: bool,
+
+    /// Write hashdeep-compatible output: a `%%%% HASHDEEP-1.0` header and
+    /// `size,<--hash-also digests>,iscc,filename` rows
+    #[arg(long, conflicts_with_all = ["format", "verify", "dry_run", "list_ignored"])]
+    hashdeep: bool,
+
+    /// Also write all results as one deterministic CBOR manifest (with schema version) to
+    /// FILE, e.g. to hash and reference the manifest itself
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["verify", "dry_run", "list_ignored"])]
+    cbor: Option<PathBuf>,
+
+    /// Checkpoint the hashing state of a single large file to FILE (periodically and when
+    /// interrupted) and resume from it if FILE exists; removed once the file is done
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["verify", "hash_also", "outboard", "warc", "segments", "dry_run", "list_ignored"]
+    )]
+    state: Option<PathBuf>,
+
+    /// Also compute a conventional digest in the same pass (can be specified multiple times)
+    #[arg(long, value_name = "ALGORITHM", value_parser = hash_algorithm_parser())]
+    hash_also: Vec<HashAlgorithm>,
+
+    /// Also compute the ISCC-SUM of every SIZE bytes of each file (suffixes: K, M, G, T),
+    /// so parts of very large files can be verified on their own; added to text and JSON
+    /// output
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_segment_size,
+        conflicts_with_all = ["verify", "hashdeep", "dry_run", "list_ignored"]
+    )]
+    segments: Option<u64>,
+
+    /// Also compute the ISCC Content-Code Text of files that are UTF-8 text and add it,
+    /// with the ISCC-CODE it forms with the Data-Code and Instance-Code, to text and JSON
+    /// output
+    #[arg(long, conflicts_with_all = ["verify", "hashdeep", "dry_run", "list_ignored"])]
+    text_code: bool,
+
+    /// Also compute a similarity hash of video files (by extension, decoded with the
+    /// ffmpeg program) and add it to text and JSON output. The hash is a hex digest
+    /// comparable among isum hashes, not an ISCC Content-Code Video
+    #[cfg(feature = "video")]
+    #[arg(long, conflicts_with_all = ["verify", "hashdeep", "dry_run", "list_ignored"])]
+    video_hash: bool,
+
+    /// Only process files in directories of at least SIZE bytes (suffixes: K, M, G, T)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    min_size: Option<u64>,
+
+    /// Only process files in directories of at most SIZE bytes (suffixes: K, M, G, T)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_size: Option<u64>,
+
+    /// Only process files in directories modified after TIME, given as a UTC date
+    /// (YYYY-MM-DD), date and time (YYYY-MM-DDTHH:MM:SS) or the path of a reference file
+    #[arg(long, value_name = "TIME", value_parser = parse_newer_than)]
+    newer_than: Option<SystemTime>,
+
+    /// Limit reading to RATE bytes per second on average (suffixes: K, M, G, T)
+    #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+    limit_rate: Option<u64>,
+
+    /// Run with the lowest CPU priority and, on Linux, the idle I/O scheduling class
+    #[arg(long)]
+    nice_io: bool,
+
+    /// Add per-file wall time and throughput to JSON/CSV output and report the
+    /// N slowest files (default 10) on stderr
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "10"
+    )]
+    time: Option<usize>,
+
+    /// Output format for generated checksums and verification results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Color verification results: auto (when stderr is a terminal and NO_COLOR is
+    /// unset), always or never
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    /// Treatment of NTFS alternate data streams in directories (Windows only)
+    #[arg(long, value_name = "POLICY", value_enum, default_value_t = AdsPolicy::Ignore)]
+    ads: AdsPolicy,
+
+    /// Treatment of symlinks in directories: skip them, follow symlinks to files, or
+    /// also walk into symlinked directories (a symlink to an ancestor is reported
+    /// and skipped)
+    #[arg(
+        long,
+        value_name = "POLICY",
+        value_parser = symlink_policy_parser(),
+        default_value_t = SymlinkPolicy::Skip
+    )]
+    symlinks: SymlinkPolicy,
+
+    /// Log file opens, hashing and directory walks at LEVEL and above to stderr,
+    /// with the duration of each step
+    #[arg(long, value_name = "LEVEL", value_enum)]
+    log_level: Option<LogLevel>,
+
+    /// Write log records as JSON lines (at level info unless --log-level is given)
+    #[arg(long)]
+    log_json: bool,
+}
+
+/// Minimum severity of the log records written by --log-level
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn level(self) -> tracing::Level {
+        match self {
+            LogLevel::Error => tracing::Level::ERROR,
+            LogLevel::Warn => tracing::Level::WARN,
+            LogLevel::Info => tracing::Level::INFO,
+            LogLevel::Debug => tracing::Level::DEBUG,
+            LogLevel::Trace => tracing::Level::TRACE,
+        }
+    }
+}
+
+/// Install the stderr log subscriber for --log-level and --log-json
+fn init_logging(cli: &Cli) {
+    let Some(level) = cli
+        .log_level
+        .or_else(|| cli.log_json.then_some(LogLevel::Info))
+    else {
+        return;
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level.level())
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(io::stderr);
+    if cli.log_json {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+}
+
+/// When to use colored, column-aligned check output
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Whether ANSI colors should be written to the given stream
+    fn enabled(self, stream: &impl IsTerminal) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                stream.is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        }
+    }
+}
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+
+/// Wrap text in an ANSI color sequence if coloring is enabled
+fn paint(text: &str, color: &str, enabled: bool) -> String {
+    if enabled {
+        format!("{color}{text}\x1b[0m")
+    } else {
+        text.to_string()
+    }
+}
+
+/// How NTFS alternate data streams are handled during directory traversal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum AdsPolicy {
+    /// Hash only the main data stream
+    Ignore,
+    /// Hash only the main data stream and warn about files with alternate streams
+    Warn,
+    /// Also hash each alternate stream as a separate `file:stream` entry
+    Include,
+}
+
+/// Subcommands (a file named like a subcommand can be passed as ./NAME)
+#[derive(Subcommand)]
+enum Command {
+    /// Verify this binary against the embedded ISCC conformance test vectors
+    Selftest,
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate the completion script for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the man page in roff format to stdout
+    Manpage,
+    /// Report groups of duplicate files and the bytes reclaimable by linking them
+    Dedupe {
+        /// Directories (or files) to scan recursively
+        #[arg(value_name = "DIR", required = true)]
+        paths: Vec<PathBuf>,
+        /// Also group near-identical files by Data-Code similarity
+        #[arg(long)]
+        similar: bool,
+        /// Maximum number of differing Data-Code bits (of 256) for --similar
+        #[arg(long, value_name = "BITS", default_value_t = 32, requires = "similar")]
+        max_distance: u32,
+        /// Print a shell script replacing duplicates with links instead of the report
+        #[arg(long, value_name = "LINK", value_enum)]
+        script: Option<LinkMode>,
+        /// Report format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Compare the content-defined chunks of two files
+    Delta {
+        /// First file
+        a: PathBuf,
+        /// Second file
+        b: PathBuf,
+        /// Report format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Compare two directory trees or checksum files (e.g. `isum --root DIR DIR > sums`),
+    /// exiting with status 1 if they differ
+    Compare {
+        /// First directory or manifest
+        a: PathBuf,
+        /// Second directory or manifest
+        b: PathBuf,
+        /// Maximum number of differing Data-Code bits for a removed and an added file
+        /// to be reported as moved
+        #[arg(long, value_name = "BITS", default_value_t = 0)]
+        max_distance: u32,
+        /// Report format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Compare two checksum files (text or `--format json` output) without reading the
+    /// files they list, exiting with status 1 if they differ
+    ManifestDiff {
+        /// Older checksum file
+        old: PathBuf,
+        /// Newer checksum file
+        new: PathBuf,
+        /// Maximum number of differing Data-Code bits for a removed and an added file
+        /// to be reported as moved
+        #[arg(long, value_name = "BITS", default_value_t = 0)]
+        max_distance: u32,
+        /// Report format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Maintain a SQLite checksum database for incremental fixity checks
+    Db {
+        #[command(subcommand)]
+        action: DbCommand,
+    },
+    /// Run a daemon on a Unix socket that keeps results and a similarity index in memory,
+    /// or send it requests
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonCommand,
+    },
+    /// Generate an Ed25519 key pair for signing manifests (--sign / --verify-signature)
+    Keygen {
+        /// Secret key file to create (keep private)
+        secret: PathBuf,
+        /// Public key file to create (distribute with the data)
+        public: PathBuf,
+    },
+    /// Create BagIt bags with ISCC manifests and validate existing bags
+    Bag {
+        #[command(subcommand)]
+        action: BagCommand,
+    },
+    /// Maintain an on-disk similarity index for near-duplicate lookups by Data-Code
+    Index {
+        #[command(subcommand)]
+        action: IndexCommand,
+    },
+    /// List the content-defined chunks the Data-Code of a file is built on
+    Chunks {
+        /// File to chunk (- for stdin)
+        file: PathBuf,
+        /// Output format (text: `<offset> <length> <feature> <blake3>` lines)
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Combine the Content-Codes of the parts of a bundled work (e.g. the text and images
+    /// of an ebook) into a Content-Code Mixed
+    Mixed {
+        /// Content-Codes of the parts
+        #[arg(value_name = "CODE", required = true, num_args = 2..)]
+        codes: Vec<String>,
+        /// Length of the Content-Code Mixed in bits (32 to 256, in steps of 32)
+        #[arg(long, value_name = "BITS", default_value_t = 64)]
+        bits: u32,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Verify a file or a partial/streamed download chunk by chunk against its outboard
+    /// tree (see --outboard), stopping at the first bad chunk
+    VerifyStream {
+        /// File to verify (- for stdin)
+        file: PathBuf,
+        /// ISCC-SUM code, Instance-Code or BLAKE3 datahash the content must match
+        #[arg(long, value_name = "CODE")]
+        expect: String,
+        /// Outboard file (default: FILE.obao)
+        #[arg(long, value_name = "OBAO")]
+        outboard: Option<PathBuf>,
+    },
+}
+
+/// `isum db` actions
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Create an empty checksum database
+    Init {
+        /// Database file
+        db: PathBuf,
+    },
+    /// Hash files and directories and record (or update) them
+    Add {
+        /// Database file
+        db: PathBuf,
+        /// Files or directories to record
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Re-hash recorded files, least recently verified first
+    Verify {
+        /// Database file
+        db: PathBuf,
+        /// Only verify files not verified within the last DAYS days
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+        /// Verify at most N files
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+        /// Output format for verification results
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Remove the records of files that no longer exist
+    Prune {
+        /// Database file
+        db: PathBuf,
+    },
+}
+
+/// `isum daemon` actions
+#[derive(Subcommand)]
+enum DaemonCommand {
+    /// Listen on SOCKET and answer requests until stopped (one JSON request per line:
+    /// `{"op": "hash", "path": ...}`, `{"op": "query", "iscc": ...}` or `{"op": "stop"}`)
+    Serve {
+        /// Unix socket to create
+        socket: PathBuf,
+        /// Similarity index (see isum index build) to load for queries
+        #[arg(long, value_name = "FILE")]
+        index: Option<PathBuf>,
+        /// Serve metrics (bytes and files hashed, chunk sizes, errors, cache hits) in
+        /// the Prometheus text format at http://ADDR/metrics
+        #[arg(long, value_name = "ADDR")]
+        metrics: Option<String>,
+    },
+    /// Hash files through a running daemon, which caches results by path, size and mtime
+    Hash {
+        /// Daemon socket
+        socket: PathBuf,
+        /// Files to hash
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+        /// Generate narrower 128-bit ISCC checksums (default: 256-bit)
+        #[arg(short, long)]
+        narrow: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Look up files similar to a code in the daemon's similarity index
+    Query {
+        /// Daemon socket
+        socket: PathBuf,
+        /// ISCC-SUM code or Data-Code unit to look up
+        #[arg(value_name = "ISCC")]
+        code: String,
+        /// Maximum number of differing Data-Code bits (of 64)
+        #[arg(long, value_name = "BITS", default_value_t = 8)]
+        max_distance: u32,
+        /// Output format (text: `<distance> <path>` lines)
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Stop a running daemon
+    Stop {
+        /// Daemon socket
+        socket: PathBuf,
+    },
+}
+
+/// `isum bag` actions
+#[derive(Subcommand)]
+enum BagCommand {
+    /// Bag a directory in place (moving its contents to data/) or, if it already is a
+    /// bag, (re)write its manifest-iscc.txt and tagmanifest-iscc.txt
+    Create {
+        /// Directory to bag
+        dir: PathBuf,
+    },
+    /// Verify the manifests (iscc, md5, sha256, sha512) and completeness of a bag,
+    /// exiting with status 1 if it is invalid
+    Validate {
+        /// Bag directory
+        dir: PathBuf,
+        /// Output format for verification results
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+}
+
+/// `isum index` actions
+#[derive(Subcommand)]
+enum IndexCommand {
+    /// Hash files and directories and write a new index (replacing an existing one)
+    Build {
+        /// Index file
+        index: PathBuf,
+        /// Files or directories to index
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// List indexed files whose Data-Code is similar to a code, closest first
+    Query {
+        /// Index file
+        index: PathBuf,
+        /// ISCC-SUM code or Data-Code unit to look up
+        #[arg(value_name = "ISCC")]
+        code: String,
+        /// Maximum number of differing Data-Code bits (of 64)
+        #[arg(long, value_name = "BITS", default_value_t = 8)]
+        max_distance: u32,
+        /// Output format (text: `<distance> <path>` lines)
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+}
+
+/// How `dedupe --script` replaces duplicates of the first file in a group
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LinkMode {
+    /// `ln -f` (files must be on the same file system)
+    Hardlink,
+    /// `cp --reflink=always` (copy-on-write clone, e.g. on Btrfs or XFS)
+    Reflink,
+}
+
+/// Output formats for generated checksums
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// `ISCC:<code> *<path>` lines
+    Text,
+    /// One JSON object per line
+    Json,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+/// Parser for --hash-also listing the algorithm names as possible values
+fn hash_algorithm_parser() -> impl TypedValueParser<Value = HashAlgorithm> {
+    PossibleValuesParser::new(HashAlgorithm::NAMES.iter().copied())
+        .map(|name| name.parse::<HashAlgorithm>().expect("possible value"))
+}
+
+/// Parser for --symlinks listing the policy names as possible values
+fn symlink_policy_parser() -> impl TypedValueParser<Value = SymlinkPolicy> {
+    PossibleValuesParser::new(SymlinkPolicy::NAMES)
+        .map(|name| name.parse::<SymlinkPolicy>().expect("possible value"))
+}
+
+/// Exit code contract (see `Exit status` in --help)
+const EXIT_VERIFICATION_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_IO_ERROR: i32 = 3;
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Cancelled by the signal handler; long-running loops stop after the in-flight
+/// file and directory walks stop before the next directory
+static INTERRUPT: LazyLock<CancellationToken> = LazyLock::new(CancellationToken::new);
+
+/// True once SIGINT/SIGTERM has been received
+fn interrupted() -> bool {
+    INTERRUPT.is_cancelled()
+}
+
+/// Stop gracefully on the first SIGINT/SIGTERM and exit immediately on the second
+fn install_signal_handler() {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPT.is_cancelled() {
+            process::exit(EXIT_INTERRUPTED);
+        }
+        INTERRUPT.cancel();
+    });
+    if let Err(e) = result {
+        eprintln!("isum: cannot install signal handler: {e}");
+    }
+}
+
+/// Process-wide read throttle configured by --limit-rate
+static RATE_LIMITER: Mutex<Option<RateLimiter>> = Mutex::new(None);
+
+/// Caps the average read throughput since the first throttled read
+struct RateLimiter {
+    bytes_per_second: u64,
+    start: Instant,
+    consumed: u64,
+}
+
+impl RateLimiter {
+    fn new(bytes_per_second: u64) -> Self {
+        RateLimiter {
+            bytes_per_second,
+            start: Instant::now(),
+            consumed: 0,
+        }
+    }
+
+    /// Account for `bytes` just read and return how long to pause to stay within the limit
+    fn consume(&mut self, bytes: usize) -> Duration {
+        self.consumed += bytes as u64;
+        let due = Duration::from_secs_f64(self.consumed as f64 / self.bytes_per_second as f64);
+        due.saturating_sub(self.start.elapsed())
+    }
+
+    /// Read size that keeps individual pauses around a tenth of a second
+    fn chunk_size(&self) -> usize {
+        (self.bytes_per_second / 10).clamp(4096, BUFFER_SIZE as u64) as usize
+    }
+}
+
+/// Per-file timings collected by --time for the slow-file report
+static TIMINGS: Mutex<Vec<Timing>> = Mutex::new(Vec::new());
+
+/// HTTP client for --lookup, shared by all files of a run
+#[cfg(feature = "network")]
+static LOOKUP_AGENT: OnceLock<ureq::Agent> = OnceLock::new();
+
+/// Query the --lookup registry for declarations of the same or similar codes
+#[cfg(feature = "network")]
+fn registry_lookup(url: &str, iscc: &str) -> Result<Value, String> {
+    let agent = LOOKUP_AGENT.get_or_init(|| {
+        ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(30)))
+            .build()
+            .into()
+    });
+    let mut response = agent
+        .get(url)
+        .query("iscc", iscc)
+        .call()
+        .map_err(|e| e.to_string())?;
+    let body: Value = response
+        .body_mut()
+        .read_json()
+        .map_err(|e| format!("invalid response: {e}"))?;
+    Ok(match body {
+        Value::Array(_) => body,
+        Value::Object(ref object) if object.get("matches").is_some_and(Value::is_array) => {
+            body["matches"].clone()
+        }
+        other => json!([other]),
+    })
+}
+
+#[cfg(not(feature = "network"))]
+fn registry_lookup(_url: &str, _iscc: &str) -> Result<Value, String> {
+    Err("isum was built without the `network` feature".to_string())
+}
+
+/// File records collected by --cbor, written as one manifest after the run
+static CBOR_RECORDS: Mutex<Vec<Value>> = Mutex::new(Vec::new());
+
+/// Write the records collected by --cbor (also after errors or an interruption)
+fn write_cbor_manifest(path: &Path) -> io::Result<()> {
+    let records = std::mem::take(&mut *CBOR_RECORDS.lock().unwrap());
+    fs::write(path, cbor::encode_manifest(records))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+/// Wall time spent reading and hashing a single file
+#[derive(Debug)]
+struct Timing {
+    path: String,
+    filesize: u64,
+    elapsed: Duration,
+}
+
+impl Timing {
+    fn bytes_per_second(&self) -> u64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => (self.filesize as f64 / secs) as u64,
+            _ => 0,
+        }
+    }
 }
 
-/// Exit codes following Unix conventions
-const EXIT_ERROR: i32 = 1;
+/// Print the `count` slowest files recorded by --time to stderr
+fn report_slowest(count: usize) {
+    let mut timings = std::mem::take(&mut *TIMINGS.lock().unwrap());
+    if timings.is_empty() || count == 0 {
+        return;
+    }
+    timings.sort_by(|a, b| b.elapsed.cmp(&a.elapsed).then_with(|| a.path.cmp(&b.path)));
+    timings.truncate(count);
+
+    eprintln!("isum: slowest {} file(s):", timings.len());
+    for timing in &timings {
+        eprintln!(
+            "  {:>10.3}s {:>10.1} MiB/s  {}",
+            timing.elapsed.as_secs_f64(),
+            timing.bytes_per_second() as f64 / (1024.0 * 1024.0),
+            timing.path
+        );
+    }
+}
+
+/// Pause after reading `bytes` if a --limit-rate is active
+fn throttle(bytes: usize) {
+    let delay = match RATE_LIMITER.lock().unwrap().as_mut() {
+        Some(limiter) => limiter.consume(bytes),
+        None => return,
+    };
+    if !delay.is_zero() {
+        thread::sleep(delay);
+    }
+}
 
 /// Buffer size for reading files (2MB)
 const BUFFER_SIZE: usize = 2 * 1024 * 1024;
 
-/// Print an error message to stderr and exit with error code
-fn error_exit(message: &str) -> ! {
-    eprintln!("isum: {message}");
-    process::exit(EXIT_ERROR);
+/// Extended attribute name used by --xattr and --check-xattr
+const XATTR_NAME: &str = "user.iscc.sum";
+
+/// Failure classes of a CLI run, each mapped to a distinct exit code
+#[derive(Debug)]
+enum CliError {
+    /// Invalid arguments or option combinations
+    Usage(String),
+    /// Files did not match their expected checksums
+    Verification(String),
+    /// Inputs could not be found or read
+    Io(io::Error),
+    /// The run was stopped by SIGINT/SIGTERM
+    Interrupted,
+}
+
+impl CliError {
+    fn usage(message: impl Into<String>) -> Self {
+        CliError::Usage(message.into())
+    }
+
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => EXIT_USAGE,
+            CliError::Verification(_) => EXIT_VERIFICATION_FAILURE,
+            CliError::Io(_) => EXIT_IO_ERROR,
+            CliError::Interrupted => EXIT_INTERRUPTED,
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        CliError::Io(err)
+    }
+}
+
+impl From<_core::Error> for CliError {
+    fn from(err: _core::Error) -> Self {
+        CliError::Io(err.into())
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Usage(message) | CliError::Verification(message) => write!(f, "{message}"),
+            CliError::Io(err) => write!(f, "{err}"),
+            CliError::Interrupted => write!(f, "interrupted, results of completed files written"),
+        }
+    }
+}
+
+/// Print an error message to stderr and exit with the error's exit code
+fn error_exit(error: &CliError) -> ! {
+    eprintln!("isum: {error}");
+    process::exit(error.exit_code());
+}
+
+/// Parse a byte size with an optional binary suffix (e.g. 512, 64K, 1.5M, 2G)
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(split);
+    let multiplier: u64 = match suffix.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("invalid size suffix '{suffix}'")),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{value}'"))?;
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parse a --segments size, which must be positive
+fn parse_segment_size(value: &str) -> Result<u64, String> {
+    match parse_size(value)? {
+        0 => Err("segment size must be greater than zero".to_string()),
+        size => Ok(size),
+    }
+}
+
+/// Parse a --limit-rate bandwidth, which must be positive
+fn parse_rate(value: &str) -> Result<u64, String> {
+    match parse_size(value)? {
+        0 => Err("rate must be greater than zero".to_string()),
+        rate => Ok(rate),
+    }
+}
+
+/// Parse a UTC date/datetime or take the modification time of a reference file
+fn parse_newer_than(value: &str) -> Result<SystemTime, String> {
+    if let Some(time) = parse_utc_timestamp(value) {
+        return Ok(time);
+    }
+    std::fs::metadata(value)
+        .and_then(|m| m.modified())
+        .map_err(|_| {
+            format!("'{value}' is neither a YYYY-MM-DD[THH:MM:SS] time nor a readable file")
+        })
+}
+
+/// Parse `YYYY-MM-DD`, `YYYY-MM-DDTHH:MM:SS` or `YYYY-MM-DD HH:MM:SS` (optional trailing Z) as UTC
+fn parse_utc_timestamp(value: &str) -> Option<SystemTime> {
+    let value = value.strip_suffix('Z').unwrap_or(value);
+    let (date, time) = match value.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+
+    let mut date_parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (
+        date_parts.next()??,
+        date_parts.next()??,
+        date_parts.next()??,
+    );
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || date.len() != 10 {
+        return None;
+    }
+
+    let seconds_of_day = match time {
+        Some(time) => {
+            let parts: Vec<i64> = time
+                .split(':')
+                .map(|p| p.parse().ok())
+                .collect::<Option<_>>()?;
+            match parts[..] {
+                [h, m, s] if h < 24 && m < 60 && s < 61 => h * 3600 + m * 60 + s,
+                [h, m] if h < 24 && m < 60 => h * 3600 + m * 60,
+                _ => return None,
+            }
+        }
+        None => 0,
+    };
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let seconds = days * 86400 + seconds_of_day;
+    if seconds >= 0 {
+        Some(UNIX_EPOCH + Duration::from_secs(seconds as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs()))
+    }
+}
+
+/// Apply --min-size, --max-size and --newer-than to a file found during traversal
+fn passes_filters(size: u64, modified: Option<SystemTime>, cli: &Cli) -> bool {
+    if cli.min_size.is_some_and(|min| size < min) || cli.max_size.is_some_and(|max| size > max) {
+        return false;
+    }
+    if let Some(threshold) = cli.newer_than {
+        return modified.is_some_and(|mtime| mtime > threshold);
+    }
+    true
 }
 
 /// Build a GlobSet from exclude patterns
@@ -82,275 +1133,3750 @@ fn build_exclude_set(patterns: &[String]) -> io::Result<Option<GlobSet>> {
         builder.add(glob);
     }
 
-    let globset = builder
-        .build()
-        .map_err(|e| io::Error::other(format!("Failed to build exclude patterns: {e}")))?;
-
-    Ok(Some(globset))
+    let globset = builder
+        .build()
+        .map_err(|e| io::Error::other(format!("Failed to build exclude patterns: {e}")))?;
+
+    Ok(Some(globset))
+}
+
+/// Lower the CPU priority of the process and switch to idle I/O scheduling on Linux
+#[cfg(unix)]
+fn lower_priority() -> io::Result<()> {
+    // SAFETY: setpriority takes no pointer arguments
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        // SAFETY: ioprio_set takes no pointer arguments
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Switch the process to background mode, which lowers both CPU and I/O priority
+#[cfg(windows)]
+fn lower_priority() -> io::Result<()> {
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, SetPriorityClass, PROCESS_MODE_BACKGROUND_BEGIN,
+    };
+
+    // SAFETY: GetCurrentProcess returns a pseudo handle that is always valid
+    if unsafe { SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lower_priority() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "not supported on this platform",
+    ))
+}
+
+/// Fill in options not given on the command line from the config file and environment
+fn apply_config(cli: &mut Cli, config: Config) {
+    if !cli.narrow && !cli.wide {
+        cli.narrow = config.narrow();
+    }
+    if cli.ignore_file.is_empty() {
+        cli.ignore_file.extend(config.ignore_file);
+    }
+    if cli.global_ignore.is_none() {
+        cli.global_ignore = config.global_ignore;
+    }
+    if cli.threads.is_none() {
+        cli.threads = config.threads;
+    }
+    // Configured excludes are combined with the ones given on the command line
+    let mut exclude = config.exclude;
+    exclude.append(&mut cli.exclude);
+    cli.exclude = exclude;
+}
+
+/// Expand wildcard arguments that do not name an existing file.
+///
+/// Windows shells pass patterns like `*.bin` through unexpanded. Patterns without
+/// matches are kept as-is so they are reported as missing files.
+fn expand_wildcards(files: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut expanded = Vec::with_capacity(files.len());
+
+    for file in files {
+        let matches: Vec<PathBuf> = file
+            .to_str()
+            .filter(|p| p.contains(['*', '?', '[']) && !file.exists())
+            .and_then(|p| glob::glob(p).ok())
+            .map(|paths| paths.filter_map(Result::ok).collect())
+            .unwrap_or_default();
+
+        if matches.is_empty() {
+            expanded.push(file);
+        } else {
+            expanded.extend(matches);
+        }
+    }
+
+    expanded
+}
+
+/// Return the extended-length (`\\?\`) form of a path so deep trees can be accessed
+#[cfg(windows)]
+fn long_path(path: &Path) -> PathBuf {
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    match absolute.to_str() {
+        Some(s) if s.starts_with(r"\\?\") || s.starts_with(r"\\.\") => absolute,
+        Some(s) if s.starts_with(r"\\") => PathBuf::from(format!(r"\\?\UNC\{}", &s[2..])),
+        Some(s) => PathBuf::from(format!(r"\\?\{s}")),
+        None => absolute,
+    }
+}
+
+/// Paths have no length limit to work around on other platforms
+#[cfg(not(windows))]
+fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// List the names of the alternate data streams of a file
+#[cfg(windows)]
+fn alternate_streams(path: &Path) -> io::Result<Vec<String>> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{ERROR_HANDLE_EOF, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard,
+        WIN32_FIND_STREAM_DATA,
+    };
+
+    let wide: Vec<u16> = long_path(path)
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect();
+    // SAFETY: WIN32_FIND_STREAM_DATA is plain data, all-zero is a valid value
+    let mut data: WIN32_FIND_STREAM_DATA = unsafe { std::mem::zeroed() };
+    let data_ptr = &mut data as *mut WIN32_FIND_STREAM_DATA as *mut _;
+
+    // SAFETY: `wide` is NUL-terminated and `data_ptr` points to a valid stream data buffer
+    let handle = unsafe { FindFirstStreamW(wide.as_ptr(), FindStreamInfoStandard, data_ptr, 0) };
+    if handle == INVALID_HANDLE_VALUE {
+        let err = io::Error::last_os_error();
+        // Files without any data stream report ERROR_HANDLE_EOF
+        return if err.raw_os_error() == Some(ERROR_HANDLE_EOF as i32) {
+            Ok(Vec::new())
+        } else {
+            Err(err)
+        };
+    }
+
+    let mut streams = Vec::new();
+    loop {
+        let name = &data.cStreamName;
+        let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+        let name = String::from_utf16_lossy(&name[..len]);
+        // Names look like ":stream:$DATA"; the unnamed "::$DATA" is the main stream
+        if let Some(stream) = name
+            .strip_prefix(':')
+            .and_then(|n| n.strip_suffix(":$DATA"))
+        {
+            if !stream.is_empty() {
+                streams.push(stream.to_string());
+            }
+        }
+        // SAFETY: `handle` is a valid find handle and `data_ptr` is still valid
+        if unsafe { FindNextStreamW(handle, data_ptr) } == 0 {
+            break;
+        }
+    }
+    // SAFETY: `handle` was returned by FindFirstStreamW and is closed exactly once
+    unsafe { FindClose(handle) };
+
+    Ok(streams)
+}
+
+/// Alternate data streams only exist on NTFS under Windows
+#[cfg(not(windows))]
+fn alternate_streams(_path: &Path) -> io::Result<Vec<String>> {
+    Ok(Vec::new())
+}
+
+/// Apply --ads to a list of files found during traversal
+fn apply_ads_policy(entries: Vec<PathBuf>, cli: &Cli) -> Vec<PathBuf> {
+    if cli.ads == AdsPolicy::Ignore {
+        return entries;
+    }
+    entries
+        .into_iter()
+        .flat_map(|path| ads_paths(path, cli))
+        .collect()
+}
+
+/// A file found during traversal followed by its alternate data streams with
+/// --ads include, warning about them with --ads warn
+fn ads_paths(path: PathBuf, cli: &Cli) -> Vec<PathBuf> {
+    if cli.ads == AdsPolicy::Ignore {
+        return vec![path];
+    }
+
+    let streams = alternate_streams(&path).unwrap_or_else(|e| {
+        eprintln!(
+            "isum: {}: cannot list alternate data streams: {e}",
+            path.display()
+        );
+        Vec::new()
+    });
+
+    if cli.ads == AdsPolicy::Warn && !streams.is_empty() {
+        eprintln!(
+            "isum: warning: {}: {} alternate data stream(s) not hashed",
+            path.display(),
+            streams.len()
+        );
+    }
+
+    let include = cli.ads == AdsPolicy::Include;
+    let mut result = vec![path.clone()];
+    for stream in streams.into_iter().filter(|_| include) {
+        let mut stream_path = path.clone().into_os_string();
+        stream_path.push(":");
+        stream_path.push(stream);
+        result.push(PathBuf::from(stream_path));
+    }
+    result
+}
+
+/// Expand `@file` response-file arguments into the lines of the file.
+///
+/// The program name (first argument) is never expanded. Each non-empty line of a
+/// response file becomes one argument; response files are not expanded recursively.
+fn expand_response_files<I>(args: I) -> io::Result<Vec<OsString>>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut expanded = Vec::new();
+
+    for (index, arg) in args.into_iter().enumerate() {
+        let response_file = arg
+            .to_str()
+            .filter(|a| index > 0 && a.len() > 1)
+            .and_then(|a| a.strip_prefix('@'));
+
+        match response_file {
+            Some(file) => {
+                let contents = fs::read_to_string(file)
+                    .map_err(|e| io::Error::new(e.kind(), format!("@{file}: {e}")))?;
+                expanded.extend(
+                    contents
+                        .lines()
+                        .map(|line| line.trim_end_matches('\r'))
+                        .filter(|line| !line.is_empty())
+                        .map(OsString::from),
+                );
+            }
+            None => expanded.push(arg),
+        }
+    }
+
+    Ok(expanded)
+}
+
+fn main() {
+    let args = match expand_response_files(std::env::args_os()) {
+        Ok(args) => args,
+        Err(e) => error_exit(&CliError::usage(e.to_string())),
+    };
+    // clap exits with code 2 (EXIT_USAGE) on invalid arguments
+    let mut cli = Cli::parse_from(args);
+    if cfg!(windows) {
+        cli.files = expand_wildcards(std::mem::take(&mut cli.files));
+    }
+    if !cli.no_config {
+        match Config::load() {
+            Ok(config) => apply_config(&mut cli, config),
+            Err(e) => error_exit(&CliError::usage(format!("config: {e}"))),
+        }
+    }
+    init_logging(&cli);
+    // Before any worker threads are started, so they inherit the lowered priorities
+    if cli.nice_io {
+        if let Err(e) = lower_priority() {
+            eprintln!("isum: warning: cannot lower process priority: {e}");
+        }
+    }
+    if let Some(root) = &cli.root {
+        match fs::canonicalize(root) {
+            Ok(root) => cli.root = Some(root),
+            Err(e) => error_exit(&CliError::usage(format!("--root {}: {e}", root.display()))),
+        }
+    }
+    if let Some(rate) = cli.limit_rate {
+        *RATE_LIMITER.lock().unwrap() = Some(RateLimiter::new(rate));
+    }
+    if let Some(threads) = cli.threads {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+        {
+            error_exit(&CliError::usage(e.to_string()));
+        }
+    }
+
+    // Stdin runs have no partial results to flush, so keep the default signal behavior
+    if !cli.files.is_empty() {
+        install_signal_handler();
+    }
+
+    // Process the result and handle errors
+    let slowest = cli.time;
+    let cbor_path = cli.cbor.clone();
+    let mut result = run(cli);
+    if let Some(count) = slowest {
+        report_slowest(count);
+    }
+    // Invalid arguments produce no results, so no manifest is written
+    if let Some(path) = cbor_path.filter(|_| !matches!(result, Err(CliError::Usage(_)))) {
+        if let Err(e) = write_cbor_manifest(&path) {
+            result = result.and(Err(e.into()));
+        }
+    }
+    if interrupted() {
+        let _ = io::Write::flush(&mut io::stdout());
+        error_exit(&CliError::Interrupted);
+    }
+    if let Err(e) = result {
+        error_exit(&e);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), CliError> {
+    if let Some(command) = &cli.command {
+        return match command {
+            Command::Selftest => run_selftest(),
+            Command::Completions { shell } => {
+                clap_complete::generate(*shell, &mut Cli::command(), "isum", &mut io::stdout());
+                Ok(())
+            }
+            Command::Manpage => {
+                Ok(clap_mangen::Man::new(Cli::command()).render(&mut io::stdout())?)
+            }
+            Command::Dedupe {
+                paths,
+                similar,
+                max_distance,
+                script,
+                format,
+            } => {
+                let max_distance = similar.then_some(*max_distance);
+                run_dedupe(&cli, paths, max_distance, *script, *format)
+            }
+            Command::Delta { a, b, format } => run_delta(a, b, *format),
+            Command::Chunks { file, format } => run_chunks(file, *format),
+            Command::Mixed {
+                codes,
+                bits,
+                format,
+            } => run_mixed(codes, *bits, *format),
+            Command::VerifyStream {
+                file,
+                expect,
+                outboard,
+            } => run_verify_stream(file, expect, outboard.as_deref()),
+            Command::Db { action } => run_db(&cli, action),
+            Command::Index { action } => run_index(&cli, action),
+            Command::Keygen { secret, public } => run_keygen(secret, public),
+            Command::Daemon { action } => run_daemon(action),
+            Command::Bag { action } => match action {
+                BagCommand::Create { dir } => bag_create(&cli, dir),
+                BagCommand::Validate { dir, format } => bag_validate(&cli, dir, *format),
+            },
+            Command::Compare {
+                a,
+                b,
+                max_distance,
+                format,
+            } => run_compare(&cli, a, b, *max_distance, *format),
+            Command::ManifestDiff {
+                old,
+                new,
+                max_distance,
+                format,
+            } => {
+                if let Some(dir) = [old, new].into_iter().find(|p| p.is_dir()) {
+                    return Err(CliError::usage(format!(
+                        "{}: is a directory (use isum compare)",
+                        dir.display()
+                    )));
+                }
+                run_compare(&cli, old, new, *max_distance, *format)
+            }
+        };
+    }
+
+    let reads_stdin = cli.files.is_empty() || cli.files.iter().any(|f| is_stdin(f));
+    if (cli.xattr || cli.sidecar || cli.manifests || cli.outboard) && reads_stdin {
+        return Err(CliError::usage(
+            "--xattr, --sidecar, --manifests and --outboard cannot be used with stdin",
+        ));
+    }
+
+    if cli.state.is_some() {
+        if cli.files.len() != 1 || !long_path(&cli.files[0]).is_file() {
+            return Err(CliError::usage("--state requires a single regular file"));
+        }
+        // --hash-also digests cannot be resumed (also when set in the config file)
+        if !cli.hash_also.is_empty() {
+            return Err(CliError::usage("--state cannot be used with --hash-also"));
+        }
+    }
+
+    if cli.with_metadata
+        && cli.format != OutputFormat::Json
+        && !cli.sidecar
+        && !cli.manifests
+        && cli.cbor.is_none()
+    {
+        return Err(CliError::usage(
+            "--with-metadata requires --format json, --sidecar, --manifests or --cbor",
+        ));
+    }
+
+    if let Some(url) = &cli.lookup {
+        if !cfg!(feature = "network") {
+            return Err(CliError::usage(
+                "--lookup requires isum built with the `network` feature",
+            ));
+        }
+        if cli.format != OutputFormat::Json {
+            return Err(CliError::usage("--lookup requires --format json"));
+        }
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(CliError::usage(format!(
+                "--lookup {url}: not an HTTP(S) URL"
+            )));
+        }
+    }
+
+    if cli.dry_run && cli.files.is_empty() {
+        return Err(CliError::usage("--dry-run requires files or directories"));
+    }
+
+    if cli.list_ignored {
+        return run_list_ignored(&cli);
+    }
+
+    if cli.check_xattr {
+        return run_check(&cli, "--check-xattr", verify_xattr);
+    }
+
+    if cli.check_sidecar {
+        return run_check(&cli, "--check-sidecar", verify_sidecar);
+    }
+
+    if cli.check_manifests {
+        return run_check_manifests(&cli);
+    }
+
+    if cli.hashdeep {
+        print_hashdeep_header(&cli);
+    } else if cli.format == OutputFormat::Csv && cli.dry_run {
+        println!("path");
+    } else if cli.format == OutputFormat::Csv {
+        let mut header = vec!["path", "iscc", "datahash", "filesize"];
+        header.extend(cli.hash_also.iter().map(|a| a.name()));
+        if cli.time.is_some() {
+            header.extend(["seconds", "bytes_per_second"]);
+        }
+        println!("{}", header.join(","));
+    }
+
+    if cli.files.is_empty() {
+        // Process stdin
+        process_stdin(&cli)?;
+    } else {
+        let exclude_set = cli_exclude_set(&cli)?;
+
+        // Process files, reporting each failing argument and going on with the rest
+        let mut failure = None;
+        for file in &cli.files {
+            if interrupted() {
+                break;
+            }
+            if let Err(e) = process_file(file, &cli, exclude_set.as_ref()) {
+                if let Some(previous) = failure.replace(e) {
+                    eprintln!("isum: {previous}");
+                }
+            }
+        }
+        // The last error is reported on exit
+        if let Some(e) = failure {
+            return Err(CliError::Io(e));
+        }
+    }
+
+    Ok(())
+}
+
+/// Files with identical content, sorted by path
+struct DuplicateGroup {
+    instance: String,
+    filesize: u64,
+    paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// Bytes freed by replacing all but the first file with links
+    fn reclaimable(&self) -> u64 {
+        self.filesize * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// Hash all files below `paths` and report exact (and optionally near) duplicates
+fn run_dedupe(
+    cli: &Cli,
+    paths: &[PathBuf],
+    max_distance: Option<u32>,
+    script: Option<LinkMode>,
+    format: OutputFormat,
+) -> Result<(), CliError> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(list_directory(path, cli, None)?.files);
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    // Group by the full 256-bit Instance-Code unit; keep the Data-Code unit for --similar
+    let mut by_instance: BTreeMap<String, (u64, String, Vec<PathBuf>)> = BTreeMap::new();
+    let mut had_errors = false;
+    for path in files {
+        if interrupted() {
+            break;
+        }
+        match hash_file(&path, false, true) {
+            Ok(result) => {
+                let units = result.units.unwrap_or_default();
+                let (Some(data), Some(instance)) = (units.first(), units.get(1)) else {
+                    continue;
+                };
+                by_instance
+                    .entry(instance.clone())
+                    .or_insert_with(|| (result.filesize, data.clone(), Vec::new()))
+                    .2
+                    .push(path);
+            }
+            Err(e) => {
+                eprintln!("isum: {}: {}", path.display(), e);
+                had_errors = true;
+            }
+        }
+    }
+
+    let mut duplicates: Vec<DuplicateGroup> = by_instance
+        .iter()
+        .filter(|(_, (_, _, paths))| paths.len() > 1)
+        .map(|(instance, (filesize, _, paths))| {
+            let mut paths = paths.clone();
+            paths.sort();
+            DuplicateGroup {
+                instance: instance.clone(),
+                filesize: *filesize,
+                paths,
+            }
+        })
+        .collect();
+    duplicates.sort_by(|a, b| {
+        b.reclaimable()
+            .cmp(&a.reclaimable())
+            .then(a.paths.cmp(&b.paths))
+    });
+
+    let similar = match max_distance {
+        Some(max_distance) => similar_groups(&by_instance, max_distance),
+        None => Vec::new(),
+    };
+
+    match script {
+        Some(mode) => print_link_script(&duplicates, mode),
+        None => print_dedupe_report(&duplicates, &similar, max_distance, format),
+    }
+
+    if had_errors {
+        Err(io::Error::other("Some files could not be processed").into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Cluster distinct contents whose Data-Codes differ in at most `max_distance` bits
+fn similar_groups(
+    by_instance: &BTreeMap<String, (u64, String, Vec<PathBuf>)>,
+    max_distance: u32,
+) -> Vec<Vec<PathBuf>> {
+    let contents: Vec<(&String, &Vec<PathBuf>)> = by_instance
+        .values()
+        .map(|(_, data, paths)| (data, paths))
+        .collect();
+
+    // Union-find over the pairwise comparisons
+    let mut parent: Vec<usize> = (0..contents.len()).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for i in 0..contents.len() {
+        for j in i + 1..contents.len() {
+            if unit_distance(contents[i].0, contents[j].0).is_some_and(|d| d <= max_distance) {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+
+    // Clusters of a single content hold exact duplicates only, reported as such
+    let mut clusters: BTreeMap<usize, (usize, Vec<PathBuf>)> = BTreeMap::new();
+    for (i, (_, paths)) in contents.iter().enumerate() {
+        let root = find(&mut parent, i);
+        let cluster = clusters.entry(root).or_default();
+        cluster.0 += 1;
+        cluster.1.extend(paths.iter().cloned());
+    }
+    let mut groups: Vec<Vec<PathBuf>> = clusters
+        .into_values()
+        .filter(|(contents, _)| *contents > 1)
+        .map(|(_, mut paths)| {
+            paths.sort();
+            paths
+        })
+        .collect();
+    groups.sort();
+    groups
+}
+
+/// Number of differing bits between two ISCC units of the same type and length
+fn unit_distance(a: &str, b: &str) -> Option<u32> {
+    let decode = |code: &str| {
+        base32::decode(
+            base32::Alphabet::Rfc4648 { padding: false },
+            code.strip_prefix("ISCC:")?,
+        )
+    };
+    let (a, b) = (decode(a)?, decode(b)?);
+    // The two header bytes encode type and length and must match
+    if a.len() != b.len() || a.len() < 2 || a[..2] != b[..2] {
+        return None;
+    }
+    Some(
+        a[2..]
+            .iter()
+            .zip(&b[2..])
+            .map(|(x, y)| (x ^ y).count_ones())
+            .sum(),
+    )
+}
+
+/// Quote a path for POSIX shells
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+/// Print commands replacing every duplicate with a link to the first file of its group
+fn print_link_script(duplicates: &[DuplicateGroup], mode: LinkMode) {
+    println!("#!/bin/sh");
+    println!("set -e");
+    for group in duplicates {
+        let keep = shell_quote(&group.paths[0]);
+        println!("# {} ({} bytes)", group.instance, group.filesize);
+        for duplicate in &group.paths[1..] {
+            let duplicate = shell_quote(duplicate);
+            match mode {
+                LinkMode::Hardlink => println!("ln -f -- {keep} {duplicate}"),
+                LinkMode::Reflink => println!("cp --reflink=always -- {keep} {duplicate}"),
+            }
+        }
+    }
+}
+
+/// Print duplicate and similar groups with the reclaimable bytes
+fn print_dedupe_report(
+    duplicates: &[DuplicateGroup],
+    similar: &[Vec<PathBuf>],
+    max_distance: Option<u32>,
+    format: OutputFormat,
+) {
+    let reclaimable: u64 = duplicates.iter().map(DuplicateGroup::reclaimable).sum();
+    match format {
+        OutputFormat::Text => {
+            for group in duplicates {
+                println!(
+                    "{} identical files ({} bytes each, {} bytes reclaimable):",
+                    group.paths.len(),
+                    group.filesize,
+                    group.reclaimable()
+                );
+                for path in &group.paths {
+                    println!("  {}", path.display());
+                }
+                println!();
+            }
+            for paths in similar {
+                println!(
+                    "{} similar files (Data-Code distance <= {}):",
+                    paths.len(),
+                    max_distance.unwrap_or_default()
+                );
+                for path in paths {
+                    println!("  {}", path.display());
+                }
+                println!();
+            }
+            eprintln!(
+                "isum: {} duplicate group(s), {} bytes reclaimable",
+                duplicates.len(),
+                reclaimable
+            );
+        }
+        OutputFormat::Json => {
+            let paths = |paths: &[PathBuf]| -> Vec<String> {
+                paths
+                    .iter()
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .collect()
+            };
+            let report = json!({
+                "duplicates": duplicates.iter().map(|group| json!({
+                    "instance": group.instance,
+                    "filesize": group.filesize,
+                    "reclaimable": group.reclaimable(),
+                    "paths": paths(&group.paths),
+                })).collect::<Vec<_>>(),
+                "similar": similar.iter().map(|group| paths(group)).collect::<Vec<_>>(),
+                "reclaimable": reclaimable,
+            });
+            println!("{report}");
+        }
+        OutputFormat::Csv => {
+            println!("group,kind,filesize,path");
+            for (index, group) in duplicates.iter().enumerate() {
+                for path in &group.paths {
+                    let path = csv_field(&path.to_string_lossy());
+                    println!("{},identical,{},{path}", index + 1, group.filesize);
+                }
+            }
+            for (index, paths) in similar.iter().enumerate() {
+                for path in paths {
+                    let path = csv_field(&path.to_string_lossy());
+                    println!("{},similar,,{path}", duplicates.len() + index + 1);
+                }
+            }
+        }
+    }
+}
+
+/// Chunks of one side of a delta and the byte ranges not shared with the other side
+struct DeltaSide<'a> {
+    path: &'a Path,
+    chunks: Vec<DataChunk>,
+    size: u64,
+    shared_chunks: usize,
+    shared_bytes: u64,
+    /// Merged `(offset, end)` ranges of unshared chunks
+    only: Vec<(u64, u64)>,
+}
+
+impl<'a> DeltaSide<'a> {
+    fn read(path: &'a Path) -> io::Result<Self> {
+        let chunks = open_file(path)
+            .and_then(|mut reader| cdc::read_data_chunks(&mut reader))
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        let size = chunks.iter().map(|c| c.length as u64).sum();
+        Ok(DeltaSide {
+            path,
+            chunks,
+            size,
+            shared_chunks: 0,
+            shared_bytes: 0,
+            only: Vec::new(),
+        })
+    }
+
+    /// Match chunks against the other side's chunks (as a multiset of feature and length)
+    fn match_against(&mut self, other: &[DataChunk]) {
+        let mut available: HashMap<(u32, usize), usize> = HashMap::new();
+        for chunk in other.iter().filter(|c| c.length > 0) {
+            *available.entry((chunk.feature, chunk.length)).or_default() += 1;
+        }
+        for chunk in self.chunks.iter().filter(|c| c.length > 0) {
+            let end = chunk.offset + chunk.length as u64;
+            match available.get_mut(&(chunk.feature, chunk.length)) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    self.shared_chunks += 1;
+                    self.shared_bytes += chunk.length as u64;
+                }
+                _ => match self.only.last_mut() {
+                    Some(range) if range.1 == chunk.offset => range.1 = end,
+                    _ => self.only.push((chunk.offset, end)),
+                },
+            }
+        }
+    }
+
+    /// Data-Code digest recomputed from the chunk features
+    fn data_digest(&self) -> Vec<u8> {
+        minhash_256(&self.chunks.iter().map(|c| c.feature).collect::<Vec<_>>())
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.chunks.iter().filter(|c| c.length > 0).count()
+    }
+}
+
+/// Report the chunks shared by two files, the ranges that differ and their similarity
+fn run_delta(a: &Path, b: &Path, format: OutputFormat) -> Result<(), CliError> {
+    let (mut a, mut b) = (DeltaSide::read(a)?, DeltaSide::read(b)?);
+    a.match_against(&b.chunks);
+    b.match_against(&a.chunks);
+
+    let similarity = match a.size + b.size {
+        0 => 100.0,
+        total => (a.shared_bytes + b.shared_bytes) as f64 * 100.0 / total as f64,
+    };
+    let distance: u32 = a
+        .data_digest()
+        .iter()
+        .zip(b.data_digest())
+        .map(|(x, y)| (x ^ y).count_ones())
+        .sum();
+
+    match format {
+        OutputFormat::Text => {
+            for (label, side) in [("A", &a), ("B", &b)] {
+                println!(
+                    "{label}: {} ({} chunks, {} bytes)",
+                    side.path.display(),
+                    side.chunk_count(),
+                    side.size
+                );
+            }
+            println!(
+                "Shared: {} chunks, {} bytes",
+                a.shared_chunks, a.shared_bytes
+            );
+            println!("Similarity: {similarity:.1}%");
+            println!("Data-Code distance: {distance} of 256 bits");
+            for (label, side) in [("A", &a), ("B", &b)] {
+                if !side.only.is_empty() {
+                    println!("Only in {label}:");
+                    for (offset, end) in &side.only {
+                        println!("  {offset}..{end} ({} bytes)", end - offset);
+                    }
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let side = |side: &DeltaSide| {
+                json!({
+                    "path": side.path.to_string_lossy(),
+                    "chunks": side.chunk_count(),
+                    "bytes": side.size,
+                    "only": side.only,
+                })
+            };
+            let report = json!({
+                "a": side(&a),
+                "b": side(&b),
+                "shared_chunks": a.shared_chunks,
+                "shared_bytes": a.shared_bytes,
+                "similarity": similarity,
+                "data_code_distance": distance,
+            });
+            println!("{report}");
+        }
+        OutputFormat::Csv => {
+            println!("file,offset,length");
+            for (label, side) in [("a", &a), ("b", &b)] {
+                for (offset, end) in &side.only {
+                    println!("{label},{offset},{}", end - offset);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A file moved between the two sides of a comparison
+struct MovedFile {
+    from: String,
+    to: String,
+    /// Data-Code bits that differ
+    distance: u32,
+    /// Same Instance-Code, i.e. unchanged content
+    identical: bool,
+}
+
+/// Split an ISCC-SUM code into its Data-Code and Instance-Code bodies
+fn split_sum_code(iscc: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+    let code: IsccCode = iscc.strip_prefix("ISCC:")?.parse().ok()?;
+    if code.maintype() != MainType::Iscc {
+        return None;
+    }
+    match code.units().as_slice() {
+        [.., data, instance] => Some((data.body().to_vec(), instance.body().to_vec())),
+        _ => None,
+    }
+}
+
+/// Differing bits of two code bodies, compared at the shorter length
+fn body_distance(a: &[u8], b: &[u8]) -> u32 {
+    a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+/// Map of `/`-separated relative paths to ISCC-SUM codes for a directory or checksum file
+/// (text lines or JSON lines as written by `--format json`)
+fn load_compare_side(cli: &Cli, path: &Path) -> Result<BTreeMap<String, String>, CliError> {
+    let mut codes = BTreeMap::new();
+    if path.is_dir() {
+        let mut had_errors = false;
+        for file in list_directory(path, cli, None)?.files {
+            if interrupted() {
+                break;
+            }
+            let relative = file.strip_prefix(path).unwrap_or(&file);
+            let relative = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            match hash_file(&file, false, false) {
+                Ok(result) => {
+                    codes.insert(relative, result.iscc);
+                }
+                Err(e) => {
+                    eprintln!("isum: {}: {}", file.display(), e);
+                    had_errors = true;
+                }
+            }
+        }
+        if had_errors {
+            return Err(io::Error::other("Some files could not be processed").into());
+        }
+    } else {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        for (index, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = if line.trim_start_matches('\u{feff}').starts_with('{') {
+                parse_json_line(line)
+            } else {
+                manifest::parse_line(line)
+            };
+            match entry {
+                Some(entry) if !entry.is_tree() => {
+                    codes.insert(entry.path, entry.iscc);
+                }
+                Some(_) => {}
+                None => eprintln!(
+                    "isum: {}: {}: improperly formatted ISCC checksum line",
+                    path.display(),
+                    index + 1
+                ),
+            }
+        }
+    }
+    Ok(codes)
+}
+
+/// Parse a `--format json` output line into a checksum entry
+fn parse_json_line(line: &str) -> Option<manifest::ChecksumLine> {
+    let value: Value = serde_json::from_str(line.trim_start_matches('\u{feff}')).ok()?;
+    Some(manifest::ChecksumLine {
+        iscc: value["iscc"]
+            .as_str()
+            .filter(|c| c.starts_with("ISCC:"))?
+            .to_string(),
+        path: value["path"]
+            .as_str()
+            .filter(|p| !p.is_empty())?
+            .to_string(),
+    })
+}
+
+/// Report added, removed, modified and moved files between two trees or manifests
+fn run_compare(
+    cli: &Cli,
+    a: &Path,
+    b: &Path,
+    max_distance: u32,
+    format: OutputFormat,
+) -> Result<(), CliError> {
+    let (old, new) = (load_compare_side(cli, a)?, load_compare_side(cli, b)?);
+    let parts = |code: &String| split_sum_code(code).unwrap_or_default();
+
+    let mut modified = Vec::new();
+    let mut unchanged = 0;
+    for (path, code) in &old {
+        if let Some(new_code) = new.get(path) {
+            let ((_, old_instance), (_, new_instance)) = (parts(code), parts(new_code));
+            let common = old_instance.len().min(new_instance.len());
+            if common == 0 || old_instance[..common] != new_instance[..common] {
+                modified.push(path.clone());
+            } else {
+                unchanged += 1;
+            }
+        }
+    }
+    let mut removed: Vec<&String> = old.keys().filter(|p| !new.contains_key(*p)).collect();
+    let mut added: Vec<&String> = new.keys().filter(|p| !old.contains_key(*p)).collect();
+
+    // Pair each removed file with the closest unclaimed added file by Data-Code
+    let mut moved = Vec::new();
+    removed.retain(|from| {
+        let (old_data, old_instance) = parts(&old[*from]);
+        let closest = added
+            .iter()
+            .enumerate()
+            .filter_map(|(index, to)| {
+                let (new_data, new_instance) = parts(&new[*to]);
+                let distance = body_distance(&old_data, &new_data);
+                let identical = old_instance.iter().zip(&new_instance).all(|(x, y)| x == y);
+                (!old_data.is_empty() && !new_data.is_empty() && distance <= max_distance)
+                    .then_some((distance, !identical, index, identical))
+            })
+            .min();
+        match closest {
+            Some((distance, _, index, identical)) => {
+                moved.push(MovedFile {
+                    from: (*from).clone(),
+                    to: added.remove(index).clone(),
+                    distance,
+                    identical,
+                });
+                false
+            }
+            None => true,
+        }
+    });
+
+    match format {
+        OutputFormat::Text => {
+            for path in &added {
+                println!("A  {path}");
+            }
+            for path in &removed {
+                println!("D  {path}");
+            }
+            for path in &modified {
+                println!("M  {path}");
+            }
+            for file in &moved {
+                let note = if file.identical {
+                    String::new()
+                } else {
+                    format!(" (modified, Data-Code distance {})", file.distance)
+                };
+                println!("R  {} -> {}{note}", file.from, file.to);
+            }
+        }
+        OutputFormat::Json => {
+            let report = json!({
+                "added": added,
+                "removed": removed,
+                "modified": modified,
+                "moved": moved.iter().map(|file| json!({
+                    "from": file.from,
+                    "to": file.to,
+                    "identical": file.identical,
+                    "distance": file.distance,
+                })).collect::<Vec<_>>(),
+                "unchanged": unchanged,
+            });
+            println!("{report}");
+        }
+        OutputFormat::Csv => {
+            println!("status,path,new_path");
+            for path in &added {
+                println!("added,{},", csv_field(path));
+            }
+            for path in &removed {
+                println!("removed,{},", csv_field(path));
+            }
+            for path in &modified {
+                println!("modified,{},", csv_field(path));
+            }
+            for file in &moved {
+                println!("moved,{},{}", csv_field(&file.from), csv_field(&file.to));
+            }
+        }
+    }
+
+    let summary = format!(
+        "{} added, {} removed, {} modified, {} moved, {unchanged} unchanged",
+        added.len(),
+        removed.len(),
+        modified.len(),
+        moved.len()
+    );
+    if added.is_empty() && removed.is_empty() && modified.is_empty() && moved.is_empty() {
+        eprintln!("isum: {summary}");
+        Ok(())
+    } else {
+        Err(CliError::Verification(summary))
+    }
+}
+
+/// Seconds since the Unix epoch
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Run an `isum db` action
+fn run_db(cli: &Cli, action: &DbCommand) -> Result<(), CliError> {
+    match action {
+        DbCommand::Init { db } => {
+            if db.exists() {
+                return Err(CliError::usage(format!("{}: already exists", db.display())));
+            }
+            ChecksumDb::create(db)?;
+            Ok(())
+        }
+        DbCommand::Add { db, paths } => db_add(cli, &ChecksumDb::open(db)?, paths),
+        DbCommand::Verify {
+            db,
+            older_than,
+            limit,
+            format,
+        } => {
+            let before = match older_than {
+                Some(days) => unix_now() - *days as i64 * 86_400,
+                None => i64::MAX,
+            };
+            db_verify(cli, &ChecksumDb::open(db)?, before, *limit, *format)
+        }
+        DbCommand::Prune { db } => {
+            let db = ChecksumDb::open(db)?;
+            let mut pruned = 0;
+            for path in db.paths()? {
+                if !long_path(Path::new(&path)).exists() {
+                    db.remove(&path)?;
+                    println!("{path}");
+                    pruned += 1;
+                }
+            }
+            eprintln!("isum: {pruned} record(s) pruned");
+            Ok(())
+        }
+    }
+}
+
+/// Hash and record files below `paths` under their absolute paths
+fn db_add(cli: &Cli, db: &ChecksumDb, paths: &[PathBuf]) -> Result<(), CliError> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = fs::canonicalize(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        if path.is_dir() {
+            files.extend(list_directory(&path, cli, None)?.files);
+        } else {
+            files.push(path);
+        }
+    }
+
+    let now = unix_now();
+    let mut had_errors = false;
+    for file in files {
+        if interrupted() {
+            break;
+        }
+        let recorded = hash_file(&file, cli.narrow, true).and_then(|result| {
+            let path = file.to_string_lossy().into_owned();
+            let added = db.get(&path)?.map_or(now, |existing| existing.added);
+            let units = result.units.unwrap_or_default();
+            db.upsert(&DbRecord {
+                size: result.filesize,
+                mtime_ns: FileMetadata::from_path(long_path(&file))?.mtime_ns,
+                iscc: result.iscc,
+                data_unit: units.first().cloned(),
+                instance_unit: units.get(1).cloned(),
+                datahash: result.datahash,
+                added,
+                last_verified: now,
+                path,
+            })
+        });
+        if let Err(e) = recorded {
+            eprintln!("isum: {}: {}", file.display(), e);
+            had_errors = true;
+        }
+    }
+
+    if had_errors {
+        Err(io::Error::other("Some files could not be processed").into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Re-hash records last verified before `before` and report them like a check run
+fn db_verify(
+    cli: &Cli,
+    db: &ChecksumDb,
+    before: i64,
+    limit: Option<usize>,
+    format: OutputFormat,
+) -> Result<(), CliError> {
+    let records = db.due(before, limit)?;
+    let mut reporter = CheckReporter::new(format, cli.color, false);
+    reporter.align(records.iter().map(|r| Path::new(&r.path)));
+
+    for record in records.iter().take_while(|_| !interrupted()) {
+        let path = Path::new(&record.path);
+        let mut result = verify_expected(path, &record.iscc);
+        if result.status == CheckStatus::Mismatch {
+            // Content changed: a legitimate edit usually changes size or mtime, bit rot does not
+            let mtime_ns = FileMetadata::from_path(long_path(path))
+                .ok()
+                .and_then(|m| m.mtime_ns);
+            let size = fs::metadata(long_path(path)).map(|m| m.len()).ok();
+            result = if size == Some(record.size) && mtime_ns == record.mtime_ns {
+                result.with_message("content changed but size and mtime did not (possible bit rot)")
+            } else {
+                result.with_message("modified since recorded (size or mtime changed)")
+            };
+        }
+        if result.status == CheckStatus::Ok {
+            db.mark_verified(&record.path, unix_now())?;
+        }
+        reporter.report(result);
+    }
+
+    reporter.finish()
+}
+
+/// Counter of `isum daemon` hash requests answered from the cache
+#[cfg(unix)]
+const DAEMON_CACHE_HITS: &str = "isum_daemon_cache_hits_total";
+
+/// A cached daemon result and the file state it was computed for
+#[cfg(unix)]
+struct CachedResult {
+    size: u64,
+    mtime_ns: Option<i64>,
+    result: IsccSumResult,
+}
+
+/// State shared by the daemon's connection threads
+#[cfg(unix)]
+struct DaemonState {
+    /// Results by canonical path and width (narrow)
+    cache: Mutex<HashMap<(String, bool), CachedResult>>,
+    index: Option<SimilarityIndex>,
+    stopping: AtomicBool,
+}
+
+#[cfg(unix)]
+impl DaemonState {
+    /// Hash a file, reusing the cached result while its size and mtime are unchanged
+    fn hash(&self, path: &str, narrow: bool) -> io::Result<(IsccSumResult, bool)> {
+        let path = fs::canonicalize(path)?;
+        let size = fs::metadata(&path)?.len();
+        let mtime_ns = FileMetadata::from_path(&path)?.mtime_ns;
+        let key = (path.to_string_lossy().into_owned(), narrow);
+        if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+            if cached.size == size && cached.mtime_ns == mtime_ns {
+                counter!(DAEMON_CACHE_HITS).increment(1);
+                return Ok((cached.result.clone(), true));
+            }
+        }
+        let result = hash_file(&path, narrow, false)?;
+        counter!(telemetry::FILES_HASHED).increment(1);
+        let cached = CachedResult {
+            size,
+            mtime_ns,
+            result: result.clone(),
+        };
+        self.cache.lock().unwrap().insert(key, cached);
+        Ok((result, false))
+    }
+
+    /// Answer a single request object
+    fn respond(&self, request: &Value) -> Value {
+        match request["op"].as_str() {
+            Some("hash") => {
+                let Some(path) = request["path"].as_str() else {
+                    return json!({"error": "missing 'path'"});
+                };
+                let narrow = request["narrow"].as_bool().unwrap_or(false);
+                match self.hash(path, narrow) {
+                    Ok((result, cached)) => json!({
+                        "path": path,
+                        "iscc": result.iscc,
+                        "datahash": result.datahash,
+                        "filesize": result.filesize,
+                        "cached": cached,
+                    }),
+                    Err(e) => {
+                        counter!(telemetry::ERRORS).increment(1);
+                        json!({"error": format!("{path}: {e}")})
+                    }
+                }
+            }
+            Some("query") => {
+                let Some(index) = &self.index else {
+                    return json!({"error": "no similarity index loaded (use --index)"});
+                };
+                let Some(key) = request["iscc"].as_str().and_then(index::data_code_key) else {
+                    return json!({"error": "'iscc' is not an ISCC-SUM code or Data-Code unit"});
+                };
+                let max_distance = request["max_distance"].as_u64().unwrap_or(8) as u32;
+                let matches: Vec<Value> = index
+                    .query(key, max_distance)
+                    .into_iter()
+                    .map(|(distance, path)| json!({"path": path, "distance": distance}))
+                    .collect();
+                json!({"matches": matches})
+            }
+            Some("stop") => {
+                self.stopping.store(true, Ordering::SeqCst);
+                json!({"ok": true})
+            }
+            _ => json!({"error": "unknown 'op' (expected hash, query or stop)"}),
+        }
+    }
+
+    /// Answer the requests of one connection, one JSON object per line each way
+    fn serve(&self, stream: UnixStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(request) => self.respond(&request),
+                Err(e) => json!({"error": format!("invalid request: {e}")}),
+            };
+            writeln!(writer, "{response}")?;
+            if self.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run an `isum daemon` action
+#[cfg(unix)]
+fn run_daemon(action: &DaemonCommand) -> Result<(), CliError> {
+    match action {
+        DaemonCommand::Serve {
+            socket,
+            index,
+            metrics,
+        } => daemon_serve(socket, index.as_deref(), metrics.as_deref()),
+        DaemonCommand::Hash {
+            socket,
+            files,
+            narrow,
+            format,
+        } => {
+            let mut client = DaemonClient::connect(socket)?;
+            if *format == OutputFormat::Csv {
+                println!("path,iscc,datahash,filesize");
+            }
+            let mut had_errors = false;
+            for file in files {
+                let path = fs::canonicalize(file).unwrap_or_else(|_| file.clone());
+                let response = client.request(&json!({
+                    "op": "hash",
+                    "path": path.to_string_lossy(),
+                    "narrow": narrow,
+                }))?;
+                if let Some(error) = response["error"].as_str() {
+                    eprintln!("isum: {error}");
+                    had_errors = true;
+                    continue;
+                }
+                let name = file.to_string_lossy();
+                let iscc = response["iscc"].as_str().unwrap_or_default();
+                match format {
+                    OutputFormat::Text => println!("{iscc} *{name}"),
+                    OutputFormat::Json => println!(
+                        "{}",
+                        json!({
+                            "path": name,
+                            "iscc": iscc,
+                            "datahash": response["datahash"],
+                            "filesize": response["filesize"],
+                        })
+                    ),
+                    OutputFormat::Csv => println!(
+                        "{},{iscc},{},{}",
+                        csv_field(&name),
+                        response["datahash"].as_str().unwrap_or_default(),
+                        response["filesize"]
+                    ),
+                }
+            }
+            if had_errors {
+                Err(io::Error::other("Some files could not be processed").into())
+            } else {
+                Ok(())
+            }
+        }
+        DaemonCommand::Query {
+            socket,
+            code,
+            max_distance,
+            format,
+        } => {
+            let response = DaemonClient::connect(socket)?.request(&json!({
+                "op": "query",
+                "iscc": code,
+                "max_distance": max_distance,
+            }))?;
+            if let Some(error) = response["error"].as_str() {
+                return Err(io::Error::other(error.to_string()).into());
+            }
+            if *format == OutputFormat::Csv {
+                println!("distance,path");
+            }
+            for entry in response["matches"].as_array().into_iter().flatten() {
+                let path = entry["path"].as_str().unwrap_or_default();
+                let distance = &entry["distance"];
+                match format {
+                    OutputFormat::Text => println!("{distance} {path}"),
+                    OutputFormat::Json => println!("{entry}"),
+                    OutputFormat::Csv => println!("{distance},{}", csv_field(path)),
+                }
+            }
+            Ok(())
+        }
+        DaemonCommand::Stop { socket } => {
+            DaemonClient::connect(socket)?.request(&json!({"op": "stop"}))?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn run_daemon(_action: &DaemonCommand) -> Result<(), CliError> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "isum daemon requires Unix domain sockets",
+    )
+    .into())
+}
+
+/// Listen on a Unix socket until a stop request or SIGINT/SIGTERM
+#[cfg(unix)]
+fn daemon_serve(
+    socket: &Path,
+    index_path: Option<&Path>,
+    metrics_addr: Option<&str>,
+) -> Result<(), CliError> {
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(CliError::usage(format!(
+                "{}: a daemon is already listening",
+                socket.display()
+            )));
+        }
+        // Left behind by a daemon that was killed
+        fs::remove_file(socket)?;
+    }
+    let index = index_path
+        .map(|path| {
+            SimilarityIndex::load(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+        })
+        .transpose()?;
+    let state = DaemonState {
+        cache: Mutex::new(HashMap::new()),
+        index,
+        stopping: AtomicBool::new(false),
+    };
+    if let Some(addr) = metrics_addr {
+        serve_metrics(addr)?;
+    }
+
+    let listener = UnixListener::bind(socket)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", socket.display(), e)))?;
+    // Poll so stop requests and signals are noticed between connections
+    listener.set_nonblocking(true)?;
+    install_signal_handler();
+    eprintln!("isum: daemon listening on {}", socket.display());
+
+    let served = thread::scope(|scope| -> io::Result<()> {
+        while !interrupted() && !state.stopping.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    let state = &state;
+                    scope.spawn(move || {
+                        if let Err(e) = state.serve(stream) {
+                            eprintln!("isum: daemon connection: {e}");
+                        }
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(20));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    });
+    let _ = fs::remove_file(socket);
+    Ok(served?)
+}
+
+/// Install the Prometheus recorder and answer `GET /metrics` on `addr` from a
+/// background thread
+#[cfg(unix)]
+fn serve_metrics(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)
+        .map_err(|e| io::Error::new(e.kind(), format!("--metrics {addr}: {e}")))?;
+    let handle = PrometheusBuilder::new()
+        .install_recorder()
+        .map_err(io::Error::other)?;
+    telemetry::describe();
+    describe_counter!(
+        DAEMON_CACHE_HITS,
+        Unit::Count,
+        "Daemon results served from the cache"
+    );
+    eprintln!("isum: metrics on http://{}/metrics", listener.local_addr()?);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = respond_metrics(stream, &handle) {
+                eprintln!("isum: metrics connection: {e}");
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Answer one HTTP request of the metrics endpoint
+#[cfg(unix)]
+fn respond_metrics(mut stream: TcpStream, handle: &PrometheusHandle) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers up to the blank line
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let (status, body) = match request_line.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+        ["GET", "/metrics"] => ("200 OK", handle.render()),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Connection to a running daemon
+#[cfg(unix)]
+struct DaemonClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+#[cfg(unix)]
+impl DaemonClient {
+    fn connect(socket: &Path) -> io::Result<Self> {
+        let stream = UnixStream::connect(socket).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "{}: {} (is isum daemon serve running?)",
+                    socket.display(),
+                    e
+                ),
+            )
+        })?;
+        Ok(DaemonClient {
+            writer: stream.try_clone()?,
+            reader: BufReader::new(stream),
+        })
+    }
+
+    /// Send one request and wait for its response
+    fn request(&mut self, request: &Value) -> io::Result<Value> {
+        writeln!(self.writer, "{request}")?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "daemon closed the connection",
+            ));
+        }
+        serde_json::from_str(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("daemon: {e}")))
+    }
+}
+
+/// Write a new key pair, refusing to overwrite existing key files
+fn run_keygen(secret: &Path, public: &Path) -> Result<(), CliError> {
+    if let Some(existing) = [secret, public].into_iter().find(|p| p.exists()) {
+        return Err(CliError::usage(format!(
+            "{}: already exists",
+            existing.display()
+        )));
+    }
+    let (secret_key, public_key) = manifest::generate_keypair()?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(secret)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", secret.display(), e)))?;
+    writeln!(file, "{secret_key}")?;
+    fs::write(public, format!("{public_key}\n"))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", public.display(), e)))?;
+    Ok(())
+}
+
+/// Sorted `/`-separated paths of the regular files below `dir`, skipping `exclude` at the top
+fn bag_files(dir: &Path, exclude: Option<&str>) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    let walker = WalkDir::new(long_path(dir))
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() != 1 || Some(e.file_name()) != exclude.map(OsStr::new));
+    for entry in walker {
+        let entry = entry.map_err(io::Error::from)?;
+        if entry.file_type().is_file() {
+            let relative = entry
+                .path()
+                .strip_prefix(long_path(dir))
+                .unwrap_or(entry.path());
+            files.push(
+                relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+            );
+        }
+    }
+    Ok(files)
+}
+
+/// Write a BagIt manifest of ISCC-SUM codes for `files` (relative to the bag)
+fn write_bag_manifest(cli: &Cli, bag: &Path, name: &str, files: &[String]) -> io::Result<()> {
+    let mut contents = String::new();
+    for file in files {
+        let path = bag.join(file);
+        let result = hash_file(&path, cli.narrow, false)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        contents.push_str(&bagit::format_manifest_line(&result.iscc, file));
+        contents.push('\n');
+    }
+    fs::write(bag.join(name), contents)
+}
+
+/// Bag a directory in place, or refresh the ISCC manifests of an existing bag
+fn bag_create(cli: &Cli, bag: &Path) -> Result<(), CliError> {
+    if !bag.is_dir() {
+        return Err(CliError::usage(format!(
+            "{}: not a directory",
+            bag.display()
+        )));
+    }
+    let payload = bag.join(bagit::PAYLOAD_DIR);
+    if !bag.join(bagit::DECLARATION_NAME).exists() {
+        // Move the contents into a staging directory first: they may include `data`
+        let staging = bag.join(".isum-bag-payload");
+        fs::create_dir(&staging)?;
+        for entry in fs::read_dir(bag)? {
+            let entry = entry?;
+            if entry.path() != staging {
+                fs::rename(entry.path(), staging.join(entry.file_name()))?;
+            }
+        }
+        fs::rename(&staging, &payload)?;
+        fs::write(bag.join(bagit::DECLARATION_NAME), bagit::DECLARATION)?;
+    } else if !payload.is_dir() {
+        return Err(CliError::usage(format!(
+            "{}: bag has no {}/ directory",
+            bag.display(),
+            bagit::PAYLOAD_DIR
+        )));
+    }
+
+    let payload_files: Vec<String> = bag_files(&payload, None)?
+        .into_iter()
+        .map(|file| format!("{}/{file}", bagit::PAYLOAD_DIR))
+        .collect();
+    write_bag_manifest(cli, bag, bagit::PAYLOAD_MANIFEST, &payload_files)?;
+
+    // Tag files are everything outside the payload except the tag manifests
+    let tag_files: Vec<String> = bag_files(bag, Some(bagit::PAYLOAD_DIR))?
+        .into_iter()
+        .filter(|file| {
+            !matches!(
+                bagit::manifest_algorithm(file),
+                Some((ManifestKind::Tag, _))
+            )
+        })
+        .collect();
+    write_bag_manifest(cli, bag, bagit::TAG_MANIFEST, &tag_files)?;
+
+    eprintln!(
+        "isum: {} payload file(s) in bag {}",
+        payload_files.len(),
+        bag.display()
+    );
+    Ok(())
+}
+
+/// Hex digest of a file with a conventional hash function
+fn file_hexdigest<D: Digest>(path: &Path) -> io::Result<String> {
+    let mut reader = open_file(path)?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => break,
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// BagIt manifest algorithms `isum bag validate` can verify
+const BAG_ALGORITHMS: [&str; 4] = ["iscc", "md5", "sha256", "sha512"];
+
+/// Verify a bag manifest entry; `None` if the algorithm is not supported
+fn verify_bag_entry(path: &Path, algorithm: &str, expected: &str) -> Option<CheckRecord> {
+    let digest = match algorithm {
+        "iscc" => return Some(verify_expected(path, expected)),
+        "md5" => file_hexdigest::<Md5>,
+        "sha256" => file_hexdigest::<Sha256>,
+        "sha512" => file_hexdigest::<Sha512>,
+        _ => return None,
+    };
+    if !path.exists() {
+        return Some(
+            CheckRecord::new(path, CheckStatus::Missing).with_message("No such file or directory"),
+        );
+    }
+    let mut record = match digest(path) {
+        Ok(actual) => {
+            let status = if actual.eq_ignore_ascii_case(expected) {
+                CheckStatus::Ok
+            } else {
+                CheckStatus::Mismatch
+            };
+            let mut record = CheckRecord::new(path, status);
+            record.actual = Some(actual);
+            record
+        }
+        Err(e) => CheckRecord::new(path, CheckStatus::Error).with_message(e.to_string()),
+    };
+    record.expected = Some(expected.to_string());
+    Some(record)
+}
+
+/// Verify every manifest of a bag and that each payload manifest lists all payload files
+fn bag_validate(cli: &Cli, bag: &Path, format: OutputFormat) -> Result<(), CliError> {
+    let mut reporter = CheckReporter::new(format, cli.color, false);
+    let declaration = bag.join(bagit::DECLARATION_NAME);
+    if !declaration.is_file() {
+        reporter
+            .report(CheckRecord::new(&declaration, CheckStatus::Missing).with_message("not a bag"));
+        return reporter.finish();
+    }
+
+    let mut manifests: Vec<(String, ManifestKind, String)> = fs::read_dir(bag)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let (kind, algorithm) = bagit::manifest_algorithm(&name)?;
+            let algorithm = algorithm.to_string();
+            Some((name, kind, algorithm))
+        })
+        .collect();
+    // Payload manifests first, as tag manifests cover them
+    manifests.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+
+    let payload_files = bag_files(&bag.join(bagit::PAYLOAD_DIR), None)?;
+    if !manifests
+        .iter()
+        .any(|(_, kind, _)| *kind == ManifestKind::Payload)
+    {
+        let record = CheckRecord::new(&bag.join(bagit::PAYLOAD_MANIFEST), CheckStatus::Missing)
+            .with_message("bag has no payload manifest");
+        reporter.report(record);
+    }
+
+    for (name, kind, algorithm) in &manifests {
+        let manifest_path = bag.join(name);
+        let contents = fs::read_to_string(&manifest_path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", manifest_path.display(), e)))?;
+        if !BAG_ALGORITHMS.contains(&algorithm.as_str()) {
+            eprintln!("isum: {name}: unsupported algorithm '{algorithm}', skipped");
+            continue;
+        }
+
+        let mut listed = HashSet::new();
+        let mut entries = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match bagit::parse_manifest_line(line) {
+                Some((checksum, path)) => {
+                    listed.insert(path.clone());
+                    entries.push((checksum, bag.join(path)));
+                }
+                None => reporter.report_format_error(&manifest_path, index + 1),
+            }
+        }
+        reporter.align(entries.iter().map(|(_, path)| path.as_path()));
+        for (checksum, path) in &entries {
+            if interrupted() {
+                break;
+            }
+            if let Some(record) = verify_bag_entry(path, algorithm, checksum) {
+                reporter.report(record);
+            }
+        }
+
+        if *kind == ManifestKind::Payload {
+            for file in &payload_files {
+                let file = format!("{}/{file}", bagit::PAYLOAD_DIR);
+                if !listed.contains(&file) {
+                    let record = CheckRecord::new(&bag.join(&file), CheckStatus::Missing)
+                        .with_message(format!("not listed in {name}"));
+                    reporter.report(record);
+                }
+            }
+        }
+    }
+
+    reporter.finish()
+}
+
+/// Run an `isum index` action
+fn run_index(cli: &Cli, action: &IndexCommand) -> Result<(), CliError> {
+    match action {
+        IndexCommand::Build {
+            index: index_path,
+            paths,
+        } => {
+            let mut files = Vec::new();
+            for path in paths {
+                let path = fs::canonicalize(path)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+                if path.is_dir() {
+                    files.extend(list_directory(&path, cli, None)?.files);
+                } else {
+                    files.push(path);
+                }
+            }
+
+            let mut similarity_index = SimilarityIndex::new();
+            let mut had_errors = false;
+            for file in files {
+                if interrupted() {
+                    break;
+                }
+                match hash_file(&file, cli.narrow, true) {
+                    Ok(result) => {
+                        let key = result
+                            .units
+                            .as_deref()
+                            .and_then(|units| units.first())
+                            .and_then(|unit| index::data_code_key(unit))
+                            .expect("hash_file returns a Data-Code unit");
+                        similarity_index.insert(key, file.to_string_lossy().into_owned());
+                    }
+                    Err(e) => {
+                        eprintln!("isum: {}: {}", file.display(), e);
+                        had_errors = true;
+                    }
+                }
+            }
+            similarity_index.save(index_path).map_err(|e| {
+                io::Error::new(e.kind(), format!("{}: {}", index_path.display(), e))
+            })?;
+            eprintln!("isum: {} file(s) indexed", similarity_index.len());
+
+            if had_errors {
+                Err(io::Error::other("Some files could not be processed").into())
+            } else {
+                Ok(())
+            }
+        }
+        IndexCommand::Query {
+            index: index_path,
+            code,
+            max_distance,
+            format,
+        } => {
+            let key = index::data_code_key(code).ok_or_else(|| {
+                CliError::usage(format!("{code}: not an ISCC-SUM code or Data-Code unit"))
+            })?;
+            let similarity_index = SimilarityIndex::load(index_path).map_err(|e| {
+                io::Error::new(e.kind(), format!("{}: {}", index_path.display(), e))
+            })?;
+
+            if *format == OutputFormat::Csv {
+                println!("distance,path");
+            }
+            for (distance, path) in similarity_index.query(key, *max_distance) {
+                match format {
+                    OutputFormat::Text => println!("{distance} {path}"),
+                    OutputFormat::Json => {
+                        println!("{}", json!({"path": path, "distance": distance}))
+                    }
+                    OutputFormat::Csv => println!("{distance},{}", csv_field(path)),
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Print offset, length, Data-Code feature (xxHash32) and BLAKE3 hash of each chunk
+fn run_chunks(path: &Path, format: OutputFormat) -> Result<(), CliError> {
+    let mut reader: Box<dyn Read> = if is_stdin(path) {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(open_file(path)?)
+    };
+    if format == OutputFormat::Csv {
+        println!("offset,length,feature,blake3");
+    }
+
+    cdc::for_each_data_chunk(&mut reader, |chunk, bytes| {
+        // The empty tail of an empty input is a MinHash feature, not a byte range
+        if chunk.length == 0 {
+            return;
+        }
+        let feature = format!("{:08x}", chunk.feature);
+        let hash = blake3::hash(bytes).to_hex();
+        match format {
+            OutputFormat::Text => println!("{} {} {feature} {hash}", chunk.offset, chunk.length),
+            OutputFormat::Json => println!(
+                "{}",
+                json!({
+                    "offset": chunk.offset,
+                    "length": chunk.length,
+                    "feature": feature,
+                    "blake3": hash.as_str(),
+                })
+            ),
+            OutputFormat::Csv => println!("{},{},{feature},{hash}", chunk.offset, chunk.length),
+        }
+    })
+    .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    Ok(())
+}
+
+fn run_mixed(codes: &[String], bits: u32, format: OutputFormat) -> Result<(), CliError> {
+    let result =
+        content_mixed::gen_mixed_code(codes, bits).map_err(|e| CliError::usage(e.to_string()))?;
+    match format {
+        OutputFormat::Text => println!("{}", result.iscc),
+        OutputFormat::Json => println!("{}", json!({"iscc": result.iscc, "parts": result.parts})),
+        OutputFormat::Csv => println!("iscc\n{}", result.iscc),
+    }
+    Ok(())
+}
+
+/// BLAKE3 root hash prefix named by an ISCC-SUM code, an Instance-Code or a datahash
+fn expected_root(code: &str) -> Option<Vec<u8>> {
+    if let Some((_, instance)) = split_sum_code(code) {
+        return Some(instance);
+    }
+    if let Some(code) = code.strip_prefix("ISCC:") {
+        let bytes = base32::decode(base32::Alphabet::Rfc4648 { padding: false }, code)?;
+        return (bytes.len() >= 10 && bytes[0] >> 4 == 0b0100).then(|| bytes[2..].to_vec());
+    }
+    // Multihash with the BLAKE3 prefix or a bare hex digest
+    let hex_digest = code
+        .strip_prefix("1e20")
+        .filter(|h| h.len() == 64)
+        .unwrap_or(code);
+    hex::decode(hex_digest)
+        .ok()
+        .filter(|digest| digest.len() == 32)
+}
+
+/// Verify a file or stdin against an outboard tree, failing at the first bad chunk
+fn run_verify_stream(path: &Path, expect: &str, tree: Option<&Path>) -> Result<(), CliError> {
+    let expected = expected_root(expect).ok_or_else(|| {
+        CliError::usage(format!(
+            "--expect {expect}: not an ISCC-SUM code, Instance-Code or BLAKE3 datahash"
+        ))
+    })?;
+    let tree = match tree {
+        Some(tree) => tree.to_path_buf(),
+        None if is_stdin(path) => {
+            return Err(CliError::usage("--outboard is required when reading stdin"))
+        }
+        None => outboard::outboard_path(path),
+    };
+    let mut tree_reader = BufReader::new(
+        File::open(&tree)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", tree.display(), e)))?,
+    );
+    let mut reader: Box<dyn Read> = if is_stdin(path) {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(open_file(path)?)
+    };
+
+    let name = path.display();
+    match outboard::verify(&mut reader, &mut tree_reader, &expected) {
+        Ok(len) => {
+            println!("{name}: OK ({len} bytes)");
+            Ok(())
+        }
+        Err(VerifyError::Io(e)) => Err(io::Error::new(e.kind(), format!("{name}: {e}")).into()),
+        Err(e) => {
+            println!("{name}: FAILED ({e})");
+            Err(CliError::Verification(format!(
+                "WARNING: {name} did NOT match its outboard"
+            )))
+        }
+    }
+}
+
+/// Run the embedded conformance vectors and report pass/fail for each
+fn run_selftest() -> Result<(), CliError> {
+    let report = conformance::selftest();
+    for outcome in &report.outcomes {
+        match &outcome.error {
+            None => println!("{}/{}: OK", outcome.function, outcome.name),
+            Some(message) => println!("{}/{}: FAILED ({message})", outcome.function, outcome.name),
+        }
+    }
+
+    let failed = report.outcomes.len() - report.passed();
+    println!(
+        "{} of {} conformance tests passed",
+        report.passed(),
+        report.outcomes.len()
+    );
+    if failed > 0 {
+        Err(CliError::Verification(format!(
+            "WARNING: {failed} conformance test(s) FAILED, do not trust this binary's output"
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Print every file and directory below the given directories that is skipped by ignore patterns
+fn run_list_ignored(cli: &Cli) -> Result<(), CliError> {
+    if cli.files.is_empty() {
+        return Err(CliError::usage(
+            "--list-ignored requires at least one directory",
+        ));
+    }
+
+    let exclude_set = cli_exclude_set(cli)?;
+    if cli.format == OutputFormat::Csv {
+        println!("path,pattern,source");
+    }
+
+    for path in cli.files.iter().filter(|p| p.is_dir()) {
+        let listing = if cli.no_recursive {
+            list_directory_flat(path, cli, exclude_set.as_ref())?
+        } else {
+            list_directory(path, cli, exclude_set.as_ref())?
+        };
+
+        for skipped in listing.skipped {
+            let path = output_path(&skipped.path, cli);
+            match cli.format {
+                OutputFormat::Text => {
+                    println!("{path}: {} ({})", skipped.pattern, skipped.source)
+                }
+                OutputFormat::Json => println!(
+                    "{}",
+                    json!({"path": path, "pattern": skipped.pattern, "source": skipped.source})
+                ),
+                OutputFormat::Csv => println!(
+                    "{},{},{}",
+                    csv_field(&path),
+                    csv_field(&skipped.pattern),
+                    csv_field(&skipped.source)
+                ),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the exclude glob set if patterns were provided
+fn cli_exclude_set(cli: &Cli) -> Result<Option<GlobSet>, CliError> {
+    if !cli.exclude.is_empty() {
+        build_exclude_set(&cli.exclude).map_err(|e| CliError::usage(e.to_string()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Process a single file and output its ISCC checksum
+fn process_file(path: &PathBuf, cli: &Cli, exclude_set: Option<&GlobSet>) -> io::Result<()> {
+    if is_stdin(path) {
+        if cli.dry_run {
+            emit_dry_run(path, cli);
+            return Ok(());
+        }
+        return process_stdin(cli);
+    }
+
+    let long = long_path(path);
+
+    // Check if file exists
+    if !long.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}: No such file or directory", path.display()),
+        ));
+    }
+
+    // Get metadata (follows symlinks by default, which matches Unix tool behavior)
+    let metadata = match long.metadata() {
+        Ok(m) => m,
+        Err(e) => {
+            // Handle permission denied and other metadata errors
+            if e.kind() == io::ErrorKind::PermissionDenied {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{}: Permission denied", path.display()),
+                ));
+            }
+            return Err(e);
+        }
+    };
+
+    // Handle special file types
+    if metadata.is_dir() {
+        // Process directory based on flags
+        if cli.no_recursive {
+            return process_directory_flat(path, cli, exclude_set);
+        } else {
+            // Recursive is the default behavior for directories
+            return process_directory(path, cli, exclude_set);
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        let file_type = metadata.file_type();
+
+        if (file_type.is_block_device() || file_type.is_char_device()) && !cli.allow_special {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: Is a device file (use --allow-special)", path.display()),
+            ));
+        }
+
+        if file_type.is_fifo() && !cli.allow_special {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: Is a named pipe (use --allow-special)", path.display()),
+            ));
+        }
+
+        if file_type.is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: Is a socket", path.display()),
+            ));
+        }
+    }
+
+    // Process as regular file
+    if cli.dry_run {
+        emit_dry_run(path, cli);
+        return Ok(());
+    }
+    if cli.warc && warc::is_warc_path(path) {
+        return process_warc(path, cli);
+    }
+    process_regular_file(path, cli).map(|_| ())
+}
+
+/// Emit one result per response record payload of a WARC archive, keyed by target URI
+fn process_warc(path: &Path, cli: &Cli) -> io::Result<()> {
+    let mut archive = warc::open_archive(path, open_file(path)?);
+    warc::for_each_response(&mut archive, |header, mut payload| {
+        let start = Instant::now();
+        let (result, extra) = digest_reader_extra(
+            &mut payload,
+            cli.narrow,
+            false,
+            &cli.hash_also,
+            cli.segments,
+        )?;
+        let uri = header
+            .target_uri
+            .as_deref()
+            .or(header.record_id.as_deref())
+            .unwrap_or_default();
+        emit_result(uri, &result, &extra, None, None, start.elapsed(), cli);
+        Ok(())
+    })
+    .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+/// True for the `-` argument, which stands for stdin
+fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Path as written to the output, relative to --root with `/` separators if given
+fn output_path(path: &Path, cli: &Cli) -> String {
+    let Some(root) = &cli.root else {
+        return path.to_string_lossy().into_owned();
+    };
+
+    // Resolve the parent only, so a symlinked file keeps its own name
+    let resolved = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            fs::canonicalize(parent).map(|p| p.join(name))
+        }
+        _ => fs::canonicalize(path),
+    };
+
+    match resolved
+        .ok()
+        .and_then(|p| p.strip_prefix(root).ok().map(Path::to_path_buf))
+    {
+        Some(relative) => relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        None => path.to_string_lossy().into_owned(),
+    }
+}
+
+/// Print a file that would be hashed (--dry-run)
+fn emit_dry_run(path: &Path, cli: &Cli) {
+    let path = output_path(path, cli);
+    match cli.format {
+        OutputFormat::Text => println!("{path}"),
+        OutputFormat::Json => println!("{}", json!({ "path": path })),
+        OutputFormat::Csv => println!("{}", csv_field(&path)),
+    }
+}
+
+/// Process stdin and output its ISCC checksum
+fn process_stdin(cli: &Cli) -> io::Result<()> {
+    let start = Instant::now();
+    let mut stdin = io::stdin();
+    let (result, extra) =
+        digest_reader_extra(&mut stdin, cli.narrow, false, &cli.hash_also, cli.segments)?;
+
+    // Output with '-' as filename for stdin
+    emit_result("-", &result, &extra, None, None, start.elapsed(), cli);
+
+    Ok(())
+}
+
+/// Print a generated checksum in the selected output format
+fn emit_result(
+    filename: &str,
+    result: &IsccSumResult,
+    extra: &[String],
+    metadata: Option<&FileMetadata>,
+    content_code: Option<&ContentCode>,
+    elapsed: Duration,
+    cli: &Cli,
+) {
+    let timing = cli.time.map(|_| Timing {
+        path: filename.to_string(),
+        filesize: result.filesize,
+        elapsed,
+    });
+
+    if cli.cbor.is_some() {
+        let mut record = json!({
+            "path": filename,
+            "iscc": result.iscc,
+            "datahash": result.datahash,
+            "filesize": result.filesize,
+        });
+        for (algorithm, digest) in cli.hash_also.iter().zip(extra) {
+            record[algorithm.name()] = json!(digest);
+        }
+        if let Some(content_code) = content_code {
+            content_code.add_to(&mut record);
+        }
+        if let Some(metadata) = metadata {
+            record["metadata"] = metadata.to_json();
+        }
+        CBOR_RECORDS.lock().unwrap().push(record);
+    }
+
+    if cli.hashdeep {
+        let mut row = vec![result.filesize.to_string()];
+        row.extend(extra.iter().cloned());
+        row.push(result.iscc.clone());
+        row.push(filename.to_string());
+        println!("{}", row.join(","));
+    } else {
+        match cli.format {
+            OutputFormat::Text => {
+                println!("{} *{}", result.iscc, filename);
+                for (algorithm, digest) in cli.hash_also.iter().zip(extra) {
+                    println!("  {}:{}", algorithm.name(), digest);
+                }
+                if let Some(content_code) = content_code {
+                    println!("  {}:{}", content_code.label(), content_code.iscc);
+                    if let Some(iscc_code) = &content_code.iscc_code {
+                        println!("  iscc-code:{iscc_code}");
+                    }
+                }
+                for segment in result.segments.iter().flatten() {
+                    println!(
+                        "  segment:{}+{}:{}",
+                        segment.offset, segment.size, segment.iscc
+                    );
+                }
+            }
+            OutputFormat::Json => {
+                let mut record = json!({
+                    "path": filename,
+                    "iscc": result.iscc,
+                    "datahash": result.datahash,
+                    "filesize": result.filesize,
+                });
+                for (algorithm, digest) in cli.hash_also.iter().zip(extra) {
+                    record[algorithm.name()] = json!(digest);
+                }
+                if let Some(content_code) = content_code {
+                    content_code.add_to(&mut record);
+                }
+                if let Some(metadata) = metadata {
+                    record["metadata"] = metadata.to_json();
+                }
+                if let Some(segments) = &result.segments {
+                    record["segments"] = segments
+                        .iter()
+                        .map(|s| {
+                            json!({
+                                "offset": s.offset,
+                                "size": s.size,
+                                "iscc": s.iscc,
+                                "datahash": s.datahash,
+                            })
+                        })
+                        .collect();
+                }
+                if let Some(url) = &cli.lookup {
+                    record["registry"] = registry_lookup(url, &result.iscc).unwrap_or_else(|e| {
+                        eprintln!("isum: {filename}: lookup failed: {e}");
+                        Value::Null
+                    });
+                }
+                if let Some(timing) = &timing {
+                    record["seconds"] = json!(timing.elapsed.as_secs_f64());
+                    record["bytes_per_second"] = json!(timing.bytes_per_second());
+                }
+                println!("{record}");
+            }
+            OutputFormat::Csv => {
+                let mut row = vec![
+                    csv_field(filename),
+                    result.iscc.clone(),
+                    result.datahash.clone(),
+                    result.filesize.to_string(),
+                ];
+                row.extend(extra.iter().cloned());
+                if let Some(timing) = &timing {
+                    row.push(format!("{:.6}", timing.elapsed.as_secs_f64()));
+                    row.push(timing.bytes_per_second().to_string());
+                }
+                println!("{}", row.join(","));
+            }
+        }
+    }
+
+    if let Some(timing) = timing {
+        TIMINGS.lock().unwrap().push(timing);
+    }
+}
+
+/// Print the hashdeep file header: format version, columns and the invocation
+fn print_hashdeep_header(cli: &Cli) {
+    let mut columns = vec!["size"];
+    columns.extend(cli.hash_also.iter().map(|a| a.name()));
+    columns.extend(["iscc", "filename"]);
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let command: Vec<String> = std::env::args().collect();
+    println!("%%%% HASHDEEP-1.0");
+    println!("%%%% {}", columns.join(","));
+    println!("## Invoked from: {}", cwd.display());
+    println!("## $ {}", command.join(" "));
+    println!("##");
+}
+
+/// Quote a CSV field if it contains separators, quotes or line breaks
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Files found during traversal and the files skipped by ignore rules
+#[derive(Default)]
+struct Listing {
+    files: Vec<PathBuf>,
+    skipped: Vec<SkippedPath>,
+}
+
+impl Listing {
+    /// Sort for deterministic output and apply --ads to the files
+    fn finish(mut self, cli: &Cli) -> Self {
+        self.files.sort();
+        self.files = apply_ads_policy(self.files, cli);
+        self.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        self
+    }
+}
+
+/// A file skipped by an --exclude pattern or an --ignore-file pattern
+struct SkippedPath {
+    path: PathBuf,
+    pattern: String,
+    /// `--exclude` or the path of the ignore file
+    source: String,
+}
+
+/// Files below a directory, streamed from a walk on a background thread so
+/// hashing starts while the rest of the tree is still being walked.
+///
+/// Iterating yields the files to hash in walk order; files (and directories)
+/// skipped by --ignore-file and --exclude patterns are collected on the way.
+struct DirectoryWalk<'a> {
+    dir_path: &'a Path,
+    /// Extended-length form of `dir_path` that is walked
+    root: PathBuf,
+    cli: &'a Cli,
+    exclude_set: Option<&'a GlobSet>,
+    handle: thread::JoinHandle<_core::Result<()>>,
+    events: Receiver<WalkEvent>,
+    skipped: Vec<SkippedPath>,
+}
+
+impl<'a> DirectoryWalk<'a> {
+    /// Start walking `dir_path`, only its direct children with `flat` and
+    /// otherwise down to --max-depth
+    fn start(
+        dir_path: &'a Path,
+        cli: &'a Cli,
+        exclude_set: Option<&'a GlobSet>,
+        flat: bool,
+    ) -> io::Result<Self> {
+        let names: Vec<&str> = cli.ignore_file.iter().map(String::as_str).collect();
+        // Global patterns seed the spec of the root directory
+        let global = match &cli.global_ignore {
+            Some(path) => Some(IgnoreSpec::from_file(path)?),
+            None => None,
+        };
+        // Unreadable directories are logged and skipped
+        let options = WalkOptions {
+            tolerate_errors: true,
+            cancel: Some(INTERRUPT.clone()),
+            symlinks: cli.symlinks,
+            max_depth: if flat { Some(0) } else { cli.max_depth },
+            case_insensitive: cli.ignore_case,
+            git: cli.git,
+            ..WalkOptions::default()
+        };
+        let root = long_path(dir_path);
+        let (handle, events) = treewalk_channel(root.clone(), &names, global.as_ref(), &options);
+        Ok(DirectoryWalk {
+            dir_path,
+            root,
+            cli,
+            exclude_set,
+            handle,
+            events,
+            skipped: Vec::new(),
+        })
+    }
+
+    /// Wait for the walk and return the skipped paths, or the error that
+    /// aborted the walk
+    fn finish(self) -> io::Result<Vec<SkippedPath>> {
+        let DirectoryWalk {
+            handle,
+            events,
+            skipped,
+            ..
+        } = self;
+        // Stops a walk the consumer gave up on
+        drop(events);
+        match handle.join() {
+            Ok(Ok(())) => Ok(skipped),
+            // An interrupted walk ends like the interrupted hashing
+            Ok(Err(_core::Error::Cancelled)) if interrupted() => Ok(skipped),
+            Ok(Err(e)) => Err(e.into()),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// A walked path relative to the given directory
+    fn relative<'p>(&self, path: &'p Path) -> &'p Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    /// Record a skipped path with the responsible pattern
+    fn skip(&mut self, path: &Path, pattern: String, source: String) {
+        let path = self.dir_path.join(self.relative(path));
+        self.skipped.push(SkippedPath {
+            path,
+            pattern,
+            source,
+        });
+    }
+}
+
+impl Iterator for DirectoryWalk<'_> {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        while let Ok(event) = self.events.recv() {
+            match event {
+                WalkEvent::File(entry) => {
+                    if skip_metadata_file(&entry.path, self.cli) {
+                        continue;
+                    }
+                    let relative_path = self.relative(&entry.path);
+                    let excluded = self
+                        .exclude_set
+                        .and_then(|set| set.matches(relative_path).first().copied());
+                    if let Some(index) = excluded {
+                        let pattern = self.cli.exclude[index].clone();
+                        self.skip(&entry.path, pattern, "--exclude".to_string());
+                    } else if passes_filters(entry.size, entry.modified, self.cli) {
+                        return Some(self.dir_path.join(relative_path));
+                    }
+                }
+                WalkEvent::Skipped(path, reason) => {
+                    // Report ignore files relative to the given path, like the skipped files
+                    let source = reason.source.map_or_else(
+                        || "--ignore-file".to_string(),
+                        |s| self.dir_path.join(self.relative(&s)).display().to_string(),
+                    );
+                    self.skip(&path, reason.pattern, source);
+                }
+                WalkEvent::Error(path, e) => {
+                    tracing::warn!(path = %path.display(), error = %e, "walk entry skipped");
+                }
+                WalkEvent::EnterDir(_) | WalkEvent::ExitDir(_) => {}
+            }
+        }
+        None
+    }
+}
+
+/// List regular files directly inside a directory (no recursion), sorted
+fn list_directory_flat(
+    dir_path: &Path,
+    cli: &Cli,
+    exclude_set: Option<&GlobSet>,
+) -> io::Result<Listing> {
+    let _span = tracing::info_span!("walk", dir = %dir_path.display()).entered();
+    collect_listing(DirectoryWalk::start(dir_path, cli, exclude_set, true)?, cli)
+}
+
+/// List regular files below a directory (recursively, honoring --max-depth), sorted
+fn list_directory(
+    dir_path: &Path,
+    cli: &Cli,
+    exclude_set: Option<&GlobSet>,
+) -> io::Result<Listing> {
+    let _span = tracing::info_span!("walk", dir = %dir_path.display()).entered();
+    collect_listing(
+        DirectoryWalk::start(dir_path, cli, exclude_set, false)?,
+        cli,
+    )
+}
+
+/// Drain a walk into a sorted listing
+fn collect_listing(mut walk: DirectoryWalk<'_>, cli: &Cli) -> io::Result<Listing> {
+    let files = walk.by_ref().collect();
+    let skipped = walk.finish()?;
+    Ok(Listing { files, skipped }.finish(cli))
+}
+
+/// Sidecar and manifest files (and manifest signatures) are not themselves hashed when writing or checking them
+fn skip_metadata_file(path: &Path, cli: &Cli) -> bool {
+    ((cli.sidecar || cli.check_sidecar) && sidecar::is_sidecar(path))
+        || (cli.outboard && outboard::is_outboard(path))
+        || ((cli.manifests || cli.check_manifests)
+            && path.file_name().is_some_and(|name| {
+                let name = name.to_string_lossy();
+                let name = name
+                    .strip_suffix(manifest::SIGNATURE_SUFFIX)
+                    .unwrap_or(&name);
+                name == MANIFEST_NAME || name == METADATA_MANIFEST_NAME
+            }))
+}
+
+/// Process a directory non-recursively (only direct children)
+fn process_directory_flat(
+    dir_path: &Path,
+    cli: &Cli,
+    exclude_set: Option<&GlobSet>,
+) -> io::Result<()> {
+    process_walk(DirectoryWalk::start(dir_path, cli, exclude_set, true)?, cli)
+}
+
+/// Process a directory recursively and output ISCC checksums for all files
+fn process_directory(dir_path: &Path, cli: &Cli, exclude_set: Option<&GlobSet>) -> io::Result<()> {
+    process_walk(
+        DirectoryWalk::start(dir_path, cli, exclude_set, false)?,
+        cli,
+    )
+}
+
+/// Hash the files of a directory walk in walk order while the walk goes on
+fn process_walk(mut walk: DirectoryWalk<'_>, cli: &Cli) -> io::Result<()> {
+    let _span = tracing::info_span!("walk", dir = %walk.dir_path.display()).entered();
+    let entries = walk.by_ref().flat_map(|path| ads_paths(path, cli));
+    let result = process_entries(entries, cli);
+    walk.finish()?;
+    result
+}
+
+/// Process files in the given order, continuing past per-file errors
+fn process_entries(entries: impl IntoIterator<Item = PathBuf>, cli: &Cli) -> io::Result<()> {
+    let signing_key = cli
+        .sign
+        .as_deref()
+        .map(manifest::read_signing_key)
+        .transpose()?;
+    let mut had_errors = false;
+    // Manifest lines grouped by the directory that contains the file
+    let mut manifests: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    let mut metadata_manifests: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+
+    for entry_path in entries {
+        if interrupted() {
+            break;
+        }
+        if cli.dry_run {
+            emit_dry_run(&entry_path, cli);
+            continue;
+        }
+        if cli.warc && warc::is_warc_path(&entry_path) {
+            if let Err(e) = process_warc(&entry_path, cli) {
+                eprintln!("isum: {e}");
+                had_errors = true;
+            }
+            continue;
+        }
+        // Process each file, but continue on errors
+        match process_regular_file(&entry_path, cli) {
+            Ok((result, metadata)) => {
+                if cli.manifests {
+                    if let (Some(parent), Some(name)) =
+                        (entry_path.parent(), entry_path.file_name())
+                    {
+                        let name = name.to_string_lossy();
+                        manifests
+                            .entry(parent.to_path_buf())
+                            .or_default()
+                            .push(manifest::format_line(&result.iscc, &name));
+                        if let Some(metadata) = metadata {
+                            metadata_manifests
+                                .entry(parent.to_path_buf())
+                                .or_default()
+                                .push(file_metadata::format_manifest_line(&name, &metadata));
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("isum: {}: {}", entry_path.display(), e);
+                had_errors = true;
+            }
+        }
+    }
+
+    let manifest_files = manifests
+        .into_iter()
+        .map(|(dir, lines)| (dir.join(MANIFEST_NAME), lines))
+        .chain(
+            metadata_manifests
+                .into_iter()
+                .map(|(dir, lines)| (dir.join(METADATA_MANIFEST_NAME), lines)),
+        );
+    for (manifest_path, lines) in manifest_files {
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        let written = fs::write(&manifest_path, &contents).and_then(|()| match &signing_key {
+            Some(key) => fs::write(
+                manifest::signature_path(&manifest_path),
+                format!("{}\n", manifest::sign(key, contents.as_bytes())),
+            ),
+            None => Ok(()),
+        });
+        if let Err(e) = written {
+            eprintln!("isum: {}: {}", manifest_path.display(), e);
+            had_errors = true;
+        }
+    }
+
+    // If we had any errors, return an error to indicate partial failure
+    if had_errors {
+        Err(io::Error::other("Some files could not be processed"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Process a regular file (extracted from process_file to avoid recursion)
+fn process_regular_file(
+    path: &PathBuf,
+    cli: &Cli,
+) -> io::Result<(IsccSumResult, Option<FileMetadata>)> {
+    let _span = tracing::info_span!("file", path = %path.display()).entered();
+    let start = Instant::now();
+    let mut reader = open_file(path)?;
+    let (result, extra) = if let Some(state) = &cli.state {
+        (digest_resumable(reader, path, state, cli)?, Vec::new())
+    } else if cli.outboard {
+        // Build the outboard tree in the same pass as the checksum
+        let len = reader.get_ref().metadata()?.len();
+        let out = File::create(outboard::outboard_path(path))?;
+        let mut tee = TeeReader {
+            inner: reader,
+            copy: OutboardEncoder::new(out, len)?,
+        };
+        let digests = digest_reader_extra(
+            &mut tee,
+            cli.narrow,
+            cli.sidecar,
+            &cli.hash_also,
+            cli.segments,
+        )?;
+        tee.copy.finalize().map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("{}: {e} (changed while reading?)", path.display()),
+            )
+        })?;
+        digests
+    } else {
+        digest_reader_extra(
+            &mut reader,
+            cli.narrow,
+            cli.sidecar,
+            &cli.hash_also,
+            cli.segments,
+        )?
+    };
+    let content_code = content_code(path, &result, cli)?;
+    let elapsed = start.elapsed();
+    let metadata = if cli.with_metadata {
+        Some(FileMetadata::from_path(long_path(path))?)
+    } else {
+        None
+    };
+
+    // Output the result in Unix checksum format
+    // Handle potentially invalid UTF-8 in filenames by using to_string_lossy
+    let filename = output_path(path, cli);
+    emit_result(
+        &filename,
+        &result,
+        &extra,
+        metadata.as_ref(),
+        content_code.as_ref(),
+        elapsed,
+        cli,
+    );
+
+    if cli.xattr {
+        write_xattr(path, &result.iscc)?;
+    }
+
+    if cli.sidecar {
+        let sidecar = Sidecar {
+            metadata: metadata.clone(),
+            ..Sidecar::from(&result)
+        };
+        sidecar.write(sidecar::sidecar_path(path))?;
+    }
+
+    Ok((result, metadata))
+}
+
+/// Content-Code of a file (--text-code) and the ISCC-CODE it forms, or the similarity
+/// hash of a video (--video-hash)
+struct ContentCode {
+    /// Kind of content: `text` or `video`
+    kind: &'static str,
+    /// Content-Code, or the hex digest of a video
+    iscc: String,
+    /// Composite of the Content-Code with the 64-bit Data-Code and Instance-Code
+    iscc_code: Option<String>,
+}
+
+impl ContentCode {
+    fn new(kind: &'static str, iscc: String, result: &IsccSumResult) -> io::Result<Self> {
+        let parse = |code: &str| code.parse::<IsccCode>().map_err(_core::Error::from);
+        let mut units = parse(&result.iscc)?.units();
+        units.push(parse(&iscc)?);
+        let iscc_code = iscc_codec::compose(&units, 64)
+            .map_err(_core::Error::from)?
+            .to_string();
+        Ok(ContentCode {
+            kind,
+            iscc,
+            iscc_code: Some(iscc_code),
+        })
+    }
+
+    /// Similarity hash of a video, which forms no ISCC-CODE
+    #[cfg(feature = "video")]
+    fn video_hash(hash: String) -> Self {
+        ContentCode {
+            kind: "video",
+            iscc: hash,
+            iscc_code: None,
+        }
+    }
+
+    /// Name of the code in text output (`text`, `video-hash`)
+    fn label(&self) -> String {
+        match self.iscc_code {
+            Some(_) => self.kind.to_string(),
+            None => format!("{}-hash", self.kind),
+        }
+    }
+
+    /// Add the codes to a JSON record
+    fn add_to(&self, record: &mut Value) {
+        match &self.iscc_code {
+            Some(iscc_code) => {
+                record[format!("{}_code", self.kind)] = json!(self.iscc);
+                record["iscc_code"] = json!(iscc_code);
+            }
+            None => record[format!("{}_hash", self.kind)] = json!(self.iscc),
+        }
+    }
+}
+
+/// File extensions --video-hash decodes
+#[cfg(feature = "video")]
+const VIDEO_EXTENSIONS: [&str; 10] = [
+    "avi", "flv", "m4v", "mkv", "mov", "mp4", "mpeg", "mpg", "webm", "wmv",
+];
+
+/// Content-Code or video hash of a file as requested by --text-code or --video-hash
+fn content_code(path: &Path, result: &IsccSumResult, cli: &Cli) -> io::Result<Option<ContentCode>> {
+    #[cfg(feature = "video")]
+    if cli.video_hash {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+        if extension.is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.as_str())) {
+            // Undecodable videos still get their checksum
+            match content_video::gen_video_hash_file(&long_path(path), 64) {
+                Ok(video) => return Ok(Some(ContentCode::video_hash(video.hash))),
+                Err(e) => eprintln!("isum: {}: no video hash: {e}", path.display()),
+            }
+        }
+    }
+    if cli.text_code {
+        let data = fs::read(long_path(path))?;
+        if let Some(text) = content_text::decode_text(&data) {
+            let text = content_text::gen_text_code(text, 64).map_err(io::Error::from)?;
+            return ContentCode::new("text", text.iscc, result).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// How often --state checkpoints a running hash
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Write a processor state atomically (via a temporary file)
+fn save_state(processor: &IsccSumProcessor, state_path: &Path) -> io::Result<()> {
+    let mut temp = state_path.as_os_str().to_os_string();
+    temp.push(".tmp");
+    fs::write(&temp, processor.serialize_state()?)?;
+    fs::rename(&temp, state_path)
+}
+
+/// Hash a file, resuming from and checkpointing to a --state file
+fn digest_resumable(
+    mut reader: BufReader<File>,
+    path: &Path,
+    state_path: &Path,
+    cli: &Cli,
+) -> io::Result<IsccSumResult> {
+    let context = |e: io::Error| io::Error::new(e.kind(), format!("{}: {e}", state_path.display()));
+    let len = reader.get_ref().metadata()?.len();
+    let options = IsccSumOptions::builder()
+        .wide(!cli.narrow)
+        .add_units(cli.sidecar)
+        .build()?;
+    let mut processor = match fs::read(state_path) {
+        Ok(state) => IsccSumProcessor::restore_state_with_options(&state, options)
+            .map_err(|e| context(e.into()))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => IsccSumProcessor::with_options(options),
+        Err(e) => return Err(context(e)),
+    };
+    if processor.filesize() > len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{}: state is at byte {} but {} has only {len} bytes",
+                state_path.display(),
+                processor.filesize(),
+                path.display()
+            ),
+        ));
+    }
+    reader.seek(SeekFrom::Start(processor.filesize()))?;
+
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    let chunk_size = RATE_LIMITER
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(BUFFER_SIZE, RateLimiter::chunk_size);
+    let mut last_checkpoint = Instant::now();
+    loop {
+        if interrupted() {
+            save_state(&processor, state_path).map_err(context)?;
+            eprintln!(
+                "isum: {}: stopped at byte {} of {len}, resume with --state {}",
+                path.display(),
+                processor.filesize(),
+                state_path.display()
+            );
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+        }
+        let bytes_read = match reader.read(&mut buffer[..chunk_size]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                // Keep the progress made before the read error
+                save_state(&processor, state_path).map_err(context)?;
+                return Err(e);
+            }
+        };
+        processor.update(&buffer[..bytes_read]);
+        throttle(bytes_read);
+        if last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+            save_state(&processor, state_path).map_err(context)?;
+            last_checkpoint = Instant::now();
+        }
+    }
+
+    let result = processor.result();
+    match fs::remove_file(state_path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(context(e)),
+        _ => Ok(result),
+    }
+}
+
+/// Reader that copies everything read from `inner` into `copy`
+struct TeeReader<R, W> {
+    inner: R,
+    copy: W,
+}
+
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.copy.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+/// Open and hash a regular file
+fn hash_file(path: &Path, narrow: bool, add_units: bool) -> io::Result<IsccSumResult> {
+    let mut reader = open_file(path)?;
+    digest_reader(&mut reader, narrow, add_units)
+}
+
+/// Hash a file with units of the given length in bits
+fn hash_file_bits(path: &Path, bits: u32) -> io::Result<IsccSumResult> {
+    let options = IsccSumOptions::builder().bits(bits).build()?;
+    digest_reader_options(&mut open_file(path)?, options)
+}
+
+/// Open a regular file for hashing
+fn open_file(path: &Path) -> io::Result<BufReader<File>> {
+    // Open the file with buffered reader for better I/O performance
+    tracing::debug!(path = %path.display(), "open");
+    let file = match File::open(long_path(path)) {
+        Ok(f) => f,
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "open failed");
+            // Handle permission denied specifically
+            if e.kind() == io::ErrorKind::PermissionDenied {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{}: Permission denied", path.display()),
+                ));
+            }
+            return Err(e);
+        }
+    };
+
+    // Use BufReader for better I/O performance
+    Ok(BufReader::with_capacity(BUFFER_SIZE, file))
+}
+
+/// Store an ISCC checksum in the extended attributes of a file
+#[cfg(unix)]
+fn write_xattr(path: &PathBuf, iscc: &str) -> io::Result<()> {
+    xattr::set(path, XATTR_NAME, iscc.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_xattr(_path: &PathBuf, _iscc: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extended attributes are not supported on this platform",
+    ))
+}
+
+/// Read the ISCC checksum stored in the extended attributes of a file
+#[cfg(unix)]
+fn read_xattr(path: &PathBuf) -> io::Result<Option<String>> {
+    match xattr::get(path, XATTR_NAME)? {
+        Some(value) => String::from_utf8(value)
+            .map(|s| Some(s.trim().to_string()))
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "malformed stored checksum")),
+        None => Ok(None),
+    }
+}
+
+#[cfg(not(unix))]
+fn read_xattr(_path: &PathBuf) -> io::Result<Option<String>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extended attributes are not supported on this platform",
+    ))
+}
+
+/// Outcome classes of a single verification
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CheckStatus {
+    Ok,
+    Mismatch,
+    Missing,
+    Malformed,
+    Error,
+}
+
+impl CheckStatus {
+    fn name(self) -> &'static str {
+        match self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Mismatch => "mismatch",
+            CheckStatus::Missing => "missing",
+            CheckStatus::Malformed => "malformed",
+            CheckStatus::Error => "error",
+        }
+    }
+}
+
+/// Result of verifying a single entry
+#[derive(Debug)]
+struct CheckRecord {
+    path: String,
+    status: CheckStatus,
+    expected: Option<String>,
+    actual: Option<String>,
+    message: Option<String>,
+    /// Verification wall time (--time)
+    timing: Option<Timing>,
+}
+
+impl CheckRecord {
+    fn new(path: &Path, status: CheckStatus) -> Self {
+        CheckRecord {
+            path: path.to_string_lossy().into_owned(),
+            status,
+            expected: None,
+            actual: None,
+            message: None,
+            timing: None,
+        }
+    }
+
+    fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
 }
 
-fn main() {
-    let cli = Cli::parse();
+/// Turn a verified entry into a failure if its metadata differs from the recorded metadata
+fn check_metadata(
+    mut record: CheckRecord,
+    path: &Path,
+    recorded: Option<&FileMetadata>,
+) -> CheckRecord {
+    if record.status != CheckStatus::Ok {
+        return record;
+    }
+    let Some(recorded) = recorded else {
+        record.status = CheckStatus::Missing;
+        return record.with_message("no metadata recorded");
+    };
+    match FileMetadata::from_path(long_path(path)) {
+        Ok(current) => {
+            let changes = current.differences(recorded);
+            if !changes.is_empty() {
+                record.status = CheckStatus::Mismatch;
+                record = record.with_message(format!("metadata changed: {}", changes.join(", ")));
+            }
+            record
+        }
+        Err(e) => {
+            record.status = CheckStatus::Error;
+            record.with_message(e.to_string())
+        }
+    }
+}
 
-    // Process the result and handle errors
-    if let Err(e) = run(cli) {
-        error_exit(&e.to_string());
+/// Run a verification and attach its wall time if --time is given
+fn timed_check(path: &Path, cli: &Cli, verify: impl FnOnce() -> CheckRecord) -> CheckRecord {
+    let start = Instant::now();
+    let mut record = verify();
+    if cli.time.is_some() && matches!(record.status, CheckStatus::Ok | CheckStatus::Mismatch) {
+        record.timing = Some(Timing {
+            path: record.path.clone(),
+            filesize: fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            elapsed: start.elapsed(),
+        });
     }
+    record
 }
 
-fn run(cli: Cli) -> io::Result<()> {
-    if cli.files.is_empty() {
-        // Process stdin
-        process_stdin(cli.narrow)?;
-    } else {
-        // Build the exclude glob set if patterns were provided
-        let exclude_set = if !cli.exclude.is_empty() {
-            build_exclude_set(&cli.exclude)?
-        } else {
-            None
-        };
+/// Prints verification records and keeps the tallies for the final summary
+struct CheckReporter {
+    format: OutputFormat,
+    counts: [usize; 5],
+    failed: usize,
+    errors: usize,
+    /// Improperly formatted manifest lines, which fail the check but are no files
+    malformed_lines: usize,
+    /// Colored statuses and summary (stderr is a terminal, or --color always)
+    color: bool,
+    /// Path column width for the current batch of entries
+    width: usize,
+    /// Include per-file timings in JSON/CSV output (--time)
+    timed: bool,
+}
 
-        // Process files
-        for file in &cli.files {
-            process_file(file, &cli, exclude_set.as_ref())?;
+impl CheckReporter {
+    fn new(format: OutputFormat, color: ColorMode, timed: bool) -> Self {
+        if format == OutputFormat::Csv && timed {
+            println!("path,status,expected,actual,message,seconds,bytes_per_second");
+        } else if format == OutputFormat::Csv {
+            println!("path,status,expected,actual,message");
+        }
+        CheckReporter {
+            format,
+            counts: [0; 5],
+            failed: 0,
+            errors: 0,
+            malformed_lines: 0,
+            color: color.enabled(&io::stderr()),
+            width: 0,
+            timed,
         }
     }
 
-    Ok(())
-}
+    /// Align the status column for the next batch of entries to the longest path
+    fn align<'a>(&mut self, paths: impl Iterator<Item = &'a Path>) {
+        self.width = paths
+            .map(|p| p.to_string_lossy().chars().count())
+            .max()
+            .unwrap_or(0);
+    }
 
-/// Process a single file and output its ISCC checksum
-fn process_file(path: &PathBuf, cli: &Cli, exclude_set: Option<&GlobSet>) -> io::Result<()> {
-    // Check if file exists
-    if !path.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("{}: No such file or directory", path.display()),
+    /// Format a `path: STATUS` line, padded to the path column and colored
+    fn status_line(&self, path: &str, status: &str, color: &str) -> String {
+        let label = format!("{path}:");
+        let status = paint(status, color, self.color);
+        format!("{label:<width$} {status}", width = self.width + 1)
+    }
+
+    /// Report a verified entry; read errors and all other non-`ok` entries count as failures
+    fn report(&mut self, record: CheckRecord) {
+        match record.status {
+            CheckStatus::Ok => {}
+            CheckStatus::Error => self.errors += 1,
+            _ => self.failed += 1,
+        }
+        self.emit(record);
+    }
+
+    /// Report an improperly formatted manifest line, which fails the check like a mismatch
+    fn report_format_error(&mut self, manifest_path: &Path, line_number: usize) {
+        self.malformed_lines += 1;
+        let record = CheckRecord::new(manifest_path, CheckStatus::Malformed).with_message(format!(
+            "{line_number}: improperly formatted ISCC checksum line"
         ));
+        self.emit(record);
     }
 
-    // Get metadata (follows symlinks by default, which matches Unix tool behavior)
-    let metadata = match path.metadata() {
-        Ok(m) => m,
-        Err(e) => {
-            // Handle permission denied and other metadata errors
-            if e.kind() == io::ErrorKind::PermissionDenied {
-                return Err(io::Error::new(
-                    io::ErrorKind::PermissionDenied,
-                    format!("{}: Permission denied", path.display()),
-                ));
+    fn emit(&mut self, record: CheckRecord) {
+        self.counts[record.status as usize] += 1;
+        match self.format {
+            OutputFormat::Text => match record.status {
+                CheckStatus::Ok => println!("{}", self.status_line(&record.path, "OK", GREEN)),
+                CheckStatus::Mismatch => {
+                    let status = match &record.message {
+                        Some(message) => format!("FAILED ({message})"),
+                        None => "FAILED".to_string(),
+                    };
+                    println!("{}", self.status_line(&record.path, &status, RED))
+                }
+                _ => {
+                    let message = record.message.as_deref().unwrap_or(record.status.name());
+                    let color = if record.status == CheckStatus::Malformed {
+                        YELLOW
+                    } else {
+                        RED
+                    };
+                    eprintln!(
+                        "isum: {}: {}",
+                        record.path,
+                        paint(message, color, self.color)
+                    );
+                }
+            },
+            OutputFormat::Json => {
+                let mut value = json!({
+                    "path": record.path,
+                    "status": record.status.name(),
+                    "expected": record.expected,
+                    "actual": record.actual,
+                    "message": record.message,
+                });
+                if self.timed {
+                    let timing = record.timing.as_ref();
+                    value["seconds"] = json!(timing.map(|t| t.elapsed.as_secs_f64()));
+                    value["bytes_per_second"] = json!(timing.map(Timing::bytes_per_second));
+                }
+                println!("{value}");
+            }
+            OutputFormat::Csv => {
+                let mut row = vec![
+                    csv_field(&record.path),
+                    record.status.name().to_string(),
+                    record.expected.unwrap_or_default(),
+                    record.actual.unwrap_or_default(),
+                    csv_field(record.message.as_deref().unwrap_or_default()),
+                ];
+                if self.timed {
+                    let timing = record.timing.as_ref();
+                    row.push(
+                        timing.map_or(String::new(), |t| format!("{:.6}", t.elapsed.as_secs_f64())),
+                    );
+                    row.push(timing.map_or(String::new(), |t| t.bytes_per_second().to_string()));
+                }
+                println!("{}", row.join(","));
             }
-            return Err(e);
         }
-    };
 
-    // Handle special file types
-    if metadata.is_dir() {
-        // Process directory based on flags
-        if cli.no_recursive {
-            return process_directory_flat(path, cli, exclude_set);
-        } else {
-            // Recursive is the default behavior for directories
-            return process_directory(path, cli, exclude_set);
+        if let Some(timing) = record.timing {
+            TIMINGS.lock().unwrap().push(timing);
         }
     }
 
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::FileTypeExt;
-        let file_type = metadata.file_type();
-
-        if file_type.is_block_device() || file_type.is_char_device() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("{}: Is a device file", path.display()),
-            ));
+    /// Print the summary and turn the tallies into the overall check result.
+    ///
+    /// Verification failures take precedence over read errors for the exit code.
+    fn finish(self) -> Result<(), CliError> {
+        let count = |status: CheckStatus| self.counts[status as usize];
+        match self.format {
+            OutputFormat::Text => {
+                let malformed = self.malformed_lines;
+                if malformed > 0 {
+                    eprintln!("isum: WARNING: {malformed} line(s) improperly formatted");
+                }
+                let total = self.counts.iter().sum::<usize>() - malformed;
+                let bad = self.failed + self.errors;
+                let summary = if bad > 0 {
+                    paint(&format!("{bad} of {total} files FAILED"), RED, self.color)
+                } else {
+                    paint(&format!("{total} files OK"), GREEN, self.color)
+                };
+                eprintln!("isum: {summary}");
+            }
+            OutputFormat::Json => {
+                let summary = json!({
+                    "summary": {
+                        "total": self.counts.iter().sum::<usize>(),
+                        "ok": count(CheckStatus::Ok),
+                        "mismatch": count(CheckStatus::Mismatch),
+                        "missing": count(CheckStatus::Missing),
+                        "malformed": count(CheckStatus::Malformed),
+                        "error": count(CheckStatus::Error),
+                        "success": self.failed == 0
+                            && self.malformed_lines == 0
+                            && self.errors == 0
+                            && !interrupted(),
+                        "interrupted": interrupted(),
+                    }
+                });
+                println!("{summary}");
+            }
+            OutputFormat::Csv => {}
         }
 
-        if file_type.is_fifo() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("{}: Is a named pipe", path.display()),
-            ));
+        if self.failed > 0 {
+            Err(CliError::Verification(format!(
+                "WARNING: {} file(s) did NOT match their stored checksum",
+                self.failed
+            )))
+        } else if self.malformed_lines > 0 {
+            Err(CliError::Verification(format!(
+                "{} manifest line(s) could not be checked",
+                self.malformed_lines
+            )))
+        } else if self.errors > 0 {
+            Err(CliError::Io(io::Error::other(format!(
+                "WARNING: {} file(s) could not be read",
+                self.errors
+            ))))
+        } else {
+            Ok(())
         }
+    }
+}
 
-        if file_type.is_socket() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("{}: Is a socket", path.display()),
-            ));
+/// Verify all given files (and directory contents) with the given verification function
+fn run_check(cli: &Cli, mode: &str, verify: fn(&PathBuf) -> CheckRecord) -> Result<(), CliError> {
+    if cli.files.is_empty() {
+        return Err(CliError::usage(format!(
+            "{mode} requires at least one file or directory"
+        )));
+    }
+
+    let exclude_set = cli_exclude_set(cli)?;
+    let mut reporter = CheckReporter::new(cli.format, cli.color, cli.time.is_some());
+
+    for path in &cli.files {
+        let entries = if path.is_dir() {
+            if cli.no_recursive {
+                list_directory_flat(path, cli, exclude_set.as_ref())?.files
+            } else {
+                list_directory(path, cli, exclude_set.as_ref())?.files
+            }
+        } else {
+            vec![path.clone()]
+        };
+
+        reporter.align(entries.iter().map(PathBuf::as_path));
+        for entry_path in entries.into_iter().take_while(|_| !interrupted()) {
+            let mut record = timed_check(&entry_path, cli, || verify(&entry_path));
+            // --verify-metadata conflicts with --check-xattr, so this is --check-sidecar
+            if cli.verify_metadata {
+                let recorded = Sidecar::read(sidecar::sidecar_path(&entry_path))
+                    .ok()
+                    .and_then(|sidecar| sidecar.metadata);
+                record = check_metadata(record, &entry_path, recorded.as_ref());
+            }
+            reporter.report(record);
         }
     }
 
-    // Process as regular file
-    process_regular_file(path, cli.narrow)
+    reporter.finish()
 }
 
-/// Process stdin and output its ISCC checksum
-fn process_stdin(narrow: bool) -> io::Result<()> {
-    let mut stdin = io::stdin();
-    let result = process_reader(&mut stdin, narrow)?;
+/// Find ISCC-SUMS manifests below the given paths and verify every listed file
+fn run_check_manifests(cli: &Cli) -> Result<(), CliError> {
+    if cli.files.is_empty() {
+        return Err(CliError::usage(
+            "--check-manifests requires at least one directory or manifest file",
+        ));
+    }
 
-    // Output with '-' as filename for stdin
-    println!("{} *-", result.iscc);
+    let verifying_key = cli
+        .verify_signature
+        .as_deref()
+        .map(manifest::read_verifying_key)
+        .transpose()?;
+    let mut reporter = CheckReporter::new(cli.format, cli.color, cli.time.is_some());
 
-    Ok(())
-}
+    for path in &cli.files {
+        let manifests: Vec<PathBuf> = if path.is_dir() {
+            let mut walker = WalkDir::new(path);
+            if cli.no_recursive {
+                walker = walker.max_depth(1);
+            } else if let Some(depth) = cli.max_depth {
+                walker = walker.max_depth(depth + 1);
+            }
+            let mut found: Vec<PathBuf> = walker
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file() && e.file_name() == MANIFEST_NAME)
+                .map(|e| e.into_path())
+                .collect();
+            found.sort();
+            found
+        } else {
+            vec![path.clone()]
+        };
 
-/// Process a directory non-recursively (only direct children)
-fn process_directory_flat(
-    dir_path: &PathBuf,
-    cli: &Cli,
-    exclude_set: Option<&GlobSet>,
-) -> io::Result<()> {
-    let mut entries = Vec::new();
-
-    // Read directory entries
-    for entry in std::fs::read_dir(dir_path)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        // Only process regular files
-        if entry.file_type()?.is_file() {
-            // Apply exclude patterns if any
-            if let Some(globset) = exclude_set {
-                let relative_path = path.strip_prefix(dir_path).unwrap_or(&path);
-                if globset.is_match(relative_path) {
+        for manifest_path in manifests {
+            if interrupted() {
+                break;
+            }
+            let contents = fs::read_to_string(&manifest_path).map_err(|e| {
+                io::Error::new(e.kind(), format!("{}: {}", manifest_path.display(), e))
+            })?;
+            let base = manifest_path.parent().unwrap_or(Path::new(""));
+            if let Some(key) = &verifying_key {
+                if let Some(record) = check_signature(&manifest_path, &contents, key) {
+                    reporter.report(record);
+                }
+            }
+
+            let lines: Vec<(usize, Option<manifest::ChecksumLine>)> = contents
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(index, line)| (index + 1, manifest::parse_line(line)))
+                .collect();
+            let paths: Vec<PathBuf> = lines
+                .iter()
+                .filter_map(|(_, entry)| entry.as_ref().map(|e| base.join(&e.path)))
+                .collect();
+            reporter.align(paths.iter().map(PathBuf::as_path));
+
+            let recorded = if cli.verify_metadata {
+                read_metadata_manifest(
+                    &base.join(METADATA_MANIFEST_NAME),
+                    verifying_key.as_ref(),
+                    &mut reporter,
+                )?
+            } else {
+                HashMap::new()
+            };
+
+            for (line_number, entry) in lines {
+                if interrupted() {
+                    break;
+                }
+                let Some(entry) = entry else {
+                    reporter.report_format_error(&manifest_path, line_number);
                     continue;
+                };
+                let entry_path = base.join(&entry.path);
+                let mut record = timed_check(&entry_path, cli, || {
+                    verify_expected(&entry_path, &entry.iscc)
+                });
+                if cli.verify_metadata {
+                    record = check_metadata(record, &entry_path, recorded.get(&entry.path));
                 }
+                reporter.report(record);
             }
-            entries.push(path);
         }
     }
 
-    // Sort entries for deterministic output
-    entries.sort();
+    reporter.finish()
+}
 
-    let mut had_errors = false;
+/// Check the detached signature of a manifest; `None` if it is valid
+fn check_signature(
+    manifest_path: &Path,
+    contents: &str,
+    key: &VerifyingKey,
+) -> Option<CheckRecord> {
+    let signature_path = manifest::signature_path(manifest_path);
+    let record = match fs::read_to_string(&signature_path) {
+        Ok(signature) => match manifest::verify_signature(key, contents.as_bytes(), &signature) {
+            Ok(()) => return None,
+            Err(e) => {
+                CheckRecord::new(manifest_path, CheckStatus::Mismatch).with_message(e.to_string())
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            CheckRecord::new(manifest_path, CheckStatus::Missing).with_message(format!(
+                "{}: No such signature file",
+                signature_path.display()
+            ))
+        }
+        Err(e) => CheckRecord::new(manifest_path, CheckStatus::Error).with_message(format!(
+            "{}: {}",
+            signature_path.display(),
+            e
+        )),
+    };
+    Some(record)
+}
 
-    for entry_path in entries {
-        // Process each file, but continue on errors
-        if let Err(e) = process_regular_file(&entry_path, cli.narrow) {
-            eprintln!("isum: {}: {}", entry_path.display(), e);
-            had_errors = true;
+/// Read an ISCC-META file into a map from manifest path to metadata (empty if absent),
+/// checking its signature if a public key is given
+fn read_metadata_manifest(
+    path: &Path,
+    verifying_key: Option<&VerifyingKey>,
+    reporter: &mut CheckReporter,
+) -> io::Result<HashMap<String, FileMetadata>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => {
+            return Err(io::Error::new(
+                e.kind(),
+                format!("{}: {}", path.display(), e),
+            ))
         }
+    };
+
+    if let Some(record) = verifying_key.and_then(|key| check_signature(path, &contents, key)) {
+        reporter.report(record);
     }
 
-    // If we had any errors, return an error to indicate partial failure
-    if had_errors {
-        Err(io::Error::other("Some files could not be processed"))
-    } else {
-        Ok(())
+    let mut recorded = HashMap::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match file_metadata::parse_manifest_line(line) {
+            Ok((name, metadata)) => {
+                recorded.insert(name, metadata);
+            }
+            Err(_) => reporter.report_format_error(path, index + 1),
+        }
     }
+    Ok(recorded)
 }
 
-/// Process a directory recursively and output ISCC checksums for all files
-fn process_directory(
-    dir_path: &PathBuf,
-    cli: &Cli,
-    exclude_set: Option<&GlobSet>,
-) -> io::Result<()> {
-    let mut walker = WalkDir::new(dir_path);
-
-    // Apply max_depth if specified
-    // Note: WalkDir considers the root as depth 0, so we add 1 to align with user expectations
-    // User's max_depth 0 = only files in root dir (WalkDir max_depth 1)
-    // User's max_depth 1 = files in root + immediate subdirs (WalkDir max_depth 2)
-    if let Some(depth) = cli.max_depth {
-        walker = walker.max_depth(depth + 1);
+/// Recompute a file's checksum at the width of the expected code and compare
+fn verify_expected(path: &Path, expected: &str) -> CheckRecord {
+    if !path.exists() {
+        return CheckRecord::new(path, CheckStatus::Missing)
+            .with_message("No such file or directory");
     }
 
-    let mut entries: Vec<_> = walker
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .filter(|e| {
-            // Apply exclude patterns if any
-            if let Some(globset) = exclude_set {
-                let path = e.path();
-                // Get relative path from the starting directory
-                let relative_path = path.strip_prefix(dir_path).unwrap_or(path);
-                !globset.is_match(relative_path)
-            } else {
-                true
-            }
-        })
-        .map(|e| e.path().to_path_buf())
-        .collect();
-
-    // Sort entries for deterministic output
-    entries.sort();
+    let Some(bits) = sum_unit_bits(expected) else {
+        let mut record = CheckRecord::new(path, CheckStatus::Malformed)
+            .with_message(format!("not an ISCC-SUM: {expected}"));
+        record.expected = Some(expected.to_string());
+        return record;
+    };
+    let mut record = match hash_file_bits(path, bits) {
+        Ok(actual) if actual.iscc == expected => {
+            let mut record = CheckRecord::new(path, CheckStatus::Ok);
+            record.actual = Some(actual.iscc);
+            record
+        }
+        Ok(actual) => {
+            let mut record = CheckRecord::new(path, CheckStatus::Mismatch);
+            record.actual = Some(actual.iscc);
+            record
+        }
+        Err(e) => CheckRecord::new(path, CheckStatus::Error).with_message(e.to_string()),
+    };
+    record.expected = Some(expected.to_string());
+    record
+}
 
-    let mut had_errors = false;
+/// Unit length in bits of an ISCC-SUM (64 to 256), the width to recompute it at
+fn sum_unit_bits(iscc: &str) -> Option<u32> {
+    let code: IsccCode = iscc.parse().ok()?;
+    let is_sum = code.maintype() == MainType::Iscc
+        && matches!(
+            code.subtype(),
+            iscc_codec::SUBTYPE_SUM | iscc_codec::SUBTYPE_WIDE
+        );
+    is_sum.then_some(code.bits() as u32 / 2)
+}
 
-    for entry_path in entries {
-        // Process each file, but continue on errors
-        if let Err(e) = process_regular_file(&entry_path, cli.narrow) {
-            eprintln!("isum: {}: {}", entry_path.display(), e);
-            had_errors = true;
+/// Recompute a file's checksum and compare it against its stored extended attribute
+fn verify_xattr(path: &PathBuf) -> CheckRecord {
+    match read_xattr(path) {
+        Ok(Some(expected)) => verify_expected(path, &expected),
+        Ok(None) => CheckRecord::new(path, CheckStatus::Missing)
+            .with_message(format!("no {XATTR_NAME} attribute")),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            CheckRecord::new(path, CheckStatus::Malformed).with_message(e.to_string())
         }
-    }
-
-    // If we had any errors, return an error to indicate partial failure
-    if had_errors {
-        Err(io::Error::other("Some files could not be processed"))
-    } else {
-        Ok(())
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            CheckRecord::new(path, CheckStatus::Missing).with_message("No such file or directory")
+        }
+        Err(e) => CheckRecord::new(path, CheckStatus::Error).with_message(e.to_string()),
     }
 }
 
-/// Process a regular file (extracted from process_file to avoid recursion)
-fn process_regular_file(path: &PathBuf, narrow: bool) -> io::Result<()> {
-    // Open the file with buffered reader for better I/O performance
-    let file = match File::open(path) {
-        Ok(f) => f,
-        Err(e) => {
-            // Handle permission denied specifically
-            if e.kind() == io::ErrorKind::PermissionDenied {
-                return Err(io::Error::new(
-                    io::ErrorKind::PermissionDenied,
-                    format!("{}: Permission denied", path.display()),
-                ));
-            }
-            return Err(e);
+/// Recompute a file's checksum and compare it against its sidecar file
+fn verify_sidecar(path: &PathBuf) -> CheckRecord {
+    let sidecar_path = sidecar::sidecar_path(path);
+    let expected = match Sidecar::read(&sidecar_path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return CheckRecord::new(path, CheckStatus::Missing)
+                .with_message(format!("{}: No such sidecar file", sidecar_path.display()));
         }
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            return CheckRecord::new(path, CheckStatus::Malformed).with_message(format!(
+                "{}: {}",
+                sidecar_path.display(),
+                e
+            ));
+        }
+        Err(e) => return CheckRecord::new(path, CheckStatus::Error).with_message(e.to_string()),
     };
 
-    // Use BufReader for better I/O performance
-    let mut reader = BufReader::with_capacity(BUFFER_SIZE, file);
+    let Some(bits) = sum_unit_bits(&expected.iscc) else {
+        return CheckRecord::new(path, CheckStatus::Malformed).with_message(format!(
+            "{}: not an ISCC-SUM: {}",
+            sidecar_path.display(),
+            expected.iscc
+        ));
+    };
+    let mut record = match hash_file_bits(path, bits) {
+        Ok(actual) => {
+            let matches = actual.iscc == expected.iscc
+                && actual.filesize == expected.filesize
+                && actual.datahash == expected.datahash;
+            let status = if matches {
+                CheckStatus::Ok
+            } else {
+                CheckStatus::Mismatch
+            };
+            let mut record = CheckRecord::new(path, status);
+            record.actual = Some(actual.iscc);
+            record
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            CheckRecord::new(path, CheckStatus::Missing).with_message("No such file or directory")
+        }
+        Err(e) => CheckRecord::new(path, CheckStatus::Error).with_message(e.to_string()),
+    };
+    record.expected = Some(expected.iscc);
+    record
+}
 
-    // Process the file and get the result
-    let result = process_reader(&mut reader, narrow)?;
+/// Process any reader (file or stdin) and return the ISCC result
+#[cfg(test)]
+fn process_reader<R: Read>(reader: &mut R, narrow: bool) -> io::Result<IsccSumResult> {
+    digest_reader(reader, narrow, false)
+}
 
-    // Output the result in Unix checksum format
-    // Handle potentially invalid UTF-8 in filenames by using to_string_lossy
-    let filename = path.to_string_lossy();
-    println!("{} *{}", result.iscc, filename);
+/// Process any reader, optionally including the full-length units in the result
+fn digest_reader<R: Read>(
+    reader: &mut R,
+    narrow: bool,
+    add_units: bool,
+) -> io::Result<IsccSumResult> {
+    digest_reader_extra(reader, narrow, add_units, &[], None).map(|(result, _)| result)
+}
 
-    Ok(())
+/// Process any reader, computing additional conventional digests and segment
+/// codes in the same pass
+fn digest_reader_extra<R: Read>(
+    reader: &mut R,
+    narrow: bool,
+    add_units: bool,
+    hash_also: &[HashAlgorithm],
+    segment_size: Option<u64>,
+) -> io::Result<(IsccSumResult, Vec<String>)> {
+    // narrow=true means 64-bit (standard), narrow=false means 128-bit (wide)
+    let mut builder = IsccSumOptions::builder()
+        .wide(!narrow)
+        .add_units(add_units)
+        .hashes(hash_also.iter().copied());
+    if let Some(size) = segment_size {
+        builder = builder.segment_size(size);
+    }
+    let result = digest_reader_options(reader, builder.build()?)?;
+    let digests = hash_also
+        .iter()
+        .map(|algorithm| result.hashes[algorithm.name()].clone())
+        .collect();
+
+    Ok((result, digests))
 }
 
-/// Process any reader (file or stdin) and return the ISCC result
-fn process_reader<R: Read>(reader: &mut R, narrow: bool) -> io::Result<IsccSumResult> {
-    let mut processor = IsccSumProcessor::new();
+/// Process any reader with the given options
+fn digest_reader_options<R: Read>(
+    reader: &mut R,
+    options: IsccSumOptions,
+) -> io::Result<IsccSumResult> {
+    let mut processor = IsccSumProcessor::with_options(options);
     // Use vec! to allocate buffer on heap
     let mut buffer = vec![0u8; BUFFER_SIZE];
+    // Smaller reads when throttled so the pauses stay short
+    let chunk_size = RATE_LIMITER
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(BUFFER_SIZE, RateLimiter::chunk_size);
 
     loop {
-        let bytes_read = reader.read(&mut buffer)?;
+        let bytes_read = reader.read(&mut buffer[..chunk_size])?;
         if bytes_read == 0 {
             break;
         }
         processor.update(&buffer[..bytes_read]);
+        throttle(bytes_read);
     }
 
-    // Get the result
-    // narrow=true means 64-bit (standard), narrow=false means 128-bit (wide)
-    // So we need to invert the narrow flag for the wide parameter
-    let wide = !narrow;
-    let result = processor.result(wide, false); // Don't include units in CLI output
-
-    Ok(result)
+    Ok(processor.result())
 }
 
 #[cfg(test)]
@@ -495,30 +5021,179 @@ mod tests {
         assert_eq!(result1.iscc, result2.iscc);
     }
 
+    /// CLI configuration as parsed from a bare `isum` invocation
+    pub(super) fn default_cli() -> Cli {
+        Cli::parse_from(["isum"])
+    }
+
     #[test]
     fn test_cli_narrow_flag() {
         // Test CLI parsing of narrow flag
         let cli = Cli {
-            files: vec![],
             narrow: true,
-            recursive: false,
-            no_recursive: false,
-            exclude: vec![],
-            max_depth: None,
+            ..default_cli()
         };
         assert!(cli.narrow);
 
-        let cli = Cli {
-            files: vec![],
-            narrow: false,
-            recursive: false,
-            no_recursive: false,
-            exclude: vec![],
-            max_depth: None,
-        };
+        let cli = default_cli();
         assert!(!cli.narrow);
     }
 
+    #[test]
+    fn test_hash_also_digests() {
+        let mut cursor = Cursor::new(b"abc");
+        let algorithms = [
+            HashAlgorithm::Md5,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Sha1,
+        ];
+        let (result, extra) =
+            digest_reader_extra(&mut cursor, false, false, &algorithms, None).unwrap();
+
+        assert_eq!(extra[0], "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            extra[1],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            extra[2],
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        assert_eq!(extra[3], "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // The ISCC itself is unaffected by the additional digests
+        let mut cursor = Cursor::new(b"abc");
+        assert_eq!(
+            process_reader(&mut cursor, false).unwrap().iscc,
+            result.iscc
+        );
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain.txt"), "plain.txt");
+        assert_eq!(csv_field("a,b.txt"), "\"a,b.txt\"");
+        assert_eq!(csv_field("say \"hi\".txt"), "\"say \"\"hi\"\".txt\"");
+    }
+
+    #[test]
+    fn test_expand_response_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let response = temp_dir.path().join("args.txt");
+        std::fs::write(&response, "--narrow\r\n\nfile one.txt\n@literal.txt\n").unwrap();
+
+        let args = vec![
+            OsString::from("isum"),
+            OsString::from(format!("@{}", response.display())),
+            OsString::from("other.txt"),
+            OsString::from("@"),
+        ];
+        let expanded = expand_response_files(args).unwrap();
+
+        assert_eq!(
+            expanded,
+            vec![
+                "isum",
+                "--narrow",
+                "file one.txt",
+                "@literal.txt",
+                "other.txt",
+                "@"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_response_files_missing() {
+        let args = vec![OsString::from("isum"), OsString::from("@/no/such/args.txt")];
+        let err = expand_response_files(args).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with("@/no/such/args.txt"));
+    }
+
+    #[test]
+    fn test_expand_wildcards() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for name in ["b.bin", "a.bin", "c.txt"] {
+            fs::write(temp_dir.path().join(name), b"data").unwrap();
+        }
+
+        let pattern = temp_dir.path().join("*.bin");
+        let missing = temp_dir.path().join("*.none");
+        let plain = temp_dir.path().join("c.txt");
+        let expanded = expand_wildcards(vec![pattern, missing.clone(), plain.clone()]);
+        assert_eq!(
+            expanded,
+            vec![
+                temp_dir.path().join("a.bin"),
+                temp_dir.path().join("b.bin"),
+                missing,
+                plain
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unit_distance() {
+        let unit = |maintype, digest: &[u8]| {
+            IsccCode::new(maintype, 0, 0, 7, digest.to_vec())
+                .unwrap()
+                .to_string()
+        };
+        let data = |digest: &[u8]| unit(MainType::Data, digest);
+        let a = data(&[0u8; 32]);
+        let mut digest = [0u8; 32];
+        digest[0] = 0b1011;
+        digest[31] = 0x80;
+        assert_eq!(unit_distance(&a, &a), Some(0));
+        assert_eq!(unit_distance(&a, &data(&digest)), Some(4));
+        // Units of different types or lengths are not comparable
+        assert_eq!(
+            unit_distance(&a, &unit(MainType::Instance, &[0u8; 32])),
+            None
+        );
+        assert_eq!(unit_distance(&a, &data(&[0u8; 32])[..20]), None);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(1000);
+        let delay = limiter.consume(500);
+        assert!(delay > Duration::from_millis(400) && delay <= Duration::from_millis(500));
+        let delay = limiter.consume(1500);
+        assert!(delay > Duration::from_millis(1900) && delay <= Duration::from_millis(2000));
+        assert_eq!(limiter.chunk_size(), 4096);
+        assert_eq!(RateLimiter::new(1 << 40).chunk_size(), BUFFER_SIZE);
+        assert!(parse_rate("0").is_err());
+        assert_eq!(parse_rate("50M"), Ok(50 << 20));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64K"), Ok(64 * 1024));
+        assert_eq!(parse_size("1.5M"), Ok(1024 * 1024 * 3 / 2));
+        assert_eq!(parse_size("2gib"), Ok(2 << 30));
+        assert!(parse_size("12X").is_err());
+        assert!(parse_size("M").is_err());
+    }
+
+    #[test]
+    fn test_parse_utc_timestamp() {
+        let secs = |t: SystemTime| t.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        assert_eq!(secs(parse_utc_timestamp("1970-01-01").unwrap()), 0);
+        assert_eq!(
+            secs(parse_utc_timestamp("2024-02-29T12:30:00Z").unwrap()),
+            1_709_209_800
+        );
+        assert_eq!(
+            secs(parse_utc_timestamp("2000-03-01 00:00").unwrap()),
+            951_868_800
+        );
+        assert!(parse_utc_timestamp("2024-13-01").is_none());
+        assert!(parse_utc_timestamp("yesterday").is_none());
+    }
+
     #[test]
     fn test_empty_file_handling() {
         // Test that empty files are handled correctly
@@ -555,6 +5230,7 @@ mod tests {
 
 #[cfg(test)]
 mod directory_tests {
+    use super::tests::default_cli;
     use super::*;
     use std::fs;
     use tempfile::TempDir;
@@ -577,15 +5253,8 @@ mod directory_tests {
     #[test]
     fn test_process_directory_basic() {
         let temp_dir = create_test_directory();
-        let cli = Cli {
-            files: vec![],
-            narrow: false,
-            recursive: false,
-            no_recursive: false,
-            exclude: vec![],
-            max_depth: None,
-        };
-        let result = process_directory(&temp_dir.path().to_path_buf(), &cli, None);
+        let cli = default_cli();
+        let result = process_directory(temp_dir.path(), &cli, None);
 
         // Should succeed for directory with readable files
         assert!(result.is_ok());
@@ -594,15 +5263,8 @@ mod directory_tests {
     #[test]
     fn test_process_directory_empty() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
-        let cli = Cli {
-            files: vec![],
-            narrow: false,
-            recursive: false,
-            no_recursive: false,
-            exclude: vec![],
-            max_depth: None,
-        };
-        let result = process_directory(&temp_dir.path().to_path_buf(), &cli, None);
+        let cli = default_cli();
+        let result = process_directory(temp_dir.path(), &cli, None);
 
         // Empty directory should succeed (no files to process)
         assert!(result.is_ok());
@@ -624,15 +5286,8 @@ mod directory_tests {
             fs::set_permissions(&unreadable_file, perms).unwrap();
         }
 
-        let cli = Cli {
-            files: vec![],
-            narrow: false,
-            recursive: false,
-            no_recursive: false,
-            exclude: vec![],
-            max_depth: None,
-        };
-        let result = process_directory(&temp_dir.path().to_path_buf(), &cli, None);
+        let cli = default_cli();
+        let result = process_directory(temp_dir.path(), &cli, None);
 
         #[cfg(unix)]
         {
@@ -660,14 +5315,14 @@ mod directory_tests {
         let test_file = temp_dir.path().join("test.txt");
         fs::write(&test_file, b"Test content").unwrap();
 
-        let result = process_regular_file(&test_file, false);
+        let result = process_regular_file(&test_file, &default_cli());
         assert!(result.is_ok());
     }
 
     #[test]
     fn test_process_regular_file_not_found() {
         let non_existent = PathBuf::from("/definitely/does/not/exist/file.txt");
-        let result = process_regular_file(&non_existent, false);
+        let result = process_regular_file(&non_existent, &default_cli());
 
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
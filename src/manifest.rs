@@ -0,0 +1,221 @@
+//! Checksum manifest line handling.
+//!
+//! Manifests use the same line formats as `isum` / `iscc-sum` output:
+//! the default `ISCC:<code> *<path>` format and the BSD-style
+//! `ISCC-SUM (<path>) = ISCC:<code>` format.
+//!
+//! Manifests can be signed with Ed25519: the signature of the exact manifest
+//! bytes is stored hex-encoded in a detached `<manifest>.sig` file. Keys are
+//! hex-encoded 32-byte secret seeds and public keys, one per file.
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// Default file name for per-directory manifests
+pub const MANIFEST_NAME: &str = "ISCC-SUMS";
+
+/// Suffix of the detached signature file written next to a signed manifest
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// A single parsed checksum line
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChecksumLine {
+    /// The ISCC code including the `ISCC:` prefix
+    pub iscc: String,
+    /// The path as written in the manifest
+    pub path: String,
+}
+
+impl ChecksumLine {
+    /// True if the entry refers to a directory checksum (tree mode, trailing slash)
+    pub fn is_tree(&self) -> bool {
+        self.path.ends_with('/')
+    }
+}
+
+/// Check that a string is an `ISCC:` prefixed base32 code
+fn is_iscc_code(code: &str) -> bool {
+    code.strip_prefix("ISCC:").is_some_and(|body| {
+        !body.is_empty()
+            && body
+                .bytes()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+    })
+}
+
+/// Parse a checksum line in either default or BSD format.
+///
+/// Returns `None` for improperly formatted lines.
+pub fn parse_line(line: &str) -> Option<ChecksumLine> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let line = line.strip_prefix('\u{feff}').unwrap_or(line);
+
+    // BSD format: ISCC-SUM (filename) = ISCC:xxx
+    if let Some(rest) = line.strip_prefix("ISCC-SUM (") {
+        let (path, iscc) = rest.rsplit_once(") = ")?;
+        if path.is_empty() || !is_iscc_code(iscc) {
+            return None;
+        }
+        return Some(ChecksumLine {
+            iscc: iscc.to_string(),
+            path: path.to_string(),
+        });
+    }
+
+    // Default format: ISCC:xxx *filename
+    let (iscc, path) = line.split_once(" *")?;
+    if path.is_empty() || !is_iscc_code(iscc) {
+        return None;
+    }
+    Some(ChecksumLine {
+        iscc: iscc.to_string(),
+        path: path.to_string(),
+    })
+}
+
+/// Format a checksum line in the default format (without line terminator)
+pub fn format_line(iscc: &str, path: &str) -> String {
+    format!("{iscc} *{path}")
+}
+
+/// Path of the detached signature of a manifest (`ISCC-SUMS` -> `ISCC-SUMS.sig`)
+pub fn signature_path(manifest: &Path) -> PathBuf {
+    let mut path = manifest.as_os_str().to_owned();
+    path.push(SIGNATURE_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Generate a new key pair as hex-encoded (secret, public) keys
+pub fn generate_keypair() -> Result<(String, String)> {
+    let mut seed = [0u8; 32];
+    getrandom::fill(&mut seed).map_err(|e| io::Error::other(format!("random source: {e}")))?;
+    let key = SigningKey::from_bytes(&seed);
+    Ok((
+        hex::encode(key.to_bytes()),
+        hex::encode(key.verifying_key().to_bytes()),
+    ))
+}
+
+/// Read a hex-encoded 32-byte key file
+fn read_key(path: &Path) -> Result<[u8; 32]> {
+    let contents = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    hex::decode(contents.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::InvalidKey {
+            path: path.to_path_buf(),
+            reason: "not a hex-encoded Ed25519 key",
+        })
+}
+
+/// Read a secret key written by `isum keygen`
+pub fn read_signing_key(path: &Path) -> Result<SigningKey> {
+    read_key(path).map(|bytes| SigningKey::from_bytes(&bytes))
+}
+
+/// Read a public key written by `isum keygen`
+pub fn read_verifying_key(path: &Path) -> Result<VerifyingKey> {
+    let bytes = read_key(path)?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| Error::InvalidKey {
+        path: path.to_path_buf(),
+        reason: "not a valid Ed25519 public key",
+    })
+}
+
+/// Sign manifest contents, returning the hex-encoded signature
+pub fn sign(key: &SigningKey, contents: &[u8]) -> String {
+    hex::encode(key.sign(contents).to_bytes())
+}
+
+/// Check a hex-encoded signature of manifest contents
+pub fn verify_signature(key: &VerifyingKey, contents: &[u8], signature: &str) -> Result<()> {
+    let signature = hex::decode(signature.trim())
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or(Error::InvalidSignature(
+            "not a hex-encoded Ed25519 signature",
+        ))?;
+    key.verify(contents, &signature)
+        .map_err(|_| Error::InvalidSignature("does not match the public key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "ISCC:K4AOMGOGQJA4Y46PAC4YPPA63GKSIFDBTTI3VPDUVUKBVU6TP2HSA7Y";
+
+    #[test]
+    fn test_parse_default_format() {
+        let line = format_line(CODE, "dir/file name.txt");
+        let parsed = parse_line(&line).unwrap();
+        assert_eq!(parsed.iscc, CODE);
+        assert_eq!(parsed.path, "dir/file name.txt");
+        assert!(!parsed.is_tree());
+    }
+
+    #[test]
+    fn test_parse_bsd_format() {
+        let line = format!("ISCC-SUM (dir/) = {CODE}");
+        let parsed = parse_line(&line).unwrap();
+        assert_eq!(parsed.iscc, CODE);
+        assert_eq!(parsed.path, "dir/");
+        assert!(parsed.is_tree());
+    }
+
+    #[test]
+    fn test_parse_with_bom_and_crlf() {
+        let line = format!("\u{feff}{CODE} *file.txt\r\n");
+        assert_eq!(parse_line(&line).unwrap().path, "file.txt");
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert!(parse_line("").is_none());
+        assert!(parse_line("garbage").is_none());
+        assert!(parse_line("ISCC:abc *file.txt").is_none());
+        assert!(parse_line(&format!("{CODE} *")).is_none());
+        assert!(parse_line(&format!("ISCC-SUM () = {CODE}")).is_none());
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (secret, public) = generate_keypair().unwrap();
+        fs::write(dir.path().join("key"), format!("{secret}\n")).unwrap();
+        fs::write(dir.path().join("key.pub"), &public).unwrap();
+        let signing_key = read_signing_key(&dir.path().join("key")).unwrap();
+        let verifying_key = read_verifying_key(&dir.path().join("key.pub")).unwrap();
+
+        let contents = format!("{}\n", format_line(CODE, "file.txt"));
+        let signature = sign(&signing_key, contents.as_bytes());
+        assert!(verify_signature(&verifying_key, contents.as_bytes(), &signature).is_ok());
+        assert!(matches!(
+            verify_signature(&verifying_key, b"tampered", &signature),
+            Err(Error::InvalidSignature("does not match the public key"))
+        ));
+        assert!(matches!(
+            verify_signature(&verifying_key, contents.as_bytes(), "00"),
+            Err(Error::InvalidSignature(_))
+        ));
+
+        fs::write(dir.path().join("bad"), "not hex").unwrap();
+        assert!(matches!(
+            read_signing_key(&dir.path().join("bad")),
+            Err(Error::InvalidKey { .. })
+        ));
+        assert!(matches!(
+            read_verifying_key(&dir.path().join("missing")),
+            Err(Error::Io { path: Some(_), .. })
+        ));
+        assert_eq!(
+            signature_path(Path::new("dir/ISCC-SUMS")),
+            PathBuf::from("dir/ISCC-SUMS.sig")
+        );
+    }
+}
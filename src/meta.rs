@@ -0,0 +1,486 @@
+//! ISCC Meta-Code generation.
+//!
+//! The Meta-Code is a similarity hash over the name (title) of a work and
+//! either its description or its metadata, so it completes the Data-Code and
+//! Instance-Code of an ISCC-SUM to a full ISCC-CODE with [`crate::codec::compose`].
+//! The algorithm follows `gen_meta_code_v0` of `iscc-core`:
+//!
+//! - name and description are cleaned ([`text_clean`]); newlines are removed
+//!   from the name, which is trimmed to 128 UTF-8 bytes, the description to
+//!   4096 bytes
+//! - the collapsed name ([`text_collapse`]) is split into character 3-grams,
+//!   hashed with BLAKE3 and combined into a 256-bit SimHash
+//! - with a description (character 3-grams) or metadata (byte 4-grams) a
+//!   second SimHash is built, and the first 128 bits of both are interleaved
+//!   in 32-bit chunks
+//!
+//! Metadata is given as a JSON object, hashed in its RFC 8785 canonical form,
+//! or as a Data-URL, hashed as its decoded payload. The `metahash` of the
+//! result is the BLAKE3 multihash of the metadata payload, or of name and
+//! description if there is no metadata.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::{PyDict, PyString};
+use serde_json::Value;
+use unicode_general_category::get_general_category;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::codec::{IsccCode, MainType};
+use crate::error::{Error, Result};
+
+/// Maximum UTF-8 length of the name in bytes
+pub const META_TRIM_NAME: usize = 128;
+
+/// Maximum UTF-8 length of the description in bytes
+pub const META_TRIM_DESCRIPTION: usize = 4096;
+
+/// Width of the character n-grams of name and description
+const NGRAM_SIZE_TEXT: usize = 3;
+
+/// Width of the byte n-grams of metadata
+const NGRAM_SIZE_BYTES: usize = 4;
+
+/// Characters kept by [`text_clean`] although they are control characters
+const NEWLINES: [char; 7] = [
+    '\u{000A}', '\u{000B}', '\u{000C}', '\u{000D}', '\u{0085}', '\u{2028}', '\u{2029}',
+];
+
+/// Result of [`gen_meta_code`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetaCodeResult {
+    /// Meta-Code with `ISCC:` prefix
+    pub iscc: String,
+    /// Cleaned and trimmed name
+    pub name: String,
+    /// Cleaned and trimmed description, if not empty
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub description: Option<String>,
+    /// Metadata as a Data-URL, if given
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub meta: Option<String>,
+    /// BLAKE3 multihash (hex) of the metadata or of name and description
+    pub metahash: String,
+}
+
+/// Generate a Meta-Code of `bits` length (32 to 256, in steps of 32).
+///
+/// `meta` is a JSON object or a string holding a Data-URL; any other JSON
+/// value is rejected. Fails if the name is empty after cleaning.
+pub fn gen_meta_code(
+    name: &str,
+    description: Option<&str>,
+    meta: Option<&Value>,
+    bits: u32,
+) -> Result<MetaCodeResult> {
+    if !(32..=256).contains(&bits) || !bits.is_multiple_of(32) {
+        return Err(Error::InvalidInput(format!(
+            "bits must be a multiple of 32 from 32 to 256, got {bits}"
+        )));
+    }
+    let name = text_trim(&text_remove_newlines(&text_clean(name)), META_TRIM_NAME);
+    if name.is_empty() {
+        return Err(Error::InvalidInput(
+            "Meta-Code requires a non-empty name (after normalization)".to_string(),
+        ));
+    }
+    let description = text_trim(
+        &text_clean(description.unwrap_or_default()),
+        META_TRIM_DESCRIPTION,
+    );
+
+    let (digest, payload, meta) = match meta {
+        Some(meta) => {
+            let (payload, data_url) = meta_payload(meta)?;
+            (
+                soft_hash_meta_bytes(&name, &payload),
+                payload,
+                Some(data_url),
+            )
+        }
+        None => {
+            let payload = format!("{name} {description}").trim().as_bytes().to_vec();
+            (soft_hash_meta(&name, &description), payload, None)
+        }
+    };
+
+    let body = digest[..bits as usize / 8].to_vec();
+    let code = IsccCode::new(MainType::Meta, 0, 0, bits / 32 - 1, body)?;
+    Ok(MetaCodeResult {
+        iscc: code.to_string(),
+        name,
+        description: (!description.is_empty()).then_some(description),
+        meta,
+        metahash: format!("1e20{}", blake3::hash(&payload).to_hex()),
+    })
+}
+
+/// Similarity digest of a name and a (possibly empty) description
+pub fn soft_hash_meta(name: &str, description: &str) -> [u8; 32] {
+    let name_hash = simhash_text(name);
+    if description.is_empty() {
+        return name_hash;
+    }
+    interleave(&name_hash, &simhash_text(description))
+}
+
+/// Similarity digest of a name and a metadata payload
+pub fn soft_hash_meta_bytes(name: &str, payload: &[u8]) -> [u8; 32] {
+    let name_hash = simhash_text(name);
+    if payload.is_empty() {
+        return name_hash;
+    }
+    let digests: Vec<[u8; 32]> = windows(payload, NGRAM_SIZE_BYTES)
+        .map(|ngram| *blake3::hash(ngram).as_bytes())
+        .collect();
+    interleave(&name_hash, &simhash(&digests))
+}
+
+/// SimHash of the BLAKE3 digests of the character 3-grams of collapsed text
+fn simhash_text(text: &str) -> [u8; 32] {
+    let chars: Vec<char> = text_collapse(text).chars().collect();
+    let digests: Vec<[u8; 32]> = windows(&chars, NGRAM_SIZE_TEXT)
+        .map(|ngram| {
+            let ngram: String = ngram.iter().collect();
+            *blake3::hash(ngram.as_bytes()).as_bytes()
+        })
+        .collect();
+    simhash(&digests)
+}
+
+/// Overlapping windows of `width` items; a shorter sequence is its only window
+fn windows<T>(items: &[T], width: usize) -> impl Iterator<Item = &[T]> {
+    let count = items.len().saturating_sub(width) + 1;
+    (0..count).map(move |start| &items[start..(start + width).min(items.len())])
+}
+
+/// Set every bit that is set in at least half of the digests
+pub(crate) fn simhash<const N: usize>(digests: &[[u8; N]]) -> [u8; N] {
+    let mut out = [0u8; N];
+    for bit in 0..N * 8 {
+        let count = digests
+            .iter()
+            .filter(|digest| (digest[bit / 8] >> (bit % 8)) & 1 == 1)
+            .count();
+        if count * 2 >= digests.len() {
+            out[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+    out
+}
+
+/// Alternate the 32-bit chunks of the first 128 bits of two digests
+fn interleave(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    for (i, chunk) in out.chunks_exact_mut(8).enumerate() {
+        chunk[..4].copy_from_slice(&a[i * 4..i * 4 + 4]);
+        chunk[4..].copy_from_slice(&b[i * 4..i * 4 + 4]);
+    }
+    out
+}
+
+/// Payload bytes and Data-URL of metadata
+fn meta_payload(meta: &Value) -> Result<(Vec<u8>, String)> {
+    match meta {
+        Value::Object(object) => {
+            let mut payload = String::new();
+            write_canonical(meta, &mut payload)?;
+            let media_type = if object.contains_key("@context") {
+                "application/ld+json"
+            } else {
+                "application/json"
+            };
+            let data_url = format!("data:{media_type};base64,{}", STANDARD.encode(&payload));
+            Ok((payload.into_bytes(), data_url))
+        }
+        Value::String(data_url) => Ok((decode_data_url(data_url)?, data_url.clone())),
+        _ => Err(Error::InvalidInput(
+            "metadata must be a JSON object or a Data-URL".to_string(),
+        )),
+    }
+}
+
+/// Payload of a `data:[<media type>][;base64],<data>` URL
+fn decode_data_url(url: &str) -> Result<Vec<u8>> {
+    let invalid = || Error::InvalidInput(format!("invalid Data-URL {url:?}"));
+    let rest = url.trim().strip_prefix("data:").ok_or_else(invalid)?;
+    let (header, data) = rest.split_once(',').ok_or_else(invalid)?;
+    let data = percent_decode(data).ok_or_else(invalid)?;
+    if header.to_ascii_lowercase().ends_with(";base64") {
+        let data: Vec<u8> = data
+            .into_iter()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .collect();
+        STANDARD.decode(data).map_err(|_| invalid())
+    } else {
+        Ok(data)
+    }
+}
+
+fn percent_decode(data: &str) -> Option<Vec<u8>> {
+    let bytes = data.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(out)
+}
+
+/// Serialize JSON in the RFC 8785 canonical form: no whitespace, object
+/// members sorted by their UTF-16 code units, numbers as in ECMAScript
+fn write_canonical(value: &Value, out: &mut String) -> Result<()> {
+    match value {
+        Value::Number(number) if number.is_i64() || number.is_u64() => {
+            out.push_str(&number.to_string())
+        }
+        Value::Number(number) => match number.as_f64() {
+            Some(float) if float.is_finite() => {
+                out.push_str(ryu_js::Buffer::new().format_finite(float))
+            }
+            _ => {
+                return Err(Error::InvalidInput(format!(
+                    "number {number} cannot be canonicalized"
+                )))
+            }
+        },
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out)?;
+            }
+            out.push(']');
+        }
+        Value::Object(object) => {
+            let mut members: Vec<(&String, &Value)> = object.iter().collect();
+            members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (i, (key, item)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(item, out)?;
+            }
+            out.push('}');
+        }
+        // Null, booleans and strings serialize the same in JCS
+        _ => out.push_str(&value.to_string()),
+    }
+    Ok(())
+}
+
+/// Clean text for display: NFKC-normalize, remove control characters other
+/// than newlines, normalize newlines to `\n`, keep at most one empty line in
+/// a row and strip leading and trailing whitespace
+pub fn text_clean(text: &str) -> String {
+    let text: String = text
+        .nfkc()
+        .filter(|&ch| NEWLINES.contains(&ch) || !is_category(ch, 'C'))
+        .collect();
+    let mut lines: Vec<&str> = Vec::new();
+    let mut previous_empty = false;
+    for line in split_lines(&text) {
+        if !line.trim().is_empty() {
+            lines.push(line);
+            previous_empty = false;
+        } else if !previous_empty {
+            lines.push("");
+            previous_empty = true;
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Replace runs of whitespace (newlines included) with single spaces
+pub fn text_remove_newlines(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Trim text to at most `max_bytes` UTF-8 bytes without splitting characters
+/// and strip leading and trailing whitespace
+pub fn text_trim(text: &str, max_bytes: usize) -> String {
+    let mut end = text.len().min(max_bytes);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].trim().to_string()
+}
+
+/// Normalize text for similarity hashing: lowercase and remove whitespace,
+/// control characters, marks (accents) and punctuation
+pub fn text_collapse(text: &str) -> String {
+    let text: String = text
+        .nfd()
+        .flat_map(char::to_lowercase)
+        .filter(|&ch| !ch.is_whitespace() && !['C', 'M', 'P'].iter().any(|&c| is_category(ch, c)))
+        .collect();
+    text.nfkc().collect()
+}
+
+/// True if the Unicode general category of `ch` is in the major class `class`
+fn is_category(ch: char, class: char) -> bool {
+    get_general_category(ch).abbreviation().starts_with(class)
+}
+
+/// Split lines at the line boundaries Python's `str.splitlines` recognizes
+fn split_lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, ch)) = chars.next() {
+        if NEWLINES.contains(&ch) {
+            lines.push(&text[start..i]);
+            start = i + ch.len_utf8();
+            if ch == '\r' && chars.peek().is_some_and(|&(_, next)| next == '\n') {
+                chars.next();
+                start += 1;
+            }
+        }
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+    lines
+}
+
+/// Generate a Meta-Code (Python-exposed function).
+///
+/// `meta` is a dict (hashed as canonical JSON) or a Data-URL string. Returns
+/// a dict with `iscc`, `name`, `metahash` and, if present, `description` and
+/// `meta`.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(name = "gen_meta_code", signature = (name, description=None, meta=None, bits=64))]
+pub fn py_gen_meta_code<'py>(
+    py: Python<'py>,
+    name: &str,
+    description: Option<&str>,
+    meta: Option<&Bound<'py, PyAny>>,
+    bits: u32,
+) -> PyResult<Bound<'py, PyDict>> {
+    let meta = match meta {
+        Some(meta) if meta.is_instance_of::<PyString>() => {
+            Some(Value::String(meta.extract::<String>()?))
+        }
+        Some(meta) => {
+            let json: String = py
+                .import("json")?
+                .call_method1("dumps", (meta,))?
+                .extract()?;
+            Some(serde_json::from_str(&json).map_err(|e| {
+                pyo3::exceptions::PyValueError::new_err(format!("Invalid metadata: {e}"))
+            })?)
+        }
+        None => None,
+    };
+    let result = gen_meta_code(name, description, meta.as_ref(), bits)?;
+    let dict = PyDict::new(py);
+    dict.set_item("iscc", result.iscc)?;
+    dict.set_item("name", result.name)?;
+    if let Some(description) = result.description {
+        dict.set_item("description", description)?;
+    }
+    if let Some(meta) = result.meta {
+        dict.set_item("meta", meta)?;
+    }
+    dict.set_item("metahash", result.metahash)?;
+    Ok(dict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_gen_meta_code() {
+        let result = gen_meta_code("Die Unendliche Geschichte", None, None, 64).unwrap();
+        assert_eq!(result.iscc, "ISCC:AAAZXZ6OU74YAZIM");
+        assert_eq!(result.description, None);
+        assert_eq!(
+            result.metahash,
+            format!(
+                "1e20{}",
+                blake3::hash(b"Die Unendliche Geschichte").to_hex()
+            )
+        );
+
+        let result = gen_meta_code(
+            " Die Unendliche\n Geschichte",
+            Some("Von Michael Ende"),
+            None,
+            64,
+        )
+        .unwrap();
+        assert_eq!(result.iscc, "ISCC:AAAZXZ6OU4E45RB5");
+        assert_eq!(result.name, "Die Unendliche Geschichte");
+        assert_eq!(result.description.as_deref(), Some("Von Michael Ende"));
+
+        assert!(gen_meta_code(" \n\u{0007}", None, None, 64).is_err());
+        assert!(gen_meta_code("Name", None, None, 48).is_err());
+        let wide = gen_meta_code("Die Unendliche Geschichte", None, None, 256).unwrap();
+        let code: IsccCode = wide.iscc.parse().unwrap();
+        assert_eq!((code.maintype(), code.bits()), (MainType::Meta, 256));
+    }
+
+    #[test]
+    fn test_gen_meta_code_metadata() {
+        let meta = json!({"title": "Der Herr der Ringe", "@context": "https://schema.org"});
+        let result = gen_meta_code("Der Herr der Ringe", None, Some(&meta), 64).unwrap();
+        let payload = br#"{"@context":"https://schema.org","title":"Der Herr der Ringe"}"#;
+        assert_eq!(
+            result.metahash,
+            format!("1e20{}", blake3::hash(payload).to_hex())
+        );
+        let data_url = result.meta.unwrap();
+        assert!(data_url.starts_with("data:application/ld+json;base64,"));
+
+        // The Data-URL of the result gives the same code
+        let again = gen_meta_code("Der Herr der Ringe", None, Some(&json!(data_url)), 64).unwrap();
+        assert_eq!(again.iscc, result.iscc);
+        assert_eq!(again.metahash, result.metahash);
+        assert!(gen_meta_code("Name", None, Some(&json!([1, 2])), 64).is_err());
+        assert!(gen_meta_code("Name", None, Some(&json!("no url")), 64).is_err());
+    }
+
+    #[test]
+    fn test_write_canonical() {
+        let value =
+            json!({"b": [1.0, 1e30, -0.5, "x\u{1}"], "a": null, "\u{fb01}": true, "\u{1f600}": 1});
+        let mut out = String::new();
+        write_canonical(&value, &mut out).unwrap();
+        assert_eq!(
+            out,
+            "{\"a\":null,\"b\":[1,1e+30,-0.5,\"x\\u0001\"],\"\u{1f600}\":1,\"\u{fb01}\":true}"
+        );
+    }
+
+    #[test]
+    fn test_text_normalization() {
+        assert_eq!(
+            text_clean("  Hello\r\n\r\n\r\n World\u{0000}\u{FB01}  "),
+            "Hello\n\n Worldfi"
+        );
+        assert_eq!(text_remove_newlines("a\n b\t\tc"), "a b c");
+        assert_eq!(text_trim("äöü", 3), "ä");
+        assert_eq!(text_collapse("Île-de-France, 2024!"), "iledefrance2024");
+        assert_eq!(windows(b"ab", 4).collect::<Vec<_>>(), [b"ab"]);
+        assert_eq!(windows(b"abcde", 4).count(), 2);
+    }
+}
@@ -0,0 +1,275 @@
+//! File system metadata recorded for audit manifests.
+//!
+//! Metadata (mode bits, ownership, modification time and symlink target) is
+//! kept separate from the content-derived ISCC: it never influences the
+//! checksum and is stored in its own `metadata` object (JSON output and
+//! sidecars) or in per-directory `ISCC-META` files next to `ISCC-SUMS`.
+
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Default file name for per-directory metadata manifests
+pub const METADATA_MANIFEST_NAME: &str = "ISCC-META";
+
+/// Audit metadata of a single file; fields a platform cannot provide are `None`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileMetadata {
+    /// Permission bits (Unix only)
+    #[cfg_attr(feature = "serde", serde(default, with = "octal_mode"))]
+    pub mode: Option<u32>,
+    /// Owner user id (Unix only)
+    pub uid: Option<u32>,
+    /// Owner group id (Unix only)
+    pub gid: Option<u32>,
+    /// Modification time in nanoseconds since the Unix epoch
+    pub mtime_ns: Option<i64>,
+    /// Target of the path if it is a symbolic link
+    pub symlink: Option<String>,
+}
+
+/// Serde representation of the mode as an octal string, as in [`FileMetadata::to_json`]
+#[cfg(feature = "serde")]
+mod octal_mode {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(mode: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error> {
+        match mode {
+            Some(mode) => serializer.serialize_some(&format!("{mode:04o}")),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u32>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|mode| {
+                u32::from_str_radix(&mode, 8)
+                    .map_err(|_| D::Error::custom(format!("invalid mode: {mode:?}")))
+            })
+            .transpose()
+    }
+}
+
+impl FileMetadata {
+    /// Collect the metadata of a path, following symlinks for everything but the link target
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let symlink = if fs::symlink_metadata(path)?.file_type().is_symlink() {
+            Some(fs::read_link(path)?.to_string_lossy().into_owned())
+        } else {
+            None
+        };
+        let metadata = fs::metadata(path)?;
+        let mtime_ns = metadata
+            .modified()
+            .ok()
+            .map(|time| match time.duration_since(UNIX_EPOCH) {
+                Ok(after) => after.as_nanos() as i64,
+                Err(before) => -(before.duration().as_nanos() as i64),
+            });
+
+        #[cfg(unix)]
+        let (mode, uid, gid) = {
+            use std::os::unix::fs::MetadataExt;
+            (
+                Some(metadata.mode() & 0o7777),
+                Some(metadata.uid()),
+                Some(metadata.gid()),
+            )
+        };
+        #[cfg(not(unix))]
+        let (mode, uid, gid) = (None, None, None);
+
+        Ok(FileMetadata {
+            mode,
+            uid,
+            gid,
+            mtime_ns,
+            symlink,
+        })
+    }
+
+    /// Serialize as a JSON object; the mode is written as an octal string
+    pub fn to_json(&self) -> Value {
+        json!({
+            "mode": self.mode.map(|mode| format!("{mode:04o}")),
+            "uid": self.uid,
+            "gid": self.gid,
+            "mtime_ns": self.mtime_ns,
+            "symlink": self.symlink,
+        })
+    }
+
+    /// Parse a JSON object written by [`FileMetadata::to_json`]
+    pub fn from_json(value: &Value) -> io::Result<Self> {
+        let invalid = |field: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed metadata: invalid '{field}'"),
+            )
+        };
+        let object = value.as_object().ok_or_else(|| invalid("metadata"))?;
+        let field = |name: &str| object.get(name).filter(|v| !v.is_null());
+        let id = |name: &str| -> io::Result<Option<u32>> {
+            field(name)
+                .map(|v| {
+                    v.as_u64()
+                        .and_then(|n| u32::try_from(n).ok())
+                        .ok_or_else(|| invalid(name))
+                })
+                .transpose()
+        };
+
+        Ok(FileMetadata {
+            mode: field("mode")
+                .map(|v| {
+                    v.as_str()
+                        .and_then(|s| u32::from_str_radix(s, 8).ok())
+                        .ok_or_else(|| invalid("mode"))
+                })
+                .transpose()?,
+            uid: id("uid")?,
+            gid: id("gid")?,
+            mtime_ns: field("mtime_ns")
+                .map(|v| v.as_i64().ok_or_else(|| invalid("mtime_ns")))
+                .transpose()?,
+            symlink: field("symlink")
+                .map(|v| {
+                    v.as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| invalid("symlink"))
+                })
+                .transpose()?,
+        })
+    }
+
+    /// Describe the fields that differ from the recorded metadata (empty if unchanged).
+    ///
+    /// Fields that were not recorded are not compared.
+    pub fn differences(&self, recorded: &FileMetadata) -> Vec<String> {
+        let mut changes = Vec::new();
+        let mode = |m: u32| format!("{m:04o}");
+        if let (Some(was), Some(now)) = (recorded.mode, self.mode) {
+            if was != now {
+                changes.push(format!("mode {} -> {}", mode(was), mode(now)));
+            }
+        }
+        if let (Some(was), Some(now)) = (recorded.uid, self.uid) {
+            if was != now {
+                changes.push(format!("uid {was} -> {now}"));
+            }
+        }
+        if let (Some(was), Some(now)) = (recorded.gid, self.gid) {
+            if was != now {
+                changes.push(format!("gid {was} -> {now}"));
+            }
+        }
+        if recorded.mtime_ns.is_some() && recorded.mtime_ns != self.mtime_ns {
+            changes.push("mtime changed".to_string());
+        }
+        if recorded.symlink != self.symlink {
+            changes.push(match (&recorded.symlink, &self.symlink) {
+                (Some(was), Some(now)) => format!("symlink {was} -> {now}"),
+                (Some(_), None) => "no longer a symlink".to_string(),
+                _ => "now a symlink".to_string(),
+            });
+        }
+        changes
+    }
+}
+
+/// Format a metadata manifest line (JSON with the file name, without line terminator)
+pub fn format_manifest_line(path: &str, metadata: &FileMetadata) -> String {
+    let mut line = Map::new();
+    line.insert("path".to_string(), json!(path));
+    if let Value::Object(fields) = metadata.to_json() {
+        line.extend(fields);
+    }
+    Value::Object(line).to_string()
+}
+
+/// Parse a metadata manifest line into the file name and its metadata
+pub fn parse_manifest_line(line: &str) -> io::Result<(String, FileMetadata)> {
+    let value: Value = serde_json::from_str(line.trim_start_matches('\u{feff}')).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("malformed metadata: {e}"),
+        )
+    })?;
+    let path = value["path"].as_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "malformed metadata: missing 'path'",
+        )
+    })?;
+    Ok((path.to_string(), FileMetadata::from_json(&value)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> FileMetadata {
+        FileMetadata {
+            mode: Some(0o644),
+            uid: Some(1000),
+            gid: Some(100),
+            mtime_ns: Some(1_700_000_000_123_456_789),
+            symlink: None,
+        }
+    }
+
+    #[test]
+    fn test_metadata_json_roundtrip() {
+        let metadata = sample();
+        let value = metadata.to_json();
+        assert_eq!(value["mode"], "0644");
+        assert_eq!(FileMetadata::from_json(&value).unwrap(), metadata);
+        assert!(FileMetadata::from_json(&json!({"mode": "rw-"})).is_err());
+    }
+
+    #[test]
+    fn test_manifest_line_roundtrip() {
+        let line = format_manifest_line("file name.txt", &sample());
+        assert!(line.starts_with(r#"{"path":"file name.txt""#));
+        let (path, metadata) = parse_manifest_line(&line).unwrap();
+        assert_eq!(path, "file name.txt");
+        assert_eq!(metadata, sample());
+        assert!(parse_manifest_line("garbage").is_err());
+    }
+
+    #[test]
+    fn test_differences() {
+        let recorded = sample();
+        assert!(recorded.differences(&recorded).is_empty());
+
+        let current = FileMetadata {
+            mode: Some(0o600),
+            symlink: Some("target".to_string()),
+            ..sample()
+        };
+        assert_eq!(
+            current.differences(&recorded),
+            vec!["mode 0644 -> 0600", "now a symlink"]
+        );
+
+        // Fields missing from the record are not compared
+        assert_eq!(current.differences(&FileMetadata::default()).len(), 1);
+    }
+
+    #[test]
+    fn test_from_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        let metadata = FileMetadata::from_path(&path).unwrap();
+        assert!(metadata.mtime_ns.is_some());
+        assert!(metadata.symlink.is_none());
+        assert_eq!(metadata.mode.is_some(), cfg!(unix));
+    }
+}
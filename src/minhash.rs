@@ -2,27 +2,141 @@
 //!
 //! This module provides the MinHash algorithm used to create compact signatures
 //! from sets of features. It's designed for high-performance similarity detection
-//! and deduplication.
+//! and deduplication. [`MinHash`] exposes it for arbitrary feature streams, with
+//! the permutations and digest layout of the ISCC.
+
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
 
 use crate::constants::{MPA, MPB};
+use crate::error::{Error, Result};
+#[cfg(feature = "std")]
 use rayon::prelude::*;
 
 const MAXI64: u64 = 0xFFFF_FFFF_FFFF_FFFF;
 const MPRIME: u64 = (1 << 61) - 1;
 const MAXH: u64 = (1 << 32) - 1;
 
-/// For each (a, b) pair in MPA and MPB, compute the minimum over all features.
-fn minhash(features: &Vec<u32>) -> Vec<u64> {
-    MPA.iter()
-        .zip(MPB.iter())
-        .map(|(&a, &b)| {
-            features
-                .par_iter()
-                .map(|&f| (((a.wrapping_mul(f as u64).wrapping_add(b)) & MAXI64) % MPRIME) & MAXH)
-                .min()
-                .unwrap_or(MAXH)
+/// Number of permutations of the ISCC MinHash
+pub const MAX_DIMS: usize = 64;
+
+/// Features per parallel task of [`MinHash::add_features`]
+const FEATURE_BLOCK: usize = 4096;
+
+/// Incremental MinHash over 32- or 64-bit features.
+///
+/// Uses the first `dims` of the 64 ISCC permutations; with all 64 and 32-bit
+/// features, [`MinHash::digest`] is the 256-bit ISCC Data-Code body digest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MinHash {
+    minima: Vec<u64>,
+}
+
+impl Default for MinHash {
+    fn default() -> Self {
+        MinHash {
+            minima: vec![MAXH; MAX_DIMS],
+        }
+    }
+}
+
+impl MinHash {
+    /// MinHash with `dims` permutations, from 1 to [`MAX_DIMS`]
+    pub fn new(dims: usize) -> Result<Self> {
+        if !(1..=MAX_DIMS).contains(&dims) {
+            return Err(Error::InvalidOptions(format!(
+                "MinHash dimensions must be from 1 to {MAX_DIMS}, got {dims}"
+            )));
+        }
+        Ok(MinHash {
+            minima: vec![MAXH; dims],
         })
-        .collect()
+    }
+
+    pub fn dims(&self) -> usize {
+        self.minima.len()
+    }
+
+    /// Add one feature; 32-bit features hash like their 64-bit extension
+    pub fn add_feature<F: Into<u64>>(&mut self, feature: F) {
+        let feature = feature.into();
+        for ((min, &a), &b) in self.minima.iter_mut().zip(&MPA).zip(&MPB) {
+            *min = (*min).min(permute(a, b, feature));
+        }
+    }
+
+    /// Add many 32-bit features at once, in parallel and vectorized
+    pub fn add_features(&mut self, features: &[u32]) {
+        let lower =
+            |a: [u64; MAX_DIMS], b: [u64; MAX_DIMS]| core::array::from_fn(|i| a[i].min(b[i]));
+        #[cfg(feature = "std")]
+        let minima = features
+            .par_chunks(FEATURE_BLOCK)
+            .map(block_minima)
+            .reduce(|| [MAXH; MAX_DIMS], lower);
+        #[cfg(not(feature = "std"))]
+        let minima = features
+            .chunks(FEATURE_BLOCK)
+            .map(block_minima)
+            .fold([MAXH; MAX_DIMS], lower);
+        for (min, block) in self.minima.iter_mut().zip(minima) {
+            *min = (*min).min(block);
+        }
+    }
+
+    /// Minimum 32-bit hash per permutation (`u32::MAX` before any feature)
+    pub fn values(&self) -> &[u64] {
+        &self.minima
+    }
+
+    /// The four least significant bits of each value, interleaved bit plane by
+    /// bit plane (`dims / 2` bytes rounded up, 32 for the ISCC)
+    pub fn digest(&self) -> Vec<u8> {
+        minhash_compress(&self.minima, 4)
+    }
+}
+
+/// Minima of a block of features, using AVX2 where the CPU supports it
+/// (detected at runtime with `std`)
+fn block_minima(features: &[u32]) -> [u64; MAX_DIMS] {
+    #[cfg(all(feature = "std", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: the CPU supports AVX2, checked just above
+        return unsafe { block_minima_avx2(features) };
+    }
+    block_minima_portable(features)
+}
+
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn block_minima_avx2(features: &[u32]) -> [u64; MAX_DIMS] {
+    block_minima_portable(features)
+}
+
+/// The 64 permutations of a feature are independent lanes, which the compiler
+/// vectorizes (SSE2/NEON, or AVX2 through [`block_minima_avx2`]).
+#[inline(always)]
+fn block_minima_portable(features: &[u32]) -> [u64; MAX_DIMS] {
+    let mut minima = [MAXH; MAX_DIMS];
+    for &feature in features {
+        for ((min, &a), &b) in minima.iter_mut().zip(&MPA).zip(&MPB) {
+            *min = (*min).min(permute(a, b, feature as u64));
+        }
+    }
+    minima
+}
+
+/// `((a * f + b) mod 2^64) mod MPRIME`, truncated to 32 bits.
+///
+/// MPRIME is the Mersenne prime 2^61 - 1, so the remainder is computed with a
+/// shift and an add instead of a division.
+#[inline(always)]
+fn permute(a: u64, b: u64, feature: u64) -> u64 {
+    let x = a.wrapping_mul(feature).wrapping_add(b) & MAXI64;
+    let r = (x & MPRIME) + (x >> 61);
+    let r = if r >= MPRIME { r - MPRIME } else { r };
+    r & MAXH
 }
 
 /// Compress the minhash vector by concatenating lsb least–significant bits from each integer.
@@ -49,9 +163,10 @@ fn minhash_compress(mhash: &[u64], lsb: u32) -> Vec<u8> {
 }
 
 /// Create a 256–bit digest from the chunk features.
-pub fn minhash_256(features: &Vec<u32>) -> Vec<u8> {
-    let mhash = minhash(features);
-    minhash_compress(&mhash, 4)
+pub fn minhash_256(features: &[u32]) -> Vec<u8> {
+    let mut minhash = MinHash::default();
+    minhash.add_features(features);
+    minhash.digest()
 }
 
 #[cfg(test)]
@@ -80,6 +195,65 @@ mod tests {
         assert_eq!(result1, result2);
     }
 
+    #[test]
+    fn test_permute_matches_modulo() {
+        let mut state: u32 = 7;
+        for _ in 0..10_000 {
+            state = state.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+            for (&a, &b) in MPA.iter().zip(&MPB) {
+                let x = a.wrapping_mul(state as u64).wrapping_add(b);
+                assert_eq!(permute(a, b, state as u64), (x % MPRIME) & MAXH);
+            }
+        }
+        // Values at and around the prime and the top of the range
+        for x in [MPRIME - 1, MPRIME, MPRIME + 1, 2 * MPRIME, MAXI64] {
+            assert_eq!(permute(x, 0, 1), (x % MPRIME) & MAXH);
+        }
+    }
+
+    #[test]
+    fn test_minhash_blocks() {
+        let features: Vec<u32> = (0..10_000u32)
+            .map(|i| i.wrapping_mul(2_654_435_761))
+            .collect();
+        let expected: Vec<u64> = MPA
+            .iter()
+            .zip(&MPB)
+            .map(|(&a, &b)| {
+                let hashes = features
+                    .iter()
+                    .map(|&f| (a.wrapping_mul(f as u64).wrapping_add(b) % MPRIME) & MAXH);
+                hashes.min().unwrap()
+            })
+            .collect();
+        let mut minhash = MinHash::default();
+        minhash.add_features(&features);
+        assert_eq!(minhash.values(), expected);
+        assert_eq!(MinHash::default().values(), [MAXH; MAX_DIMS]);
+    }
+
+    #[test]
+    fn test_minhash_incremental() {
+        let features: Vec<u32> = (0..1000u32).map(|i| i.wrapping_mul(0x9E37_79B9)).collect();
+        let mut bulk = MinHash::default();
+        bulk.add_features(&features);
+        let mut single = MinHash::default();
+        features.iter().for_each(|&f| single.add_feature(f));
+        assert_eq!(single, bulk);
+        assert_eq!(single.digest(), minhash_256(&features));
+
+        let mut wide = MinHash::default();
+        features.iter().for_each(|&f| wide.add_feature(f as u64));
+        assert_eq!(wide, bulk);
+
+        let mut small = MinHash::new(16).unwrap();
+        small.add_features(&features);
+        assert_eq!(small.values(), &bulk.values()[..16]);
+        assert_eq!(small.digest().len(), 8);
+        assert!(MinHash::new(0).is_err());
+        assert!(MinHash::new(65).is_err());
+    }
+
     #[test]
     fn test_minhash_compress() {
         let mhash = vec![0b1010u64, 0b1100u64];
@@ -0,0 +1,416 @@
+//! Configuration of ISCC-SUM processing.
+//!
+//! [`IsccSumOptions`] collects everything that controls how a processor hashes
+//! its input and what the result contains. Options are created with
+//! [`IsccSumOptions::builder`], which validates them once, so processors and
+//! the convenience functions in [`crate::sum`] can take them as they are.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cancel::CancellationToken;
+use crate::cdc::CdcParams;
+use crate::codec::SUM_BITS;
+use crate::error::{Error, Result};
+pub use crate::instance::DatahashEncoding;
+use crate::instance::InstanceKey;
+use crate::progress::{Progress, ProgressCallback};
+
+/// Conventional digests that can be computed alongside the ISCC
+///
+/// MD5, SHA-1 and SHA-256 need the `hashes` feature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HashAlgorithm {
+    #[cfg(feature = "hashes")]
+    Md5,
+    #[cfg(feature = "hashes")]
+    Sha1,
+    #[cfg(feature = "hashes")]
+    Sha256,
+    /// Taken from the Instance-Code, which is a BLAKE3 hash
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Names of all algorithms, as accepted by [`HashAlgorithm::from_str`]
+    pub const NAMES: &'static [&'static str] = &[
+        #[cfg(feature = "hashes")]
+        "md5",
+        #[cfg(feature = "hashes")]
+        "sha1",
+        #[cfg(feature = "hashes")]
+        "sha256",
+        "blake3",
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "hashes")]
+            HashAlgorithm::Md5 => "md5",
+            #[cfg(feature = "hashes")]
+            HashAlgorithm::Sha1 => "sha1",
+            #[cfg(feature = "hashes")]
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            #[cfg(feature = "hashes")]
+            "md5" => Ok(HashAlgorithm::Md5),
+            #[cfg(feature = "hashes")]
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            #[cfg(feature = "hashes")]
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            _ => Err(format!("unknown hash algorithm: {name}")),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Options for ISCC-SUM processors and the convenience functions.
+///
+/// The default is the standard 64-bit ISCC-SUM without units or extra digests.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IsccSumOptions {
+    pub(crate) bits: u32,
+    pub(crate) add_units: bool,
+    pub(crate) add_minhash: bool,
+    pub(crate) add_features: bool,
+    pub(crate) datahash_encoding: DatahashEncoding,
+    pub(crate) instance_key: Option<InstanceKey>,
+    pub(crate) cdc: CdcParams,
+    pub(crate) hashes: Vec<HashAlgorithm>,
+    pub(crate) segment_size: Option<u64>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    pub(crate) progress: Option<ProgressCallback>,
+}
+
+impl Default for IsccSumOptions {
+    fn default() -> Self {
+        IsccSumOptions {
+            bits: 64,
+            add_units: false,
+            add_minhash: false,
+            add_features: false,
+            datahash_encoding: DatahashEncoding::Hex,
+            instance_key: None,
+            cdc: CdcParams::default(),
+            hashes: Vec::new(),
+            segment_size: None,
+            cancellation: None,
+            progress: None,
+        }
+    }
+}
+
+impl IsccSumOptions {
+    pub fn builder() -> IsccSumOptionsBuilder {
+        IsccSumOptions::default().to_builder()
+    }
+
+    /// Builder starting from these options
+    pub fn to_builder(&self) -> IsccSumOptionsBuilder {
+        let derived = CdcParams::derived(self.cdc.avg_size());
+        IsccSumOptionsBuilder {
+            options: self.clone(),
+            avg_chunk_size: self.cdc.avg_size(),
+            min_chunk_size: Some(self.cdc.min_size()).filter(|&s| s != derived.min_size()),
+            max_chunk_size: Some(self.cdc.max_size()).filter(|&s| s != derived.max_size()),
+        }
+    }
+
+    /// Length of the Data-Code and Instance-Code in the composite (64, 128, 192 or 256)
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// True for wide composites (units longer than 64 bits)
+    pub fn wide(&self) -> bool {
+        self.bits > 64
+    }
+
+    /// Whether the result includes the full 256-bit units
+    pub fn add_units(&self) -> bool {
+        self.add_units
+    }
+
+    /// Whether the result includes the raw Data-Code MinHash values
+    pub fn add_minhash(&self) -> bool {
+        self.add_minhash
+    }
+
+    /// Whether the result includes the Data-Code chunk features
+    pub fn add_features(&self) -> bool {
+        self.add_features
+    }
+
+    /// Encoding of the datahash in the result
+    pub fn datahash_encoding(&self) -> DatahashEncoding {
+        self.datahash_encoding
+    }
+
+    /// Average chunk size of the Data-Code content-defined chunking
+    pub fn avg_chunk_size(&self) -> usize {
+        self.cdc.avg_size()
+    }
+
+    /// Chunk sizes of the Data-Code content-defined chunking
+    pub fn cdc_params(&self) -> CdcParams {
+        self.cdc
+    }
+
+    /// Key of the non-standard keyed Instance-Code
+    pub fn instance_key(&self) -> Option<&InstanceKey> {
+        self.instance_key.as_ref()
+    }
+
+    /// True unless the chunk sizes, the bit length or a key produce codes
+    /// outside the ISCC standard (64- or 128-bit units, default chunking,
+    /// plain BLAKE3)
+    pub fn is_standard(&self) -> bool {
+        self.cdc.is_standard() && self.bits <= 128 && self.instance_key.is_none()
+    }
+
+    /// Conventional digests computed in the same pass, in request order
+    pub fn hashes(&self) -> &[HashAlgorithm] {
+        &self.hashes
+    }
+
+    /// Size of the segments with their own ISCC-SUM in the result, if any
+    pub fn segment_size(&self) -> Option<u64> {
+        self.segment_size
+    }
+
+    /// Token checked by the convenience functions between buffers
+    pub fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    /// Callback receiving the progress of processors and the convenience functions
+    pub fn progress(&self) -> Option<&ProgressCallback> {
+        self.progress.as_ref()
+    }
+
+    /// Fail if the cancellation token asks to stop
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        self.cancellation
+            .as_ref()
+            .map_or(Ok(()), CancellationToken::check)
+    }
+}
+
+/// Builder for [`IsccSumOptions`]
+#[derive(Clone, Debug)]
+pub struct IsccSumOptionsBuilder {
+    options: IsccSumOptions,
+    avg_chunk_size: usize,
+    min_chunk_size: Option<usize>,
+    max_chunk_size: Option<usize>,
+}
+
+impl IsccSumOptionsBuilder {
+    /// Length of the Data-Code and Instance-Code in the composite: 64, 128, 192
+    /// or 256 bits. Only 64 and 128 bits yield codes `iscc-core` can decode.
+    pub fn bits(mut self, bits: u32) -> Self {
+        self.options.bits = bits;
+        self
+    }
+
+    /// Shorthand for 128 (wide) or 64 bits
+    pub fn wide(self, wide: bool) -> Self {
+        self.bits(if wide { 128 } else { 64 })
+    }
+
+    /// Include the full 256-bit Data-Code and Instance-Code units in the result
+    pub fn add_units(mut self, add_units: bool) -> Self {
+        self.options.add_units = add_units;
+        self
+    }
+
+    /// Include the 64 raw Data-Code MinHash values in the result, e.g. as input
+    /// for clustering
+    pub fn add_minhash(mut self, add_minhash: bool) -> Self {
+        self.options.add_minhash = add_minhash;
+        self
+    }
+
+    /// Include the Data-Code chunk features (xxHash32 per chunk, in stream
+    /// order) in the result
+    pub fn add_features(mut self, add_features: bool) -> Self {
+        self.options.add_features = add_features;
+        self
+    }
+
+    /// Compute the Instance-Code and datahash with keyed BLAKE3. The codes are
+    /// not ISCC-standard and only comparable to codes made with the same key,
+    /// e.g. to scope them to a tenant. Keyed processors cannot save their state.
+    pub fn instance_key(mut self, key: InstanceKey) -> Self {
+        self.options.instance_key = Some(key);
+        self
+    }
+
+    /// Encode the datahash as hex (default), multibase or CIDv1
+    pub fn datahash_encoding(mut self, encoding: DatahashEncoding) -> Self {
+        self.options.datahash_encoding = encoding;
+        self
+    }
+
+    /// Average chunk size of the Data-Code chunking, a power of two from 64 to
+    /// 65536. Only the default of 1024 bytes yields standard Data-Codes.
+    pub fn avg_chunk_size(mut self, size: usize) -> Self {
+        self.avg_chunk_size = size;
+        self
+    }
+
+    /// Minimum chunk size of the Data-Code chunking (default: a quarter of the
+    /// average). Other values yield non-standard Data-Codes.
+    pub fn min_chunk_size(mut self, size: usize) -> Self {
+        self.min_chunk_size = Some(size);
+        self
+    }
+
+    /// Maximum chunk size of the Data-Code chunking (default: eight times the
+    /// average). Other values yield non-standard Data-Codes.
+    pub fn max_chunk_size(mut self, size: usize) -> Self {
+        self.max_chunk_size = Some(size);
+        self
+    }
+
+    /// Also compute a conventional digest (added once, in call order)
+    pub fn hash(mut self, algorithm: HashAlgorithm) -> Self {
+        if !self.options.hashes.contains(&algorithm) {
+            self.options.hashes.push(algorithm);
+        }
+        self
+    }
+
+    /// Also compute several conventional digests
+    pub fn hashes(self, algorithms: impl IntoIterator<Item = HashAlgorithm>) -> Self {
+        algorithms.into_iter().fold(self, Self::hash)
+    }
+
+    /// Also compute the ISCC-SUM of every segment of `size` bytes (the last one
+    /// may be shorter), so parts of a very large file can be verified on their
+    /// own. Each segment is hashed a second time; processors computing segments
+    /// cannot save their state or be merged.
+    pub fn segment_size(mut self, size: u64) -> Self {
+        self.options.segment_size = Some(size);
+        self
+    }
+
+    /// Stop hashing with an error once the token is cancelled or expires
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.options.cancellation = Some(token);
+        self
+    }
+
+    /// Report progress to `callback`: processors call it after every update
+    /// with the bytes hashed so far, [`crate::sum::hash_tree`] also counts the
+    /// completed files
+    pub fn progress<F: Fn(Progress) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.options.progress = Some(ProgressCallback::new(callback));
+        self
+    }
+
+    /// Validate and return the options
+    pub fn build(self) -> Result<IsccSumOptions> {
+        let invalid = Error::InvalidOptions;
+        let mut options = self.options;
+        if !SUM_BITS.contains(&options.bits) {
+            return Err(invalid(format!(
+                "bits must be 64, 128, 192 or 256, got {}",
+                options.bits
+            )));
+        }
+        if options.segment_size == Some(0) {
+            return Err(invalid(
+                "segment size must be greater than zero".to_string(),
+            ));
+        }
+        let avg = self.avg_chunk_size;
+        options.cdc = CdcParams::with_sizes(
+            avg,
+            self.min_chunk_size.unwrap_or(avg / 4),
+            self.max_chunk_size.unwrap_or(avg * 8),
+        )?;
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let options = IsccSumOptions::builder()
+            .wide(true)
+            .add_units(true)
+            .hash(HashAlgorithm::Blake3)
+            .hashes([HashAlgorithm::Blake3])
+            .build()
+            .unwrap();
+        assert_eq!(options.bits(), 128);
+        assert!(options.add_units());
+        assert_eq!(options.avg_chunk_size(), crate::cdc::DATA_AVG_CHUNK_SIZE);
+        assert!(options.is_standard());
+        assert_eq!(options.hashes(), [HashAlgorithm::Blake3]);
+        assert_eq!(
+            IsccSumOptions::builder().build().unwrap(),
+            IsccSumOptions::default()
+        );
+        assert!(options.to_builder().bits(96).build().is_err());
+        assert!(options.to_builder().avg_chunk_size(1000).build().is_err());
+        assert!(options.to_builder().avg_chunk_size(32).build().is_err());
+        assert!(options.to_builder().segment_size(0).build().is_err());
+
+        let custom = IsccSumOptions::builder()
+            .min_chunk_size(128)
+            .max_chunk_size(4096)
+            .build()
+            .unwrap();
+        assert_eq!(custom.cdc_params().min_size(), 128);
+        assert!(!custom.is_standard());
+        // Explicit sizes survive to_builder, derived ones follow the average
+        let coarse = custom.to_builder().avg_chunk_size(2048).build().unwrap();
+        assert_eq!(coarse.cdc_params().max_size(), 4096);
+        let coarse = options.to_builder().avg_chunk_size(2048).build().unwrap();
+        assert_eq!(coarse.cdc_params().min_size(), 512);
+        assert!(IsccSumOptions::builder()
+            .min_chunk_size(1024)
+            .build()
+            .is_err());
+        for &name in HashAlgorithm::NAMES {
+            assert_eq!(name.parse::<HashAlgorithm>().unwrap().name(), name);
+        }
+        for name in DatahashEncoding::NAMES {
+            assert_eq!(name.parse::<DatahashEncoding>().unwrap().name(), name);
+        }
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_builder_hashes() {
+        let options = IsccSumOptions::builder()
+            .hash(HashAlgorithm::Sha256)
+            .hashes([HashAlgorithm::Md5, HashAlgorithm::Sha256])
+            .build()
+            .unwrap();
+        assert_eq!(
+            options.hashes(),
+            [HashAlgorithm::Sha256, HashAlgorithm::Md5]
+        );
+    }
+}
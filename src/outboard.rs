@@ -0,0 +1,426 @@
+//! BLAKE3 outboard chunk trees for streaming verification.
+//!
+//! The Instance-Code is a prefix of the BLAKE3 root hash, so the interior of
+//! the BLAKE3 tree can be stored next to a file and used to verify the content
+//! while it streams in. The file layout is the `bao` outboard format: the
+//! content length as 8 little-endian bytes followed by the parent nodes (left
+//! and right child chaining values, 64 bytes each) in pre-order. Data is
+//! checked chunk by chunk (1024 bytes), so corruption is reported at the first
+//! bad chunk instead of after the whole file has been read.
+
+use blake3::hazmat::{
+    left_subtree_len, merge_subtrees_non_root, merge_subtrees_root, ChainingValue, HasherExt, Mode,
+};
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// File name suffix of outboard files
+pub const OUTBOARD_SUFFIX: &str = ".obao";
+
+/// BLAKE3 chunk size in bytes
+pub const CHUNK_LEN: u64 = blake3::CHUNK_LEN as u64;
+
+const HEADER_LEN: u64 = 8;
+const PARENT_LEN: u64 = 64;
+
+/// Outboard path of a file (`movie.mp4` -> `movie.mp4.obao`)
+pub fn outboard_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_os_string();
+    name.push(OUTBOARD_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Check whether a path looks like an outboard file
+pub fn is_outboard<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(OUTBOARD_SUFFIX))
+}
+
+/// Size of the outboard file for content of `len` bytes
+pub fn outboard_len(len: u64) -> u64 {
+    let chunks = len.div_ceil(CHUNK_LEN).max(1);
+    HEADER_LEN + (chunks - 1) * PARENT_LEN
+}
+
+/// Chaining value of a non-root chunk starting at `offset`
+fn chunk_cv(data: &[u8], offset: u64) -> ChainingValue {
+    let mut hasher = blake3::Hasher::new();
+    hasher.set_input_offset(offset).update(data);
+    hasher.finalize_non_root()
+}
+
+/// A subtree whose parent node still waits for its right child
+#[derive(Debug)]
+struct Pending {
+    index: u64,
+    start: u64,
+    len: u64,
+    left: Option<ChainingValue>,
+}
+
+/// Incremental outboard writer fed with the content in order.
+///
+/// The content length must be known up front (it fixes the tree shape). Parent
+/// nodes are written to their pre-order position as soon as both children are
+/// known, so the content is read only once.
+pub struct OutboardEncoder<W: Write + Seek> {
+    out: W,
+    len: u64,
+    pending: Vec<Pending>,
+    next_index: u64,
+    chunk_start: u64,
+    chunk_len: u64,
+    chunk: Vec<u8>,
+    root: Option<blake3::Hash>,
+}
+
+impl<W: Write + Seek> OutboardEncoder<W> {
+    /// Start an outboard for `len` bytes of content
+    pub fn new(mut out: W, len: u64) -> io::Result<Self> {
+        out.write_all(&len.to_le_bytes())?;
+        let mut encoder = OutboardEncoder {
+            out,
+            len,
+            pending: Vec::new(),
+            next_index: 0,
+            chunk_start: 0,
+            chunk_len: 0,
+            chunk: Vec::with_capacity(CHUNK_LEN as usize),
+            root: None,
+        };
+        encoder.descend(0, len);
+        Ok(encoder)
+    }
+
+    /// Walk down the left edge of a subtree, numbering its parents in pre-order
+    fn descend(&mut self, start: u64, mut len: u64) {
+        while len > CHUNK_LEN {
+            self.pending.push(Pending {
+                index: self.next_index,
+                start,
+                len,
+                left: None,
+            });
+            self.next_index += 1;
+            len = left_subtree_len(len);
+        }
+        self.chunk_start = start;
+        self.chunk_len = len;
+    }
+
+    /// Hash the completed chunk and merge it into its ancestors
+    fn finish_chunk(&mut self) -> io::Result<()> {
+        if self.len <= CHUNK_LEN {
+            self.root = Some(blake3::hash(&self.chunk));
+            return Ok(());
+        }
+        let mut cv = chunk_cv(&self.chunk, self.chunk_start);
+        self.chunk.clear();
+        loop {
+            let Some(top) = self.pending.last_mut() else {
+                unreachable!("chunk outside of the tree");
+            };
+            if top.left.is_none() {
+                top.left = Some(cv);
+                let left_len = left_subtree_len(top.len);
+                let (start, len) = (top.start + left_len, top.len - left_len);
+                self.descend(start, len);
+                return Ok(());
+            }
+            let node = self.pending.pop().expect("pending parent");
+            let left = node.left.expect("left child");
+            self.out
+                .seek(SeekFrom::Start(HEADER_LEN + node.index * PARENT_LEN))?;
+            self.out.write_all(&left)?;
+            self.out.write_all(&cv)?;
+            if self.pending.is_empty() {
+                self.root = Some(merge_subtrees_root(&left, &cv, Mode::Hash));
+                return Ok(());
+            }
+            cv = merge_subtrees_non_root(&left, &cv, Mode::Hash);
+        }
+    }
+
+    /// Feed the next part of the content
+    pub fn update(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            if self.root.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "content longer than announced",
+                ));
+            }
+            let take = ((self.chunk_len as usize) - self.chunk.len()).min(data.len());
+            self.chunk.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.chunk.len() as u64 == self.chunk_len {
+                self.finish_chunk()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush the outboard and return the BLAKE3 root hash of the content
+    pub fn finalize(mut self) -> io::Result<blake3::Hash> {
+        if self.len == 0 {
+            self.finish_chunk()?;
+        }
+        let root = self.root.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "content shorter than announced",
+            )
+        })?;
+        self.out.flush()?;
+        Ok(root)
+    }
+}
+
+impl<W: Write + Seek> Write for OutboardEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Why streamed content failed verification
+#[derive(Debug)]
+pub enum VerifyError {
+    /// The outboard tree does not hash to the expected root
+    RootMismatch,
+    /// The chunk starting at `offset` does not match the outboard
+    BadChunk { offset: u64 },
+    /// The content ended after `verified` of `len` bytes
+    Truncated { verified: u64, len: u64 },
+    /// The content is longer than the `len` bytes recorded in the outboard
+    TrailingData { len: u64 },
+    /// Reading the content or the outboard failed
+    Io(io::Error),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::RootMismatch => write!(f, "outboard does not match the expected hash"),
+            VerifyError::BadChunk { offset } => write!(f, "bad chunk at offset {offset}"),
+            VerifyError::Truncated { verified, len } => {
+                write!(f, "incomplete: {verified} of {len} bytes verified")
+            }
+            VerifyError::TrailingData { len } => {
+                write!(f, "content continues after {len} bytes")
+            }
+            VerifyError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+impl From<io::Error> for VerifyError {
+    fn from(e: io::Error) -> Self {
+        VerifyError::Io(e)
+    }
+}
+
+/// Read up to `buf.len()` bytes; fewer only at the end of the input
+fn read_full<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+struct Verifier<'a, D: Read + ?Sized, O: Read + ?Sized> {
+    data: &'a mut D,
+    outboard: &'a mut O,
+    len: u64,
+    chunk: Vec<u8>,
+}
+
+impl<D: Read + ?Sized, O: Read + ?Sized> Verifier<'_, D, O> {
+    fn read_parent(&mut self) -> Result<(ChainingValue, ChainingValue), VerifyError> {
+        let mut node = [0u8; PARENT_LEN as usize];
+        self.outboard.read_exact(&mut node).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                VerifyError::Io(io::Error::new(e.kind(), "outboard file is truncated"))
+            } else {
+                VerifyError::Io(e)
+            }
+        })?;
+        let (left, right) = node.split_at(32);
+        Ok((left.try_into().unwrap(), right.try_into().unwrap()))
+    }
+
+    fn read_chunk(&mut self, start: u64, len: u64) -> Result<(), VerifyError> {
+        self.chunk.resize(len as usize, 0);
+        let got = read_full(self.data, &mut self.chunk)?;
+        if got < len as usize {
+            return Err(VerifyError::Truncated {
+                verified: start,
+                len: self.len,
+            });
+        }
+        Ok(())
+    }
+
+    fn subtree(
+        &mut self,
+        start: u64,
+        len: u64,
+        expected: &ChainingValue,
+    ) -> Result<(), VerifyError> {
+        if len <= CHUNK_LEN {
+            self.read_chunk(start, len)?;
+            if chunk_cv(&self.chunk, start) != *expected {
+                return Err(VerifyError::BadChunk { offset: start });
+            }
+            return Ok(());
+        }
+        let (left, right) = self.read_parent()?;
+        if merge_subtrees_non_root(&left, &right, Mode::Hash) != *expected {
+            // A corrupt outboard is reported at the first chunk it covers
+            return Err(VerifyError::BadChunk { offset: start });
+        }
+        let left_len = left_subtree_len(len);
+        self.subtree(start, left_len, &left)?;
+        self.subtree(start + left_len, len - left_len, &right)
+    }
+}
+
+/// Verify streamed content against its outboard, chunk by chunk.
+///
+/// `expected` is a prefix (at least 8 bytes) of the BLAKE3 root hash, such as
+/// an Instance-Code body or a full digest. Returns the content length on
+/// success; reading stops at the first chunk that fails.
+pub fn verify<D, O>(data: &mut D, outboard: &mut O, expected: &[u8]) -> Result<u64, VerifyError>
+where
+    D: Read + ?Sized,
+    O: Read + ?Sized,
+{
+    if expected.len() < 8 || expected.len() > 32 {
+        return Err(VerifyError::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "expected hash must be 8 to 32 bytes",
+        )));
+    }
+    let mut header = [0u8; HEADER_LEN as usize];
+    outboard
+        .read_exact(&mut header)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "not an outboard file"))?;
+    let len = u64::from_le_bytes(header);
+    let mut verifier = Verifier {
+        data,
+        outboard,
+        len,
+        chunk: Vec::with_capacity(CHUNK_LEN as usize),
+    };
+
+    if len <= CHUNK_LEN {
+        // A single chunk is its own root; it can only be checked whole
+        verifier.read_chunk(0, len)?;
+        if !blake3::hash(&verifier.chunk)
+            .as_bytes()
+            .starts_with(expected)
+        {
+            return Err(VerifyError::BadChunk { offset: 0 });
+        }
+    } else {
+        let (left, right) = verifier.read_parent()?;
+        let root = merge_subtrees_root(&left, &right, Mode::Hash);
+        if !root.as_bytes().starts_with(expected) {
+            return Err(VerifyError::RootMismatch);
+        }
+        let left_len = left_subtree_len(len);
+        verifier.subtree(0, left_len, &left)?;
+        verifier.subtree(left_len, len - left_len, &right)?;
+    }
+
+    if read_full(verifier.data, &mut [0u8; 1])? > 0 {
+        return Err(VerifyError::TrailingData { len });
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn encode(data: &[u8], step: usize) -> (Vec<u8>, blake3::Hash) {
+        let mut out = Cursor::new(Vec::new());
+        let mut encoder = OutboardEncoder::new(&mut out, data.len() as u64).unwrap();
+        for part in data.chunks(step.max(1)) {
+            encoder.update(part).unwrap();
+        }
+        let root = encoder.finalize().unwrap();
+        (out.into_inner(), root)
+    }
+
+    #[test]
+    fn test_encode_matches_blake3() {
+        for len in [0, 1, 1024, 1025, 2048, 3000, 4096, 5121, 70_000] {
+            let data = sample(len);
+            for step in [1000, 4096] {
+                let (outboard, root) = encode(&data, step);
+                assert_eq!(root, blake3::hash(&data), "len {len}");
+                assert_eq!(outboard.len() as u64, outboard_len(len as u64));
+                assert_eq!(&outboard[..8], &(len as u64).to_le_bytes());
+                let length = verify(&mut &data[..], &mut &outboard[..], root.as_bytes());
+                assert_eq!(length.unwrap(), len as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_fails_at_first_bad_chunk() {
+        let mut data = sample(10_000);
+        let (outboard, root) = encode(&data, 4096);
+        data[5000] ^= 1;
+        let mut reader = Cursor::new(&data);
+        let err = verify(&mut reader, &mut &outboard[..], &root.as_bytes()[..8]).unwrap_err();
+        assert!(matches!(err, VerifyError::BadChunk { offset: 4096 }));
+        // Nothing after the bad chunk was read
+        assert_eq!(reader.position(), 5120);
+
+        let err = verify(&mut &data[..], &mut &outboard[..], &[0u8; 8]).unwrap_err();
+        assert!(matches!(err, VerifyError::RootMismatch));
+    }
+
+    #[test]
+    fn test_verify_partial_content() {
+        let data = sample(10_000);
+        let (outboard, root) = encode(&data, 4096);
+        let err = verify(&mut &data[..6000], &mut &outboard[..], root.as_bytes()).unwrap_err();
+        assert!(matches!(
+            err,
+            VerifyError::Truncated {
+                verified: 5120,
+                len: 10_000
+            }
+        ));
+        let mut longer = data.clone();
+        longer.push(0);
+        let err = verify(&mut &longer[..], &mut &outboard[..], root.as_bytes()).unwrap_err();
+        assert!(matches!(err, VerifyError::TrailingData { len: 10_000 }));
+
+        let mut encoder = OutboardEncoder::new(Cursor::new(Vec::new()), 10).unwrap();
+        encoder.update(b"short").unwrap();
+        assert!(encoder.finalize().is_err());
+    }
+}
@@ -0,0 +1,47 @@
+//! Progress reporting for long-running operations.
+//!
+//! A callback registered with
+//! [`crate::options::IsccSumOptionsBuilder::progress`] is called by
+//! processors after every update and by [`crate::sum::hash_tree`] as files
+//! complete, so GUIs and servers can show progress without wrapping readers.
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Progress of a hashing operation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Bytes hashed so far
+    pub bytes: u64,
+    /// Files completed so far (0 when hashing a single stream)
+    pub files: u64,
+}
+
+/// Shared progress callback; called from the thread doing the hashing
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new<F: Fn(Progress) + Send + Sync + 'static>(callback: F) -> Self {
+        ProgressCallback(Arc::new(callback))
+    }
+
+    pub fn report(&self, progress: Progress) {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Callbacks are equal if they are clones of each other
+impl PartialEq for ProgressCallback {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ProgressCallback {}
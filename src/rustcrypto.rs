@@ -0,0 +1,154 @@
+//! RustCrypto `digest` trait implementations (`digest` feature).
+//!
+//! [`DataHasher`], [`InstanceHasher`] and [`IsccSumProcessor`] implement
+//! `Update`, `FixedOutput`, `FixedOutputReset`, `Reset` and `HashMarker`, and
+//! with that `digest::Digest`, so they can be used by generic code written
+//! against the `digest` ecosystem. The outputs are the raw digests:
+//!
+//! - `DataHasher`: the 256-bit Data-Code digest
+//! - `InstanceHasher`: the 256-bit BLAKE3 hash
+//! - `IsccSumProcessor`: the Data-Code digest followed by the BLAKE3 hash
+//!   (64 bytes), from which the ISCC-SUM of any length is derived
+//!
+//! Resetting keeps the configured options (such as the Data-Code chunk size).
+
+use digest::consts::{U32, U64};
+use digest::{FixedOutput, FixedOutputReset, HashMarker, Output, OutputSizeUser, Reset, Update};
+
+use crate::data::DataHasher;
+use crate::instance::InstanceHasher;
+use crate::sum::IsccSumProcessor;
+
+impl Update for DataHasher {
+    fn update(&mut self, data: &[u8]) {
+        self.push(data);
+    }
+}
+
+impl OutputSizeUser for DataHasher {
+    type OutputSize = U32;
+}
+
+impl FixedOutput for DataHasher {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        out.copy_from_slice(&self.digest());
+    }
+}
+
+impl Reset for DataHasher {
+    fn reset(&mut self) {
+        *self = DataHasher::with_cdc_params(self.cdc);
+    }
+}
+
+impl FixedOutputReset for DataHasher {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        out.copy_from_slice(&self.digest());
+        Reset::reset(self);
+    }
+}
+
+impl HashMarker for DataHasher {}
+
+impl Update for InstanceHasher {
+    fn update(&mut self, data: &[u8]) {
+        self.push(data);
+    }
+}
+
+impl OutputSizeUser for InstanceHasher {
+    type OutputSize = U32;
+}
+
+impl FixedOutput for InstanceHasher {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        out.copy_from_slice(&self.digest());
+    }
+}
+
+impl Reset for InstanceHasher {
+    fn reset(&mut self) {
+        *self = match self.key() {
+            Some(&key) => InstanceHasher::with_key(key),
+            None => InstanceHasher::new(),
+        };
+    }
+}
+
+impl FixedOutputReset for InstanceHasher {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        out.copy_from_slice(&self.digest());
+        Reset::reset(self);
+    }
+}
+
+impl HashMarker for InstanceHasher {}
+
+impl Update for IsccSumProcessor {
+    fn update(&mut self, data: &[u8]) {
+        IsccSumProcessor::update(self, data);
+    }
+}
+
+impl OutputSizeUser for IsccSumProcessor {
+    type OutputSize = U64;
+}
+
+impl FixedOutput for IsccSumProcessor {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        out.copy_from_slice(&self.digests());
+    }
+}
+
+impl Reset for IsccSumProcessor {
+    fn reset(&mut self) {
+        *self = IsccSumProcessor::with_options(self.options().clone());
+    }
+}
+
+impl FixedOutputReset for IsccSumProcessor {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        out.copy_from_slice(&self.digests());
+        Reset::reset(self);
+    }
+}
+
+impl HashMarker for IsccSumProcessor {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use digest::Digest;
+
+    /// Hash in two updates through the generic `Digest` interface
+    fn generic<D: Digest>(data: &[u8]) -> Vec<u8> {
+        let mut hasher = D::new();
+        let (a, b) = data.split_at(data.len() / 3);
+        Digest::update(&mut hasher, a);
+        Digest::update(&mut hasher, b);
+        hasher.finalize().to_vec()
+    }
+
+    #[test]
+    fn test_digest_traits() {
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i * 11 % 249) as u8).collect();
+        let mut data_hasher = DataHasher::new();
+        data_hasher.push(&data);
+        let data_digest = data_hasher.digest();
+        let instance_digest = blake3::hash(&data).as_bytes().to_vec();
+
+        assert_eq!(generic::<DataHasher>(&data), data_digest);
+        assert_eq!(generic::<InstanceHasher>(&data), instance_digest);
+        assert_eq!(
+            generic::<IsccSumProcessor>(&data),
+            [data_digest, instance_digest].concat()
+        );
+
+        // Reset starts over
+        let mut hasher = <InstanceHasher as Digest>::new();
+        Digest::update(&mut hasher, b"discarded");
+        let first = hasher.finalize_reset();
+        Digest::update(&mut hasher, b"discarded");
+        assert_eq!(hasher.finalize(), first);
+    }
+}
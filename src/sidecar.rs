@@ -0,0 +1,215 @@
+//! Sidecar file support for ISCC-SUM results.
+//!
+//! A sidecar is a small JSON document stored next to the file it describes
+//! (e.g. `movie.mp4.iscc`) holding the composite code, units, filesize and
+//! datahash, and optionally the file system metadata of the file. It allows
+//! per-file verification without a central manifest.
+
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::metadata::FileMetadata;
+use crate::sum::IsccSumResult;
+
+/// File extension appended to the described file's name
+pub const SIDECAR_EXTENSION: &str = "iscc";
+
+/// Contents of a sidecar file
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sidecar {
+    pub iscc: String,
+    pub datahash: String,
+    pub filesize: u64,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub units: Option<Vec<String>>,
+    /// File system metadata, kept apart from the content-derived fields
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub metadata: Option<FileMetadata>,
+}
+
+impl Sidecar {
+    /// Serialize the sidecar as pretty-printed JSON with a trailing newline
+    pub fn to_json(&self) -> String {
+        let mut value = json!({
+            "iscc": self.iscc,
+            "datahash": self.datahash,
+            "filesize": self.filesize,
+        });
+        if let Some(units) = &self.units {
+            value["units"] = json!(units);
+        }
+        if let Some(metadata) = &self.metadata {
+            value["metadata"] = metadata.to_json();
+        }
+        // Serializing a Value cannot fail
+        let mut out = serde_json::to_string_pretty(&value).unwrap_or_default();
+        out.push('\n');
+        out
+    }
+
+    /// Parse sidecar JSON contents
+    pub fn parse(contents: &str) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let value: Value = serde_json::from_str(contents.trim_start_matches('\u{feff}'))
+            .map_err(|e| invalid(&format!("malformed sidecar: {e}")))?;
+
+        let iscc = value["iscc"]
+            .as_str()
+            .filter(|s| s.starts_with("ISCC:"))
+            .ok_or_else(|| invalid("malformed sidecar: missing or invalid 'iscc'"))?;
+        let datahash = value["datahash"]
+            .as_str()
+            .ok_or_else(|| invalid("malformed sidecar: missing 'datahash'"))?;
+        let filesize = value["filesize"]
+            .as_u64()
+            .ok_or_else(|| invalid("malformed sidecar: missing 'filesize'"))?;
+        let units = match &value["units"] {
+            Value::Null => None,
+            Value::Array(items) => Some(
+                items
+                    .iter()
+                    .map(|u| u.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| invalid("malformed sidecar: invalid 'units'"))?,
+            ),
+            _ => return Err(invalid("malformed sidecar: invalid 'units'")),
+        };
+        let metadata = match &value["metadata"] {
+            Value::Null => None,
+            metadata => Some(FileMetadata::from_json(metadata)?),
+        };
+
+        Ok(Sidecar {
+            iscc: iscc.to_string(),
+            datahash: datahash.to_string(),
+            filesize,
+            units,
+            metadata,
+        })
+    }
+
+    /// Read and parse the sidecar at the given path
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Write the sidecar to the given path
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+}
+
+impl From<&IsccSumResult> for Sidecar {
+    fn from(result: &IsccSumResult) -> Self {
+        Sidecar {
+            iscc: result.iscc.clone(),
+            datahash: result.datahash.clone(),
+            filesize: result.filesize,
+            units: result.units.clone(),
+            metadata: None,
+        }
+    }
+}
+
+/// Return the sidecar path for a file (`movie.mp4` -> `movie.mp4.iscc`)
+pub fn sidecar_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_os_string();
+    name.push(".");
+    name.push(SIDECAR_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Check whether a path looks like a sidecar file
+pub fn is_sidecar<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|ext| ext == SIDECAR_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Sidecar {
+        Sidecar {
+            iscc: "ISCC:K4AOMGOGQJA4Y46PAC4YPPA63GKSIFDBTTI3VPDUVUKBVU6TP2HSA7Y".to_string(),
+            datahash: "1e20".to_string() + &"ab".repeat(32),
+            filesize: 42,
+            units: Some(vec!["ISCC:GAD1".to_string(), "ISCC:IAD2".to_string()]),
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_sidecar_roundtrip() {
+        let sidecar = sample();
+        let parsed = Sidecar::parse(&sidecar.to_json()).unwrap();
+        assert_eq!(parsed, sidecar);
+    }
+
+    #[test]
+    fn test_sidecar_without_units() {
+        let sidecar = Sidecar {
+            units: None,
+            ..sample()
+        };
+        let json = sidecar.to_json();
+        assert!(!json.contains("units"));
+        assert_eq!(Sidecar::parse(&json).unwrap(), sidecar);
+    }
+
+    #[test]
+    fn test_sidecar_with_metadata() {
+        let sidecar = Sidecar {
+            metadata: Some(FileMetadata {
+                mode: Some(0o640),
+                mtime_ns: Some(42),
+                ..FileMetadata::default()
+            }),
+            ..sample()
+        };
+        let json = sidecar.to_json();
+        assert!(json.contains(r#""mode": "0640""#));
+        assert_eq!(Sidecar::parse(&json).unwrap(), sidecar);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_sidecar_serde_matches_file_format() {
+        let sidecar = Sidecar {
+            units: None,
+            metadata: Some(FileMetadata {
+                mode: Some(0o640),
+                mtime_ns: Some(42),
+                ..FileMetadata::default()
+            }),
+            ..sample()
+        };
+        let value = serde_json::to_value(&sidecar).unwrap();
+        assert_eq!(
+            value,
+            serde_json::from_str::<Value>(&sidecar.to_json()).unwrap()
+        );
+        assert_eq!(serde_json::from_value::<Sidecar>(value).unwrap(), sidecar);
+    }
+
+    #[test]
+    fn test_sidecar_malformed() {
+        assert!(Sidecar::parse("not json").is_err());
+        assert!(Sidecar::parse(r#"{"iscc": "nope", "datahash": "", "filesize": 1}"#).is_err());
+        assert!(Sidecar::parse(r#"{"iscc": "ISCC:AAAA", "datahash": ""}"#).is_err());
+    }
+
+    #[test]
+    fn test_sidecar_path() {
+        assert_eq!(
+            sidecar_path("dir/movie.mp4"),
+            PathBuf::from("dir/movie.mp4.iscc")
+        );
+        assert!(is_sidecar("dir/movie.mp4.iscc"));
+        assert!(!is_sidecar("dir/movie.mp4"));
+    }
+}
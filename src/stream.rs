@@ -0,0 +1,176 @@
+//! `std::io` adapters that compute the ISCC-SUM of the bytes passing through.
+//!
+//! [`IsccSumWriter`] wraps a writer, so data can be hashed while it is
+//! copied or uploaded, without reading it a second time. [`IsccSumReader`]
+//! wraps a reader, so download and extraction code gets the ISCC of what it
+//! read as a byproduct.
+
+use std::io::{self, Read, Write};
+
+use crate::options::IsccSumOptions;
+use crate::sum::{IsccSumProcessor, IsccSumResult};
+
+/// Writer that hashes everything written through it to the inner writer.
+///
+/// Only bytes the inner writer accepts are hashed, so the result matches
+/// what was written even after partial writes.
+pub struct IsccSumWriter<W: Write> {
+    inner: W,
+    processor: IsccSumProcessor,
+}
+
+impl<W: Write> IsccSumWriter<W> {
+    /// Wrap a writer, hashing with the default options
+    pub fn new(inner: W) -> Self {
+        Self::with_options(inner, IsccSumOptions::default())
+    }
+
+    pub fn with_options(inner: W, options: IsccSumOptions) -> Self {
+        IsccSumWriter {
+            inner,
+            processor: IsccSumProcessor::with_options(options),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Mutable access to the inner writer; bytes written to it directly are not hashed
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Number of bytes hashed so far
+    pub fn filesize(&self) -> u64 {
+        self.processor.filesize()
+    }
+
+    /// Flush the inner writer and return the result with the inner writer
+    pub fn finalize(mut self) -> io::Result<(IsccSumResult, W)> {
+        self.inner.flush()?;
+        Ok((self.processor.result(), self.inner))
+    }
+}
+
+impl<W: Write> Write for IsccSumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.processor.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader that hashes everything read through it from the inner reader
+pub struct IsccSumReader<R: Read> {
+    inner: R,
+    processor: IsccSumProcessor,
+}
+
+impl<R: Read> IsccSumReader<R> {
+    /// Wrap a reader, hashing with the default options
+    pub fn new(inner: R) -> Self {
+        Self::with_options(inner, IsccSumOptions::default())
+    }
+
+    pub fn with_options(inner: R, options: IsccSumOptions) -> Self {
+        IsccSumReader {
+            inner,
+            processor: IsccSumProcessor::with_options(options),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Mutable access to the inner reader; bytes read from it directly are not hashed
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Number of bytes hashed so far
+    pub fn filesize(&self) -> u64 {
+        self.processor.filesize()
+    }
+
+    /// Result for the bytes read so far, with the inner reader.
+    ///
+    /// Unread input is not hashed; read to the end first for the ISCC of the
+    /// whole stream.
+    pub fn finalize(self) -> (IsccSumResult, R) {
+        (self.processor.result(), self.inner)
+    }
+}
+
+impl<R: Read> Read for IsccSumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.processor.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sum::hash_bytes;
+
+    #[test]
+    fn test_writer_hashes_written_bytes() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let options = IsccSumOptions::builder().add_units(true).build().unwrap();
+        let mut writer = IsccSumWriter::with_options(Vec::new(), options.clone());
+        io::copy(&mut &data[..], &mut writer).unwrap();
+        assert_eq!(writer.filesize(), data.len() as u64);
+        let (result, copy) = writer.finalize().unwrap();
+        assert_eq!(copy, data);
+        assert_eq!(result, hash_bytes(&data, &options));
+    }
+
+    #[test]
+    fn test_reader_hashes_read_bytes() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i * 17 % 253) as u8).collect();
+        let mut reader = IsccSumReader::new(&data[..]);
+        let mut copy = Vec::new();
+        reader.read_to_end(&mut copy).unwrap();
+        assert_eq!(copy, data);
+        let (result, rest) = reader.finalize();
+        assert!(rest.is_empty());
+        assert_eq!(result, hash_bytes(&data, &IsccSumOptions::default()));
+
+        // Only what was read is hashed
+        let mut reader = IsccSumReader::new(&data[..]);
+        let mut head = [0u8; 1000];
+        reader.read_exact(&mut head).unwrap();
+        assert_eq!(reader.filesize(), 1000);
+        let (result, _) = reader.finalize();
+        assert_eq!(result, hash_bytes(&head, &IsccSumOptions::default()));
+    }
+
+    #[test]
+    fn test_writer_partial_writes() {
+        // A writer accepting at most 7 bytes per call
+        struct Trickle(Vec<u8>);
+        impl Write for Trickle {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let n = buf.len().min(7);
+                self.0.extend_from_slice(&buf[..n]);
+                Ok(n)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let data = b"Hello World, hashed while it is written";
+        let mut writer = IsccSumWriter::new(Trickle(Vec::new()));
+        writer.write_all(data).unwrap();
+        let (result, inner) = writer.finalize().unwrap();
+        assert_eq!(inner.0, data);
+        assert_eq!(result, hash_bytes(data, &IsccSumOptions::default()));
+    }
+}
@@ -1,41 +1,152 @@
 // ISCC-SUM implementation combining Data-Code and Instance-Code in a single pass
 
+use crate::cdc::CdcParams;
+use crate::codec::{IsccCode, MainType, SUBTYPE_SUM, SUBTYPE_WIDE};
 use crate::data::DataHasher;
-use crate::instance::InstanceHasher;
+use crate::error::{Error, Result};
+use crate::instance::{encode_multihash, InstanceHasher, InstanceKey};
+use crate::options::{HashAlgorithm, IsccSumOptions};
+use crate::progress::Progress;
+#[cfg(feature = "treewalk")]
+use crate::progress::ProgressCallback;
+#[cfg(feature = "treewalk")]
+use crate::treewalk::treewalk_iscc;
 use base32;
-use pyo3::exceptions::PyKeyError;
+#[cfg(feature = "hashes")]
+use md5::Md5;
+#[cfg(feature = "python")]
+use pyo3::exceptions::{PyKeyError, PyValueError};
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::types::PyBytes;
+#[cfg(feature = "python")]
 use pyo3::IntoPyObject;
+#[cfg(feature = "hashes")]
+use sha1::Sha1;
+#[cfg(feature = "hashes")]
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read};
 use std::path::Path;
+#[cfg(feature = "treewalk")]
+use std::path::PathBuf;
+
+/// File signature of a serialized processor state
+const STATE_MAGIC: &[u8; 8] = b"ISUMSTAT";
+
+/// Version of the serialized processor state layout
+pub const STATE_VERSION: u8 = 3;
+
+/// Alignment of the range offsets accepted by [`IsccSumProcessor::for_range`]
+pub const RANGE_ALIGNMENT: u64 = crate::instance::BLOCK_SIZE as u64;
+
+/// Size of the read buffer used by [`hash_reader`] and [`hash_file`]
+const READ_BUFFER_SIZE: usize = 2 * 1024 * 1024;
 
 /// Result object for ISCC-SUM operations
-#[pyclass(mapping)]
-#[derive(Clone)]
+#[cfg_attr(feature = "python", pyclass(mapping, get_all))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IsccSumResult {
-    #[pyo3(get)]
+    /// ISCC-SUM code (`ISCC:...`)
     pub iscc: String,
-    #[pyo3(get)]
+    /// Hex-encoded BLAKE3 multihash of the content
     pub datahash: String,
-    #[pyo3(get)]
+    /// Size of the content in bytes
     pub filesize: u64,
-    #[pyo3(get)]
+    /// Data-Code and Instance-Code units, if requested
     pub units: Option<Vec<String>>,
+    /// Hex digests of the extra hashes requested in the options, by algorithm name
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub hashes: BTreeMap<String, String>,
+    /// Raw Data-Code MinHash values, if requested
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub minhash: Option<Vec<u64>>,
+    /// Data-Code chunk features (xxHash32 per chunk), if requested
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub features: Option<Vec<u32>>,
+    /// ISCC-SUMs of the segments of the input, if a segment size was given
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub segments: Option<Vec<IsccSegment>>,
+    /// True if the options produce codes that are not ISCC-standard (custom
+    /// chunk sizes, more than 128 bits or a keyed Instance-Code), so they
+    /// only compare to codes made with the same options
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "core::ops::Not::not")
+    )]
+    pub nonstandard: bool,
 }
 
+/// ISCC-SUM of one segment of the input (see [`IsccSumOptionsBuilder::segment_size`]),
+/// hashed as if it were a file of its own
+///
+/// [`IsccSumOptionsBuilder::segment_size`]: crate::options::IsccSumOptionsBuilder::segment_size
+#[cfg_attr(feature = "python", pyclass(get_all))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IsccSegment {
+    /// Offset of the segment in the input
+    pub offset: u64,
+    /// Size of the segment in bytes; only the last segment may be shorter than
+    /// the segment size
+    pub size: u64,
+    /// ISCC-SUM code of the segment
+    pub iscc: String,
+    /// Datahash of the segment
+    pub datahash: String,
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
+impl IsccSegment {
+    fn __repr__(&self) -> String {
+        format!(
+            "IsccSegment(offset={}, size={}, iscc='{}')",
+            self.offset, self.size, self.iscc
+        )
+    }
+}
+
 impl IsccSumResult {
     /// Create a new IsccSumResult
-    #[new]
-    fn new(iscc: String, datahash: String, filesize: u64, units: Option<Vec<String>>) -> Self {
+    pub fn new(iscc: String, datahash: String, filesize: u64, units: Option<Vec<String>>) -> Self {
         Self {
             iscc,
             datahash,
             filesize,
             units,
+            hashes: BTreeMap::new(),
+            minhash: None,
+            features: None,
+            segments: None,
+            nonstandard: false,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl IsccSumResult {
+    /// Create a new IsccSumResult
+    #[new]
+    fn py_new(iscc: String, datahash: String, filesize: u64, units: Option<Vec<String>>) -> Self {
+        Self::new(iscc, datahash, filesize, units)
+    }
 
     /// String representation
     fn __repr__(&self) -> String {
@@ -47,87 +158,514 @@ impl IsccSumResult {
 
     /// Dict-like getitem access for backward compatibility
     fn __getitem__<'py>(&self, py: Python<'py>, key: &str) -> PyResult<Bound<'py, PyAny>> {
+        if !self.__contains__(key) {
+            return Err(PyKeyError::new_err(format!("Key '{key}' not found")));
+        }
         match key {
             "iscc" => Ok(self.iscc.as_str().into_pyobject(py)?.into_any()),
             "datahash" => Ok(self.datahash.as_str().into_pyobject(py)?.into_any()),
             "filesize" => Ok(self.filesize.into_pyobject(py)?.into_any()),
             "units" => Ok(self.units.as_ref().into_pyobject(py)?.into_any()),
-            _ => Err(PyKeyError::new_err(format!("Key '{key}' not found"))),
+            "hashes" => Ok(self.hashes.clone().into_pyobject(py)?.into_any()),
+            "minhash" => Ok(self.minhash.as_ref().into_pyobject(py)?.into_any()),
+            "features" => Ok(self.features.as_ref().into_pyobject(py)?.into_any()),
+            "segments" => Ok(self.segments.clone().into_pyobject(py)?.into_any()),
+            "nonstandard" => Ok(self.nonstandard.into_pyobject(py)?.to_owned().into_any()),
+            _ => unreachable!("key checked above"),
         }
     }
 
     /// Dict-like contains check
     fn __contains__(&self, key: &str) -> bool {
-        matches!(key, "iscc" | "datahash" | "filesize" | "units")
+        self.py_keys().contains(&key)
     }
 
     /// Length for dict-like behavior
     fn __len__(&self) -> usize {
-        4
+        self.py_keys().len()
     }
 }
 
-/// ISCC-SUM processor for generating combined Data-Code and Instance-Code
-#[pyclass]
+#[cfg(feature = "python")]
+impl IsccSumResult {
+    /// Keys of the dict-like access: the optional results only when present
+    fn py_keys(&self) -> Vec<&'static str> {
+        let mut keys = vec!["iscc", "datahash", "filesize", "units"];
+        if !self.hashes.is_empty() {
+            keys.push("hashes");
+        }
+        if self.minhash.is_some() {
+            keys.push("minhash");
+        }
+        if self.features.is_some() {
+            keys.push("features");
+        }
+        if self.segments.is_some() {
+            keys.push("segments");
+        }
+        if self.nonstandard {
+            keys.push("nonstandard");
+        }
+        keys
+    }
+}
+
+/// Incremental state for an extra conventional digest
+#[derive(Clone)]
+enum ExtraHasher {
+    #[cfg(feature = "hashes")]
+    Md5(Md5),
+    #[cfg(feature = "hashes")]
+    Sha1(Sha1),
+    #[cfg(feature = "hashes")]
+    Sha256(Sha256),
+    /// BLAKE3 is already computed for the Instance-Code and taken from there
+    Blake3,
+    /// Plain BLAKE3 next to a keyed Instance-Code
+    Blake3Plain(Box<blake3::Hasher>),
+}
+
+impl ExtraHasher {
+    fn new(algorithm: HashAlgorithm, keyed: bool) -> Self {
+        match algorithm {
+            #[cfg(feature = "hashes")]
+            HashAlgorithm::Md5 => ExtraHasher::Md5(Md5::new()),
+            #[cfg(feature = "hashes")]
+            HashAlgorithm::Sha1 => ExtraHasher::Sha1(Sha1::new()),
+            #[cfg(feature = "hashes")]
+            HashAlgorithm::Sha256 => ExtraHasher::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 if keyed => {
+                ExtraHasher::Blake3Plain(Box::new(blake3::Hasher::new()))
+            }
+            HashAlgorithm::Blake3 => ExtraHasher::Blake3,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            #[cfg(feature = "hashes")]
+            ExtraHasher::Md5(h) => h.update(data),
+            #[cfg(feature = "hashes")]
+            ExtraHasher::Sha1(h) => h.update(data),
+            #[cfg(feature = "hashes")]
+            ExtraHasher::Sha256(h) => h.update(data),
+            ExtraHasher::Blake3Plain(h) => {
+                h.update(data);
+            }
+            ExtraHasher::Blake3 => {}
+        }
+    }
+
+    /// Hex digest of the data so far, using the Instance-Code digest for BLAKE3
+    fn hexdigest(&self, blake3: &[u8]) -> String {
+        match self {
+            #[cfg(feature = "hashes")]
+            ExtraHasher::Md5(h) => hex::encode(h.clone().finalize()),
+            #[cfg(feature = "hashes")]
+            ExtraHasher::Sha1(h) => hex::encode(h.clone().finalize()),
+            #[cfg(feature = "hashes")]
+            ExtraHasher::Sha256(h) => hex::encode(h.clone().finalize()),
+            ExtraHasher::Blake3Plain(h) => h.finalize().to_hex().to_string(),
+            ExtraHasher::Blake3 => hex::encode(blake3),
+        }
+    }
+}
+
+/// Digests of a segment, encoded as configured when the result is taken
+#[derive(Clone)]
+struct SegmentDigests {
+    offset: u64,
+    size: u64,
+    data_digest: Vec<u8>,
+    instance_digest: Vec<u8>,
+    multihash: Vec<u8>,
+}
+
+/// Segment hashing state of a processor
+#[derive(Clone)]
+struct Segments {
+    size: u64,
+    cdc: CdcParams,
+    instance_key: Option<InstanceKey>,
+    /// Completed segments
+    done: Vec<SegmentDigests>,
+    data_hasher: DataHasher,
+    instance_hasher: InstanceHasher,
+}
+
+impl Segments {
+    fn new(size: u64, options: &IsccSumOptions) -> Self {
+        Segments {
+            size,
+            cdc: options.cdc,
+            instance_key: options.instance_key,
+            done: Vec::new(),
+            data_hasher: DataHasher::with_cdc_params(options.cdc),
+            instance_hasher: instance_hasher(options.instance_key),
+        }
+    }
+
+    /// Hash `data`, completing a segment at every segment boundary
+    fn push(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let room = self.size - self.instance_hasher.filesize();
+            let take = data.len().min(room.try_into().unwrap_or(usize::MAX));
+            let (part, rest) = data.split_at(take);
+            self.data_hasher.push(part);
+            self.instance_hasher.push(part);
+            if self.instance_hasher.filesize() == self.size {
+                self.finish_segment();
+            }
+            data = rest;
+        }
+    }
+
+    /// Digests of the current segment
+    fn current_segment(&self) -> SegmentDigests {
+        SegmentDigests {
+            offset: self.done.len() as u64 * self.size,
+            size: self.instance_hasher.filesize(),
+            data_digest: self.data_hasher.digest(),
+            instance_digest: self.instance_hasher.digest(),
+            multihash: self.instance_hasher.multihash_bytes(),
+        }
+    }
+
+    /// Complete the current segment and start the next one
+    fn finish_segment(&mut self) {
+        let segment = self.current_segment();
+        self.done.push(segment);
+        self.data_hasher = DataHasher::with_cdc_params(self.cdc);
+        self.instance_hasher = instance_hasher(self.instance_key);
+    }
+
+    /// Completed segments and the started one
+    fn results(&self, options: &IsccSumOptions) -> Vec<IsccSegment> {
+        let current = Some(self.current_segment()).filter(|segment| segment.size > 0);
+        self.done
+            .iter()
+            .chain(current.as_ref())
+            .map(|segment| IsccSegment {
+                offset: segment.offset,
+                size: segment.size,
+                iscc: sum_code(&segment.data_digest, &segment.instance_digest, options.bits),
+                datahash: encode_multihash(&segment.multihash, options.datahash_encoding),
+            })
+            .collect()
+    }
+}
+
+/// Instance-Code hasher, keyed if a key is given
+fn instance_hasher(key: Option<InstanceKey>) -> InstanceHasher {
+    match key {
+        Some(key) => InstanceHasher::with_key(key),
+        None => InstanceHasher::new(),
+    }
+}
+
+/// ISCC-SUM code (`ISCC:...`) of a Data-Code and an Instance-Code digest
+fn sum_code(data_digest: &[u8], instance_digest: &[u8], bits: u32) -> String {
+    // Truncate both digests to the unit length; longer codes are wide
+    // composites with the extra length in the header length field
+    let unit_len = bits as usize / 8;
+    let (subtype, length) = if bits > 64 {
+        (SUBTYPE_WIDE, (bits - 128) / 64)
+    } else {
+        (SUBTYPE_SUM, 0)
+    };
+    let body = [&data_digest[..unit_len], &instance_digest[..unit_len]].concat();
+    IsccCode::new(MainType::Iscc, subtype, 0, length, body)
+        .expect("ISCC-SUM header fields are valid")
+        .to_string()
+}
+
+/// ISCC-SUM processor for generating combined Data-Code and Instance-Code.
+///
+/// Processors are `Send` and `Sync`, so servers can pool them (see
+/// [`IsccSumProcessor::reset`]) and hash the ranges of one input on several
+/// threads with [`IsccSumProcessor::fork`] and [`IsccSumProcessor::merge`].
+/// Updating takes `&mut self`; share a processor between threads behind a
+/// `Mutex` if several of them feed the same stream.
+///
+/// A clone is a snapshot of the processor state, including extra digests: take
+/// its result for an interim code (or serialize it as a checkpoint) while the
+/// original keeps processing the stream.
+#[derive(Clone)]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct IsccSumProcessor {
     data_hasher: DataHasher,
     instance_hasher: InstanceHasher,
+    options: IsccSumOptions,
+    extra_hashers: Vec<(HashAlgorithm, ExtraHasher)>,
+    segments: Option<Segments>,
 }
 
 // Public Rust API (for use from main.rs)
 impl IsccSumProcessor {
-    /// Create a new ISCC-SUM processor
+    /// Create a new ISCC-SUM processor with the default options
     pub fn new() -> Self {
+        Self::with_options(IsccSumOptions::default())
+    }
+
+    /// Create a new ISCC-SUM processor
+    pub fn with_options(options: IsccSumOptions) -> Self {
         Self {
-            data_hasher: DataHasher::new(),
-            instance_hasher: InstanceHasher::new(),
+            data_hasher: DataHasher::with_cdc_params(options.cdc),
+            instance_hasher: instance_hasher(options.instance_key),
+            extra_hashers: options
+                .hashes
+                .iter()
+                .map(|&algorithm| {
+                    let keyed = options.instance_key.is_some();
+                    (algorithm, ExtraHasher::new(algorithm, keyed))
+                })
+                .collect(),
+            segments: options
+                .segment_size
+                .map(|size| Segments::new(size, &options)),
+            options,
         }
     }
 
+    pub fn options(&self) -> &IsccSumOptions {
+        &self.options
+    }
+
     /// Update the processor with new data
     pub fn update(&mut self, data: &[u8]) {
+        #[cfg(feature = "metrics")]
+        crate::telemetry::record_bytes(data.len());
         self.data_hasher.push(data);
         self.instance_hasher.push(data);
+        for (_, hasher) in &mut self.extra_hashers {
+            hasher.update(data);
+        }
+        if let Some(segments) = &mut self.segments {
+            segments.push(data);
+        }
+        if let Some(progress) = &self.options.progress {
+            progress.report(Progress {
+                bytes: self.filesize(),
+                files: 0,
+            });
+        }
     }
 
-    /// Get the final ISCC-SUM result
-    pub fn result(&mut self, wide: bool, add_units: bool) -> IsccSumResult {
-        // Get digests
-        let data_digest = self.data_hasher.digest();
-        let instance_digest = self.instance_hasher.digest();
+    /// Number of bytes processed so far
+    pub fn filesize(&self) -> u64 {
+        self.instance_hasher.filesize()
+    }
 
-        // Use appropriate length based on wide parameter
-        let data_code = if wide {
-            &data_digest[..16] // 128 bits for wide
-        } else {
-            &data_digest[..8] // 64 bits for standard
+    /// Processor for the part of a stream starting at `offset`, to be merged into
+    /// the processor of the preceding part with [`IsccSumProcessor::merge`].
+    ///
+    /// `offset` must be a multiple of [`RANGE_ALIGNMENT`] and every range but the
+    /// last must end on such a boundary, so a large input can be hashed as
+    /// disjoint ranges on different machines (exchanging the serialized states).
+    pub fn for_range(offset: u64) -> Result<Self> {
+        Ok(Self {
+            data_hasher: DataHasher::for_range(),
+            instance_hasher: InstanceHasher::for_range(offset)?,
+            ..Self::new()
+        })
+    }
+
+    /// Processor with the options of this one for the part of the stream
+    /// starting at `offset`, like [`IsccSumProcessor::for_range`] but also for
+    /// keyed hashing and non-default chunk sizes. Fails for misaligned offsets
+    /// and for processors computing extra hashes or segments, which cannot be
+    /// merged.
+    pub fn fork(&self, offset: u64) -> Result<Self> {
+        if !self.extra_hashers.is_empty() {
+            return Err(Error::Unsupported(
+                "processors computing extra hashes cannot be merged",
+            ));
+        }
+        if self.segments.is_some() {
+            return Err(Error::Unsupported(
+                "processors computing segments cannot be merged",
+            ));
+        }
+        Ok(Self {
+            data_hasher: self.data_hasher.fork(),
+            instance_hasher: self.instance_hasher.fork(offset)?,
+            options: self.options.clone(),
+            extra_hashers: Vec::new(),
+            segments: None,
+        })
+    }
+
+    /// Discard the data processed so far, keeping the options, so a pooled
+    /// processor can be reused for the next stream (starting at offset 0)
+    pub fn reset(&mut self) {
+        *self = Self::with_options(self.options.clone());
+    }
+
+    /// Stream offset of the first byte processed by this processor
+    pub fn offset(&self) -> u64 {
+        self.instance_hasher.offset()
+    }
+
+    /// Append the processor of the directly following range.
+    ///
+    /// After merging all ranges in order into the first one (at offset 0), the
+    /// result equals that of hashing the whole stream. Fails without changing
+    /// this processor if `next` does not follow directly or the Data-Code
+    /// chunking does not resynchronize at the boundary. Processors computing
+    /// extra hashes or segments cannot be merged.
+    pub fn merge(&mut self, next: IsccSumProcessor) -> Result<()> {
+        if !self.extra_hashers.is_empty() || !next.extra_hashers.is_empty() {
+            return Err(Error::Unsupported(
+                "processors computing extra hashes cannot be merged",
+            ));
+        }
+        if self.segments.is_some() || next.segments.is_some() {
+            return Err(Error::Unsupported(
+                "processors computing segments cannot be merged",
+            ));
+        }
+        self.instance_hasher.check_merge(&next.instance_hasher)?;
+        self.data_hasher.merge(next.data_hasher)?;
+        self.instance_hasher.merge(next.instance_hasher)
+    }
+
+    /// Serialize the processor state, to resume hashing later with
+    /// [`IsccSumProcessor::restore_state`] instead of starting over.
+    ///
+    /// Layout: the magic bytes `ISUMSTAT`, a `u8` format version (currently 3),
+    /// the average, minimum and maximum chunk size of the Data-Code as `u32`
+    /// (little-endian), the Data-Code state (see [`DataHasher::write_state`])
+    /// and the Instance-Code state (see [`InstanceHasher::write_state`]).
+    /// Version 2 states hold only the average chunk size and version 1 states
+    /// none, the other sizes are derived or default. The state holds the input offset; the
+    /// caller continues with the input from there. The state of extra hashes
+    /// and segments cannot be saved, so processors computing them fail with
+    /// `Unsupported`.
+    pub fn serialize_state(&self) -> Result<Vec<u8>> {
+        if !self.extra_hashers.is_empty() {
+            return Err(Error::Unsupported(
+                "the state of extra hashes cannot be saved",
+            ));
+        }
+        if self.options.instance_key.is_some() {
+            return Err(Error::Unsupported(
+                "the state of keyed hashing cannot be saved",
+            ));
+        }
+        if self.segments.is_some() {
+            return Err(Error::Unsupported(
+                "the state of processors computing segments cannot be saved",
+            ));
+        }
+        let mut out = STATE_MAGIC.to_vec();
+        out.push(STATE_VERSION);
+        let cdc = self.options.cdc;
+        for size in [cdc.avg_size(), cdc.min_size(), cdc.max_size()] {
+            out.extend_from_slice(&(size as u32).to_le_bytes());
+        }
+        self.data_hasher.write_state(&mut out);
+        self.instance_hasher.write_state(&mut out);
+        Ok(out)
+    }
+
+    /// Recreate a processor from a state written by [`IsccSumProcessor::serialize_state`]
+    pub fn restore_state(state: &[u8]) -> Result<Self> {
+        let (cdc, data_hasher, instance_hasher) = Self::read_state(state)?;
+        let options = IsccSumOptions {
+            cdc,
+            ..IsccSumOptions::default()
         };
-        let instance_code = if wide {
-            &instance_digest[..16] // 128 bits for wide
-        } else {
-            &instance_digest[..8] // 64 bits for standard
+        Ok(Self {
+            data_hasher,
+            instance_hasher,
+            ..Self::with_options(options)
+        })
+    }
+
+    /// Recreate a processor from a state, with the given options for the result.
+    ///
+    /// The options must use the chunk sizes of the state and no extra hashes or
+    /// segments.
+    pub fn restore_state_with_options(state: &[u8], options: IsccSumOptions) -> Result<Self> {
+        let (cdc, data_hasher, instance_hasher) = Self::read_state(state)?;
+        if options.cdc != cdc
+            || !options.hashes.is_empty()
+            || options.instance_key.is_some()
+            || options.segment_size.is_some()
+        {
+            return Err(Error::InvalidOptions(
+                "options do not match the state".to_string(),
+            ));
+        }
+        Ok(Self {
+            data_hasher,
+            instance_hasher,
+            ..Self::with_options(options)
+        })
+    }
+
+    fn read_state(state: &[u8]) -> Result<(CdcParams, DataHasher, InstanceHasher)> {
+        let mut input = state;
+        let invalid = |message: &str| Error::InvalidState(message.to_string());
+        if input.len() < 9 || &input[..8] != STATE_MAGIC {
+            return Err(invalid("not an isum state"));
+        }
+        let version = input[8];
+        input = &input[9..];
+        let mut read_size = || -> Result<usize> {
+            let size = crate::instance::take(&mut input, 4)?;
+            Ok(u32::from_le_bytes(size.try_into().unwrap()) as usize)
         };
+        let cdc = match version {
+            1 => Ok(CdcParams::default()),
+            2 => CdcParams::new(read_size()?),
+            STATE_VERSION => {
+                let (avg, min, max) = (read_size()?, read_size()?, read_size()?);
+                CdcParams::with_sizes(avg, min, max)
+            }
+            _ => return Err(invalid(&format!("unsupported state version {version}"))),
+        }
+        .map_err(|_| invalid("invalid chunk sizes in state"))?;
+        let mut data_hasher = DataHasher::read_state(&mut input)?;
+        data_hasher.cdc = cdc;
+        let instance_hasher = InstanceHasher::read_state(&mut input)?;
+        if !input.is_empty() {
+            return Err(invalid("trailing bytes after state"));
+        }
+        Ok((cdc, data_hasher, instance_hasher))
+    }
 
-        // Construct header
-        let main_type: u8 = 0b0101; // ISCC composite code
-        let sub_type: u8 = if wide { 0b0111 } else { 0b0101 }; // SUM or SUM wide
-        let version: u8 = 0b0000; // V0
-        let length: u8 = 0b0000; // no optional units in header
+    /// Full Data-Code digest followed by the Instance-Code digest (64 bytes)
+    #[cfg(feature = "digest")]
+    pub(crate) fn digests(&self) -> Vec<u8> {
+        let mut digests = self.data_hasher.digest();
+        digests.extend_from_slice(&self.instance_hasher.digest());
+        digests
+    }
 
-        let header_byte1: u8 = (main_type << 4) | sub_type;
-        let header_byte2: u8 = (version << 4) | length;
+    /// Get the ISCC-SUM result for the data processed so far, as configured by
+    /// the options.
+    ///
+    /// Does not change the processor: it can be called mid-stream for an
+    /// interim code of a growing input and again after more updates.
+    pub fn result(&self) -> IsccSumResult {
+        self.result_for(&self.options)
+    }
 
-        // Combine header and body
-        let mut iscc_bytes = vec![header_byte1, header_byte2];
-        iscc_bytes.extend_from_slice(data_code);
-        iscc_bytes.extend_from_slice(instance_code);
+    /// Result as requested by `options` (extra hashes aside, which are fixed
+    /// when the processor is created)
+    fn result_for(&self, options: &IsccSumOptions) -> IsccSumResult {
+        let (bits, add_units) = (options.bits, options.add_units);
+        // Get digests
+        let data_digest = self.data_hasher.digest();
+        let instance_digest = self.instance_hasher.digest();
 
-        // Base32 encode without padding
-        let iscc_code = base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &iscc_bytes);
-        let iscc = format!("ISCC:{iscc_code}");
+        let iscc = sum_code(&data_digest, &instance_digest, bits);
 
         // Get datahash and filesize
-        let datahash = self.instance_hasher.multihash();
+        let datahash = self
+            .instance_hasher
+            .encoded_multihash(options.datahash_encoding);
         let filesize = self.instance_hasher.filesize();
 
         // Build units if requested
@@ -164,22 +702,66 @@ impl IsccSumProcessor {
         };
 
         // Create and return IsccSumResult
-        IsccSumResult::new(iscc, datahash, filesize, units)
+        let mut result = IsccSumResult::new(iscc, datahash, filesize, units);
+        result.hashes = self
+            .extra_hashers
+            .iter()
+            .map(|(algorithm, hasher)| {
+                (
+                    algorithm.name().to_string(),
+                    hasher.hexdigest(&instance_digest),
+                )
+            })
+            .collect();
+        if options.add_minhash {
+            result.minhash = Some(self.data_hasher.minhash().values().to_vec());
+        }
+        if options.add_features {
+            result.features = Some(self.data_hasher.features());
+        }
+        result.segments = self
+            .segments
+            .as_ref()
+            .map(|segments| segments.results(options));
+        result.nonstandard = !options.is_standard();
+        result
     }
 }
 
+// Processors are handed between the threads of servers and pools
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<IsccSumProcessor>();
+    assert_send_sync::<DataHasher>();
+    assert_send_sync::<InstanceHasher>();
+};
+
 impl Default for IsccSumProcessor {
     fn default() -> Self {
         Self::new()
     }
 }
 
+#[cfg(feature = "python")]
 #[pymethods]
 impl IsccSumProcessor {
-    /// Create a new ISCC-SUM processor
+    /// Create a processor, optionally computing extra digests ("md5", "sha1",
+    /// "sha256" or "blake3") and the ISCC-SUMs of segments of `segment_size`
+    /// bytes in the same pass
     #[new]
-    fn py_new() -> Self {
-        Self::new()
+    #[pyo3(signature = (hashes=None, segment_size=None))]
+    fn py_new(hashes: Option<Vec<String>>, segment_size: Option<u64>) -> PyResult<Self> {
+        let algorithms = hashes
+            .unwrap_or_default()
+            .iter()
+            .map(|name| name.parse::<HashAlgorithm>())
+            .collect::<Result<Vec<_>, String>>()
+            .map_err(PyValueError::new_err)?;
+        let mut builder = IsccSumOptions::builder().hashes(algorithms);
+        if let Some(size) = segment_size {
+            builder = builder.segment_size(size);
+        }
+        Ok(Self::with_options(builder.build()?))
     }
 
     /// Update the processor with new data
@@ -188,41 +770,236 @@ impl IsccSumProcessor {
         self.update(data);
     }
 
-    /// Get the final ISCC-SUM result
-    #[pyo3(name = "result")]
-    fn py_result(&mut self, wide: bool, add_units: bool) -> PyResult<IsccSumResult> {
-        Ok(self.result(wide, add_units))
+    /// Get the ISCC-SUM result; `bits` (64, 128, 192 or 256) overrides `wide`.
+    /// `add_minhash` and `add_features` add the raw Data-Code similarity features.
+    #[pyo3(
+        name = "result",
+        signature = (wide=false, add_units=false, bits=None, add_minhash=false, add_features=false)
+    )]
+    fn py_result(
+        &self,
+        wide: bool,
+        add_units: bool,
+        bits: Option<u32>,
+        add_minhash: bool,
+        add_features: bool,
+    ) -> PyResult<IsccSumResult> {
+        let options = py_options(wide, add_units, bits)
+            .add_minhash(add_minhash)
+            .add_features(add_features)
+            .build()?;
+        Ok(self.result_for(&options))
     }
-}
 
-/// Generate ISCC-SUM from a file path (Python-exposed function)
-#[pyfunction]
-#[pyo3(signature = (filepath, wide=false, add_units=true))]
-pub fn code_iscc_sum(filepath: &str, wide: bool, add_units: bool) -> PyResult<IsccSumResult> {
-    let path = Path::new(filepath);
-    let mut file = File::open(path)
-        .map_err(|e| pyo3::exceptions::PyIOError::new_err(format!("Failed to open file: {e}")))?;
+    /// Return a copy of the processor to take interim results from
+    #[pyo3(name = "copy")]
+    fn py_copy(&self) -> Self {
+        self.clone()
+    }
+
+    /// Serialize the processor state as bytes
+    #[pyo3(name = "serialize_state")]
+    fn py_serialize_state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new(py, &self.serialize_state()?))
+    }
+
+    /// Recreate a processor from serialized state bytes
+    #[staticmethod]
+    #[pyo3(name = "restore_state")]
+    fn py_restore_state(state: &[u8]) -> PyResult<Self> {
+        Ok(Self::restore_state(state)?)
+    }
 
-    let mut processor = IsccSumProcessor::new();
-    let mut buffer = vec![0; 2 * 1024 * 1024]; // 2MB buffer
+    /// Create a processor for the part of a stream starting at offset
+    #[staticmethod]
+    #[pyo3(name = "for_range")]
+    fn py_for_range(offset: u64) -> PyResult<Self> {
+        Ok(Self::for_range(offset)?)
+    }
 
+    /// Append the processor of the directly following range
+    #[pyo3(name = "merge")]
+    fn py_merge(&mut self, mut next: PyRefMut<'_, Self>) -> PyResult<()> {
+        let next = std::mem::take(&mut *next);
+        Ok(self.merge(next)?)
+    }
+}
+
+/// Hash all bytes of a reader, checking the cancellation token of the options
+/// and calling `check` before every read so the caller can stop early.
+/// Read errors are reported for `path` if given.
+fn hash_reader_checked<R, E, F>(
+    mut reader: R,
+    path: Option<&Path>,
+    options: &IsccSumOptions,
+    mut check: F,
+) -> Result<IsccSumResult, E>
+where
+    R: Read,
+    E: From<Error>,
+    F: FnMut() -> Result<(), E>,
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("hash", path = ?path).entered();
+    let mut processor = IsccSumProcessor::with_options(options.clone());
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
     loop {
-        let bytes_read = file.read(&mut buffer).map_err(|e| {
-            pyo3::exceptions::PyIOError::new_err(format!("Failed to read file: {e}"))
-        })?;
-        if bytes_read == 0 {
-            break;
+        options.check_cancelled()?;
+        check()?;
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => processor.update(&buffer[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %e, bytes = processor.filesize(), "read failed");
+                #[cfg(feature = "metrics")]
+                crate::telemetry::record_error();
+                let error = match path {
+                    Some(path) => Error::io(path, e),
+                    None => e.into(),
+                };
+                return Err(error.into());
+            }
         }
-        processor.update(&buffer[..bytes_read]);
     }
+    #[cfg(feature = "tracing")]
+    tracing::debug!(bytes = processor.filesize(), "hashed");
+    Ok(processor.result())
+}
+
+/// Generate ISCC-SUM for all bytes of a reader
+pub fn hash_reader<R: Read>(reader: R, options: &IsccSumOptions) -> Result<IsccSumResult> {
+    hash_reader_checked(reader, None, options, || Ok(()))
+}
 
-    Ok(processor.result(wide, add_units))
+/// Generate ISCC-SUM for the contents of a file
+pub fn hash_file<P: AsRef<Path>>(path: P, options: &IsccSumOptions) -> Result<IsccSumResult> {
+    let path = path.as_ref();
+    #[cfg(feature = "tracing")]
+    tracing::debug!(path = %path.display(), "open");
+    let file = File::open(path).map_err(|e| {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(path = %path.display(), error = %e, "open failed");
+        #[cfg(feature = "metrics")]
+        crate::telemetry::record_error();
+        Error::io(path, e)
+    })?;
+    let result = hash_reader_checked::<_, Error, _>(file, Some(path), options, || Ok(()))?;
+    #[cfg(feature = "metrics")]
+    crate::telemetry::record_file();
+    Ok(result)
+}
+
+/// Generate ISCC-SUMs for the files of a directory tree, in the order and with
+/// the ignore rules of [`treewalk_iscc`].
+///
+/// The progress callback of the options receives the bytes hashed across all
+/// files and the number of completed files.
+#[cfg(feature = "treewalk")]
+pub fn hash_tree<P: AsRef<Path>>(
+    root: P,
+    options: &IsccSumOptions,
+) -> Result<Vec<(PathBuf, IsccSumResult)>> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("hash_tree", root = %root.as_ref().display()).entered();
+    let mut results = Vec::new();
+    let mut done = Progress::default();
+    for path in treewalk_iscc(root)? {
+        let mut file_options = options.clone();
+        file_options.progress = options.progress.clone().map(|callback| {
+            ProgressCallback::new(move |progress: Progress| {
+                callback.report(Progress {
+                    bytes: done.bytes + progress.bytes,
+                    files: done.files,
+                })
+            })
+        });
+        let result = hash_file(&path, &file_options)?;
+        done.bytes += result.filesize;
+        done.files += 1;
+        if let Some(callback) = &options.progress {
+            callback.report(done);
+        }
+        results.push((path, result));
+    }
+    Ok(results)
+}
+
+/// Generate ISCC-SUM for a byte slice
+pub fn hash_bytes(data: &[u8], options: &IsccSumOptions) -> IsccSumResult {
+    let mut processor = IsccSumProcessor::with_options(options.clone());
+    processor.update(data);
+    processor.result()
+}
+
+/// Generate ISCC-SUM from a file path
+pub fn code_iscc_sum<P: AsRef<Path>>(
+    path: P,
+    wide: bool,
+    add_units: bool,
+) -> Result<IsccSumResult> {
+    let options = IsccSumOptions::builder()
+        .wide(wide)
+        .add_units(add_units)
+        .build()?;
+    hash_file(path, &options)
+}
+
+/// Options from the Python arguments, where `bits` takes precedence over `wide`
+#[cfg(feature = "python")]
+fn py_options(
+    wide: bool,
+    add_units: bool,
+    bits: Option<u32>,
+) -> crate::options::IsccSumOptionsBuilder {
+    let builder = IsccSumOptions::builder().wide(wide).add_units(add_units);
+    match bits {
+        Some(bits) => builder.bits(bits),
+        None => builder,
+    }
+}
+
+/// Generate ISCC-SUM from a file path (Python-exposed function).
+///
+/// Checks for signals between buffers, so Ctrl-C interrupts hashing of large
+/// files with `KeyboardInterrupt`.
+#[cfg(feature = "python")]
+#[pyfunction]
+#[pyo3(
+    name = "code_iscc_sum",
+    signature = (filepath, wide=false, add_units=true, bits=None, segment_size=None)
+)]
+pub fn py_code_iscc_sum(
+    py: Python<'_>,
+    filepath: &str,
+    wide: bool,
+    add_units: bool,
+    bits: Option<u32>,
+    segment_size: Option<u64>,
+) -> PyResult<IsccSumResult> {
+    let path = Path::new(filepath);
+    let mut builder = py_options(wide, add_units, bits);
+    if let Some(size) = segment_size {
+        builder = builder.segment_size(size);
+    }
+    let options = builder.build()?;
+    let file = File::open(path).map_err(|e| Error::io(path, e))?;
+    hash_reader_checked(file, Some(path), &options, || py.check_signals())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn options(wide: bool, add_units: bool) -> IsccSumOptions {
+        IsccSumOptions::builder()
+            .wide(wide)
+            .add_units(add_units)
+            .build()
+            .unwrap()
+    }
+
     #[test]
     fn test_iscc_sum_processor() {
         let processor = IsccSumProcessor::new();
@@ -233,4 +1010,426 @@ mod tests {
             &processor.data_hasher as *const _
         ));
     }
+
+    #[test]
+    fn test_segments() {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i * 13 % 239) as u8).collect();
+        let wide = options(true, false);
+        let segmented = wide.to_builder().segment_size(100_000).build().unwrap();
+        let mut processor = IsccSumProcessor::with_options(segmented.clone());
+        for part in data.chunks(65_536) {
+            processor.update(part);
+        }
+        assert!(processor.serialize_state().is_err());
+        let result = processor.result();
+        let whole = hash_bytes(&data, &wide);
+        assert_eq!(result.iscc, whole.iscc);
+
+        // Each segment is the ISCC-SUM of its bytes; the last one is shorter
+        let segments = result.segments.unwrap();
+        let bounds: Vec<(u64, u64)> = segments.iter().map(|s| (s.offset, s.size)).collect();
+        assert_eq!(
+            bounds,
+            [(0, 100_000), (100_000, 100_000), (200_000, 100_000)]
+        );
+        for segment in &segments {
+            let start = segment.offset as usize;
+            let part = hash_bytes(&data[start..start + segment.size as usize], &wide);
+            assert_eq!(
+                (&segment.iscc, &segment.datahash),
+                (&part.iscc, &part.datahash)
+            );
+        }
+        let partial = hash_bytes(&data[..150_000], &segmented).segments.unwrap();
+        assert_eq!(partial.len(), 2);
+        assert_eq!(partial[1].size, 50_000);
+        assert_eq!(hash_bytes(&[], &segmented).segments, Some(Vec::new()));
+        assert_eq!(hash_bytes(&data, &IsccSumOptions::default()).segments, None);
+
+        // Segment hashing cannot be saved or merged
+        assert!(processor.fork(0).is_err());
+    }
+
+    #[test]
+    fn test_result_bits() {
+        let data = b"Hello World";
+        let full = hash_bytes(data, &options(false, true)).units.unwrap();
+        let full: Vec<IsccCode> = full.iter().map(|unit| unit.parse().unwrap()).collect();
+        for bits in crate::codec::SUM_BITS {
+            let options = IsccSumOptions::builder().bits(bits).build().unwrap();
+            let code: IsccCode = hash_bytes(data, &options).iscc.parse().unwrap();
+            assert_eq!(code.bits() as u32, 2 * bits);
+            let units = code.units();
+            for (unit, full) in units.iter().zip(&full) {
+                assert_eq!(unit.maintype(), full.maintype());
+                assert_eq!(unit.body(), &full.body()[..bits as usize / 8]);
+            }
+            assert_eq!(crate::codec::compose(&units, bits), Ok(code));
+        }
+        assert!(IsccSumOptions::builder().bits(320).build().is_err());
+    }
+
+    #[test]
+    fn test_similarity_features() {
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i * 7 % 253) as u8).collect();
+        let plain = hash_bytes(&data, &IsccSumOptions::default());
+        assert_eq!((plain.minhash, plain.features), (None, None));
+
+        let options = IsccSumOptions::builder()
+            .add_minhash(true)
+            .add_features(true)
+            .build()
+            .unwrap();
+        let result = hash_bytes(&data, &options);
+        let (chunks, tail) = crate::cdc::cdc_chunks(&data, false, 1024).unwrap();
+        let features: Vec<u32> = chunks
+            .iter()
+            .chain([&tail])
+            .map(|chunk| xxhash_rust::xxh32::xxh32(chunk, 0))
+            .collect();
+        assert_eq!(result.features.as_ref(), Some(&features));
+        let mut minhash = crate::minhash::MinHash::default();
+        minhash.add_features(&features);
+        assert_eq!(result.minhash.as_deref(), Some(minhash.values()));
+        assert_eq!(result.iscc, plain.iscc);
+    }
+
+    #[test]
+    fn test_datahash_encoding() {
+        let plain = hash_bytes(b"Hello World", &IsccSumOptions::default());
+        let options = IsccSumOptions::builder()
+            .datahash_encoding(crate::options::DatahashEncoding::Cid)
+            .hash(HashAlgorithm::Blake3)
+            .build()
+            .unwrap();
+        let result = hash_bytes(b"Hello World", &options);
+        assert!(result.datahash.starts_with("bafkr4i"));
+        assert_eq!(result.hashes["blake3"], plain.datahash[4..]);
+        assert_eq!(result.iscc, plain.iscc);
+    }
+
+    #[test]
+    fn test_keyed_instance_code() {
+        use crate::instance::InstanceKey;
+
+        let plain = hash_bytes(b"Hello World", &options(false, true));
+        let keyed_options = |secret: &[u8]| {
+            options(false, true)
+                .to_builder()
+                .instance_key(InstanceKey::derive("tenant", secret))
+                .hash(HashAlgorithm::Blake3)
+                .build()
+                .unwrap()
+        };
+        let keyed = hash_bytes(b"Hello World", &keyed_options(b"one"));
+        assert!(!keyed_options(b"one").is_standard());
+        assert!(keyed.nonstandard && !plain.nonstandard);
+        assert_ne!(keyed.datahash, plain.datahash);
+        assert_ne!(keyed.iscc, plain.iscc);
+        assert_ne!(
+            keyed.datahash,
+            hash_bytes(b"Hello World", &keyed_options(b"two")).datahash
+        );
+        // The Data-Code and the requested plain BLAKE3 are unaffected
+        assert_eq!(keyed.units.unwrap()[0], plain.units.unwrap()[0]);
+        assert_eq!(keyed.hashes["blake3"], plain.datahash[4..]);
+
+        let processor = IsccSumProcessor::with_options(keyed_options(b"one"));
+        assert!(processor.serialize_state().is_err());
+    }
+
+    #[test]
+    fn test_interim_results() {
+        let data: Vec<u8> = (0..150_000u32).map(|i| (i * 19 % 241) as u8).collect();
+        let options = options(false, true);
+        let mut processor = IsccSumProcessor::with_options(options.clone());
+        processor.update(&data[..50_000]);
+        let interim = processor.result();
+        assert_eq!(processor.result(), interim);
+        assert_eq!(interim, hash_bytes(&data[..50_000], &options));
+        processor.update(&data[50_000..]);
+        assert_eq!(processor.result(), hash_bytes(&data, &options));
+    }
+
+    #[test]
+    fn test_convenience_functions_agree() {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        std::fs::write(&path, &data).unwrap();
+        let extra = options(true, true).to_builder().hash(HashAlgorithm::Blake3);
+        #[cfg(feature = "hashes")]
+        let extra = extra.hash(HashAlgorithm::Md5);
+        let extra = extra.build().unwrap();
+        for options in [IsccSumOptions::default(), extra] {
+            let expected = hash_bytes(&data, &options);
+            assert_eq!(expected.units.is_some(), options.add_units());
+            assert_eq!(expected.hashes.len(), options.hashes().len());
+            assert_eq!(hash_reader(&data[..], &options).unwrap(), expected);
+            assert_eq!(hash_file(&path, &options).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "treewalk")]
+    fn test_progress() {
+        use std::sync::{Arc, Mutex};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.txt"), vec![1u8; 3000]).unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/b.txt"), b"Hello World").unwrap();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        let options = IsccSumOptions::builder()
+            .progress(move |progress| sink.lock().unwrap().push(progress))
+            .build()
+            .unwrap();
+
+        let mut processor = IsccSumProcessor::with_options(options.clone());
+        processor.update(b"Hello");
+        processor.update(b" World");
+        let expected = [(5, 0), (11, 0)].map(|(bytes, files)| Progress { bytes, files });
+        assert_eq!(*reports.lock().unwrap(), expected);
+
+        reports.lock().unwrap().clear();
+        let results = hash_tree(dir.path(), &options).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].0, dir.path().join("sub/b.txt"));
+        assert_eq!(results[1].1.filesize, 11);
+        let expected = [(3000, 0), (3000, 1), (3011, 1), (3011, 2)]
+            .map(|(bytes, files)| Progress { bytes, files });
+        assert_eq!(*reports.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let token = crate::cancel::CancellationToken::new();
+        let options = IsccSumOptions::builder()
+            .cancellation(token.clone())
+            .build()
+            .unwrap();
+        assert!(hash_reader(&b"data"[..], &options).is_ok());
+        token.cancel();
+        let error = hash_reader(&b"data"[..], &options).unwrap_err();
+        assert!(matches!(error, Error::Cancelled));
+    }
+
+    #[test]
+    fn test_code_iscc_sum() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("hello.txt");
+        std::fs::write(&path, b"Hello World").unwrap();
+        let mut processor = IsccSumProcessor::with_options(options(true, true));
+        processor.update(b"Hello World");
+        assert_eq!(
+            code_iscc_sum(&path, true, true).unwrap(),
+            processor.result()
+        );
+        let error = code_iscc_sum(dir.path().join("missing"), false, false).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(matches!(&error, Error::Io { path: Some(p), .. } if p.ends_with("missing")));
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let data: Vec<u8> = (0..700_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let mut whole = IsccSumProcessor::with_options(options(false, true));
+        whole.update(&data);
+        let expected = whole.result();
+
+        let align = RANGE_ALIGNMENT as usize;
+        let bounds = [0, 2 * align, 3 * align, 9 * align, data.len()];
+        let mut parts = bounds.windows(2).map(|w| {
+            let mut part = IsccSumProcessor::for_range(w[0] as u64).unwrap();
+            part.update(&data[w[0]..w[1]]);
+            let state = part.serialize_state().unwrap();
+            IsccSumProcessor::restore_state_with_options(&state, options(false, true)).unwrap()
+        });
+        let mut merged = parts.next().unwrap();
+        for part in parts {
+            merged.merge(part).unwrap();
+        }
+        let result = merged.result();
+        assert_eq!(result.iscc, expected.iscc);
+        assert_eq!(result.units, expected.units);
+        assert_eq!(result.filesize, data.len() as u64);
+
+        // Ranges must be adjacent and aligned
+        assert!(IsccSumProcessor::for_range(1000).is_err());
+        let mut first = IsccSumProcessor::new();
+        first.update(&data[..align]);
+        let mut gap = IsccSumProcessor::for_range(2 * align as u64).unwrap();
+        gap.update(&data[2 * align..]);
+        assert!(first.merge(gap).is_err());
+        assert_eq!(first.filesize(), align as u64);
+        let next = IsccSumProcessor::for_range(align as u64).unwrap();
+        let segmented = options(false, false)
+            .to_builder()
+            .segment_size(align as u64)
+            .build()
+            .unwrap();
+        let mut segmented = IsccSumProcessor::with_options(segmented);
+        segmented.update(&data[..align]);
+        assert!(segmented.merge(next).is_err());
+    }
+
+    #[test]
+    fn test_fork_threads() {
+        use crate::instance::InstanceKey;
+
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let data: Vec<u8> = (0..500_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let options = IsccSumOptions::builder()
+            .instance_key(InstanceKey::derive("tenant", b"secret"))
+            .min_chunk_size(128)
+            .build()
+            .unwrap();
+        let mut whole = IsccSumProcessor::with_options(options.clone());
+        whole.update(&data);
+
+        // Hash the ranges on worker threads and merge them back in order
+        let align = RANGE_ALIGNMENT as usize;
+        let bounds = [0, 3 * align, 5 * align, data.len()];
+        let mut first = IsccSumProcessor::with_options(options);
+        let forks: Vec<IsccSumProcessor> = bounds[1..]
+            .windows(2)
+            .map(|w| first.fork(w[0] as u64).unwrap())
+            .collect();
+        let parts = std::thread::scope(|scope| {
+            let handles: Vec<_> = forks
+                .into_iter()
+                .zip(bounds[1..].windows(2))
+                .map(|(mut part, w)| {
+                    let data = &data[w[0]..w[1]];
+                    scope.spawn(move || {
+                        part.update(data);
+                        part
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        first.update(&data[..bounds[1]]);
+        for part in parts {
+            first.merge(part).unwrap();
+        }
+        assert_eq!(first.result(), whole.result());
+
+        // A reset processor starts over with the same options
+        first.reset();
+        first.update(&data);
+        assert_eq!(first.result(), whole.result());
+        assert_eq!(
+            first.options().instance_key(),
+            whole.options().instance_key()
+        );
+        assert!(first.fork(1000).is_err());
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_clone_snapshot() {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i * 7 % 253) as u8).collect();
+        let options = IsccSumOptions::builder()
+            .hashes([HashAlgorithm::Sha256])
+            .build()
+            .unwrap();
+        let mut processor = IsccSumProcessor::with_options(options.clone());
+        processor.update(&data[..100_000]);
+        let mut snapshot = processor.clone();
+        processor.update(&data[100_000..]);
+
+        let mut head = IsccSumProcessor::with_options(options.clone());
+        head.update(&data[..100_000]);
+        assert_eq!(snapshot.result(), head.result());
+
+        // The snapshot continues independently of the original
+        snapshot.update(&data[100_000..]);
+        let mut whole = IsccSumProcessor::with_options(options);
+        whole.update(&data);
+        assert_eq!(processor.result(), whole.result());
+        assert_eq!(snapshot.result(), whole.result());
+    }
+
+    #[test]
+    fn test_state_resume() {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i * 7 % 253) as u8).collect();
+        let mut whole = IsccSumProcessor::with_options(options(true, true));
+        whole.update(&data);
+        let expected = whole.result();
+
+        let mut first = IsccSumProcessor::new();
+        first.update(&data[..123_457]);
+        let state = first.serialize_state().unwrap();
+        let mut resumed =
+            IsccSumProcessor::restore_state_with_options(&state, options(true, true)).unwrap();
+        assert_eq!(resumed.filesize(), 123_457);
+        resumed.update(&data[123_457..]);
+        let result = resumed.result();
+        assert_eq!(result.iscc, expected.iscc);
+        assert_eq!(result.datahash, expected.datahash);
+        assert_eq!(result.units, expected.units);
+
+        assert!(IsccSumProcessor::restore_state(b"ISUMSTAT").is_err());
+        let mut future = state.clone();
+        future[8] = STATE_VERSION + 1;
+        assert!(IsccSumProcessor::restore_state(&future).is_err());
+        assert!(IsccSumProcessor::restore_state(&state[..state.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_options_in_state() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i * 13 % 251) as u8).collect();
+        let coarse = IsccSumOptions::builder()
+            .avg_chunk_size(4096)
+            .min_chunk_size(512)
+            .build()
+            .unwrap();
+        let expected = hash_bytes(&data, &coarse);
+        assert_ne!(
+            expected.iscc,
+            hash_bytes(&data, &IsccSumOptions::default()).iscc
+        );
+
+        let mut first = IsccSumProcessor::with_options(coarse.clone());
+        first.update(&data[..50_000]);
+        let state = first.serialize_state().unwrap();
+        let mut resumed = IsccSumProcessor::restore_state(&state).unwrap();
+        assert_eq!(resumed.options(), &coarse);
+        resumed.update(&data[50_000..]);
+        assert_eq!(resumed.result(), expected);
+        assert!(
+            IsccSumProcessor::restore_state_with_options(&state, IsccSumOptions::default())
+                .is_err()
+        );
+
+        // The state of extra hashes cannot be saved
+        let extra = coarse
+            .to_builder()
+            .hash(HashAlgorithm::Blake3)
+            .build()
+            .unwrap();
+        assert!(IsccSumProcessor::with_options(extra)
+            .serialize_state()
+            .is_err());
+    }
 }
@@ -0,0 +1,108 @@
+//! Metrics recorded through the [`metrics`] facade.
+//!
+//! The library only emits counters and histograms; an application installs a
+//! recorder (such as `metrics-exporter-prometheus`, which `isum daemon serve
+//! --metrics` uses) to collect them. Without a recorder recording is a no-op.
+//! [`describe`] registers the units and help texts of all metrics.
+
+use metrics::{counter, describe_counter, describe_histogram, histogram, Unit};
+
+/// Counter of bytes passed to ISCC-SUM processors
+pub const BYTES_HASHED: &str = "iscc_sum_bytes_hashed_total";
+/// Counter of files hashed completely
+pub const FILES_HASHED: &str = "iscc_sum_files_hashed_total";
+/// Histogram of Data-Code chunk sizes in bytes
+pub const CHUNK_SIZE: &str = "iscc_sum_chunk_size_bytes";
+/// Counter of files that could not be opened or read
+pub const ERRORS: &str = "iscc_sum_errors_total";
+
+/// Register the units and descriptions of the library metrics with the
+/// installed recorder
+pub fn describe() {
+    describe_counter!(BYTES_HASHED, Unit::Bytes, "Bytes hashed");
+    describe_counter!(FILES_HASHED, Unit::Count, "Files hashed");
+    describe_histogram!(CHUNK_SIZE, Unit::Bytes, "Data-Code chunk sizes");
+    describe_counter!(
+        ERRORS,
+        Unit::Count,
+        "Files that could not be opened or read"
+    );
+}
+
+pub(crate) fn record_bytes(bytes: usize) {
+    counter!(BYTES_HASHED).increment(bytes as u64);
+}
+
+pub(crate) fn record_file() {
+    counter!(FILES_HASHED).increment(1);
+}
+
+pub(crate) fn record_chunk(length: usize) {
+    histogram!(CHUNK_SIZE).record(length as f64);
+}
+
+pub(crate) fn record_error() {
+    counter!(ERRORS).increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::IsccSumOptions;
+    use crate::sum::{hash_file, hash_reader};
+    use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Recorder keeping the counters by name
+    #[derive(Default)]
+    struct Counters(Mutex<HashMap<String, Arc<AtomicU64>>>);
+
+    impl Counters {
+        fn get(&self, name: &str) -> u64 {
+            self.0
+                .lock()
+                .unwrap()
+                .get(name)
+                .map_or(0, |value| value.load(Ordering::Relaxed))
+        }
+    }
+
+    impl Recorder for Counters {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let mut counters = self.0.lock().unwrap();
+            let value = counters.entry(key.name().to_string()).or_default();
+            Counter::from_arc(value.clone())
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    #[test]
+    fn test_counters() {
+        let recorder = Counters::default();
+        let options = IsccSumOptions::default();
+        metrics::with_local_recorder(&recorder, || {
+            describe();
+            hash_reader(&b"hello world"[..], &options).unwrap();
+            let temp = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(temp.path(), b"data").unwrap();
+            hash_file(temp.path(), &options).unwrap();
+            assert!(hash_file(temp.path().with_extension("missing"), &options).is_err());
+        });
+        assert_eq!(recorder.get(BYTES_HASHED), 15);
+        assert_eq!(recorder.get(FILES_HASHED), 1);
+        assert_eq!(recorder.get(ERRORS), 1);
+    }
+}
@@ -1,44 +1,162 @@
 // Rust implementation of the treewalk algorithm for deterministic file tree traversal
 
-use globset::{Glob, GlobSet, GlobSetBuilder};
-use std::cmp::Ordering;
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::fmt;
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
 use unicode_normalization::UnicodeNormalization;
 
+use crate::cancel::CancellationToken;
+use crate::error::{Error, Result};
+
 /// Represents a directory entry with type information
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DirEntry {
+    /// File name; names that are not valid UTF-8 are converted lossily, `path`
+    /// keeps the exact name
     pub name: String,
     pub path: std::path::PathBuf,
     pub is_dir: bool,
     pub is_file: bool,
+    /// True for a followed symlink (on Windows also a junction or volume mount
+    /// point); the other fields describe its target
+    pub is_symlink: bool,
+    /// Size in bytes from the metadata read while listing
+    pub size: u64,
+    /// Modification time, if the platform provides it
+    pub modified: Option<SystemTime>,
 }
 
-/// Error types for treewalk operations
-#[derive(Debug)]
-pub enum TreewalkError {
-    IoError(io::Error),
-    InvalidPath(String),
+/// How symlinks are treated when listing and walking directories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SymlinkPolicy {
+    /// Leave symlinks out
+    #[default]
+    Skip,
+    /// List symlinks to files as the files they point to, skip the others
+    FollowFiles,
+    /// List symlinks to files and walk into symlinks to directories; a symlink
+    /// to one of its own ancestor directories is an error
+    FollowAll,
 }
 
-impl From<io::Error> for TreewalkError {
-    fn from(err: io::Error) -> Self {
-        TreewalkError::IoError(err)
+impl SymlinkPolicy {
+    /// Names of all policies, as accepted by [`SymlinkPolicy::from_str`]
+    pub const NAMES: [&'static str; 3] = ["skip", "follow-files", "follow-all"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SymlinkPolicy::Skip => "skip",
+            SymlinkPolicy::FollowFiles => "follow-files",
+            SymlinkPolicy::FollowAll => "follow-all",
+        }
     }
 }
 
-impl std::fmt::Display for TreewalkError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            TreewalkError::IoError(err) => write!(f, "IO error: {err}"),
-            TreewalkError::InvalidPath(path) => write!(f, "Invalid path: {path}"),
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "skip" => Ok(SymlinkPolicy::Skip),
+            "follow-files" => Ok(SymlinkPolicy::FollowFiles),
+            "follow-all" => Ok(SymlinkPolicy::FollowAll),
+            _ => Err(format!("unknown symlink policy: {name}")),
         }
     }
 }
 
-impl std::error::Error for TreewalkError {}
+impl fmt::Display for SymlinkPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Identity of a directory for cycle detection: device and inode on Unix,
+/// volume serial number and file index on Windows (so a junction and its
+/// target are the same directory), the canonical path elsewhere
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(windows)]
+type DirId = (u32, u64);
+#[cfg(not(any(unix, windows)))]
+type DirId = PathBuf;
+
+fn dir_id(path: &Path) -> Result<DirId> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = fs::metadata(path).map_err(|e| Error::io(path, e))?;
+        Ok((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::Storage::FileSystem::{
+            GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, FILE_FLAG_BACKUP_SEMANTICS,
+        };
+
+        // Directories can only be opened with backup semantics; no access
+        // rights are needed to query the file index
+        let file = fs::OpenOptions::new()
+            .access_mode(0)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+            .open(path)
+            .map_err(|e| Error::io(path, e))?;
+        // SAFETY: BY_HANDLE_FILE_INFORMATION is plain data, all-zero is a valid value
+        let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+        // SAFETY: the handle is open for the lifetime of `file` and `info` is a valid buffer
+        if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut info) } == 0 {
+            return Err(Error::io(path, io::Error::last_os_error()));
+        }
+        let index = (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow);
+        Ok((info.dwVolumeSerialNumber, index))
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        fs::canonicalize(path).map_err(|e| Error::io(path, e))
+    }
+}
+
+/// Directories from the root of a walk down to the current directory; only
+/// tracked when symlinks to directories are followed
+#[derive(Debug, Default)]
+struct Ancestors(Vec<DirId>);
+
+impl Ancestors {
+    /// Enter `dir`, failing if a symlink led back to one of its ancestors
+    fn enter(&mut self, dir: &Path, symlinks: SymlinkPolicy) -> Result<()> {
+        if symlinks != SymlinkPolicy::FollowAll {
+            return Ok(());
+        }
+        let id = dir_id(dir)?;
+        if self.0.contains(&id) {
+            return Err(Error::io(
+                dir,
+                io::Error::other("symlink cycle: directory is its own ancestor"),
+            ));
+        }
+        self.0.push(id);
+        Ok(())
+    }
+
+    fn leave(&mut self, symlinks: SymlinkPolicy) {
+        if symlinks == SymlinkPolicy::FollowAll {
+            self.0.pop();
+        }
+    }
+}
 
 /// Represents a single gitignore pattern with metadata
 #[derive(Debug, Clone)]
@@ -50,8 +168,104 @@ struct PatternEntry {
     pattern: String,
     /// True if this is a whitelist pattern (starts with !)
     is_whitelist: bool,
+    /// True if the pattern only matches directories (ends with /)
+    dir_only: bool,
     /// Line number in the ignore file (for precedence)
     line_number: usize,
+    /// Ignore file the pattern was read from
+    source: Option<PathBuf>,
+    /// True if the pattern matches regardless of letter case
+    case_insensitive: bool,
+}
+
+impl PatternEntry {
+    /// Glob matching the relative paths the pattern applies to; as in git,
+    /// `*` and `?` do not match `/`
+    fn glob(&self) -> Result<Glob> {
+        Ok(GlobBuilder::new(&self.glob_pattern())
+            .literal_separator(true)
+            .case_insensitive(self.case_insensitive)
+            .build()?)
+    }
+
+    fn glob_pattern(&self) -> String {
+        // Directory patterns match the directory itself, its contents are
+        // excluded along with it
+        let pattern = self.pattern.strip_suffix('/').unwrap_or(&self.pattern);
+        let pattern = normalize_asterisks(pattern);
+        if pattern.contains('/') {
+            // A slash at the beginning or in the middle anchors the pattern to
+            // the directory of the ignore file
+            pattern
+                .strip_prefix('/')
+                .map_or_else(|| pattern.clone(), str::to_string)
+        } else {
+            // Other patterns match at any level below it
+            format!("**/{pattern}")
+        }
+    }
+}
+
+/// Compiled globs of the patterns read from one ignore file
+#[derive(Debug, Clone)]
+struct PatternSet {
+    /// Directory of the ignore file relative to the walk root; the patterns
+    /// only apply below it and are anchored to it
+    base: PathBuf,
+    /// Walk root relative to the directory of an ignore file above it; walked
+    /// paths are matched below this prefix
+    prefix: PathBuf,
+    /// Index of the first pattern of the set in `IgnoreSpec::entries`
+    start: usize,
+    /// Globs of the patterns in order of appearance
+    globs: GlobSet,
+}
+
+impl PatternSet {
+    /// Path relative to the directory of the ignore file, if the patterns apply to it
+    fn relative<'a>(&self, path: &'a Path) -> Option<Cow<'a, Path>> {
+        let rel = path
+            .strip_prefix(&self.base)
+            .ok()
+            .filter(|rel| !rel.as_os_str().is_empty())?;
+        if self.prefix.as_os_str().is_empty() {
+            Some(Cow::Borrowed(rel))
+        } else {
+            Some(Cow::Owned(self.prefix.join(rel)))
+        }
+    }
+}
+
+/// Keep `**` only as a whole path component (`**/a`, `a/**`, `a/**/b`); other
+/// runs of asterisks match like a single `*`, as in git
+fn normalize_asterisks(pattern: &str) -> String {
+    pattern
+        .split('/')
+        .map(|component| {
+            if component.len() >= 2 && component.bytes().all(|b| b == b'*') {
+                "**".to_string()
+            } else {
+                let mut normalized = String::with_capacity(component.len());
+                for c in component.chars() {
+                    if !(c == '*' && normalized.ends_with('*')) {
+                        normalized.push(c);
+                    }
+                }
+                normalized
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The ignore pattern responsible for excluding a path
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IgnoreMatch {
+    /// The pattern as written (without `!` prefix)
+    pub pattern: String,
+    /// The ignore file the pattern was read from, if known
+    pub source: Option<PathBuf>,
 }
 
 /// Wrapper around GlobSet for handling gitignore-style patterns
@@ -59,6 +273,8 @@ struct PatternEntry {
 pub struct IgnoreSpec {
     /// All patterns in order of appearance
     entries: Vec<PatternEntry>,
+    /// Matchers compiled once per ignore file, in order of appearance
+    sets: Vec<PatternSet>,
 }
 
 impl IgnoreSpec {
@@ -68,12 +284,32 @@ impl IgnoreSpec {
     }
 
     /// Parse gitignore-style patterns from lines
-    pub fn from_lines<I, S>(lines: I) -> Result<Self, TreewalkError>
+    pub fn from_lines<I, S>(lines: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::parse(lines, false)
+    }
+
+    /// Parse gitignore-style patterns from lines, matching them regardless of
+    /// letter case, as on the default filesystems of Windows and macOS where
+    /// `Build/` and `build/` are the same directory
+    pub fn from_lines_case_insensitive<I, S>(lines: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::parse(lines, true)
+    }
+
+    fn parse<I, S>(lines: I, case_insensitive: bool) -> Result<Self>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<str>,
     {
         let mut entries = Vec::new();
+        let mut globs = GlobSetBuilder::new();
         let mut line_number = 0;
 
         for line in lines {
@@ -84,30 +320,117 @@ impl IgnoreSpec {
                 continue;
             }
 
-            // Check for negation pattern
+            // Check for negation pattern; `\!` and `\#` start literal patterns
             let (pattern, is_whitelist) = if let Some(stripped) = line.strip_prefix('!') {
                 // Remove the ! prefix
                 (stripped, true)
+            } else if let Some(literal) = line
+                .strip_prefix('\\')
+                .filter(|rest| rest.starts_with(['!', '#']))
+            {
+                (literal, false)
             } else {
                 (line, false)
             };
-
-            entries.push(PatternEntry {
+            let entry = PatternEntry {
                 original: line.to_string(),
                 pattern: pattern.to_string(),
                 is_whitelist,
+                dir_only: pattern.ends_with('/'),
                 line_number,
-            });
+                source: None,
+                case_insensitive,
+            };
+            globs.add(entry.glob()?);
+            entries.push(entry);
 
             line_number += 1;
         }
 
-        Ok(IgnoreSpec { entries })
+        if entries.is_empty() {
+            return Ok(IgnoreSpec::new());
+        }
+        let sets = vec![PatternSet {
+            base: PathBuf::new(),
+            prefix: PathBuf::new(),
+            start: 0,
+            globs: globs.build()?,
+        }];
+        Ok(IgnoreSpec { entries, sets })
+    }
+
+    /// Read gitignore-style patterns from a file, such as a global ignore file
+    /// seeding the spec of a walk; the file is recorded as their source
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        Ok(Self::from_lines(contents.lines())?.with_source(path))
+    }
+
+    /// Match all patterns of this spec regardless of letter case, or again
+    /// case-sensitively; the patterns are compiled anew
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Result<Self> {
+        if self
+            .entries
+            .iter()
+            .all(|entry| entry.case_insensitive == case_insensitive)
+        {
+            return Ok(self);
+        }
+        for entry in &mut self.entries {
+            entry.case_insensitive = case_insensitive;
+        }
+        let ends: Vec<usize> = self
+            .sets
+            .iter()
+            .skip(1)
+            .map(|set| set.start)
+            .chain([self.entries.len()])
+            .collect();
+        for (set, end) in self.sets.iter_mut().zip(ends) {
+            let mut globs = GlobSetBuilder::new();
+            for entry in &self.entries[set.start..end] {
+                globs.add(entry.glob()?);
+            }
+            set.globs = globs.build()?;
+        }
+        Ok(self)
+    }
+
+    /// Record the ignore file all patterns of this spec were read from
+    pub fn with_source<P: AsRef<Path>>(mut self, source: P) -> Self {
+        for entry in &mut self.entries {
+            entry.source = Some(source.as_ref().to_path_buf());
+        }
+        self
+    }
+
+    /// Anchor all patterns of this spec to `dir`, the directory of their ignore
+    /// file relative to the walk root: they only match paths below it, and
+    /// patterns with a slash match relative to it (as in git)
+    pub fn with_base<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        for set in &mut self.sets {
+            set.base = dir.as_ref().to_path_buf();
+        }
+        self
+    }
+
+    /// Anchor all patterns of this spec to the directory of an ignore file
+    /// above the walk root; `prefix` is the walk root relative to it
+    fn with_prefix<P: AsRef<Path>>(mut self, prefix: P) -> Self {
+        for set in &mut self.sets {
+            set.prefix = prefix.as_ref().to_path_buf();
+        }
+        self
     }
 
     /// Combine two IgnoreSpec instances
+    ///
+    /// The patterns are compiled when they are parsed; combining only merges
+    /// the compiled matchers.
     pub fn combine(&self, other: &IgnoreSpec) -> IgnoreSpec {
         let mut entries = self.entries.clone();
+        let mut sets = self.sets.clone();
 
         // When combining, we need to adjust line numbers to maintain precedence
         let offset = self.entries.len();
@@ -115,141 +438,69 @@ impl IgnoreSpec {
             entry.line_number += offset;
             entries.push(entry);
         }
-
-        IgnoreSpec { entries }
-    }
-
-    /// Build separate GlobSets for ignore and whitelist patterns
-    #[allow(dead_code)]
-    fn build_globsets(&self) -> Result<(GlobSet, GlobSet), TreewalkError> {
-        let mut ignore_builder = GlobSetBuilder::new();
-        let mut whitelist_builder = GlobSetBuilder::new();
-
-        for entry in &self.entries {
-            // Convert gitignore pattern to glob pattern
-            let glob_pattern = if entry.pattern.ends_with('/') {
-                // Directory pattern - match the directory and everything under it
-                format!("{}**", entry.pattern)
-            } else if let Some(stripped) = entry.pattern.strip_prefix('/') {
-                // Anchored pattern - match from root
-                stripped.to_string()
-            } else if entry.pattern.contains('/') && !entry.pattern.starts_with("**/") {
-                // Pattern with slash but not starting with **/ - make it relative
-                format!("**/{}", entry.pattern)
-            } else {
-                // Simple pattern - match anywhere
-                format!("**/{}", entry.pattern)
-            };
-
-            let glob = Glob::new(&glob_pattern).map_err(|e| {
-                TreewalkError::InvalidPath(format!("Invalid pattern '{}': {}", entry.pattern, e))
-            })?;
-
-            if entry.is_whitelist {
-                whitelist_builder.add(glob);
-            } else {
-                ignore_builder.add(glob);
-            }
+        for mut set in other.sets.clone() {
+            set.start += offset;
+            sets.push(set);
         }
 
-        let ignore_set = ignore_builder
-            .build()
-            .map_err(|e| TreewalkError::InvalidPath(format!("Failed to build ignore set: {e}")))?;
-        let whitelist_set = whitelist_builder.build().map_err(|e| {
-            TreewalkError::InvalidPath(format!("Failed to build whitelist set: {e}"))
-        })?;
-
-        Ok((ignore_set, whitelist_set))
+        IgnoreSpec { entries, sets }
     }
 
     /// Check if a path matches any ignore pattern
-    pub fn matches<P: AsRef<Path>>(&self, path: P) -> Result<bool, TreewalkError> {
+    pub fn matches<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
         self.matches_with_precedence(path.as_ref(), false)
     }
 
     /// Check if a path matches as a directory (with trailing slash)
-    pub fn matches_dir<P: AsRef<Path>>(&self, path: P) -> Result<bool, TreewalkError> {
+    pub fn matches_dir<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
         self.matches_with_precedence(path.as_ref(), true)
     }
 
-    /// Internal method that properly handles precedence
-    fn matches_with_precedence(&self, path: &Path, is_dir: bool) -> Result<bool, TreewalkError> {
-        let path_str = path.to_string_lossy();
-
-        // For directories, also check with trailing slash
-        let dir_path = if is_dir {
-            Some(format!("{path_str}/"))
-        } else {
-            None
-        };
-
-        // Find all matching patterns and respect their order (last match wins)
-        let mut should_ignore = false;
-
-        for entry in &self.entries {
-            let glob_pattern = if entry.pattern.ends_with('/') {
-                format!("{}**", entry.pattern)
-            } else if let Some(stripped) = entry.pattern.strip_prefix('/') {
-                stripped.to_string()
-            } else {
-                // Both cases: pattern with slash or simple pattern - match anywhere
-                format!("**/{}", entry.pattern)
-            };
-
-            // Try to match the pattern
-            if let Ok(glob) = Glob::new(&glob_pattern) {
-                let matches = glob.compile_matcher().is_match(path)
-                    || (is_dir
-                        && dir_path
-                            .as_ref()
-                            .is_some_and(|dp| glob.compile_matcher().is_match(dp.as_str())));
-
-                if matches {
-                    // Last matching pattern determines the outcome
-                    should_ignore = !entry.is_whitelist;
-                }
-            }
-        }
-
-        Ok(should_ignore)
+    /// Return the pattern that causes a path to be ignored, or `None` if it is not ignored
+    pub fn ignoring_pattern<P: AsRef<Path>>(&self, path: P, is_dir: bool) -> Option<IgnoreMatch> {
+        self.excluding_pattern(path.as_ref(), is_dir)
+            .map(|entry| IgnoreMatch {
+                pattern: entry.pattern.clone(),
+                source: entry.source.clone(),
+            })
     }
 
-    /// Check if a directory has any whitelisted content (for traversal decisions)
-    pub fn has_whitelisted_content(&self, dir_path: &Path) -> Result<bool, TreewalkError> {
-        let dir_str = dir_path.to_string_lossy();
-
-        for entry in &self.entries {
-            if !entry.is_whitelist {
-                continue;
-            }
-
-            // Check if this whitelist pattern could match something under this directory
-            if entry.pattern.starts_with(&format!("{dir_str}/"))
-                || entry.pattern.starts_with(&format!("{dir_str}"))
-            {
-                return Ok(true);
-            }
+    /// Internal method that properly handles precedence
+    fn matches_with_precedence(&self, path: &Path, is_dir: bool) -> Result<bool> {
+        Ok(self.excluding_pattern(path, is_dir).is_some())
+    }
 
-            // Check if pattern could match files under this directory
-            // For example, !build/dist/ should allow traversal into build/
-            let parts: Vec<&str> = entry.pattern.split('/').collect();
-            let dir_parts: Vec<&str> = dir_str.split('/').collect();
-
-            if parts.len() > dir_parts.len() {
-                let mut matches = true;
-                for (i, dir_part) in dir_parts.iter().enumerate() {
-                    if parts[i] != *dir_part {
-                        matches = false;
-                        break;
-                    }
-                }
-                if matches {
-                    return Ok(true);
-                }
-            }
-        }
+    /// Find the pattern excluding a path, as git does: the last matching pattern
+    /// decides, but a path below an excluded directory cannot be re-included
+    fn excluding_pattern(&self, path: &Path, is_dir: bool) -> Option<&PatternEntry> {
+        let mut parents: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .collect();
+        parents.reverse();
+        parents
+            .into_iter()
+            .map(|parent| (parent, true))
+            .chain([(path, is_dir)])
+            .find_map(|(path, is_dir)| {
+                self.last_match(path, is_dir)
+                    .filter(|entry| !entry.is_whitelist)
+            })
+    }
 
-        Ok(false)
+    /// Find the last pattern matching a path; it determines the outcome
+    fn last_match(&self, path: &Path, is_dir: bool) -> Option<&PatternEntry> {
+        // Find all matching patterns and respect their order (last match wins)
+        self.sets.iter().rev().find_map(|set| {
+            let rel = set.relative(path)?;
+            set.globs
+                .matches(&rel)
+                .into_iter()
+                .rev()
+                .map(|index| &self.entries[set.start + index])
+                .find(|entry| is_dir || !entry.dir_only)
+        })
     }
 }
 
@@ -266,47 +517,97 @@ impl IgnoreSpec {
 /// # Returns
 ///
 /// Sorted vector of DirEntry objects (excluding symlinks)
-pub fn listdir<P: AsRef<Path>>(path: P) -> Result<Vec<DirEntry>, TreewalkError> {
-    let path = path.as_ref();
+pub fn listdir<P: AsRef<Path>>(path: P) -> Result<Vec<DirEntry>> {
+    listdir_with_symlinks(path, SymlinkPolicy::Skip)
+}
+
+/// List directory entries like [`listdir`], treating symlinks according to
+/// `symlinks`. Broken symlinks are always left out.
+pub fn listdir_with_symlinks<P: AsRef<Path>>(
+    path: P,
+    symlinks: SymlinkPolicy,
+) -> Result<Vec<DirEntry>> {
+    listdir_with(path.as_ref(), symlinks, |_, e| Err(e))
+}
+
+/// List a directory like [`listdir_with_symlinks`], passing entries that cannot
+/// be read to `on_error`; entries for which it returns `Ok` are left out of the listing
+fn listdir_with<F>(path: &Path, symlinks: SymlinkPolicy, mut on_error: F) -> Result<Vec<DirEntry>>
+where
+    F: FnMut(PathBuf, Error) -> Result<()>,
+{
     let mut entries = Vec::new();
 
     // Read directory entries
-    for entry in fs::read_dir(path)? {
-        let entry = entry?;
-        let metadata = entry.metadata()?;
+    let read_dir = fs::read_dir(path).map_err(|e| {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(dir = %path.display(), error = %e, "cannot read directory");
+        Error::io(path, e)
+    })?;
+    for entry in read_dir {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                on_error(path.to_path_buf(), Error::io(path, e))?;
+                continue;
+            }
+        };
+        let mut metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                on_error(entry.path(), Error::io(entry.path(), e))?;
+                continue;
+            }
+        };
 
-        // Skip symlinks
-        if metadata.is_symlink() {
-            continue;
+        // Skip symlinks unless the policy follows them to their target. On
+        // Windows this covers all name-surrogate reparse points, so junctions
+        // and volume mount points are links too; other reparse points (such as
+        // deduplicated or cloud files) are listed like the files they stand for.
+        let is_symlink = metadata.is_symlink();
+        if is_symlink {
+            let Ok(target) = fs::metadata(entry.path()) else {
+                continue;
+            };
+            let follow = match symlinks {
+                SymlinkPolicy::Skip => false,
+                SymlinkPolicy::FollowFiles => target.is_file(),
+                SymlinkPolicy::FollowAll => target.is_file() || target.is_dir(),
+            };
+            if !follow {
+                continue;
+            }
+            metadata = target;
         }
 
-        let name = entry
-            .file_name()
-            .into_string()
-            .map_err(|_| TreewalkError::InvalidPath("Invalid UTF-8 in filename".to_string()))?;
-
         entries.push(DirEntry {
-            name,
+            name: entry.file_name().to_string_lossy().into_owned(),
             path: entry.path(),
             is_dir: metadata.is_dir(),
             is_file: metadata.is_file(),
+            is_symlink,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
         });
     }
 
     // Sort entries by normalized name with original name as tie-breaker
-    entries.sort_by(|a, b| {
-        let a_normalized = a.name.nfc().collect::<String>();
-        let b_normalized = b.name.nfc().collect::<String>();
-
-        match a_normalized.as_bytes().cmp(b_normalized.as_bytes()) {
-            Ordering::Equal => a.name.as_bytes().cmp(b.name.as_bytes()),
-            other => other,
-        }
-    });
+    entries.sort_by_cached_key(|entry| sort_key(entry.path.file_name().unwrap_or_default()));
 
     Ok(entries)
 }
 
+/// Sort key of a file name: the NFC-normalized UTF-8 bytes of the name and its
+/// original bytes as tie-breaker. Names that are not valid UTF-8 (possible on
+/// Unix filesystems) sort by their raw bytes instead.
+fn sort_key(name: &OsStr) -> (Vec<u8>, Vec<u8>) {
+    let raw = name.as_encoded_bytes().to_vec();
+    match name.to_str() {
+        Some(name) => (name.nfc().collect::<String>().into_bytes(), raw),
+        None => (raw.clone(), raw),
+    }
+}
+
 /// Recursively walk a directory tree with deterministic ordering.
 ///
 /// This function traverses the directory tree starting from the given path,
@@ -325,36 +626,55 @@ pub fn listdir<P: AsRef<Path>>(path: P) -> Result<Vec<DirEntry>, TreewalkError>
 /// # Returns
 ///
 /// Iterator of absolute file paths (directories are traversed but not yielded)
-pub fn treewalk<P: AsRef<Path>>(path: P) -> Result<Vec<std::path::PathBuf>, TreewalkError> {
+pub fn treewalk<P: AsRef<Path>>(path: P) -> Result<Vec<std::path::PathBuf>> {
+    treewalk_with_symlinks(path, SymlinkPolicy::Skip)
+}
+
+/// Walk a directory tree like [`treewalk`], treating symlinks according to `symlinks`
+pub fn treewalk_with_symlinks<P: AsRef<Path>>(
+    path: P,
+    symlinks: SymlinkPolicy,
+) -> Result<Vec<std::path::PathBuf>> {
     let root = path.as_ref();
 
     // Verify the path exists and is a directory
-    if !root.exists() {
-        return Err(TreewalkError::IoError(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Path does not exist: {}", root.display()),
-        )));
-    }
-
-    if !root.is_dir() {
-        return Err(TreewalkError::IoError(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!("Path is not a directory: {}", root.display()),
-        )));
-    }
+    check_directory(root)?;
 
     let mut result = Vec::new();
-    treewalk_recursive(root, &mut result)?;
+    let mut ancestors = Ancestors::default();
+    treewalk_recursive(root, symlinks, &mut ancestors, &mut result)?;
     Ok(result)
 }
 
+/// Verify the path exists and is a directory
+fn check_directory(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Err(Error::io(
+            path,
+            io::Error::new(io::ErrorKind::NotFound, "path does not exist"),
+        ));
+    }
+    if !path.is_dir() {
+        return Err(Error::io(
+            path,
+            io::Error::new(io::ErrorKind::InvalidInput, "path is not a directory"),
+        ));
+    }
+    Ok(())
+}
+
 /// Helper function for recursive tree traversal
 fn treewalk_recursive(
     dir: &Path,
+    symlinks: SymlinkPolicy,
+    ancestors: &mut Ancestors,
     result: &mut Vec<std::path::PathBuf>,
-) -> Result<(), TreewalkError> {
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("walk_dir", dir = %dir.display()).entered();
+    ancestors.enter(dir, symlinks)?;
     // Get sorted entries from the directory
-    let entries = listdir(dir)?;
+    let entries = listdir_with_symlinks(dir, symlinks)?;
 
     // Separate entries into files and directories
     let mut ignore_files = Vec::new();
@@ -386,9 +706,10 @@ fn treewalk_recursive(
 
     // Recursively process directories
     for entry in &directories {
-        treewalk_recursive(&entry.path, result)?;
+        treewalk_recursive(&entry.path, symlinks, ancestors, result)?;
     }
 
+    ancestors.leave(symlinks);
     Ok(())
 }
 
@@ -412,122 +733,568 @@ pub fn treewalk_ignore<P: AsRef<Path>>(
     ignore_file_name: &str,
     root_path: Option<&Path>,
     ignore_spec: Option<&IgnoreSpec>,
-) -> Result<Vec<std::path::PathBuf>, TreewalkError> {
+) -> Result<Vec<std::path::PathBuf>> {
+    treewalk_ignore_with_skipped(path, ignore_file_name, root_path, ignore_spec)
+        .map(|(files, _)| files)
+}
+
+/// Paths skipped by ignore patterns, with the pattern responsible for each
+pub type SkippedPaths = Vec<(PathBuf, IgnoreMatch)>;
+
+/// Directories and files that could not be read, with the error for each
+pub type WalkErrors = Vec<(PathBuf, Error)>;
+
+/// Shared callback deciding whether a walk skips a directory; called with the
+/// path of each directory before descending into it
+#[derive(Clone)]
+pub struct PruneCallback(Arc<dyn Fn(&Path) -> bool + Send + Sync>);
+
+impl PruneCallback {
+    pub fn new<F: Fn(&Path) -> bool + Send + Sync + 'static>(callback: F) -> Self {
+        PruneCallback(Arc::new(callback))
+    }
+
+    pub fn prune(&self, dir: &Path) -> bool {
+        (self.0)(dir)
+    }
+}
+
+impl fmt::Debug for PruneCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PruneCallback")
+    }
+}
+
+/// Shared callback deciding whether a walk yields a file; called with the
+/// entry of each file that is not ignored, before it is opened
+#[derive(Clone)]
+pub struct FileFilter(Arc<dyn Fn(&DirEntry) -> bool + Send + Sync>);
+
+impl FileFilter {
+    pub fn new<F: Fn(&DirEntry) -> bool + Send + Sync + 'static>(callback: F) -> Self {
+        FileFilter(Arc::new(callback))
+    }
+
+    pub fn keep(&self, entry: &DirEntry) -> bool {
+        (self.0)(entry)
+    }
+}
+
+impl fmt::Debug for FileFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FileFilter")
+    }
+}
+
+/// True if the extension of `name` is one of `extensions` (with or without the
+/// leading dot, compared ignoring ASCII case)
+fn has_extension(name: &str, extensions: &[String]) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            extensions
+                .iter()
+                .any(|wanted| wanted.trim_start_matches('.').eq_ignore_ascii_case(ext))
+        })
+}
+
+/// Options of [`treewalk_ignore_with_options`]
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Report unreadable directories, ignore files and entries in
+    /// [`Walk::errors`] and continue with the rest of the tree instead of
+    /// failing the whole walk. A directory whose listing or ignore file cannot
+    /// be read is not descended into.
+    pub tolerate_errors: bool,
+    /// Stop with [`Error::Cancelled`] or [`Error::DeadlineExceeded`] before the
+    /// next directory once the token asks to stop
+    pub cancel: Option<CancellationToken>,
+    /// Also return the [`DirEntry`] of each file in [`Walk::entries`], so
+    /// consumers need no second `stat` for size and modification time
+    pub metadata: bool,
+    /// Treatment of symlinks; a symlink cycle is reported like an unreadable
+    /// directory
+    pub symlinks: SymlinkPolicy,
+    /// Match the patterns of ignore files (and of the initial spec) regardless
+    /// of letter case
+    pub case_insensitive: bool,
+    /// Only yield files with one of these extensions (e.g. `["jpg", "png"]`,
+    /// ignoring case); all files if empty
+    pub extensions: Vec<String>,
+    /// Leave out files with one of these extensions
+    pub exclude_extensions: Vec<String>,
+    /// Only yield files for which the filter returns true; applied after the
+    /// extension filters. Filtered files are not reported as skipped.
+    pub filter: Option<FileFilter>,
+    /// Also return the directories descended into (below the walked
+    /// directory) in [`Walk::directories`], so empty directories can be
+    /// represented
+    pub directories: bool,
+    /// Deepest level of subdirectories to descend into: 0 yields only the
+    /// files of the walked directory, 1 also those of its subdirectories
+    pub max_depth: Option<usize>,
+    /// Directories for which the callback returns true are not descended into
+    /// (e.g. `.git` or `node_modules`); they are not reported as skipped
+    pub prune: Option<PruneCallback>,
+    /// Respect git repositories at or below the walked directory: skip `.git`,
+    /// read `.git/info/exclude` (below the repository's own ignore files in
+    /// precedence) and do not apply the ignore files of an enclosing repository
+    /// inside a nested one. In a repository enclosing the walked directory from
+    /// above, its `.git/info/exclude` and the ignore files in the directories
+    /// down to the walked one apply as well.
+    pub git: bool,
+}
+
+/// Result of [`treewalk_ignore_with_options`]
+#[derive(Debug, Default)]
+pub struct Walk {
+    /// Non-ignored files in walk order
+    pub files: Vec<PathBuf>,
+    /// Entries of the files in `files`, only collected with
+    /// [`WalkOptions::metadata`]
+    pub entries: Vec<DirEntry>,
+    /// Entries of the directories descended into, in walk order (each before
+    /// its contents), only collected with [`WalkOptions::directories`]
+    pub directories: Vec<DirEntry>,
+    /// Ignored files and directories (directories are not descended into)
+    pub skipped: SkippedPaths,
+    /// Entries that could not be read, only collected with
+    /// [`WalkOptions::tolerate_errors`]
+    pub errors: WalkErrors,
+}
+
+/// Walk a directory tree like [`treewalk_ignore`] and also report what was skipped.
+///
+/// Returns the non-ignored files and, for every ignored file or directory (directories
+/// are not descended into), the pattern and ignore file responsible.
+pub fn treewalk_ignore_with_skipped<P: AsRef<Path>>(
+    path: P,
+    ignore_file_name: &str,
+    root_path: Option<&Path>,
+    ignore_spec: Option<&IgnoreSpec>,
+) -> Result<(Vec<PathBuf>, SkippedPaths)> {
+    let options = WalkOptions::default();
+    treewalk_ignore_with_options(path, ignore_file_name, root_path, ignore_spec, &options)
+        .map(|walk| (walk.files, walk.skipped))
+}
+
+/// Walk a directory tree like [`treewalk_ignore_with_skipped`], stopping with
+/// [`Error::Cancelled`] or [`Error::DeadlineExceeded`] before the next directory
+/// once the token asks to stop.
+pub fn treewalk_ignore_cancellable<P: AsRef<Path>>(
+    path: P,
+    ignore_file_name: &str,
+    root_path: Option<&Path>,
+    ignore_spec: Option<&IgnoreSpec>,
+    cancel: &CancellationToken,
+) -> Result<(Vec<PathBuf>, SkippedPaths)> {
+    let options = WalkOptions {
+        cancel: Some(cancel.clone()),
+        ..WalkOptions::default()
+    };
+    treewalk_ignore_with_options(path, ignore_file_name, root_path, ignore_spec, &options)
+        .map(|walk| (walk.files, walk.skipped))
+}
+
+/// Walk a directory tree like [`treewalk_ignore_with_skipped`] with [`WalkOptions`].
+///
+/// The root directory itself must be readable; with
+/// [`WalkOptions::tolerate_errors`] all errors below it are collected in
+/// [`Walk::errors`] instead of aborting the walk.
+pub fn treewalk_ignore_with_options<P: AsRef<Path>>(
+    path: P,
+    ignore_file_name: &str,
+    root_path: Option<&Path>,
+    ignore_spec: Option<&IgnoreSpec>,
+    options: &WalkOptions,
+) -> Result<Walk> {
+    treewalk_ignore_files(path, &[ignore_file_name], root_path, ignore_spec, options)
+}
+
+/// Walk a directory tree like [`treewalk_ignore_with_options`], reading several
+/// ignore files (e.g. `[".isccignore", ".gitignore"]`) in each directory.
+///
+/// The names are given in order of precedence: where the patterns of two
+/// ignore files in the same directory disagree, the file named first decides.
+/// Patterns of a subdirectory still take precedence over those of its parents.
+pub fn treewalk_ignore_files<P: AsRef<Path>>(
+    path: P,
+    ignore_file_names: &[&str],
+    root_path: Option<&Path>,
+    ignore_spec: Option<&IgnoreSpec>,
+    options: &WalkOptions,
+) -> Result<Walk> {
     let path = path.as_ref();
     let root_path = root_path.unwrap_or(path);
 
     // Verify the path exists and is a directory
-    if !path.exists() {
-        return Err(TreewalkError::IoError(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Path does not exist: {}", path.display()),
-        )));
+    check_directory(path)?;
+
+    let base_spec = ignore_spec
+        .cloned()
+        .unwrap_or_else(IgnoreSpec::new)
+        .with_case_insensitive(options.case_insensitive)?;
+    let mut walker = IgnoreWalker {
+        ignore_file_names,
+        root_path,
+        options,
+        base_spec: &base_spec,
+        ancestors: Ancestors::default(),
+        sink: Sink::Collect(Walk::default()),
+    };
+    let root_spec = walker.root_spec()?;
+    walker.walk_dir(path, &root_spec, 0)?;
+    match walker.sink {
+        Sink::Collect(walk) => Ok(walk),
+        Sink::Channel(_) => unreachable!("collecting walk sends no events"),
     }
+}
 
-    if !path.is_dir() {
-        return Err(TreewalkError::IoError(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!("Path is not a directory: {}", path.display()),
-        )));
-    }
+/// Number of [`WalkEvent`]s [`treewalk_channel`] buffers before the walk waits
+/// for the consumer
+pub const WALK_CHANNEL_CAPACITY: usize = 1024;
 
-    let mut result = Vec::new();
-    let base_spec = ignore_spec.cloned().unwrap_or_else(IgnoreSpec::new);
-    treewalk_ignore_recursive(path, ignore_file_name, root_path, &base_spec, &mut result)?;
-    Ok(result)
+/// Event of a walk streamed by [`treewalk_channel`]
+#[derive(Debug)]
+pub enum WalkEvent {
+    /// A directory below the walked directory is descended into; its contents
+    /// follow until the matching [`WalkEvent::ExitDir`]
+    EnterDir(DirEntry),
+    /// All contents of the directory have been walked
+    ExitDir(PathBuf),
+    /// A non-ignored file
+    File(DirEntry),
+    /// An ignored file or directory (directories are not descended into)
+    Skipped(PathBuf, IgnoreMatch),
+    /// An entry that could not be read, only sent with
+    /// [`WalkOptions::tolerate_errors`]
+    Error(PathBuf, Error),
 }
 
-/// Helper function for recursive tree traversal with ignore patterns
-fn treewalk_ignore_recursive(
-    dir: &Path,
-    ignore_file_name: &str,
-    root_path: &Path,
-    ignore_spec: &IgnoreSpec,
-    result: &mut Vec<std::path::PathBuf>,
-) -> Result<(), TreewalkError> {
-    // Check for ignore file in current directory and update spec
-    let mut current_spec = ignore_spec.clone();
-    let ignore_file_path = dir.join(ignore_file_name);
+/// Walk a directory tree like [`treewalk_ignore_files`] on a background thread,
+/// streaming the events over a bounded channel.
+///
+/// The walk blocks while [`WALK_CHANNEL_CAPACITY`] events wait for the consumer,
+/// so huge trees are never held in memory and a slow consumer (such as a hashing
+/// pipeline) holds the walk back. The thread returns the error that aborted the
+/// walk, or [`Error::Cancelled`] once the receiver is dropped.
+pub fn treewalk_channel<P: Into<PathBuf>>(
+    path: P,
+    ignore_file_names: &[&str],
+    ignore_spec: Option<&IgnoreSpec>,
+    options: &WalkOptions,
+) -> (JoinHandle<Result<()>>, Receiver<WalkEvent>) {
+    let (sender, receiver) = mpsc::sync_channel(WALK_CHANNEL_CAPACITY);
+    let path = path.into();
+    let ignore_file_names: Vec<String> = ignore_file_names
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    let ignore_spec = ignore_spec.cloned().unwrap_or_else(IgnoreSpec::new);
+    let options = options.clone();
+    let handle = thread::spawn(move || {
+        check_directory(&path)?;
+        let names: Vec<&str> = ignore_file_names.iter().map(String::as_str).collect();
+        let base_spec = ignore_spec.with_case_insensitive(options.case_insensitive)?;
+        let mut walker = IgnoreWalker {
+            ignore_file_names: &names,
+            root_path: &path,
+            options: &options,
+            base_spec: &base_spec,
+            ancestors: Ancestors::default(),
+            sink: Sink::Channel(sender),
+        };
+        let root_spec = walker.root_spec()?;
+        walker.walk_dir(&path, &root_spec, 0)
+    });
+    (handle, receiver)
+}
+
+/// Destination of the results of an [`IgnoreWalker`]
+enum Sink {
+    /// Collect everything for [`treewalk_ignore_files`]
+    Collect(Walk),
+    /// Stream events for [`treewalk_channel`]
+    Channel(SyncSender<WalkEvent>),
+}
 
-    if ignore_file_path.exists() && ignore_file_path.is_file() {
-        let contents = fs::read_to_string(&ignore_file_path).map_err(TreewalkError::IoError)?;
-        let lines: Vec<&str> = contents.lines().collect();
-        let new_spec = IgnoreSpec::from_lines(lines)?;
-        current_spec = current_spec.combine(&new_spec);
+/// Repository directory of git (a file in worktrees and submodules)
+const GIT_DIR: &str = ".git";
+
+/// State of a walk with ignore patterns
+struct IgnoreWalker<'a> {
+    /// Ignore file names in order of precedence
+    ignore_file_names: &'a [&'a str],
+    root_path: &'a Path,
+    options: &'a WalkOptions,
+    /// Spec of the walked directory before its ignore files, which nested git
+    /// repositories start from
+    base_spec: &'a IgnoreSpec,
+    ancestors: Ancestors,
+    sink: Sink,
+}
+
+impl IgnoreWalker<'_> {
+    /// Collect or send an event; fails once the receiver of a channel is gone
+    fn emit(&mut self, event: WalkEvent) -> Result<()> {
+        let walk = match &mut self.sink {
+            Sink::Channel(sender) => return sender.send(event).map_err(|_| Error::Cancelled),
+            Sink::Collect(walk) => walk,
+        };
+        match event {
+            WalkEvent::EnterDir(entry) => {
+                if self.options.directories {
+                    walk.directories.push(entry);
+                }
+            }
+            WalkEvent::ExitDir(_) => {}
+            WalkEvent::File(entry) => {
+                walk.files.push(entry.path.clone());
+                if self.options.metadata {
+                    walk.entries.push(entry);
+                }
+            }
+            WalkEvent::Skipped(path, reason) => walk.skipped.push((path, reason)),
+            WalkEvent::Error(path, error) => walk.errors.push((path, error)),
+        }
+        Ok(())
     }
 
-    // Get sorted entries from the directory
-    let entries = listdir(dir)?;
+    /// Fail with `error`, or record it and continue with tolerated errors;
+    /// errors at the root of the walk always fail
+    fn fail(&mut self, path: &Path, error: Error, depth: usize) -> Result<()> {
+        if !self.options.tolerate_errors || depth == 0 {
+            return Err(error);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::warn!(path = %path.display(), error = %error, "walk entry skipped");
+        self.emit(WalkEvent::Error(path.to_path_buf(), error))
+    }
 
-    // Separate entries into files and directories
-    let mut ignore_files = Vec::new();
-    let mut regular_files = Vec::new();
-    let mut directories = Vec::new();
+    /// Whether a file passes the extension filters and the filter callback
+    fn wanted(&self, entry: &DirEntry) -> bool {
+        let options = self.options;
+        (options.extensions.is_empty() || has_extension(&entry.name, &options.extensions))
+            && !has_extension(&entry.name, &options.exclude_extensions)
+            && options
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.keep(entry))
+    }
 
-    for entry in entries {
-        if entry.is_dir {
-            directories.push(entry);
-        } else if entry.is_file {
-            // Check if this is an ignore file
-            if entry.name.starts_with('.') && entry.name.ends_with("ignore") {
-                ignore_files.push(entry);
-            } else {
-                regular_files.push(entry);
+    /// Spec the walk starts from: the base spec and, with [`WalkOptions::git`],
+    /// the patterns of a git repository enclosing the root directory from
+    /// above: its `.git/info/exclude` and the ignore files in the directories
+    /// from the repository down to the root directory
+    fn root_spec(&self) -> Result<IgnoreSpec> {
+        let mut spec = self.base_spec.clone();
+        if !self.options.git || self.root_path.join(GIT_DIR).exists() {
+            return Ok(spec);
+        }
+        let root = fs::canonicalize(self.root_path).map_err(|e| Error::io(self.root_path, e))?;
+        let Some(repository) = root
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join(GIT_DIR).exists())
+        else {
+            return Ok(spec);
+        };
+        let exclude_path = repository.join(GIT_DIR).join("info").join("exclude");
+        let mut sources = vec![(exclude_path, repository)];
+        // Parent directories before their subdirectories, so deeper files win
+        let dirs: Vec<&Path> = root
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(repository))
+            .collect();
+        for dir in dirs.into_iter().rev() {
+            for name in self.ignore_file_names.iter().rev() {
+                sources.push((dir.join(name), dir));
+            }
+        }
+        for (path, dir) in sources {
+            if path.is_file() {
+                let prefix = root.strip_prefix(dir).unwrap_or(&root);
+                let new_spec = read_ignore_file(&path, self.options.case_insensitive)?;
+                spec = spec.combine(&new_spec.with_prefix(prefix));
             }
         }
+        Ok(spec)
     }
 
-    // Helper to check if a path should be ignored
-    let should_ignore = |path: &Path| -> Result<bool, TreewalkError> {
-        let rel_path = path.strip_prefix(root_path).map_err(|_| {
-            TreewalkError::InvalidPath(format!(
-                "Failed to compute relative path for: {}",
-                path.display()
-            ))
-        })?;
-        current_spec.matches(rel_path)
-    };
+    /// Spec of a directory: the parent spec extended by its ignore files
+    fn dir_spec(&self, dir: &Path, ignore_spec: &IgnoreSpec, depth: usize) -> Result<IgnoreSpec> {
+        let mut spec = ignore_spec.clone();
+        if self.options.git && dir.join(GIT_DIR).exists() {
+            // The patterns of an enclosing repository stop at a nested one
+            if depth > 0 {
+                spec = self.base_spec.clone();
+            }
+            let exclude_path = dir.join(GIT_DIR).join("info").join("exclude");
+            spec = self.add_ignore_file(spec, &exclude_path, dir)?;
+        }
+        // Later patterns win, so the file of highest precedence is added last
+        for name in self.ignore_file_names.iter().rev() {
+            spec = self.add_ignore_file(spec, &dir.join(name), dir)?;
+        }
+        Ok(spec)
+    }
 
-    // Yield ignore files first (they are not filtered)
-    for entry in &ignore_files {
-        if !should_ignore(&entry.path)? {
-            result.push(entry.path.clone());
+    /// Extend `spec` by the patterns of an ignore file in `dir`, if it exists
+    fn add_ignore_file(
+        &self,
+        spec: IgnoreSpec,
+        ignore_file_path: &Path,
+        dir: &Path,
+    ) -> Result<IgnoreSpec> {
+        if !(ignore_file_path.exists() && ignore_file_path.is_file()) {
+            return Ok(spec);
         }
+        let new_spec = read_ignore_file(ignore_file_path, self.options.case_insensitive)?
+            .with_base(relative_path(dir, self.root_path)?);
+        Ok(spec.combine(&new_spec))
     }
 
-    // Yield regular files second
-    for entry in &regular_files {
-        if !should_ignore(&entry.path)? {
-            result.push(entry.path.clone());
+    /// With [`WalkOptions::git`], the reason to skip a `.git` directory or file
+    fn git_dir(&self, entry: &DirEntry) -> Option<IgnoreMatch> {
+        (self.options.git && entry.name == GIT_DIR).then(|| IgnoreMatch {
+            pattern: GIT_DIR.to_string(),
+            source: None,
+        })
+    }
+
+    /// Recursive tree traversal with ignore patterns
+    fn walk_dir(&mut self, dir: &Path, ignore_spec: &IgnoreSpec, depth: usize) -> Result<()> {
+        if let Some(cancel) = &self.options.cancel {
+            cancel.check()?;
         }
+        if let Err(e) = self.ancestors.enter(dir, self.options.symlinks) {
+            return self.fail(dir, e, depth);
+        }
+        let result = self.walk_entries(dir, ignore_spec, depth);
+        self.ancestors.leave(self.options.symlinks);
+        result
     }
 
-    // Recursively process directories (check if directory itself is ignored)
-    for entry in &directories {
-        let rel_path = entry.path.strip_prefix(root_path).map_err(|_| {
-            TreewalkError::InvalidPath(format!(
-                "Failed to compute relative path for: {}",
-                entry.path.display()
-            ))
-        })?;
-
-        // Check if directory should be excluded
-        // Also check if it has whitelisted content that should be traversed
-        let is_ignored = current_spec.matches_dir(rel_path)?;
-        let has_whitelisted = current_spec.has_whitelisted_content(rel_path)?;
-
-        if !is_ignored || has_whitelisted {
-            treewalk_ignore_recursive(
-                &entry.path,
-                ignore_file_name,
-                root_path,
-                &current_spec,
-                result,
-            )?;
+    /// Yield the files of a directory and walk its subdirectories
+    fn walk_entries(&mut self, dir: &Path, ignore_spec: &IgnoreSpec, depth: usize) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("walk_dir", dir = %dir.display()).entered();
+
+        // Check for ignore file in current directory and update spec; without
+        // its patterns nothing below the directory can be filtered correctly
+        let current_spec = match self.dir_spec(dir, ignore_spec, depth) {
+            Ok(spec) => spec,
+            Err(e) => return self.fail(dir, e, depth),
+        };
+
+        // Get sorted entries from the directory
+        let tolerate_errors = self.options.tolerate_errors;
+        let mut errors = Vec::new();
+        let listing = listdir_with(dir, self.options.symlinks, |path, e| {
+            if tolerate_errors {
+                errors.push((path, e));
+                Ok(())
+            } else {
+                Err(e)
+            }
+        });
+        let entries = match listing {
+            Ok(entries) => entries,
+            Err(e) => return self.fail(dir, e, depth),
+        };
+        for (path, e) in errors {
+            self.fail(&path, e, depth + 1)?;
+        }
+
+        // Separate entries into files and directories
+        let mut ignore_files = Vec::new();
+        let mut regular_files = Vec::new();
+        let mut directories = Vec::new();
+
+        for entry in entries {
+            if entry.is_dir {
+                directories.push(entry);
+            } else if entry.is_file {
+                // Check if this is an ignore file
+                if entry.name.starts_with('.') && entry.name.ends_with("ignore") {
+                    ignore_files.push(entry);
+                } else {
+                    regular_files.push(entry);
+                }
+            }
+        }
+
+        // Yield ignore files first, then regular files
+        for entry in ignore_files.into_iter().chain(regular_files) {
+            if !self.wanted(&entry) {
+                continue;
+            }
+            if let Some(reason) = self.git_dir(&entry) {
+                self.emit(WalkEvent::Skipped(entry.path, reason))?;
+                continue;
+            }
+            let rel_path = relative_path(&entry.path, self.root_path)?;
+            match current_spec.ignoring_pattern(rel_path, false) {
+                None => self.emit(WalkEvent::File(entry))?,
+                Some(reason) => self.emit(WalkEvent::Skipped(entry.path, reason))?,
+            }
+        }
+
+        if self.options.max_depth.is_some_and(|max| depth >= max) {
+            return Ok(());
+        }
+
+        // Recursively process directories (check if directory itself is ignored)
+        for entry in directories {
+            if let Some(reason) = self.git_dir(&entry) {
+                self.emit(WalkEvent::Skipped(entry.path, reason))?;
+                continue;
+            }
+            let rel_path = relative_path(&entry.path, self.root_path)?;
+            if let Some(prune) = &self.options.prune {
+                if prune.prune(&entry.path) {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(dir = %entry.path.display(), "pruned by callback");
+                    continue;
+                }
+            }
+
+            // Excluded directories are not descended into, so nothing below them
+            // can be re-included (as in git)
+            if !current_spec.matches_dir(rel_path)? {
+                let path = entry.path.clone();
+                self.emit(WalkEvent::EnterDir(entry))?;
+                self.walk_dir(&path, &current_spec, depth + 1)?;
+                self.emit(WalkEvent::ExitDir(path))?;
+            } else if let Some(reason) = current_spec.ignoring_pattern(rel_path, true) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(dir = %entry.path.display(), pattern = %reason.pattern, "pruned");
+                self.emit(WalkEvent::Skipped(entry.path, reason))?;
+            }
         }
+
+        Ok(())
     }
+}
 
-    Ok(())
+/// Patterns of an ignore file, recorded as their source
+fn read_ignore_file(path: &Path, case_insensitive: bool) -> Result<IgnoreSpec> {
+    let contents = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    let lines: Vec<&str> = contents.lines().collect();
+    #[cfg(feature = "tracing")]
+    tracing::debug!(path = %path.display(), patterns = lines.len(), "ignore file");
+    Ok(IgnoreSpec::parse(lines, case_insensitive)?.with_source(path))
+}
+
+/// Path relative to the root of the walk
+fn relative_path<'a>(path: &'a Path, root_path: &Path) -> Result<&'a Path> {
+    path.strip_prefix(root_path)
+        .map_err(|_| Error::InvalidPath {
+            path: path.to_path_buf(),
+            reason: "not below the root path",
+        })
 }
 
 /// Walk a directory tree with ISCC-specific ignore rules.
@@ -545,7 +1312,7 @@ fn treewalk_ignore_recursive(
 /// # Returns
 ///
 /// Iterator of absolute file paths for non-ignored, non-ISCC metadata files
-pub fn treewalk_iscc<P: AsRef<Path>>(path: P) -> Result<Vec<std::path::PathBuf>, TreewalkError> {
+pub fn treewalk_iscc<P: AsRef<Path>>(path: P) -> Result<Vec<std::path::PathBuf>> {
     let path = path.as_ref();
 
     // Use treewalk_ignore with .isccignore files
@@ -569,16 +1336,6 @@ pub fn treewalk_iscc<P: AsRef<Path>>(path: P) -> Result<Vec<std::path::PathBuf>,
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_error_conversion() {
-        let io_err = io::Error::new(io::ErrorKind::NotFound, "test error");
-        let treewalk_err: TreewalkError = io_err.into();
-        match treewalk_err {
-            TreewalkError::IoError(_) => (),
-            _ => panic!("Expected IoError variant"),
-        }
-    }
-
     #[test]
     fn test_listdir_basic_sorting() {
         use std::fs::{self, File};
@@ -731,6 +1488,132 @@ mod tests {
         assert!(!names.contains(&"symlink_to_dir"));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_listdir_symlink_policies() {
+        use std::fs::{self, File};
+        use std::os::unix::fs::symlink;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir_path = temp_dir.path();
+
+        fs::write(dir_path.join("regular.txt"), b"data").unwrap();
+        fs::create_dir(dir_path.join("subdir")).unwrap();
+        File::create(dir_path.join("subdir/inner.txt")).unwrap();
+        symlink(dir_path.join("regular.txt"), dir_path.join("link_file")).unwrap();
+        symlink(dir_path.join("subdir"), dir_path.join("link_dir")).unwrap();
+        symlink(dir_path.join("missing"), dir_path.join("link_broken")).unwrap();
+
+        let names = |policy| -> Vec<String> {
+            listdir_with_symlinks(dir_path, policy)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.name)
+                .collect()
+        };
+        assert_eq!(names(SymlinkPolicy::Skip), vec!["regular.txt", "subdir"]);
+        assert_eq!(
+            names(SymlinkPolicy::FollowFiles),
+            vec!["link_file", "regular.txt", "subdir"]
+        );
+        assert_eq!(
+            names(SymlinkPolicy::FollowAll),
+            vec!["link_dir", "link_file", "regular.txt", "subdir"]
+        );
+
+        let entries = listdir_with_symlinks(dir_path, SymlinkPolicy::FollowAll).unwrap();
+        assert!(entries[0].is_dir && entries[0].is_symlink);
+        assert!(entries[1].is_file && entries[1].is_symlink);
+        assert_eq!(entries[1].size, 4);
+        assert!(!entries[2].is_symlink);
+
+        assert_eq!(
+            treewalk_with_symlinks(dir_path, SymlinkPolicy::FollowAll).unwrap(),
+            vec![
+                dir_path.join("link_file"),
+                dir_path.join("regular.txt"),
+                dir_path.join("link_dir/inner.txt"),
+                dir_path.join("subdir/inner.txt"),
+            ]
+        );
+        assert_eq!("follow-files".parse(), Ok(SymlinkPolicy::FollowFiles));
+        assert_eq!(SymlinkPolicy::FollowAll.to_string(), "follow-all");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_treewalk_symlink_cycle() {
+        use std::fs::{self, File};
+        use std::os::unix::fs::symlink;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        File::create(root.join("a.txt")).unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        File::create(root.join("sub/b.txt")).unwrap();
+        symlink(root, root.join("sub/loop")).unwrap();
+
+        // Skipping symlinks never follows the loop
+        assert_eq!(treewalk(root).unwrap().len(), 2);
+
+        let result = treewalk_with_symlinks(root, SymlinkPolicy::FollowAll);
+        assert!(matches!(result, Err(Error::Io { .. })));
+
+        let options = WalkOptions {
+            symlinks: SymlinkPolicy::FollowAll,
+            tolerate_errors: true,
+            ..WalkOptions::default()
+        };
+        let walk = treewalk_ignore_with_options(root, ".gitignore", None, None, &options).unwrap();
+        assert_eq!(walk.files, vec![root.join("a.txt"), root.join("sub/b.txt")]);
+        assert_eq!(walk.errors.len(), 1);
+        assert_eq!(walk.errors[0].0, root.join("sub/loop"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_treewalk_junction_cycle() {
+        use std::fs::{self, File};
+        use std::process::Command;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        File::create(root.join("a.txt")).unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        File::create(root.join("sub/b.txt")).unwrap();
+        // Junctions need no privileges, unlike symlinks
+        let status = Command::new("cmd")
+            .arg("/C")
+            .arg("mklink")
+            .arg("/J")
+            .arg(root.join("sub").join("loop"))
+            .arg(root)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success());
+
+        // Junctions are links: skipped by default, never walked twice
+        assert_eq!(treewalk(root).unwrap().len(), 2);
+        let result = treewalk_with_symlinks(root, SymlinkPolicy::FollowAll);
+        assert!(matches!(result, Err(Error::Io { .. })));
+
+        let options = WalkOptions {
+            symlinks: SymlinkPolicy::FollowAll,
+            tolerate_errors: true,
+            ..WalkOptions::default()
+        };
+        let walk = treewalk_ignore_with_options(root, ".gitignore", None, None, &options).unwrap();
+        assert_eq!(walk.files, vec![root.join("a.txt"), root.join("sub/b.txt")]);
+        assert_eq!(walk.errors.len(), 1);
+        assert_eq!(walk.errors[0].0, root.join("sub/loop"));
+    }
+
     #[test]
     fn test_listdir_empty_directory() {
         use tempfile::TempDir;
@@ -746,7 +1629,7 @@ mod tests {
         let result = listdir("/this/path/should/not/exist/anywhere");
         assert!(result.is_err());
         match result.unwrap_err() {
-            TreewalkError::IoError(_) => (),
+            Error::Io { .. } => (),
             _ => panic!("Expected IoError for nonexistent path"),
         }
     }
@@ -763,7 +1646,7 @@ mod tests {
         let result = listdir(&file_path);
         assert!(result.is_err());
         match result.unwrap_err() {
-            TreewalkError::IoError(_) => (),
+            Error::Io { .. } => (),
             _ => panic!("Expected IoError when path is a file"),
         }
     }
@@ -967,7 +1850,7 @@ mod tests {
         let result = treewalk("/this/path/should/not/exist");
         assert!(result.is_err());
         match result.unwrap_err() {
-            TreewalkError::IoError(e) => {
+            Error::Io { source: e, .. } => {
                 assert_eq!(e.kind(), io::ErrorKind::NotFound);
             }
             _ => panic!("Expected IoError with NotFound"),
@@ -986,7 +1869,7 @@ mod tests {
         let result = treewalk(&file_path);
         assert!(result.is_err());
         match result.unwrap_err() {
-            TreewalkError::IoError(e) => {
+            Error::Io { source: e, .. } => {
                 assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
             }
             _ => panic!("Expected IoError with InvalidInput"),
@@ -1075,6 +1958,84 @@ mod tests {
         assert!(spec.entries[3].is_whitelist);
     }
 
+    #[test]
+    fn test_ignore_spec_invalid_pattern() {
+        // Patterns are compiled when parsed, so invalid globs fail early
+        let result = IgnoreSpec::from_lines(vec!["*.tmp", "[unclosed"]);
+        assert!(matches!(result, Err(Error::InvalidPattern(_))));
+    }
+
+    #[test]
+    fn test_ignore_spec_from_file() {
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("ignore");
+        std::fs::write(&path, "Thumbs.db\n.DS_Store\n").unwrap();
+
+        let spec = IgnoreSpec::from_file(&path).unwrap();
+        assert!(spec.matches("photos/Thumbs.db").unwrap());
+        assert!(!spec.matches("photos/image.jpg").unwrap());
+        assert_eq!(
+            spec.ignoring_pattern(".DS_Store", false),
+            Some(IgnoreMatch {
+                pattern: ".DS_Store".to_string(),
+                source: Some(path.clone()),
+            })
+        );
+        let missing = IgnoreSpec::from_file(temp_dir.path().join("missing"));
+        assert!(matches!(missing, Err(Error::Io { .. })));
+    }
+
+    #[test]
+    fn test_ignore_spec_case_insensitive() {
+        let spec = IgnoreSpec::from_lines(vec!["build/", "*.LOG"]).unwrap();
+        assert!(!spec.matches_dir("Build").unwrap());
+        assert!(!spec.matches("debug.log").unwrap());
+
+        let folded = IgnoreSpec::from_lines_case_insensitive(vec!["build/", "*.LOG"]).unwrap();
+        assert!(folded.matches_dir("Build").unwrap());
+        assert!(folded.matches("debug.log").unwrap());
+
+        // Recompiling keeps the precedence of combined specs
+        let combined = spec
+            .combine(&IgnoreSpec::from_lines(vec!["!KEEP.log"]).unwrap())
+            .with_case_insensitive(true)
+            .unwrap();
+        assert!(combined.matches_dir("BUILD").unwrap());
+        assert!(combined.matches("debug.log").unwrap());
+        assert!(!combined.matches("keep.log").unwrap());
+        let combined = combined.with_case_insensitive(false).unwrap();
+        assert!(!combined.matches("debug.log").unwrap());
+    }
+
+    #[test]
+    fn test_treewalk_ignore_case_insensitive() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join(".gitignore"), "build/\n").unwrap();
+        fs::create_dir(root.join("Build")).unwrap();
+        File::create(root.join("Build/out.bin")).unwrap();
+        File::create(root.join("Thumbs.db")).unwrap();
+
+        let seed = IgnoreSpec::from_lines(vec!["thumbs.db"]).unwrap();
+        let walk = |case_insensitive| {
+            let options = WalkOptions {
+                case_insensitive,
+                ..WalkOptions::default()
+            };
+            treewalk_ignore_with_options(root, ".gitignore", None, Some(&seed), &options)
+                .unwrap()
+                .files
+        };
+        assert_eq!(walk(false).len(), 3);
+        assert_eq!(walk(true), vec![root.join(".gitignore")]);
+    }
+
     #[test]
     fn test_ignore_spec_combine() {
         let spec1 = IgnoreSpec::from_lines(vec!["*.tmp", "*.log"]).unwrap();
@@ -1118,13 +2079,191 @@ mod tests {
         assert!(!spec.matches("important.log").unwrap()); // Should NOT be ignored due to !
         assert!(!spec.matches("file.txt").unwrap());
 
-        // Test directory negation
+        // Test directory negation: nothing below an excluded directory can be
+        // re-included, but the contents of a directory can
         let spec2 = IgnoreSpec::from_lines(vec!["build/", "!build/dist/"]).unwrap();
 
         assert!(spec2.matches_dir("build").unwrap());
         assert!(spec2.matches("build/temp.txt").unwrap());
-        assert!(!spec2.matches_dir("build/dist").unwrap()); // Should NOT be ignored
-        assert!(!spec2.matches("build/dist/app.js").unwrap());
+        assert!(spec2.matches_dir("build/dist").unwrap());
+        assert!(spec2.matches("build/dist/app.js").unwrap());
+        let reason = spec2.ignoring_pattern("build/dist/app.js", false).unwrap();
+        assert_eq!(reason.pattern, "build/");
+
+        let spec3 = IgnoreSpec::from_lines(vec!["build/*", "!build/dist/"]).unwrap();
+
+        assert!(!spec3.matches_dir("build").unwrap());
+        assert!(spec3.matches("build/temp.txt").unwrap());
+        assert!(!spec3.matches_dir("build/dist").unwrap()); // Should NOT be ignored
+        assert!(!spec3.matches("build/dist/app.js").unwrap());
+
+        // Directory patterns do not match files
+        assert!(!spec2.matches("build").unwrap());
+    }
+
+    #[test]
+    fn test_ignore_spec_gitignore_examples() {
+        // "Exclude everything except a specific directory foo/bar" from gitignore(5)
+        let spec = IgnoreSpec::from_lines(vec!["/*", "!/foo", "/foo/*", "!/foo/bar"]).unwrap();
+
+        assert!(spec.matches("file.txt").unwrap());
+        assert!(spec.matches_dir("other").unwrap());
+        assert!(spec.matches("other/file.txt").unwrap());
+        assert!(!spec.matches_dir("foo").unwrap());
+        assert!(spec.matches("foo/file.txt").unwrap());
+        assert!(spec.matches_dir("foo/baz").unwrap());
+        assert!(!spec.matches_dir("foo/bar").unwrap());
+        assert!(!spec.matches("foo/bar/file.txt").unwrap());
+
+        // Later patterns override earlier ones in both directions
+        let spec = IgnoreSpec::from_lines(vec!["!*.log", "*.log", "!keep.log"]).unwrap();
+        assert!(spec.matches("debug.log").unwrap());
+        assert!(!spec.matches("keep.log").unwrap());
+        let spec = IgnoreSpec::from_lines(vec!["!keep.log", "*.log"]).unwrap();
+        assert!(spec.matches("keep.log").unwrap());
+
+        // A backslash makes a leading ! or # literal
+        let spec = IgnoreSpec::from_lines(vec![r"\!important.txt", r"\#notes"]).unwrap();
+        assert!(spec.matches("!important.txt").unwrap());
+        assert!(spec.matches("#notes").unwrap());
+        assert!(!spec.matches("important.txt").unwrap());
+    }
+
+    #[test]
+    fn test_ignore_spec_anchoring() {
+        // Patterns of a nested ignore file only apply below its directory, and
+        // a slash anchors them to it
+        let spec = IgnoreSpec::from_lines(vec!["/top.txt", "doc/*.md", "*.log"])
+            .unwrap()
+            .with_base("sub");
+        assert!(spec.matches("sub/top.txt").unwrap());
+        assert!(!spec.matches("sub/deeper/top.txt").unwrap());
+        assert!(!spec.matches("top.txt").unwrap());
+        assert!(spec.matches("sub/doc/a.md").unwrap());
+        assert!(!spec.matches("sub/x/doc/a.md").unwrap());
+        assert!(!spec.matches("sub/doc/x/a.md").unwrap());
+        assert!(spec.matches("sub/deeper/debug.log").unwrap());
+        assert!(!spec.matches("debug.log").unwrap());
+        assert!(!spec.matches("subway/debug.log").unwrap());
+    }
+
+    #[test]
+    fn test_ignore_spec_double_asterisk() {
+        let spec = IgnoreSpec::from_lines(vec!["**/logs", "out/**", "a/**/b", "x**y"]).unwrap();
+        assert!(spec.matches_dir("logs").unwrap());
+        assert!(spec.matches_dir("deep/logs").unwrap());
+        assert!(spec.matches("out/file").unwrap());
+        assert!(spec.matches("out/deep/file").unwrap());
+        assert!(!spec.matches_dir("out").unwrap());
+        assert!(spec.matches("a/b").unwrap());
+        assert!(spec.matches("a/x/y/b").unwrap());
+        assert!(!spec.matches("c/a/b").unwrap());
+        // Other consecutive asterisks match like a single one
+        assert!(spec.matches("xzy").unwrap());
+        assert!(spec.matches("dir/xzy").unwrap());
+        assert!(!spec.matches("x/y").unwrap());
+    }
+
+    /// Compare the walk with the paths `git check-ignore` reports as ignored
+    #[test]
+    fn test_git_check_ignore_compatibility() {
+        use std::collections::BTreeSet;
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+        use tempfile::TempDir;
+
+        if Command::new("git").arg("--version").output().is_err() {
+            return;
+        }
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let files = [
+            "a.log",
+            "keep.log",
+            "notes.md",
+            "top.txt",
+            "build/out.o",
+            "build/dist/app.js",
+            "cache/x/data.bin",
+            "cache/keep/data.bin",
+            "docs/top.txt",
+            "docs/guide.md",
+            "docs/api/index.md",
+            "docs/api/v1/old.md",
+            "src/main.rs",
+            "src/gen/code.rs",
+            "src/gen/keep.rs",
+            "src/lib/gen/code.rs",
+            "src/deep/nested/tmp.txt",
+            "vendor/lib/x**y.c",
+            "vendor/lib/xzzy.c",
+        ];
+        let ignore_files = [
+            (
+                ".gitignore",
+                "*.log\n!keep.log\n/top.txt\nbuild/\n!build/dist/\ncache/*\n!cache/keep/\n",
+            ),
+            ("docs/.gitignore", "/top.txt\napi/**/*.md\n!api/index.md\n"),
+            ("src/.gitignore", "gen/\n!gen/keep.rs\ndeep/**/tmp.txt\n"),
+            ("vendor/.gitignore", "lib/x**y.c\n"),
+        ];
+        for path in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        for (path, contents) in ignore_files {
+            fs::write(root.join(path), contents).unwrap();
+        }
+        let git = || {
+            let mut command = Command::new("git");
+            command.current_dir(root).env("GIT_CONFIG_NOSYSTEM", "1");
+            command
+        };
+        assert!(git().args(["init", "-q"]).status().unwrap().success());
+
+        let all: Vec<&str> = files
+            .iter()
+            .chain(ignore_files.iter().map(|(path, _)| path))
+            .copied()
+            .collect();
+        let mut child = git()
+            .args(["check-ignore", "--stdin", "--no-index"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(all.join("\n").as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        let ignored: BTreeSet<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert!(!ignored.is_empty());
+
+        let walked: BTreeSet<String> = treewalk_ignore(root, ".gitignore", None, None)
+            .unwrap()
+            .iter()
+            .map(|p| {
+                p.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .filter(|p| !p.starts_with(".git/"))
+            .collect();
+        let expected: BTreeSet<String> = all
+            .iter()
+            .map(|p| p.to_string())
+            .filter(|p| !ignored.contains(p))
+            .collect();
+        assert_eq!(walked, expected);
     }
 
     #[test]
@@ -1202,6 +2341,599 @@ mod tests {
             .any(|p| p.starts_with("node_modules/")));
     }
 
+    #[test]
+    fn test_treewalk_ignore_with_skipped() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join(".gitignore"), "build/\n*.log\n").unwrap();
+        File::create(root.join("keep.txt")).unwrap();
+        File::create(root.join("debug.log")).unwrap();
+        fs::create_dir(root.join("build")).unwrap();
+        File::create(root.join("build/output.exe")).unwrap();
+
+        let (paths, skipped) =
+            treewalk_ignore_with_skipped(root, ".gitignore", None, None).unwrap();
+        assert_eq!(
+            paths,
+            treewalk_ignore(root, ".gitignore", None, None).unwrap()
+        );
+
+        let source = Some(root.join(".gitignore"));
+        assert_eq!(
+            skipped,
+            vec![
+                (
+                    root.join("debug.log"),
+                    IgnoreMatch {
+                        pattern: "*.log".to_string(),
+                        source: source.clone(),
+                    }
+                ),
+                (
+                    root.join("build"),
+                    IgnoreMatch {
+                        pattern: "build/".to_string(),
+                        source,
+                    }
+                ),
+            ]
+        );
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let result = treewalk_ignore_cancellable(root, ".gitignore", None, None, &cancel);
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn test_treewalk_ignore_tolerate_errors() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        File::create(root.join("a.txt")).unwrap();
+        // An invalid pattern makes the ignore file of `broken` unusable
+        fs::create_dir(root.join("broken")).unwrap();
+        fs::write(root.join("broken/.gitignore"), "[unclosed\n").unwrap();
+        File::create(root.join("broken/hidden.txt")).unwrap();
+        fs::create_dir(root.join("ok")).unwrap();
+        File::create(root.join("ok/b.txt")).unwrap();
+
+        let result = treewalk_ignore_with_skipped(root, ".gitignore", None, None);
+        assert!(matches!(result, Err(Error::InvalidPattern(_))));
+
+        let options = WalkOptions {
+            tolerate_errors: true,
+            ..WalkOptions::default()
+        };
+        let walk = treewalk_ignore_with_options(root, ".gitignore", None, None, &options).unwrap();
+        assert_eq!(walk.files, vec![root.join("a.txt"), root.join("ok/b.txt")]);
+        assert_eq!(walk.errors.len(), 1);
+        assert_eq!(walk.errors[0].0, root.join("broken"));
+        assert!(matches!(walk.errors[0].1, Error::InvalidPattern(_)));
+    }
+
+    #[test]
+    fn test_treewalk_ignore_metadata() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.join("a.txt"), b"hello").unwrap();
+        fs::write(root.join("debug.log"), b"ignored").unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub/b.txt"), b"hello world").unwrap();
+
+        let walk =
+            treewalk_ignore_with_options(root, ".gitignore", None, None, &WalkOptions::default())
+                .unwrap();
+        assert!(walk.entries.is_empty());
+
+        let options = WalkOptions {
+            metadata: true,
+            ..WalkOptions::default()
+        };
+        let walk = treewalk_ignore_with_options(root, ".gitignore", None, None, &options).unwrap();
+        let paths: Vec<_> = walk.entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(paths, walk.files);
+        let sizes: Vec<_> = walk.entries.iter().map(|e| e.size).collect();
+        assert_eq!(sizes, vec![6, 5, 11]);
+        for entry in &walk.entries {
+            assert!(entry.is_file);
+            assert_eq!(
+                entry.modified,
+                fs::metadata(&entry.path).unwrap().modified().ok()
+            );
+        }
+    }
+
+    #[test]
+    fn test_treewalk_ignore_files_precedence() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join(".gitignore"), "*.log\n*.tmp\n").unwrap();
+        fs::write(root.join(".isccignore"), "!keep.log\n*.bin\n").unwrap();
+        for name in ["a.txt", "debug.log", "keep.log", "data.bin", "x.tmp"] {
+            File::create(root.join(name)).unwrap();
+        }
+        fs::create_dir(root.join("sub")).unwrap();
+        // A subdirectory overrides both files of its parent
+        fs::write(root.join("sub/.gitignore"), "!*.tmp\n").unwrap();
+        File::create(root.join("sub/y.tmp")).unwrap();
+
+        let options = WalkOptions::default();
+        let walk =
+            treewalk_ignore_files(root, &[".isccignore", ".gitignore"], None, None, &options)
+                .unwrap();
+        assert_eq!(
+            walk.files,
+            vec![
+                root.join(".gitignore"),
+                root.join(".isccignore"),
+                root.join("a.txt"),
+                root.join("keep.log"),
+                root.join("sub/.gitignore"),
+                root.join("sub/y.tmp"),
+            ]
+        );
+        let source = |path: &PathBuf| {
+            walk.skipped
+                .iter()
+                .find(|(skipped, _)| skipped == path)
+                .and_then(|(_, reason)| reason.source.clone())
+        };
+        assert_eq!(
+            source(&root.join("data.bin")),
+            Some(root.join(".isccignore"))
+        );
+        assert_eq!(
+            source(&root.join("debug.log")),
+            Some(root.join(".gitignore"))
+        );
+
+        // With the order reversed .gitignore decides about keep.log
+        let walk =
+            treewalk_ignore_files(root, &[".gitignore", ".isccignore"], None, None, &options)
+                .unwrap();
+        assert!(!walk.files.contains(&root.join("keep.log")));
+    }
+
+    #[test]
+    fn test_treewalk_ignore_directories() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join(".gitignore"), "build/\n").unwrap();
+        fs::create_dir_all(root.join("a/empty")).unwrap();
+        File::create(root.join("a/file.txt")).unwrap();
+        fs::create_dir(root.join("b")).unwrap();
+        fs::create_dir(root.join("build")).unwrap();
+
+        let walk =
+            treewalk_ignore_with_options(root, ".gitignore", None, None, &WalkOptions::default())
+                .unwrap();
+        assert!(walk.directories.is_empty());
+
+        let options = WalkOptions {
+            directories: true,
+            ..WalkOptions::default()
+        };
+        let walk = treewalk_ignore_with_options(root, ".gitignore", None, None, &options).unwrap();
+        let directories: Vec<_> = walk.directories.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(
+            directories,
+            vec![root.join("a"), root.join("a/empty"), root.join("b")]
+        );
+        assert!(walk.directories.iter().all(|e| e.is_dir && !e.is_file));
+        assert_eq!(
+            walk.files,
+            vec![root.join(".gitignore"), root.join("a/file.txt")]
+        );
+    }
+
+    #[test]
+    fn test_treewalk_channel() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join(".gitignore"), "build/\n*.log\n").unwrap();
+        fs::create_dir_all(root.join("a/empty")).unwrap();
+        File::create(root.join("a/file.txt")).unwrap();
+        File::create(root.join("debug.log")).unwrap();
+        fs::create_dir(root.join("build")).unwrap();
+
+        let (handle, receiver) =
+            treewalk_channel(root, &[".gitignore"], None, &WalkOptions::default());
+        let events: Vec<String> = receiver
+            .iter()
+            .map(|event| match event {
+                WalkEvent::EnterDir(entry) => format!("enter {}", entry.name),
+                WalkEvent::ExitDir(path) => {
+                    format!("exit {}", path.file_name().unwrap().to_string_lossy())
+                }
+                WalkEvent::File(entry) => format!("file {}", entry.name),
+                WalkEvent::Skipped(path, reason) => format!(
+                    "skip {} {}",
+                    path.file_name().unwrap().to_string_lossy(),
+                    reason.pattern
+                ),
+                WalkEvent::Error(path, error) => format!("error {} {error}", path.display()),
+            })
+            .collect();
+        handle.join().unwrap().unwrap();
+        assert_eq!(
+            events,
+            vec![
+                "file .gitignore",
+                "skip debug.log *.log",
+                "enter a",
+                "file file.txt",
+                "enter empty",
+                "exit empty",
+                "exit a",
+                "skip build build/",
+            ]
+        );
+
+        // The same files as the collecting walk
+        let walk =
+            treewalk_ignore_with_options(root, ".gitignore", None, None, &WalkOptions::default())
+                .unwrap();
+        let (handle, receiver) =
+            treewalk_channel(root, &[".gitignore"], None, &WalkOptions::default());
+        let files: Vec<PathBuf> = receiver
+            .iter()
+            .filter_map(|event| match event {
+                WalkEvent::File(entry) => Some(entry.path),
+                _ => None,
+            })
+            .collect();
+        handle.join().unwrap().unwrap();
+        assert_eq!(files, walk.files);
+    }
+
+    #[test]
+    fn test_treewalk_channel_backpressure_and_errors() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for i in 0..WALK_CHANNEL_CAPACITY * 2 {
+            File::create(root.join(format!("{i:05}.txt"))).unwrap();
+        }
+
+        // Dropping the receiver stops the blocked walk
+        let (handle, receiver) = treewalk_channel(root, &[], None, &WalkOptions::default());
+        assert!(matches!(receiver.recv().unwrap(), WalkEvent::File(_)));
+        drop(receiver);
+        assert!(matches!(handle.join().unwrap(), Err(Error::Cancelled)));
+
+        // A walk that cannot start reports its error through the handle
+        let (handle, receiver) =
+            treewalk_channel(root.join("missing"), &[], None, &WalkOptions::default());
+        assert!(receiver.recv().is_err());
+        assert!(handle.join().unwrap().is_err());
+
+        // Invalid ignore files below the root are sent as tolerated errors
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub/.gitignore"), "[\n").unwrap();
+        let options = WalkOptions {
+            tolerate_errors: true,
+            ..WalkOptions::default()
+        };
+        let (handle, receiver) = treewalk_channel(root, &[".gitignore"], None, &options);
+        let errors: Vec<PathBuf> = receiver
+            .iter()
+            .filter_map(|event| match event {
+                WalkEvent::Error(path, _) => Some(path),
+                _ => None,
+            })
+            .collect();
+        handle.join().unwrap().unwrap();
+        assert_eq!(errors, vec![root.join("sub")]);
+    }
+
+    #[test]
+    fn test_treewalk_ignore_git() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        File::create(root.join(".git/HEAD")).unwrap();
+        fs::write(root.join(".git/info/exclude"), "*.secret\n*.tmp\n").unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n!keep.tmp\n").unwrap();
+        for name in ["a.txt", "a.secret", "a.log", "drop.tmp", "keep.tmp"] {
+            File::create(root.join(name)).unwrap();
+        }
+        // A nested repository (here a submodule with a `.git` file)
+        fs::create_dir(root.join("nested")).unwrap();
+        fs::write(root.join("nested/.git"), "gitdir: ../.git/modules/nested\n").unwrap();
+        File::create(root.join("nested/b.log")).unwrap();
+        File::create(root.join("nested/b.secret")).unwrap();
+
+        let names = |walk: &Walk| -> Vec<String> {
+            walk.files
+                .iter()
+                .map(|p| {
+                    p.strip_prefix(root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect()
+        };
+
+        let walk =
+            treewalk_ignore_with_options(root, ".gitignore", None, None, &WalkOptions::default())
+                .unwrap();
+        assert!(walk.files.contains(&root.join(".git/HEAD")));
+        assert!(walk.files.contains(&root.join("a.secret")));
+
+        let options = WalkOptions {
+            git: true,
+            ..WalkOptions::default()
+        };
+        let walk = treewalk_ignore_with_options(root, ".gitignore", None, None, &options).unwrap();
+        assert_eq!(
+            names(&walk),
+            vec![
+                ".gitignore",
+                "a.txt",
+                "keep.tmp",
+                "nested/b.log",
+                "nested/b.secret"
+            ]
+        );
+        let git_skipped: Vec<&PathBuf> = walk
+            .skipped
+            .iter()
+            .filter(|(_, reason)| reason.source.is_none())
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(
+            git_skipped,
+            vec![&root.join(".git"), &root.join("nested/.git")]
+        );
+        let (_, reason) = walk
+            .skipped
+            .iter()
+            .find(|(path, _)| path.ends_with("a.secret"))
+            .unwrap();
+        assert_eq!(
+            reason.source.as_deref(),
+            Some(root.join(".git/info/exclude").as_path())
+        );
+    }
+
+    #[test]
+    fn test_treewalk_ignore_git_subdirectory() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let repo = fs::canonicalize(temp_dir.path()).unwrap();
+        let root = repo.join("sub");
+
+        fs::create_dir_all(repo.join(".git/info")).unwrap();
+        fs::write(repo.join(".git/info/exclude"), "*.secret\n").unwrap();
+        fs::write(repo.join(".gitignore"), "*.log\n/sub/build/\n").unwrap();
+        fs::create_dir_all(root.join("build")).unwrap();
+        fs::create_dir_all(root.join("other/build")).unwrap();
+        fs::write(root.join(".gitignore"), "!keep.log\n").unwrap();
+        for name in ["a.txt", "a.secret", "a.log", "keep.log", "build/out.bin"] {
+            File::create(root.join(name)).unwrap();
+        }
+        File::create(root.join("other/build/out.bin")).unwrap();
+
+        let walk = |git: bool| {
+            let options = WalkOptions {
+                git,
+                ..WalkOptions::default()
+            };
+            treewalk_ignore_with_options(&root, ".gitignore", None, None, &options).unwrap()
+        };
+        let names = |walk: &Walk| -> Vec<String> {
+            walk.files
+                .iter()
+                .map(|p| {
+                    p.strip_prefix(&root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect()
+        };
+
+        // Without git the ignore files above the walked directory do not apply
+        assert_eq!(names(&walk(false)).len(), 7);
+
+        let walk = walk(true);
+        assert_eq!(
+            names(&walk),
+            vec![".gitignore", "a.txt", "keep.log", "other/build/out.bin"]
+        );
+        let sources: Vec<(PathBuf, Option<PathBuf>)> = walk
+            .skipped
+            .into_iter()
+            .map(|(path, reason)| (path, reason.source))
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                (root.join("a.log"), Some(repo.join(".gitignore"))),
+                (root.join("a.secret"), Some(repo.join(".git/info/exclude"))),
+                (root.join("build"), Some(repo.join(".gitignore"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_treewalk_ignore_file_filters() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join(".gitignore"), "skip.jpg\n").unwrap();
+        for name in ["a.JPG", "b.png", "c.txt", "skip.jpg", "d.raw.png"] {
+            File::create(root.join(name)).unwrap();
+        }
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub/large.png"), b"0123456789").unwrap();
+
+        let walk = |options: &WalkOptions| {
+            treewalk_ignore_with_options(root, ".gitignore", None, None, options)
+                .unwrap()
+                .files
+        };
+        let options = WalkOptions {
+            extensions: vec![".jpg".to_string(), "png".to_string()],
+            ..WalkOptions::default()
+        };
+        assert_eq!(
+            walk(&options),
+            vec![
+                root.join("a.JPG"),
+                root.join("b.png"),
+                root.join("d.raw.png"),
+                root.join("sub/large.png")
+            ]
+        );
+
+        let options = WalkOptions {
+            exclude_extensions: vec!["png".to_string(), "txt".to_string()],
+            ..WalkOptions::default()
+        };
+        assert_eq!(
+            walk(&options),
+            vec![root.join(".gitignore"), root.join("a.JPG")]
+        );
+
+        let options = WalkOptions {
+            extensions: vec!["png".to_string()],
+            filter: Some(FileFilter::new(|entry: &DirEntry| entry.size > 0)),
+            ..WalkOptions::default()
+        };
+        assert_eq!(walk(&options), vec![root.join("sub/large.png")]);
+
+        // Filtered files are not reported as skipped by a pattern
+        let walked =
+            treewalk_ignore_with_options(root, ".gitignore", None, None, &options).unwrap();
+        assert!(walked.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_treewalk_ignore_max_depth_and_prune() {
+        use std::fs::{self, File};
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        File::create(root.join("a.txt")).unwrap();
+        fs::create_dir_all(root.join("sub/deeper")).unwrap();
+        File::create(root.join("sub/b.txt")).unwrap();
+        File::create(root.join("sub/deeper/c.txt")).unwrap();
+        fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+        File::create(root.join("node_modules/pkg/index.js")).unwrap();
+
+        let walk = |options: &WalkOptions| {
+            treewalk_ignore_with_options(root, ".gitignore", None, None, options)
+                .unwrap()
+                .files
+        };
+        let depth = |max_depth| WalkOptions {
+            max_depth: Some(max_depth),
+            ..WalkOptions::default()
+        };
+        assert_eq!(walk(&depth(0)), vec![root.join("a.txt")]);
+        assert_eq!(walk(&depth(1)).len(), 2);
+        assert_eq!(walk(&depth(2)).len(), 4);
+
+        let options = WalkOptions {
+            prune: Some(PruneCallback::new(|dir: &Path| {
+                dir.file_name().is_some_and(|name| name == "node_modules")
+            })),
+            ..WalkOptions::default()
+        };
+        let walked =
+            treewalk_ignore_with_options(root, ".gitignore", None, None, &options).unwrap();
+        assert_eq!(
+            walked.files,
+            vec![
+                root.join("a.txt"),
+                root.join("sub/b.txt"),
+                root.join("sub/deeper/c.txt")
+            ]
+        );
+        assert!(walked.skipped.is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_treewalk_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::fs::{self, File};
+        use std::os::unix::ffi::OsStrExt;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let invalid = root.join(OsStr::from_bytes(b"bad\xff.txt"));
+        for path in [
+            root.join("zz.txt"),
+            invalid.clone(),
+            root.join("bad.txt"),
+            root.join("a.txt"),
+        ] {
+            File::create(path).unwrap();
+        }
+
+        // Invalid names sort by their raw bytes instead of failing the listing
+        let entries = listdir(root).unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.path.clone()).collect();
+        let expected = vec![
+            root.join("a.txt"),
+            root.join("bad.txt"),
+            invalid.clone(),
+            root.join("zz.txt"),
+        ];
+        assert_eq!(paths, expected);
+        assert_eq!(entries[2].name, "bad\u{FFFD}.txt");
+        assert_eq!(treewalk(root).unwrap(), expected);
+
+        // Patterns match the raw bytes of invalid names
+        fs::write(root.join(".gitignore"), "bad?.txt\n").unwrap();
+        let walk = treewalk_ignore_with_skipped(root, ".gitignore", None, None).unwrap();
+        assert_eq!(walk.1.len(), 1);
+        assert_eq!(walk.1[0].0, invalid);
+    }
+
     #[test]
     fn test_treewalk_ignore_cascading() {
         use std::fs::{self, File};
@@ -1310,10 +3042,29 @@ mod tests {
             })
             .collect();
 
-        // Should include important.log (negated) and build/dist/app.js (negated directory)
+        // Should include important.log (negated), but not build/dist/app.js: a
+        // directory below an excluded one cannot be re-included
         assert!(relative_paths.contains(&".gitignore".to_string()));
         assert!(relative_paths.contains(&"important.log".to_string())); // Negated
         assert!(relative_paths.contains(&"file.txt".to_string()));
+        assert!(!relative_paths.contains(&"build/dist/app.js".to_string()));
+
+        // Excluding the contents instead of the directory allows re-including
+        fs::write(
+            root.join(".gitignore"),
+            "*.log\n!important.log\nbuild/*\n!build/dist/\n",
+        )
+        .unwrap();
+        let paths = treewalk_ignore(root, ".gitignore", None, None).unwrap();
+        let relative_paths: Vec<String> = paths
+            .iter()
+            .map(|p| {
+                p.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
         assert!(relative_paths.contains(&"build/dist/app.js".to_string())); // In negated directory
 
         // Should NOT include regular log files or non-negated build files
@@ -1327,7 +3078,7 @@ mod tests {
         let result = treewalk_ignore("/this/path/should/not/exist", ".gitignore", None, None);
         assert!(result.is_err());
         match result.unwrap_err() {
-            TreewalkError::IoError(e) => {
+            Error::Io { source: e, .. } => {
                 assert_eq!(e.kind(), io::ErrorKind::NotFound);
             }
             _ => panic!("Expected IoError with NotFound"),
@@ -1346,7 +3097,7 @@ mod tests {
         let result = treewalk_ignore(&file_path, ".gitignore", None, None);
         assert!(result.is_err());
         match result.unwrap_err() {
-            TreewalkError::IoError(e) => {
+            Error::Io { source: e, .. } => {
                 assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
             }
             _ => panic!("Expected IoError with InvalidInput"),
@@ -1596,7 +3347,7 @@ mod tests {
         let result = treewalk_iscc("/this/path/should/not/exist");
         assert!(result.is_err());
         match result.unwrap_err() {
-            TreewalkError::IoError(e) => {
+            Error::Io { source: e, .. } => {
                 assert_eq!(e.kind(), io::ErrorKind::NotFound);
             }
             _ => panic!("Expected IoError with NotFound"),
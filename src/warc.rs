@@ -0,0 +1,220 @@
+//! Streaming reader for WARC (ISO 28500) web archives.
+//!
+//! Records are read one at a time from plain or gzip-compressed archives
+//! (`.warc.gz` files are concatenated gzip members, one per record), so
+//! archives of any size can be processed without extracting them. For
+//! `response` records the HTTP status line and headers are skipped and only
+//! the payload is passed on.
+
+use flate2::read::MultiGzDecoder;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// True for `.warc` and `.warc.gz` file names (case-insensitive)
+pub fn is_warc_path(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy().to_ascii_lowercase();
+        name.ends_with(".warc") || name.ends_with(".warc.gz")
+    })
+}
+
+/// Open a WARC archive for reading, decompressing `.gz` archives on the fly
+pub fn open_archive<R: Read + 'static>(path: &Path, reader: R) -> Box<dyn BufRead> {
+    let gzipped = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
+    if gzipped {
+        Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+    } else {
+        Box::new(BufReader::new(reader))
+    }
+}
+
+/// Named fields of a WARC record header
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WarcHeader {
+    /// `WARC-Type` (e.g. `response`, `request`, `metadata`)
+    pub warc_type: String,
+    /// `WARC-Target-URI`, if present
+    pub target_uri: Option<String>,
+    /// `WARC-Record-ID`, if present
+    pub record_id: Option<String>,
+    /// Length of the record block in bytes
+    pub content_length: u64,
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Read a CRLF or LF terminated line; `None` at end of input
+fn read_line<R: BufRead + ?Sized>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    while line.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+        line.pop();
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+/// Read the next record header; `None` at the end of the archive
+fn read_header<R: BufRead + ?Sized>(reader: &mut R) -> io::Result<Option<WarcHeader>> {
+    // Skip blank lines separating records
+    let version = loop {
+        match read_line(reader)? {
+            None => return Ok(None),
+            Some(line) if line.is_empty() => continue,
+            Some(line) => break line,
+        }
+    };
+    if !version.starts_with("WARC/") {
+        return Err(invalid(format!("not a WARC record: {version:?}")));
+    }
+
+    let mut header = WarcHeader::default();
+    let mut content_length = None;
+    loop {
+        let line = read_line(reader)?.ok_or_else(|| invalid("truncated WARC header"))?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "warc-type" => header.warc_type = value,
+            "warc-target-uri" => {
+                // WARC/1.0 wrote URIs in angle brackets
+                let uri = value.trim_start_matches('<').trim_end_matches('>');
+                header.target_uri = Some(uri.to_string());
+            }
+            "warc-record-id" => header.record_id = Some(value),
+            "content-length" => {
+                content_length = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid Content-Length: {value:?}")))?,
+                )
+            }
+            _ => {}
+        }
+    }
+    header.content_length = content_length.ok_or_else(|| invalid("missing Content-Length"))?;
+    Ok(Some(header))
+}
+
+/// Call `f` with the header and payload of each `response` record in the archive.
+///
+/// The payload reader yields the HTTP body (after the status line and headers)
+/// as stored in the archive; whatever `f` leaves unread is skipped.
+pub fn for_each_response<R, F>(reader: &mut R, mut f: F) -> io::Result<()>
+where
+    R: BufRead + ?Sized,
+    F: FnMut(&WarcHeader, &mut dyn Read) -> io::Result<()>,
+{
+    while let Some(header) = read_header(reader)? {
+        let mut block = (&mut *reader).take(header.content_length);
+        if header.warc_type == "response" {
+            // Skip the HTTP status line and headers
+            while let Some(line) = read_line(&mut block)? {
+                if line.is_empty() {
+                    break;
+                }
+            }
+            f(&header, &mut block)?;
+        }
+        io::copy(&mut block, &mut io::sink())?;
+        if block.limit() > 0 {
+            return Err(invalid("truncated WARC record"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn record(warc_type: &str, uri: &str, block: &[u8]) -> Vec<u8> {
+        let mut out = format!(
+            "WARC/1.1\r\nWARC-Type: {warc_type}\r\nWARC-Target-URI: {uri}\r\n\
+             Content-Length: {}\r\n\r\n",
+            block.len()
+        )
+        .into_bytes();
+        out.extend_from_slice(block);
+        out.extend_from_slice(b"\r\n\r\n");
+        out
+    }
+
+    fn sample_records() -> Vec<Vec<u8>> {
+        vec![
+            record("warcinfo", "", b"software: test\r\n"),
+            record("request", "http://example.com/", b"GET / HTTP/1.1\r\n\r\n"),
+            record(
+                "response",
+                "http://example.com/",
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<html></html>",
+            ),
+            record(
+                "response",
+                "<http://example.com/empty>",
+                b"HTTP/1.1 204 No Content\r\n\r\n",
+            ),
+        ]
+    }
+
+    fn collect(reader: &mut dyn BufRead) -> Vec<(String, Vec<u8>)> {
+        let mut payloads = Vec::new();
+        for_each_response(reader, |header, payload| {
+            let mut body = Vec::new();
+            payload.read_to_end(&mut body)?;
+            payloads.push((header.target_uri.clone().unwrap(), body));
+            Ok(())
+        })
+        .unwrap();
+        payloads
+    }
+
+    #[test]
+    fn test_response_payloads() {
+        let archive = sample_records().concat();
+        let payloads = collect(&mut &archive[..]);
+        assert_eq!(
+            payloads,
+            vec![
+                ("http://example.com/".to_string(), b"<html></html>".to_vec()),
+                ("http://example.com/empty".to_string(), Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gzip_members() {
+        let mut archive = Vec::new();
+        for record in sample_records() {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&record).unwrap();
+            archive.extend(encoder.finish().unwrap());
+        }
+        let mut reader = open_archive(Path::new("crawl.warc.gz"), io::Cursor::new(archive));
+        assert_eq!(collect(&mut reader).len(), 2);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut input: &[u8] = b"not a warc file\r\n";
+        assert!(for_each_response(&mut input, |_, _| Ok(())).is_err());
+        let mut truncated = &record("response", "http://x/", b"HTTP/1.1 200 OK\r\n\r\nbody")[..40];
+        assert!(for_each_response(&mut truncated, |_, _| Ok(())).is_err());
+        assert!(is_warc_path(Path::new("dir/CRAWL.WARC.GZ")));
+        assert!(!is_warc_path(Path::new("notes.txt")));
+    }
+}
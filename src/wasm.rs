@@ -0,0 +1,124 @@
+//! WebAssembly bindings (feature `wasm`).
+//!
+//! Exports the in-memory hashing API to JavaScript with `wasm-bindgen`, so web
+//! apps can compute ISCC-SUMs of user-selected files client-side by feeding
+//! the bytes of a `File` or `Blob` slice by slice:
+//!
+//! ```text
+//! const processor = new IsccSumProcessor(false, true, 64);
+//! for await (const chunk of file.stream()) processor.update(chunk);
+//! const result = processor.result();
+//! ```
+//!
+//! Nothing here touches the filesystem. The names follow the Python
+//! bindings; functions are camelCase as usual in JavaScript.
+
+use wasm_bindgen::prelude::*;
+
+use crate::codec::{self, IsccCode};
+use crate::options::IsccSumOptions;
+use crate::sum;
+
+/// Incremental ISCC-SUM hasher for JavaScript
+#[wasm_bindgen(js_name = IsccSumProcessor)]
+pub struct WasmIsccSumProcessor {
+    inner: sum::IsccSumProcessor,
+}
+
+#[wasm_bindgen(js_class = IsccSumProcessor)]
+impl WasmIsccSumProcessor {
+    /// Processor producing wide (128-bit unit) or standard codes of `bits` length
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        wide: Option<bool>,
+        add_units: Option<bool>,
+        bits: Option<u32>,
+    ) -> Result<WasmIsccSumProcessor, JsError> {
+        let mut builder = IsccSumOptions::builder()
+            .wide(wide.unwrap_or(false))
+            .add_units(add_units.unwrap_or(true));
+        if let Some(bits) = bits {
+            builder = builder.bits(bits);
+        }
+        let options = builder.build()?;
+        Ok(WasmIsccSumProcessor {
+            inner: sum::IsccSumProcessor::with_options(options),
+        })
+    }
+
+    /// Feed the next bytes of the content
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Result for the content fed so far
+    pub fn result(&self) -> WasmIsccSumResult {
+        self.inner.result().into()
+    }
+}
+
+/// ISCC-SUM of some content
+#[wasm_bindgen(js_name = IsccSumResult, getter_with_clone)]
+pub struct WasmIsccSumResult {
+    /// ISCC-SUM code (`ISCC:...`)
+    pub iscc: String,
+    /// Hex-encoded BLAKE3 multihash of the content
+    pub datahash: String,
+    /// Size of the content in bytes (exact up to 2^53)
+    pub filesize: f64,
+    /// Data-Code and Instance-Code units, if requested
+    pub units: Option<Vec<String>>,
+}
+
+impl From<sum::IsccSumResult> for WasmIsccSumResult {
+    fn from(result: sum::IsccSumResult) -> Self {
+        WasmIsccSumResult {
+            iscc: result.iscc,
+            datahash: result.datahash,
+            filesize: result.filesize as f64,
+            units: result.units,
+        }
+    }
+}
+
+/// ISCC-SUM of a complete byte array
+#[wasm_bindgen(js_name = hashBytes)]
+pub fn hash_bytes(data: &[u8], wide: Option<bool>) -> Result<WasmIsccSumResult, JsError> {
+    let options = IsccSumOptions::builder()
+        .wide(wide.unwrap_or(false))
+        .build()?;
+    Ok(sum::hash_bytes(data, &options).into())
+}
+
+/// Similarity of two codes, see [`codec::compare`]
+#[wasm_bindgen(js_name = Comparison, getter_with_clone)]
+pub struct WasmComparison {
+    /// Similarity from 0.0 (unrelated) to 1.0 (same content)
+    pub similarity: f64,
+    /// Names of the common units (`data`, `instance`, ...)
+    pub units: Vec<String>,
+    /// Number of differing bits per common unit, in the order of `units`
+    pub distances: Vec<u32>,
+    /// Number of compared bits per common unit, in the order of `units`
+    pub bits: Vec<u32>,
+}
+
+/// Compare the units two ISCC codes have in common
+#[wasm_bindgen]
+pub fn compare(a: &str, b: &str) -> Result<WasmComparison, JsError> {
+    let parse = |code: &str| {
+        code.parse::<IsccCode>()
+            .map_err(|e| JsError::new(&format!("Invalid ISCC {code:?}: {e}")))
+    };
+    let comparison = codec::compare(&parse(a)?, &parse(b)?);
+    Ok(WasmComparison {
+        similarity: comparison.similarity,
+        units: comparison
+            .units
+            .iter()
+            .map(|unit| unit.maintype.name().to_string())
+            .collect(),
+        distances: comparison.units.iter().map(|unit| unit.distance).collect(),
+        bits: comparison.units.iter().map(|unit| unit.bits).collect(),
+    })
+}
@@ -335,3 +335,1292 @@ fn test_complex_glob_pattern() {
         .stdout(predicate::str::contains("temp1.tmp").not())
         .stdout(predicate::str::contains("temp2.tmp").not());
 }
+
+#[test]
+fn test_manifests_write_and_check() {
+    let temp_dir = TempDir::new().unwrap();
+    create_nested_directory_structure(temp_dir.path());
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--manifests")
+        .arg(temp_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    // Each directory lists only its immediate files
+    let root_manifest = fs::read_to_string(temp_dir.path().join("ISCC-SUMS")).unwrap();
+    assert!(root_manifest.contains(" *file1.txt\n"));
+    assert!(!root_manifest.contains("file3.txt"));
+    let nested_manifest =
+        fs::read_to_string(temp_dir.path().join("subdir1/subdir2/ISCC-SUMS")).unwrap();
+    assert_eq!(nested_manifest.lines().count(), 2);
+    assert!(nested_manifest.contains(" *file5.txt\n"));
+
+    // Re-running must not list the manifests themselves
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--manifests")
+        .arg(temp_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+    let root_manifest = fs::read_to_string(temp_dir.path().join("ISCC-SUMS")).unwrap();
+    assert!(!root_manifest.contains("ISCC-SUMS"));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-manifests")
+        .arg(temp_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("file5.txt: OK"));
+
+    fs::write(temp_dir.path().join("subdir1/file3.txt"), b"changed").unwrap();
+    fs::remove_file(temp_dir.path().join("tmpdir/temp1.tmp")).unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-manifests")
+        .arg(temp_dir.path().to_str().unwrap())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("file3.txt: FAILED"))
+        .stderr(predicate::str::contains("temp1.tmp"))
+        .stderr(predicate::str::contains("2 file(s) did NOT match"));
+}
+
+#[test]
+fn test_check_manifests_malformed_line() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("ISCC-SUMS"), "not a checksum line\n").unwrap();
+
+    // Malformed lines fail the check like mismatches
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-manifests")
+        .arg(temp_dir.path().to_str().unwrap())
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("improperly formatted"));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--check-manifests", "--format", "json"])
+        .arg(temp_dir.path().to_str().unwrap())
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("\"malformed\":1"))
+        .stdout(predicate::str::contains("\"success\":false"));
+}
+
+#[test]
+fn test_check_manifests_wide_codes() {
+    use _core::options::IsccSumOptions;
+    use _core::sum::hash_bytes;
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("a.txt"), b"first").unwrap();
+    fs::write(root.join("b.txt"), b"second").unwrap();
+
+    // 192- and 256-bit ISCC-SUMs are recomputed at their own width
+    let code = |data: &[u8], bits| {
+        let options = IsccSumOptions::builder().bits(bits).build().unwrap();
+        hash_bytes(data, &options).iscc
+    };
+    let manifest = format!(
+        "{} *a.txt\n{} *b.txt\n",
+        code(b"first", 192),
+        code(b"second", 256)
+    );
+    fs::write(root.join("ISCC-SUMS"), manifest).unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-manifests")
+        .arg(root)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("2 files OK"));
+
+    fs::write(root.join("b.txt"), b"changed").unwrap();
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-manifests")
+        .arg(root)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::is_match(r"b\.txt: +FAILED").unwrap());
+}
+
+#[test]
+fn test_check_manifests_json_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("good.txt"), b"good").unwrap();
+    fs::write(root.join("bad.txt"), b"bad").unwrap();
+    fs::write(root.join("gone.txt"), b"gone").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--manifests")
+        .arg(root.to_str().unwrap())
+        .assert()
+        .success();
+
+    fs::write(root.join("bad.txt"), b"worse").unwrap();
+    fs::remove_file(root.join("gone.txt")).unwrap();
+    let mut manifest = fs::read_to_string(root.join("ISCC-SUMS")).unwrap();
+    manifest.push_str("garbage line\n");
+    fs::write(root.join("ISCC-SUMS"), manifest).unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    let output = cmd
+        .args(["--check-manifests", "--format", "json"])
+        .arg(root.to_str().unwrap())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].contains("bad.txt") && lines[0].contains("\"status\":\"mismatch\""));
+    assert!(lines[1].contains("gone.txt") && lines[1].contains("\"status\":\"missing\""));
+    assert!(lines[2].contains("good.txt") && lines[2].contains("\"status\":\"ok\""));
+    assert!(lines[3].contains("\"status\":\"malformed\""));
+    assert!(lines[4].starts_with("{\"summary\":{\"total\":4,\"ok\":1,\"mismatch\":1,"));
+    assert!(lines[4].contains("\"success\":false"));
+}
+
+#[test]
+fn test_check_sidecar_json_ok() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("data.bin");
+    fs::write(&file_path, b"payload").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--sidecar")
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--check-sidecar", "--format", "json"])
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"status\":\"ok\""))
+        .stdout(predicate::str::contains("\"success\":true"));
+}
+
+#[test]
+fn test_size_filters() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("small.bin"), vec![0u8; 10]).unwrap();
+    fs::write(root.join("medium.bin"), vec![0u8; 2048]).unwrap();
+    fs::write(root.join("large.bin"), vec![0u8; 5000]).unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--min-size", "1K", "--max-size", "4K"])
+        .arg(root.to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("medium.bin"))
+        .stdout(predicate::str::contains("small.bin").not())
+        .stdout(predicate::str::contains("large.bin").not());
+}
+
+#[test]
+fn test_newer_than_filter() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("file.txt"), b"content").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--newer-than", "2000-01-01"])
+        .arg(root.to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("file.txt"));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--newer-than", "9999-01-01T00:00:00"])
+        .arg(root.to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--newer-than", "not-a-time-or-file"])
+        .arg(root.to_str().unwrap())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_config_file_and_env_precedence() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("data");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("keep.txt"), b"content").unwrap();
+    fs::write(root.join("skip.tmp"), b"content").unwrap();
+    let config = temp_dir.path().join("config.toml");
+    fs::write(&config, "bits = 128\nexclude = [\"*.tmp\"]\n").unwrap();
+
+    // The config file sets narrow checksums and excludes
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .env("ISUM_CONFIG", &config)
+        .arg(&root)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success());
+    assert!(stdout.contains("keep.txt") && !stdout.contains("skip.tmp"));
+    let narrow_len = stdout.split_whitespace().next().unwrap().len();
+
+    // Environment variables override the config file, --wide overrides both
+    for cmd in [
+        Command::cargo_bin("isum")
+            .unwrap()
+            .env("ISUM_CONFIG", &config)
+            .env("ISUM_BITS", "256")
+            .arg(&root)
+            .output(),
+        Command::cargo_bin("isum")
+            .unwrap()
+            .env("ISUM_CONFIG", &config)
+            .arg("--wide")
+            .arg(&root)
+            .output(),
+    ] {
+        let stdout = String::from_utf8(cmd.unwrap().stdout).unwrap();
+        assert!(stdout.split_whitespace().next().unwrap().len() > narrow_len);
+    }
+
+    // --no-config ignores both
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.env("ISUM_CONFIG", &config)
+        .env("ISUM_BITS", "128")
+        .arg("--no-config")
+        .arg(&root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("skip.tmp"));
+
+    // Invalid configuration is a usage error
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.env("ISUM_CONFIG", &config)
+        .env("ISUM_BITS", "64")
+        .arg(&root)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("ISUM_BITS"));
+}
+
+#[test]
+fn test_ignore_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_nested_directory_structure(root);
+    fs::write(root.join(".isccignore"), "*.log\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--ignore-file", ".isccignore"])
+        .arg(root.to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("file1.txt"))
+        .stdout(predicate::str::contains(".log").not());
+}
+
+#[test]
+fn test_global_ignore() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("tree");
+    fs::create_dir(&root).unwrap();
+    create_nested_directory_structure(&root);
+    fs::write(root.join("subdir1/Thumbs.db"), b"junk").unwrap();
+    let global = temp_dir.path().join("ignore");
+    fs::write(&global, "Thumbs.db\n*.log\n").unwrap();
+
+    // Applies without --ignore-file, and per-directory files can re-include
+    fs::write(root.join("subdir1/.isccignore"), "!file6.log\n").unwrap();
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--global-ignore"])
+        .arg(&global)
+        .arg(&root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("file1.txt"))
+        .stdout(predicate::str::contains("Thumbs.db").not())
+        .stdout(predicate::str::contains("file2.log").not());
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args([
+        "--no-config",
+        "--ignore-file",
+        ".isccignore",
+        "--global-ignore",
+    ])
+    .arg(&global)
+    .arg(&root)
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("file6.log"))
+    .stdout(predicate::str::contains("file2.log").not());
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--list-ignored", "--global-ignore"])
+        .arg(&global)
+        .arg(&root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(global.display().to_string()));
+}
+
+#[test]
+fn test_ignore_case() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_nested_directory_structure(root);
+    fs::write(root.join(".isccignore"), "SUBDIR1/\nFILE1.TXT\n").unwrap();
+
+    let run = |extra: &[&str]| {
+        let output = Command::cargo_bin("isum")
+            .unwrap()
+            .args(["--no-config", "--ignore-file", ".isccignore"])
+            .args(extra)
+            .arg(root)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let sensitive = run(&[]);
+    assert!(sensitive.contains("file1.txt") && sensitive.contains("file3.txt"));
+    let insensitive = run(&["--ignore-case"]);
+    assert!(!insensitive.contains("file1.txt") && !insensitive.contains("file3.txt"));
+    assert!(insensitive.contains("file2.log"));
+}
+
+#[test]
+fn test_git_repository() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_nested_directory_structure(root);
+    fs::create_dir_all(root.join(".git/info")).unwrap();
+    fs::write(root.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    fs::write(root.join(".git/info/exclude"), "*.tmp\n").unwrap();
+
+    let run = |extra: &[&str]| {
+        let output = Command::cargo_bin("isum")
+            .unwrap()
+            .arg("--no-config")
+            .args(extra)
+            .arg(root)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let plain = run(&[]);
+    assert!(plain.contains("HEAD") && plain.contains("temp1.tmp"));
+    let git = run(&["--git"]);
+    assert!(!git.contains("HEAD") && !git.contains("temp1.tmp"));
+    assert!(git.contains("file1.txt") && git.contains("file2.log"));
+}
+
+#[test]
+fn test_multiple_ignore_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_nested_directory_structure(root);
+    fs::write(root.join(".gitignore"), "*.log\n*.tmp\n").unwrap();
+    fs::write(root.join(".isccignore"), "!file2.log\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--ignore-file", ".isccignore"])
+        .args(["--ignore-file", ".gitignore"])
+        .arg(root.to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("file2.log"))
+        .stdout(predicate::str::contains("file6.log").not())
+        .stdout(predicate::str::contains("temp1.tmp").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlinks_policy() {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("file.txt"), b"content").unwrap();
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("sub/inner.txt"), b"inner").unwrap();
+    symlink(root.join("file.txt"), root.join("link.txt")).unwrap();
+    symlink(root.join("sub"), root.join("linkdir")).unwrap();
+    symlink(root, root.join("sub/loop")).unwrap();
+
+    let run = |policy: &str| {
+        let output = Command::cargo_bin("isum")
+            .unwrap()
+            .args([
+                "--no-config",
+                "--ignore-file",
+                ".isccignore",
+                "--symlinks",
+                policy,
+            ])
+            .arg(root)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let skip = run("skip");
+    assert_eq!(skip.lines().count(), 2);
+    assert!(!skip.contains("link.txt"));
+
+    let follow_files = run("follow-files");
+    assert_eq!(follow_files.lines().count(), 3);
+    assert!(follow_files.contains("link.txt"));
+    assert!(!follow_files.contains("linkdir"));
+
+    // The loop back to the root is skipped
+    let follow_all = run("follow-all");
+    assert_eq!(follow_all.lines().count(), 4);
+    assert!(follow_all.contains("linkdir/inner.txt"));
+}
+
+#[test]
+fn test_check_color_and_summary() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("a.txt"), b"a").unwrap();
+    fs::write(root.join("longer_name.txt"), b"b").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--manifests").arg(root).assert().success();
+    fs::write(root.join("a.txt"), b"changed").unwrap();
+
+    // The status column is padded to the longest path, colored on request
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--check-manifests", "--color", "always"])
+        .arg(root)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "a.txt:           \x1b[31mFAILED\x1b[0m",
+        ))
+        .stdout(predicate::str::contains(
+            "longer_name.txt: \x1b[32mOK\x1b[0m",
+        ))
+        .stderr(predicate::str::contains("1 of 2 files FAILED"));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--check-manifests", "--color", "never"])
+        .arg(root)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("a.txt:           FAILED"))
+        .stdout(predicate::str::contains("\x1b[").not())
+        .stderr(predicate::str::contains("isum: 1 of 2 files FAILED"));
+}
+
+#[test]
+fn test_list_ignored() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_nested_directory_structure(root);
+    fs::write(root.join(".isccignore"), "*.log\n").unwrap();
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args([
+            "--no-config",
+            "--list-ignored",
+            "--ignore-file",
+            ".isccignore",
+        ])
+        .args(["--exclude", "*.tmp"])
+        .arg(root)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let ignore_file = root.join(".isccignore");
+    let expected_log = format!(
+        "{}: *.log ({})",
+        root.join("file2.log").display(),
+        ignore_file.display()
+    );
+    let expected_tmp = format!(
+        "{}: *.tmp (--exclude)",
+        root.join("subdir1").join("file4.tmp").display()
+    );
+    assert!(stdout.lines().any(|l| l == expected_log), "{stdout}");
+    assert!(stdout.lines().any(|l| l == expected_tmp), "{stdout}");
+    assert_eq!(stdout.lines().count(), 5);
+    assert!(!stdout.contains("file1.txt"));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--list-ignored", "--check-manifests"])
+        .arg(root)
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_list_ignored_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_nested_directory_structure(root);
+    fs::write(root.join(".isccignore"), "tmpdir/\n").unwrap();
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args([
+            "--no-config",
+            "--list-ignored",
+            "--ignore-file",
+            ".isccignore",
+        ])
+        .arg(root)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // Ignored directories are listed once instead of each file below them
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = format!(
+        "{}: tmpdir/ ({})",
+        root.join("tmpdir").display(),
+        root.join(".isccignore").display()
+    );
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), [expected]);
+}
+
+#[test]
+fn test_dry_run() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_nested_directory_structure(root);
+
+    let hashed = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--no-config", "--exclude", "*.tmp"])
+        .arg(root)
+        .output()
+        .unwrap();
+    let dry_run = Command::cargo_bin("isum")
+        .unwrap()
+        .args([
+            "--no-config",
+            "--exclude",
+            "*.tmp",
+            "--dry-run",
+            "--manifests",
+        ])
+        .arg(root)
+        .output()
+        .unwrap();
+    assert!(dry_run.status.success());
+
+    // Same files in the same order, without checksums and without writing manifests
+    let hashed_paths: Vec<String> = String::from_utf8(hashed.stdout)
+        .unwrap()
+        .lines()
+        .map(|l| l.split_once(" *").unwrap().1.to_string())
+        .collect();
+    let listed: Vec<String> = String::from_utf8(dry_run.stdout)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect();
+    assert_eq!(listed, hashed_paths);
+    assert!(!root.join("ISCC-SUMS").exists());
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--dry-run").write_stdin("data").assert().code(2);
+}
+
+#[test]
+fn test_root_relative_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_nested_directory_structure(root);
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--no-config", "--exclude", "*.tmp", "--root"])
+        .arg(root)
+        .arg(root.join("subdir1"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let paths: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|l| l.split_once(" *").unwrap().1.to_string())
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            "subdir1/file3.txt",
+            "subdir1/subdir2/file5.txt",
+            "subdir1/subdir2/file6.log"
+        ]
+    );
+
+    // Output is independent of where the tree is reached from
+    let relative = Command::cargo_bin("isum")
+        .unwrap()
+        .current_dir(root.join("subdir1"))
+        .args(["--no-config", "--exclude", "*.tmp", "--root", "..", "."])
+        .output()
+        .unwrap();
+    let absolute = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--no-config", "--exclude", "*.tmp", "--root"])
+        .arg(root)
+        .arg(root.join("subdir1"))
+        .output()
+        .unwrap();
+    assert_eq!(relative.stdout, absolute.stdout);
+
+    Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--no-config", "--root"])
+        .arg(root.join("missing"))
+        .arg(root)
+        .assert()
+        .code(2);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_with_metadata_and_verify_metadata() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    create_nested_directory_structure(root);
+
+    // The manifests written below are not covered by sidecars
+    let isum = || {
+        let mut cmd = Command::cargo_bin("isum").unwrap();
+        cmd.args([
+            "--no-config",
+            "--exclude",
+            "ISCC-*",
+            "--exclude",
+            "**/ISCC-*",
+        ]);
+        cmd
+    };
+
+    // Metadata needs an output that can hold it
+    isum().arg("--with-metadata").arg(root).assert().code(2);
+
+    isum()
+        .args(["--with-metadata", "--format", "json"])
+        .arg(root.join("file1.txt"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""metadata":{"mode":"#));
+
+    isum()
+        .args(["--with-metadata", "--manifests", "--sidecar"])
+        .arg(root)
+        .assert()
+        .success();
+    assert!(root.join("ISCC-META").exists());
+    assert!(root.join("subdir1/subdir2/ISCC-META").exists());
+    let sidecar = fs::read_to_string(root.join("file1.txt.iscc")).unwrap();
+    assert!(sidecar.contains(r#""metadata""#));
+
+    for mode in ["--check-manifests", "--check-sidecar"] {
+        isum()
+            .args([mode, "--verify-metadata"])
+            .arg(root)
+            .assert()
+            .success();
+    }
+
+    // Content unchanged, mode bits changed
+    let file = root.join("subdir1/file3.txt");
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o600)).unwrap();
+    for mode in ["--check-manifests", "--check-sidecar"] {
+        isum().arg(mode).arg(root).assert().success();
+        isum()
+            .args([mode, "--verify-metadata"])
+            .arg(root)
+            .assert()
+            .code(1)
+            .stdout(
+                predicate::str::is_match(r"file3\.txt: +FAILED \(metadata changed: mode").unwrap(),
+            );
+    }
+
+    isum()
+        .args(["--check-xattr", "--verify-metadata"])
+        .arg(root)
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_compare_trees_and_manifests() {
+    let temp_dir = TempDir::new().unwrap();
+    let (old, new) = (temp_dir.path().join("old"), temp_dir.path().join("new"));
+    fs::create_dir_all(old.join("sub")).unwrap();
+    fs::create_dir_all(new.join("sub")).unwrap();
+    fs::write(old.join("same.txt"), b"unchanged").unwrap();
+    fs::write(new.join("same.txt"), b"unchanged").unwrap();
+    fs::write(old.join("edit.txt"), b"version one").unwrap();
+    fs::write(new.join("edit.txt"), b"version two").unwrap();
+    fs::write(old.join("sub/photo.jpg"), b"photo bytes").unwrap();
+    fs::write(new.join("renamed.jpg"), b"photo bytes").unwrap();
+    fs::write(old.join("gone.txt"), b"deleted").unwrap();
+    fs::write(new.join("sub/added.txt"), b"brand new file").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("compare")
+        .arg(&old)
+        .arg(&new)
+        .assert()
+        .code(1)
+        .stdout("A  sub/added.txt\nD  gone.txt\nM  edit.txt\nR  sub/photo.jpg -> renamed.jpg\n")
+        .stderr(predicate::str::contains(
+            "1 added, 1 removed, 1 modified, 1 moved, 1 unchanged",
+        ));
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["compare", "--format", "json"])
+        .arg(&old)
+        .arg(&new)
+        .output()
+        .unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["moved"][0]["identical"], true);
+    assert_eq!(report["unchanged"], 1);
+
+    // A checksum file written with --root compares equal to its tree
+    let sums = temp_dir.path().join("old.sums");
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--no-config", "--narrow", "--root"])
+        .arg(&old)
+        .arg(&old)
+        .output()
+        .unwrap();
+    fs::write(&sums, output.stdout).unwrap();
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("compare")
+        .arg(&sums)
+        .arg(&old)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("4 unchanged"));
+}
+
+#[test]
+fn test_manifest_diff() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("data");
+    fs::create_dir(&root).unwrap();
+    create_nested_directory_structure(&root);
+    let snapshot = |name: &str| {
+        let output = Command::cargo_bin("isum")
+            .unwrap()
+            .args(["--no-config", "--format", "json", "--root"])
+            .arg(&root)
+            .arg(&root)
+            .output()
+            .unwrap();
+        let path = temp_dir.path().join(name);
+        fs::write(&path, output.stdout).unwrap();
+        path
+    };
+
+    let old = snapshot("old.json");
+    fs::write(root.join("file1.txt"), b"changed").unwrap();
+    fs::remove_file(root.join("file2.log")).unwrap();
+    fs::write(root.join("new.txt"), b"new").unwrap();
+    let new = snapshot("new.json");
+
+    // Works from the snapshots alone
+    fs::remove_dir_all(&root).unwrap();
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("manifest-diff")
+        .arg(&old)
+        .arg(&new)
+        .assert()
+        .code(1)
+        .stdout("A  new.txt\nD  file2.log\nM  file1.txt\n");
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("manifest-diff")
+        .arg(&new)
+        .arg(&new)
+        .assert()
+        .success()
+        .stdout("");
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("manifest-diff")
+        .arg(&old)
+        .arg(temp_dir.path())
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_db_subcommands() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("archive");
+    fs::create_dir(&root).unwrap();
+    create_nested_directory_structure(&root);
+    let db = temp_dir.path().join("fixity.db");
+    let isum = || Command::cargo_bin("isum").unwrap();
+
+    isum().args(["db", "verify"]).arg(&db).assert().code(3);
+    isum().args(["db", "init"]).arg(&db).assert().success();
+    isum().args(["db", "init"]).arg(&db).assert().code(2);
+    isum()
+        .args(["db", "add"])
+        .arg(&db)
+        .arg(&root)
+        .assert()
+        .success();
+
+    isum()
+        .args(["db", "verify"])
+        .arg(&db)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("9 files OK"));
+
+    // Recently verified files are skipped with --older-than
+    isum()
+        .args(["db", "verify", "--older-than", "1"])
+        .arg(&db)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("0 files OK"));
+    isum()
+        .args(["db", "verify", "--limit", "2"])
+        .arg(&db)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("2 files OK"));
+
+    // An edit changes size and mtime; a removed file is reported missing, then pruned
+    fs::write(root.join("file1.txt"), b"edited content").unwrap();
+    fs::remove_file(root.join("file2.log")).unwrap();
+    isum()
+        .args(["db", "verify"])
+        .arg(&db)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::is_match(r"file1\.txt: +FAILED \(modified since recorded").unwrap())
+        .stderr(predicate::str::contains(
+            "file2.log: No such file or directory",
+        ));
+
+    isum()
+        .args(["db", "prune"])
+        .arg(&db)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("file2.log"))
+        .stderr(predicate::str::contains("1 record(s) pruned"));
+    isum()
+        .args(["db", "add"])
+        .arg(&db)
+        .arg(&root)
+        .assert()
+        .success();
+    isum()
+        .args(["db", "verify"])
+        .arg(&db)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("8 files OK"));
+}
+
+#[test]
+fn test_index_subcommands() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("files");
+    fs::create_dir(&root).unwrap();
+    let pseudo_random = |seed: u32| -> Vec<u8> {
+        let mut state = seed;
+        (0..200_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect()
+    };
+    let original = pseudo_random(1);
+    let mut edited = original.clone();
+    edited[100_000..100_010].copy_from_slice(b"0123456789");
+    fs::write(root.join("original.bin"), &original).unwrap();
+    fs::write(root.join("edited.bin"), &edited).unwrap();
+    fs::write(root.join("other.bin"), pseudo_random(2)).unwrap();
+    let index = temp_dir.path().join("files.idx");
+    let isum = || Command::cargo_bin("isum").unwrap();
+
+    isum()
+        .args(["index", "build"])
+        .arg(&index)
+        .arg(&root)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("3 file(s) indexed"));
+
+    let output = isum().arg(root.join("original.bin")).output().unwrap();
+    let code = String::from_utf8(output.stdout).unwrap();
+    let code = code.split_whitespace().next().unwrap().to_string();
+
+    let output = isum()
+        .args(["index", "query"])
+        .arg(&index)
+        .arg(&code)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{stdout}");
+    assert!(lines
+        .iter()
+        .any(|l| l.starts_with("0 ") && l.ends_with("original.bin")));
+    assert!(lines.iter().any(|l| l.ends_with("edited.bin")));
+
+    isum()
+        .args(["index", "query", "--format", "json", "--max-distance", "0"])
+        .arg(&index)
+        .arg(&code)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""distance":0"#))
+        .stdout(predicate::str::contains("other.bin").not());
+
+    isum()
+        .args(["index", "query"])
+        .arg(&index)
+        .arg("ISCC:INVALID")
+        .assert()
+        .code(2);
+    isum()
+        .args(["index", "query"])
+        .arg(temp_dir.path().join("missing.idx"))
+        .arg(&code)
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_bag_create_and_validate() {
+    let temp_dir = TempDir::new().unwrap();
+    let bag = temp_dir.path().join("bag");
+    fs::create_dir(&bag).unwrap();
+    create_nested_directory_structure(&bag);
+    // A top-level `data` entry must not clash with the payload directory
+    fs::write(bag.join("data"), b"payload named data").unwrap();
+    let isum = || Command::cargo_bin("isum").unwrap();
+
+    isum()
+        .args(["bag", "validate"])
+        .arg(&bag)
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("bagit.txt: not a bag"));
+
+    isum()
+        .args(["bag", "create"])
+        .arg(&bag)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("10 payload file(s)"));
+    assert!(bag.join("data/data").is_file());
+    assert!(bag.join("data/subdir1/subdir2/file5.txt").is_file());
+    let manifest = fs::read_to_string(bag.join("manifest-iscc.txt")).unwrap();
+    assert_eq!(manifest.lines().count(), 10);
+    assert!(manifest.contains("  data/subdir1/file3.txt\n"));
+    let tag_manifest = fs::read_to_string(bag.join("tagmanifest-iscc.txt")).unwrap();
+    assert!(tag_manifest.contains("  bagit.txt\n"));
+    assert!(tag_manifest.contains("  manifest-iscc.txt\n"));
+
+    // Conventional manifests are verified too; unknown algorithms are skipped
+    fs::write(
+        bag.join("manifest-md5.txt"),
+        "7e55db001d319a94b0b713529a756623  data/file1.txt\n",
+    )
+    .unwrap();
+    fs::write(bag.join("manifest-sha1.txt"), "abc  data/file1.txt\n").unwrap();
+    isum()
+        .args(["bag", "validate"])
+        .arg(&bag)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("file1.txt: OK"))
+        .stderr(predicate::str::contains("unsupported algorithm 'sha1'"))
+        .stderr(predicate::str::contains(
+            "data/file2.log: not listed in manifest-md5.txt",
+        ));
+    fs::remove_file(bag.join("manifest-md5.txt")).unwrap();
+    fs::remove_file(bag.join("manifest-sha1.txt")).unwrap();
+
+    // Re-running create on a bag refreshes the manifests
+    isum().args(["bag", "create"]).arg(&bag).assert().success();
+    isum()
+        .args(["bag", "validate"])
+        .arg(&bag)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("12 files OK"));
+
+    // Modified and unlisted payload files invalidate the bag
+    fs::write(bag.join("data/file1.txt"), b"tampered").unwrap();
+    fs::write(bag.join("data/extra.txt"), b"extra").unwrap();
+    isum()
+        .args(["bag", "validate"])
+        .arg(&bag)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::is_match(r"data/file1\.txt: +FAILED").unwrap())
+        .stderr(predicate::str::contains(
+            "data/extra.txt: not listed in manifest-iscc.txt",
+        ));
+}
+
+#[test]
+fn test_signed_manifests() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("dataset");
+    fs::create_dir(&root).unwrap();
+    create_nested_directory_structure(&root);
+    let keys = temp_dir.path();
+    let isum = || Command::cargo_bin("isum").unwrap();
+
+    isum()
+        .arg("keygen")
+        .arg(keys.join("isum.key"))
+        .arg(keys.join("isum.pub"))
+        .assert()
+        .success();
+    isum()
+        .arg("keygen")
+        .arg(keys.join("isum.key"))
+        .arg(keys.join("other.pub"))
+        .assert()
+        .code(2);
+    isum()
+        .arg("keygen")
+        .arg(keys.join("other.key"))
+        .arg(keys.join("other.pub"))
+        .assert()
+        .success();
+
+    isum()
+        .args(["--manifests", "--sign"])
+        .arg(keys.join("isum.key"))
+        .arg(&root)
+        .assert()
+        .success();
+    assert!(root.join("ISCC-SUMS.sig").is_file());
+    assert!(root.join("subdir1/subdir2/ISCC-SUMS.sig").is_file());
+
+    // Re-signing does not hash the previous signatures
+    isum()
+        .args(["--manifests", "--sign"])
+        .arg(keys.join("isum.key"))
+        .arg(&root)
+        .assert()
+        .success();
+    let manifest = fs::read_to_string(root.join("ISCC-SUMS")).unwrap();
+    assert!(!manifest.contains(".sig"));
+
+    isum()
+        .args(["--check-manifests", "--verify-signature"])
+        .arg(keys.join("isum.pub"))
+        .arg(&root)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("9 files OK"));
+
+    // A different key and a tampered manifest both fail
+    isum()
+        .args(["--check-manifests", "--verify-signature"])
+        .arg(keys.join("other.pub"))
+        .arg(&root)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "ISCC-SUMS: FAILED (invalid signature: does not match the public key)",
+        ));
+    let tampered = manifest.replacen("file1.txt", "file2.log", 1);
+    fs::write(root.join("ISCC-SUMS"), tampered).unwrap();
+    fs::remove_file(root.join("tmpdir/ISCC-SUMS.sig")).unwrap();
+    isum()
+        .args(["--check-manifests", "--verify-signature"])
+        .arg(keys.join("isum.pub"))
+        .arg(&root)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "invalid signature: does not match",
+        ))
+        .stderr(predicate::str::contains("No such signature file"));
+
+    // --sign requires --manifests
+    isum()
+        .arg("--sign")
+        .arg(keys.join("isum.key"))
+        .arg(&root)
+        .assert()
+        .code(2);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_metrics() {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    let temp_dir = TempDir::new().unwrap();
+    let file = temp_dir.path().join("data.txt");
+    fs::write(&file, b"data").unwrap();
+    let socket = temp_dir.path().join("isum.sock");
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr = format!("127.0.0.1:{port}");
+    let isum = || Command::cargo_bin("isum").unwrap();
+
+    let mut daemon = std::process::Command::new(assert_cmd::cargo::cargo_bin("isum"))
+        .args(["daemon", "serve"])
+        .arg(&socket)
+        .args(["--metrics", &addr])
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    for _ in 0..500 {
+        if socket.exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    for _ in 0..2 {
+        isum()
+            .args(["daemon", "hash"])
+            .arg(&socket)
+            .arg(&file)
+            .assert()
+            .success();
+    }
+
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("iscc_sum_files_hashed_total 1"));
+    assert!(response.contains("iscc_sum_bytes_hashed_total 4"));
+    assert!(response.contains("isum_daemon_cache_hits_total 1"));
+
+    isum()
+        .args(["daemon", "stop"])
+        .arg(&socket)
+        .assert()
+        .success();
+    assert!(daemon.wait().unwrap().success());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_subcommands() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("files");
+    fs::create_dir(&root).unwrap();
+    create_nested_directory_structure(&root);
+    let socket = temp_dir.path().join("isum.sock");
+    let index = temp_dir.path().join("files.idx");
+    let isum = || Command::cargo_bin("isum").unwrap();
+
+    isum()
+        .args(["index", "build"])
+        .arg(&index)
+        .arg(&root)
+        .assert()
+        .success();
+    let mut daemon = std::process::Command::new(assert_cmd::cargo::cargo_bin("isum"))
+        .args(["daemon", "serve"])
+        .arg(&socket)
+        .arg("--index")
+        .arg(&index)
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    for _ in 0..500 {
+        if socket.exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    // Daemon results match direct hashing, also when served from the cache
+    let file = root.join("file1.txt");
+    let direct = isum().arg(&file).output().unwrap().stdout;
+    for _ in 0..2 {
+        isum()
+            .args(["daemon", "hash"])
+            .arg(&socket)
+            .arg(&file)
+            .assert()
+            .success()
+            .stdout(direct.clone());
+    }
+    isum()
+        .args(["daemon", "hash"])
+        .arg(&socket)
+        .arg(root.join("missing.txt"))
+        .assert()
+        .code(3);
+
+    let code = String::from_utf8(direct).unwrap();
+    let code = code.split_whitespace().next().unwrap().to_string();
+    isum()
+        .args(["daemon", "query", "--max-distance", "0"])
+        .arg(&socket)
+        .arg(&code)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("file1.txt"));
+
+    // A second daemon on the same socket is refused
+    isum()
+        .args(["daemon", "serve"])
+        .arg(&socket)
+        .assert()
+        .code(2);
+
+    isum()
+        .args(["daemon", "stop"])
+        .arg(&socket)
+        .assert()
+        .success();
+    assert!(daemon.wait().unwrap().success());
+    assert!(!socket.exists());
+    isum()
+        .args(["daemon", "hash"])
+        .arg(&socket)
+        .arg(&file)
+        .assert()
+        .code(3);
+}
@@ -69,6 +69,27 @@ fn test_nonexistent_file() {
         .stderr(predicate::str::contains("No such file or directory"));
 }
 
+#[test]
+fn test_failing_argument_does_not_stop_the_rest() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.txt");
+    fs::write(&file_path, b"hello world").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg(temp_dir.path().join("missing.txt").to_str().unwrap())
+        .arg(temp_dir.path().join("gone.txt").to_str().unwrap())
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("test.txt"))
+        .stderr(predicate::str::contains(
+            "missing.txt: No such file or directory",
+        ))
+        .stderr(predicate::str::contains(
+            "gone.txt: No such file or directory",
+        ));
+}
+
 #[test]
 fn test_empty_directory() {
     let temp_dir = TempDir::new().unwrap();
@@ -92,3 +113,1015 @@ fn test_directory_with_files() {
         .stdout(predicate::str::contains("file1.txt"))
         .stdout(predicate::str::contains("file2.txt"));
 }
+
+#[cfg(unix)]
+#[test]
+fn test_xattr_store_and_check() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.txt");
+    fs::write(&file_path, b"hello world").unwrap();
+
+    // Skip on filesystems without user extended attribute support
+    if xattr::set(&file_path, "user.iscc.probe", b"1").is_err() {
+        return;
+    }
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--xattr")
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .success();
+
+    let stored = xattr::get(&file_path, "user.iscc.sum").unwrap().unwrap();
+    assert!(String::from_utf8(stored).unwrap().starts_with("ISCC:"));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-xattr")
+        .arg(temp_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("test.txt: OK"));
+
+    fs::write(&file_path, b"hello world!").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-xattr")
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("test.txt: FAILED"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_check_xattr_missing_attribute() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("plain.txt");
+    fs::write(&file_path, b"no attribute").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-xattr")
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_sidecar_write_and_check() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("movie.mp4");
+    fs::write(&file_path, b"not really a movie").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--sidecar")
+        .arg(temp_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+
+    let sidecar = fs::read_to_string(temp_dir.path().join("movie.mp4.iscc")).unwrap();
+    assert!(sidecar.contains("\"iscc\": \"ISCC:"));
+    assert!(sidecar.contains("\"filesize\": 18"));
+    assert!(sidecar.contains("\"units\""));
+
+    // Re-running over the directory must not create sidecars of sidecars
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--sidecar")
+        .arg(temp_dir.path().to_str().unwrap())
+        .assert()
+        .success();
+    assert!(!temp_dir.path().join("movie.mp4.iscc.iscc").exists());
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-sidecar")
+        .arg(temp_dir.path().to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("movie.mp4: OK"));
+
+    fs::write(&file_path, b"not really a movie!").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-sidecar")
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("movie.mp4: FAILED"));
+}
+
+#[test]
+fn test_check_sidecar_missing() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("lonely.txt");
+    fs::write(&file_path, b"no sidecar").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-sidecar")
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No such sidecar file"));
+}
+
+#[test]
+fn test_check_sidecar_malformed() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("data.txt");
+    fs::write(&file_path, b"data").unwrap();
+    fs::write(temp_dir.path().join("data.txt.iscc"), "not json").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--check-sidecar", "--format", "json"])
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("\"status\":\"malformed\""))
+        .stdout(predicate::str::contains("\"success\":false"));
+}
+
+#[test]
+fn test_hash_also_json_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("abc.txt");
+    fs::write(&file_path, b"abc").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--hash-also", "sha256", "--format", "json"])
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\"sha256\":\"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\"",
+        ))
+        .stdout(predicate::str::contains("\"filesize\":3"));
+}
+
+#[test]
+fn test_segments_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("data.bin");
+    let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(&file_path, &data).unwrap();
+    fs::write(temp_dir.path().join("head.bin"), &data[..2048]).unwrap();
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--segments", "2K", "--format", "json"])
+        .arg(&file_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let segments = record["segments"].as_array().unwrap();
+    let bounds: Vec<(u64, u64)> = segments
+        .iter()
+        .map(|s| (s["offset"].as_u64().unwrap(), s["size"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(bounds, [(0, 2048), (2048, 2048), (4096, 904)]);
+
+    // The first segment has the code of a file with its bytes
+    let head = Command::cargo_bin("isum")
+        .unwrap()
+        .arg(temp_dir.path().join("head.bin"))
+        .output()
+        .unwrap();
+    let head_iscc = String::from_utf8(head.stdout).unwrap();
+    assert!(head_iscc.starts_with(segments[0]["iscc"].as_str().unwrap()));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--segments", "2K"])
+        .arg(&file_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  segment:4096+904:ISCC:"));
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--segments", "0"])
+        .arg(&file_path)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_hash_also_csv_output() {
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--hash-also", "md5", "--format", "csv"])
+        .write_stdin("abc")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "path,iscc,datahash,filesize,md5\n-,ISCC:",
+        ))
+        .stdout(predicate::str::contains(
+            ",3,900150983cd24fb0d6963f7d28e17f72\n",
+        ));
+}
+
+#[test]
+fn test_response_file_arguments() {
+    let temp_dir = TempDir::new().unwrap();
+    let file1 = temp_dir.path().join("one.txt");
+    let file2 = temp_dir.path().join("two.txt");
+    fs::write(&file1, b"one").unwrap();
+    fs::write(&file2, b"two").unwrap();
+    let response = temp_dir.path().join("args.txt");
+    fs::write(
+        &response,
+        format!("--narrow\n{}\n{}\n", file1.display(), file2.display()),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg(format!("@{}", response.display()))
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"^ISCC:[A-Z0-9]{29} \*.*one.txt\nISCC:[A-Z0-9]{29} \*.*two.txt\n$",
+            )
+            .unwrap(),
+        );
+}
+
+#[test]
+fn test_exit_codes() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("data.txt");
+    fs::write(&file_path, b"data").unwrap();
+
+    // 2: usage errors, from clap and from our own option validation
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--no-such-option").assert().code(2);
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--xattr").write_stdin("data").assert().code(2);
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--exclude", "[", file_path.to_str().unwrap()])
+        .assert()
+        .code(2);
+
+    // 3: I/O errors on inputs
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg(temp_dir.path().join("missing.txt").to_str().unwrap())
+        .assert()
+        .code(3);
+
+    // 1: verification failures
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--sidecar")
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .code(0);
+    fs::write(&file_path, b"changed").unwrap();
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--check-sidecar")
+        .arg(file_path.to_str().unwrap())
+        .assert()
+        .code(1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_interrupt_flushes_completed_results() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command as StdCommand, Stdio};
+
+    let temp_dir = TempDir::new().unwrap();
+    let data = vec![7u8; 256 * 1024];
+    for i in 0..400 {
+        fs::write(temp_dir.path().join(format!("file{i:03}.bin")), &data).unwrap();
+    }
+
+    let mut child = StdCommand::new(assert_cmd::cargo::cargo_bin("isum"))
+        .arg("--manifests")
+        .arg(temp_dir.path())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // Interrupt as soon as the first result has been printed
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut first = String::new();
+    stdout.read_line(&mut first).unwrap();
+    StdCommand::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+
+    let rest: Vec<String> = stdout.lines().map(|l| l.unwrap()).collect();
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(130));
+
+    // Every printed line is complete and the manifest lists exactly the completed files
+    let printed = 1 + rest.len();
+    assert!(printed < 400);
+    assert!(rest
+        .iter()
+        .all(|l| l.starts_with("ISCC:") && l.contains(" *")));
+    let manifest = fs::read_to_string(temp_dir.path().join("ISCC-SUMS")).unwrap();
+    assert_eq!(manifest.lines().count(), printed);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_state_resumes_interrupted_hash() {
+    use std::process::{Command as StdCommand, Stdio};
+
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("disk.img");
+    let data: Vec<u8> = (0..3 * 1024 * 1024u32).map(|i| (i % 253) as u8).collect();
+    fs::write(&file_path, &data).unwrap();
+    let state_path = temp_dir.path().join("disk.state");
+
+    let expected = Command::cargo_bin("isum")
+        .unwrap()
+        .arg("--no-config")
+        .arg(&file_path)
+        .output()
+        .unwrap()
+        .stdout;
+
+    // Interrupt a throttled run part way through
+    let child = StdCommand::new(assert_cmd::cargo::cargo_bin("isum"))
+        .args(["--no-config", "--limit-rate", "1M", "--state"])
+        .arg(&state_path)
+        .arg(&file_path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(800));
+    StdCommand::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("resume with --state"));
+    assert!(state_path.exists());
+
+    // The resumed run produces the same checksum and removes the state
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--state"])
+        .arg(&state_path)
+        .arg(&file_path)
+        .assert()
+        .success()
+        .stdout(expected);
+    assert!(!state_path.exists());
+
+    fs::write(&state_path, b"garbage").unwrap();
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--state"])
+        .arg(&state_path)
+        .arg(&file_path)
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("not an isum state"));
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--state"])
+        .arg(&state_path)
+        .arg(temp_dir.path())
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_selftest_subcommand() {
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("selftest")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "code_iscc_sum/test_0000_empty_64: OK",
+        ))
+        .stdout(predicate::str::contains("FAILED").not())
+        .stdout(predicate::str::contains("conformance tests passed"));
+}
+
+#[test]
+fn test_completions_and_manpage() {
+    for (shell, marker) in [
+        ("bash", "_isum()"),
+        ("zsh", "#compdef isum"),
+        ("fish", "complete -c isum"),
+        ("powershell", "Register-ArgumentCompleter"),
+    ] {
+        let mut cmd = Command::cargo_bin("isum").unwrap();
+        cmd.args(["completions", shell])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(marker))
+            .stdout(predicate::str::contains("check-manifests"));
+    }
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("manpage")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".TH isum 1"))
+        .stdout(predicate::str::contains("selftest"));
+}
+
+#[test]
+fn test_limit_rate() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("data.bin");
+    fs::write(&file_path, vec![1u8; 100 * 1024]).unwrap();
+
+    // 100 KiB at 200 KiB/s takes about half a second
+    let start = std::time::Instant::now();
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--limit-rate", "200K"])
+        .arg(&file_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("data.bin"));
+    assert!(start.elapsed() >= std::time::Duration::from_millis(400));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--limit-rate", "0"])
+        .arg(&file_path)
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_time_report() {
+    let temp_dir = TempDir::new().unwrap();
+    for (name, size) in [("small.bin", 10), ("large.bin", 200 * 1024)] {
+        fs::write(temp_dir.path().join(name), vec![7u8; size]).unwrap();
+    }
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--no-config", "--time=1", "--format", "json"])
+        .arg(temp_dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    for line in String::from_utf8(output.stdout).unwrap().lines() {
+        let record: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert!(record["seconds"].as_f64().unwrap() >= 0.0);
+        assert!(record["bytes_per_second"].is_u64());
+    }
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("slowest 1 file(s)"));
+    assert_eq!(stderr.lines().count(), 2);
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--time", "--format", "csv"])
+        .arg(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "path,iscc,datahash,filesize,seconds,bytes_per_second\n",
+        ))
+        .stderr(predicate::str::contains("slowest 2 file(s)"));
+}
+
+#[test]
+fn test_cbor_manifest() {
+    let temp_dir = TempDir::new().unwrap();
+    let data = temp_dir.path().join("data");
+    fs::create_dir(&data).unwrap();
+    for name in ["b.txt", "a.txt", "c.txt"] {
+        fs::write(data.join(name), name.as_bytes()).unwrap();
+    }
+    let cbor = |name: &str| -> Vec<u8> {
+        let path = temp_dir.path().join(name);
+        Command::cargo_bin("isum")
+            .unwrap()
+            .args(["--no-config", "--hash-also", "sha256", "--cbor"])
+            .arg(&path)
+            .arg(&data)
+            .assert()
+            .success();
+        fs::read(path).unwrap()
+    };
+
+    // Identical results give identical bytes; the document is a map with schema and version
+    let first = cbor("first.cbor");
+    assert_eq!(first, cbor("second.cbor"));
+    assert_eq!(first[0], 0xa3);
+    let text = String::from_utf8_lossy(&first);
+    assert!(text.contains("iscc-sum-manifest"));
+    assert!(text.contains("sha256"));
+    assert!(text.find("a.txt").unwrap() < text.find("b.txt").unwrap());
+
+    // No manifest is written for invalid arguments
+    let path = temp_dir.path().join("invalid.cbor");
+    Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--no-config", "--limit-rate", "0", "--cbor"])
+        .arg(&path)
+        .arg(&data)
+        .assert()
+        .code(2);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_hashdeep_output() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a, b.txt"), b"Hello, World!").unwrap();
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--no-config", "--hashdeep", "--hash-also", "md5"])
+        .arg(temp_dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "%%%% HASHDEEP-1.0");
+    assert_eq!(lines[1], "%%%% size,md5,iscc,filename");
+    assert!(lines[2].starts_with("## Invoked from: "));
+    assert!(lines[3].starts_with("## $ "));
+    assert_eq!(lines[4], "##");
+    // The filename is the last column and is written as is
+    assert!(lines[5].starts_with("13,65a8e27d8879283831b664bd8b7f0ad4,ISCC:"));
+    assert!(lines[5].ends_with("a, b.txt"));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--hashdeep", "--format", "json"])
+        .arg(temp_dir.path())
+        .assert()
+        .code(2);
+}
+
+#[test]
+#[cfg(feature = "network")]
+fn test_registry_lookup() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("image.jpg");
+    fs::write(&file_path, b"Hello, World!").unwrap();
+
+    // Minimal registry answering a single request and reporting its request line
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/lookup", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request_line = String::new();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        reader.read_line(&mut request_line).unwrap();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+        }
+        let body = r#"{"matches": [{"iscc": "ISCC:KACT", "actor": "example"}]}"#;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        request_line
+    });
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--no-config", "--format", "json", "--lookup", &url])
+        .arg(&file_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(record["registry"][0]["actor"], "example");
+    let request_line = server.join().unwrap();
+    assert!(request_line.starts_with("GET /lookup?iscc=ISCC%3A"));
+
+    // Unreachable registries are reported but do not fail the run
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--format", "json", "--lookup", &url])
+        .arg(&file_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""registry":null"#))
+        .stderr(predicate::str::contains("lookup failed"));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--lookup", &url])
+        .arg(&file_path)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--lookup requires --format json"));
+}
+
+#[test]
+fn test_warc_response_records() {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let temp_dir = TempDir::new().unwrap();
+    let payload = b"<html>archived page</html>";
+    fs::write(temp_dir.path().join("payload.html"), payload).unwrap();
+
+    let mut block = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n".to_vec();
+    block.extend_from_slice(payload);
+    let mut archive = Vec::new();
+    for (warc_type, block) in [
+        ("request", &b"GET / HTTP/1.1\r\n\r\n"[..]),
+        ("response", &block),
+    ] {
+        let mut record = format!(
+            "WARC/1.1\r\nWARC-Type: {warc_type}\r\nWARC-Target-URI: https://example.com/\r\n\
+             Content-Length: {}\r\n\r\n",
+            block.len()
+        )
+        .into_bytes();
+        record.extend_from_slice(block);
+        record.extend_from_slice(b"\r\n\r\n");
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&record).unwrap();
+        archive.extend(encoder.finish().unwrap());
+    }
+    fs::write(temp_dir.path().join("crawl.warc.gz"), archive).unwrap();
+
+    let expected = Command::cargo_bin("isum")
+        .unwrap()
+        .arg(temp_dir.path().join("payload.html"))
+        .output()
+        .unwrap()
+        .stdout;
+    let expected = String::from_utf8(expected).unwrap();
+    let code = expected.split_whitespace().next().unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--warc"])
+        .arg(temp_dir.path().join("crawl.warc.gz"))
+        .assert()
+        .success()
+        .stdout(format!("{code} *https://example.com/\n"));
+
+    // Without --warc the archive is hashed as a file; corrupt archives are errors
+    fs::write(temp_dir.path().join("broken.warc"), b"garbage").unwrap();
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--warc"])
+        .arg(temp_dir.path().join("broken.warc"))
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("not a WARC record"));
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config"])
+        .arg(temp_dir.path().join("broken.warc"))
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_outboard_streaming_verification() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("video.bin");
+    let content: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(&file_path, &content).unwrap();
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["--no-config", "--outboard"])
+        .arg(&file_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let code = stdout.split_whitespace().next().unwrap().to_string();
+    let outboard = temp_dir.path().join("video.bin.obao");
+    // Length header plus 19 parent nodes for 20 chunks
+    assert_eq!(fs::metadata(&outboard).unwrap().len(), 8 + 19 * 64);
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("verify-stream")
+        .arg(&file_path)
+        .args(["--expect", &code])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("OK (20000 bytes)"));
+
+    // A partial download verifies up to where it ends
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["verify-stream", "-", "--outboard"])
+        .arg(&outboard)
+        .args(["--expect", &code])
+        .write_stdin(content[..7000].to_vec())
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains(
+            "incomplete: 6144 of 20000 bytes verified",
+        ));
+
+    // Corruption is reported at the first bad chunk
+    let mut corrupt = content.clone();
+    corrupt[12_345] ^= 0xff;
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["verify-stream", "-", "--outboard"])
+        .arg(&outboard)
+        .args(["--expect", &code])
+        .write_stdin(corrupt)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("bad chunk at offset 12288"));
+
+    // Outboard files are not hashed themselves and stdin has no place for one
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--outboard"])
+        .arg(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".obao").not());
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--no-config", "--outboard"])
+        .write_stdin("data")
+        .assert()
+        .code(2);
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["verify-stream", "-", "--expect", "ISCC:AAAA"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_dash_reads_stdin() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("data.txt");
+    fs::write(&file_path, b"Hello, World!").unwrap();
+
+    let from_stdin = Command::cargo_bin("isum")
+        .unwrap()
+        .write_stdin("Hello, World!")
+        .output()
+        .unwrap();
+    let line = String::from_utf8(from_stdin.stdout).unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    let output = cmd
+        .arg(&file_path)
+        .arg("-")
+        .write_stdin("Hello, World!")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("data.txt"));
+    assert_eq!(format!("{}\n", lines[1]), line);
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--sidecar", "-"])
+        .write_stdin("data")
+        .assert()
+        .code(2);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_allow_special() {
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("/dev/null")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--allow-special"));
+
+    // A character device is hashed like an empty file
+    let empty = Command::cargo_bin("isum").unwrap().output().unwrap();
+    let expected = String::from_utf8(empty.stdout)
+        .unwrap()
+        .replace(" *-", " */dev/null");
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--allow-special", "/dev/null"])
+        .assert()
+        .success()
+        .stdout(expected);
+}
+
+#[test]
+fn test_dedupe_subcommand() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("a.txt"), b"same content").unwrap();
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("sub").join("b.txt"), b"same content").unwrap();
+    fs::write(root.join("c.txt"), b"other content").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("dedupe")
+        .arg(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "2 identical files (12 bytes each, 12 bytes reclaimable):",
+        ))
+        .stdout(predicate::str::contains("c.txt").not())
+        .stderr(predicate::str::contains(
+            "1 duplicate group(s), 12 bytes reclaimable",
+        ));
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["dedupe", "--format", "json"])
+        .arg(root)
+        .output()
+        .unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["reclaimable"], 12);
+    assert_eq!(
+        report["duplicates"][0]["paths"].as_array().unwrap().len(),
+        2
+    );
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["dedupe", "--script", "hardlink"])
+        .arg(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("#!/bin/sh\n"))
+        .stdout(predicate::str::contains("ln -f -- '"));
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["dedupe", "--max-distance", "8"])
+        .arg(root)
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn test_delta_subcommand() {
+    let temp_dir = TempDir::new().unwrap();
+    // Pseudo-random data so the chunker finds boundaries
+    let data: Vec<u8> = (0..100_000u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+        .collect();
+    let mut changed = data.clone();
+    changed.splice(50_000..50_000, b"inserted".iter().copied());
+    let (a, b) = (temp_dir.path().join("a.bin"), temp_dir.path().join("b.bin"));
+    fs::write(&a, &data).unwrap();
+    fs::write(&b, &changed).unwrap();
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["delta", "--format", "json"])
+        .arg(&a)
+        .arg(&b)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["a"]["bytes"], 100_000);
+    assert_eq!(report["b"]["bytes"], 100_008);
+    assert!(report["similarity"].as_f64().unwrap() > 90.0);
+    // The insertion only changes the chunk around it
+    let only_b = report["b"]["only"].as_array().unwrap();
+    assert_eq!(only_b.len(), 1);
+    assert!(only_b[0][0].as_u64().unwrap() <= 50_000);
+    assert!(only_b[0][1].as_u64().unwrap() >= 50_008);
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("delta")
+        .arg(&a)
+        .arg(&a)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Similarity: 100.0%"))
+        .stdout(predicate::str::contains(
+            "Data-Code distance: 0 of 256 bits",
+        ))
+        .stdout(predicate::str::contains("Only in").not());
+}
+
+#[test]
+fn test_chunks_subcommand() {
+    let data: Vec<u8> = (0..20_000u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+        .collect();
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("data.bin");
+    fs::write(&file_path, &data).unwrap();
+
+    let output = Command::cargo_bin("isum")
+        .unwrap()
+        .args(["chunks", "--format", "json"])
+        .arg(&file_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Chunks are contiguous, cover the file and carry the hash of their bytes
+    let mut offset = 0;
+    for line in String::from_utf8(output.stdout).unwrap().lines() {
+        let chunk: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(chunk["offset"], offset);
+        let length = chunk["length"].as_u64().unwrap() as usize;
+        let bytes = &data[offset..offset + length];
+        assert_eq!(chunk["blake3"], blake3::hash(bytes).to_hex().as_str());
+        offset += length;
+    }
+    assert_eq!(offset, data.len());
+
+    // Same chunks from stdin, nothing for empty input
+    let text = Command::cargo_bin("isum")
+        .unwrap()
+        .arg("chunks")
+        .arg(&file_path)
+        .output()
+        .unwrap();
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["chunks", "-"])
+        .write_stdin(data)
+        .assert()
+        .success()
+        .stdout(String::from_utf8(text.stdout).unwrap());
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["chunks", "-"])
+        .write_stdin("")
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_nice_io() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("data.txt");
+    fs::write(&file_path, b"data").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.arg("--nice-io")
+        .arg(&file_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("data.txt"))
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn test_log_json() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("data.txt"), b"data").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--log-json", "-r"])
+        .arg(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("data.txt"))
+        .stderr(predicate::function(|err: &str| {
+            let records: Vec<serde_json::Value> = err
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            ["walk", "file"]
+                .iter()
+                .all(|name| records.iter().any(|r| r["span"]["name"] == *name))
+        }));
+}
+
+#[test]
+fn test_text_code_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let text_path = temp_dir.path().join("hello.txt");
+    fs::write(&text_path, "Hello World\n").unwrap();
+    let binary_path = temp_dir.path().join("data.bin");
+    fs::write(&binary_path, b"\x00\x01\x02").unwrap();
+
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["--text-code", "--format", "json"])
+        .arg(text_path.to_str().unwrap())
+        .arg(binary_path.to_str().unwrap())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\"text_code\":\"ISCC:EAASKDNZNYGUUF5A\",\"iscc_code\":\"ISCC:KA",
+        ))
+        .stdout(predicate::function(|out: &str| {
+            out.lines()
+                .filter(|line| line.contains("text_code"))
+                .count()
+                == 1
+        }));
+}
+
+#[test]
+fn test_mixed_subcommand() {
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["mixed", "ISCC:EAASKDNZNYGUUF5A", "ISCC:EIAQAAAAAAAAAAAA"])
+        .args(["--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\"iscc\":\"ISCC:EQASEJINXFXA2SQX\"",
+        ));
+
+    // Parts must be Content-Codes of at least the requested length
+    let mut cmd = Command::cargo_bin("isum").unwrap();
+    cmd.args(["mixed", "ISCC:EAASKDNZNYGUUF5A", "ISCC:EIAQAAAAAAAAAAAA"])
+        .args(["--bits", "128"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("shorter than 128 bits"));
+}
@@ -5,7 +5,7 @@ import tempfile
 
 import pytest
 
-from iscc_sum import IsccSumProcessor, code_iscc_sum
+from iscc_sum import IsccSumProcessor, code_iscc_sum, compare
 from iscc_sum.code_iscc_sum_ref import code_iscc_sum as code_iscc_sum_ref
 
 
@@ -122,3 +122,81 @@ def test_incremental_processing():
     assert result1["iscc"] == result2["iscc"]
     assert result1["datahash"] == result2["datahash"]
     assert result1["filesize"] == result2["filesize"]
+
+
+def test_segments():
+    # type: () -> None
+    """Test that segments are hashed like files of their own."""
+    data = bytes(range(256)) * 40
+    processor = IsccSumProcessor(segment_size=4096)
+    processor.update(data[:1000])
+    processor.update(data[1000:])
+    result = processor.result(wide=False, add_units=False)
+    segments = result.segments
+    assert [(s.offset, s.size) for s in segments] == [(0, 4096), (4096, 4096), (8192, 2048)]
+    assert "segments" in result
+    assert len(result) == 5
+    assert [s.iscc for s in result["segments"]] == [s.iscc for s in segments]
+    for segment in segments:
+        single = IsccSumProcessor()
+        single.update(data[segment.offset : segment.offset + segment.size])
+        assert segment.iscc == single.result(wide=False, add_units=False).iscc
+    plain = IsccSumProcessor().result(wide=False, add_units=False)
+    assert plain.segments is None
+    assert "segments" not in plain
+    assert len(plain) == 4
+
+
+def test_result_dict_optional_keys():
+    # type: () -> None
+    """Test that the optional results are dict keys only when present."""
+    processor = IsccSumProcessor(hashes=["sha256"])
+    processor.update(b"Hello World")
+    result = processor.result(add_minhash=True, add_features=True)
+    assert len(result) == 7
+    assert result["hashes"] == result.hashes
+    assert result["minhash"] == result.minhash
+    assert result["features"] == result.features
+    plain = IsccSumProcessor()
+    plain.update(b"Hello World")
+    plain_result = plain.result()
+    assert len(plain_result) == 4
+    for key in ("hashes", "minhash", "features"):
+        assert key in result
+        assert key not in plain_result
+        with pytest.raises(KeyError):
+            plain_result[key]
+
+
+def test_copy_snapshot():
+    # type: () -> None
+    """Test that a copy yields interim results while the original continues."""
+    processor = IsccSumProcessor()
+    processor.update(b"Hello, ")
+    snapshot = processor.copy()
+    processor.update(b"World!")
+
+    single = IsccSumProcessor()
+    single.update(b"Hello, ")
+    assert snapshot.result()["iscc"] == single.result()["iscc"]
+    assert processor.result()["filesize"] == 13
+    assert snapshot.result()["filesize"] == 7
+
+
+def test_compare():
+    # type: () -> None
+    """Test comparing codes of the same and of different content."""
+    processor = IsccSumProcessor()
+    processor.update(b"Hello, World!")
+    wide = processor.result(wide=True, add_units=False)["iscc"]
+    processor = IsccSumProcessor()
+    processor.update(b"Hello, World!")
+    narrow = processor.result(wide=False, add_units=False)["iscc"]
+
+    result = compare(wide, narrow)
+    assert result["similarity"] == 1.0
+    assert result["distances"] == {"data": 0, "instance": 0}
+    assert result["bits"] == {"data": 64, "instance": 64}
+
+    with pytest.raises(ValueError):
+        compare(wide, "not-an-iscc")
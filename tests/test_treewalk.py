@@ -3,6 +3,7 @@ Test module for iscc_sum.treewalk functionality.
 """
 
 import os
+import sys
 from pathlib import Path
 
 import pytest
@@ -89,6 +90,32 @@ class TestListdir:
         assert len(result) == 1
         assert result[0].name == "real.txt"
 
+    @pytest.mark.skipif(not sys.platform.startswith("linux"), reason="Requires non-UTF-8 file names")
+    def test_non_utf8_names(self, tmp_path):
+        # type: (Path) -> None
+        """Test that non-UTF-8 names are sorted by raw bytes instead of failing."""
+        for name in [b"zz.txt", b"bad\xff.txt", b"bad.txt", b"a.txt"]:
+            open(os.path.join(os.fsencode(tmp_path), name), "wb").close()
+        result = listdir(tmp_path)
+        assert [os.fsencode(e.name) for e in result] == [b"a.txt", b"bad.txt", b"bad\xff.txt", b"zz.txt"]
+
+    def test_symlink_policies(self, fs):
+        # type: (FakeFilesystem) -> None
+        """Test that symlink policies follow symlinks to files and directories."""
+        fs.create_dir("/test/sub")
+        fs.create_file("/test/real.txt")
+        fs.create_symlink("/test/link.txt", "/test/real.txt")
+        fs.create_symlink("/test/linkdir", "/test/sub")
+        assert [e.name for e in listdir("/test", symlinks="follow-files")] == ["link.txt", "real.txt", "sub"]
+        assert [e.name for e in listdir("/test", symlinks="follow-all")] == [
+            "link.txt",
+            "linkdir",
+            "real.txt",
+            "sub",
+        ]
+        with pytest.raises(ValueError):
+            listdir("/test", symlinks="follow")
+
     def test_path_object_input(self, fs):
         # type: (FakeFilesystem) -> None
         """Test that Path objects are accepted as input."""
@@ -598,6 +625,18 @@ class TestEdgeCases:
         assert len(result) == 1
         assert result[0].name == "file.txt"
 
+    def test_circular_references_detected_when_following(self, fs):
+        # type: (FakeFilesystem) -> None
+        """Test that following symlinks raises on a symlink to an ancestor."""
+        fs.create_dir("/test/sub")
+        fs.create_symlink("/test/sub/link", "/test")
+        fs.create_file("/test/file.txt")
+
+        with pytest.raises(OSError):
+            list(treewalk("/test", symlinks="follow-all"))
+        with pytest.raises(OSError):
+            list(treewalk_ignore("/test", ".gitignore", symlinks="follow-all"))
+
     def test_unicode_in_ignore_patterns(self, fs):
         # type: (FakeFilesystem) -> None
         """Test unicode characters in ignore patterns."""